@@ -1,15 +1,30 @@
 use anyhow::{anyhow, Result};
-use log::debug;
-use std::fmt::Write;
-use std::path::PathBuf;
+use log::{debug, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::LazyLock;
 use which::which;
 
+use crate::interpreter::PythonInstallation;
+
+/// Environment variables setuptools reads to pick the compiler and linker
+pub const COMPILER_ENV_VARS: [&str; 3] = ["CC", "CXX", "LDSHARED"];
+
+/// Wrappers that may precede the compiler in `CC`, e.g. `ccache gcc`
+const COMPILER_LAUNCHERS: [&str; 3] = ["ccache", "sccache", "distcc"];
+
+static VERSION_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d+\.\d+(\.\d+)?").unwrap());
+
 /// Represents the build tools available on the system
 pub struct BuildTools {
     /// Path to MSVC compiler (cl.exe)
     pub msvc: Option<PathBuf>,
-    /// Path to MinGW compiler (gcc.exe)
+    /// Path to MinGW compiler (gcc.exe, Windows only)
     pub mingw: Option<PathBuf>,
     /// Path to dlltool.exe (part of MinGW)
     pub dlltool: Option<PathBuf>,
@@ -17,8 +32,14 @@ pub struct BuildTools {
     pub vs: Option<PathBuf>,
     /// Path to GCC compiler (for Unix systems)
     pub gcc: Option<PathBuf>,
+    /// Path to Clang compiler
+    pub clang: Option<PathBuf>,
     /// Path to Xcode Command Line Tools (for macOS)
     pub xcode: Option<PathBuf>,
+    /// `CC`, `CXX` and `LDSHARED`, when set
+    pub env_overrides: Vec<(String, String)>,
+    /// The compiler builds will use
+    pub selected: Option<Compiler>,
 }
 
 impl BuildTools {
@@ -37,6 +58,11 @@ impl BuildTools {
         self.gcc.is_some()
     }
 
+    /// Check if Clang is available
+    pub const fn has_clang(&self) -> bool {
+        self.clang.is_some()
+    }
+
     /// Check if Xcode Command Line Tools are available (for macOS)
     pub const fn has_xcode(&self) -> bool {
         self.xcode.is_some()
@@ -44,7 +70,12 @@ impl BuildTools {
 
     /// Check if any build tools are available
     pub fn has_any_tools(&self) -> bool {
-        self.has_msvc() || self.has_mingw() || self.has_gcc() || self.has_xcode()
+        self.has_msvc()
+            || self.has_mingw()
+            || self.has_gcc()
+            || self.has_clang()
+            || self.has_xcode()
+            || self.selected.is_some()
     }
 
     /// Get a string representation of the available build tools
@@ -71,10 +102,22 @@ impl BuildTools {
             writeln!(info, "GCC: {}", gcc.display()).unwrap();
         }
 
+        if let Some(clang) = &self.clang {
+            writeln!(info, "Clang: {}", clang.display()).unwrap();
+        }
+
         if let Some(xcode) = &self.xcode {
             writeln!(info, "Xcode Command Line Tools: {}", xcode.display()).unwrap();
         }
 
+        for (name, value) in &self.env_overrides {
+            writeln!(info, "{name}={value}").unwrap();
+        }
+
+        if let Some(selected) = &self.selected {
+            writeln!(info, "Using: {} ({})", selected, selected.reason).unwrap();
+        }
+
         if info.is_empty() {
             info.push_str("No build tools found");
         }
@@ -83,6 +126,225 @@ impl BuildTools {
     }
 }
 
+/// Family of a C compiler, which decides the flags it takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompilerKind {
+    /// Microsoft's cl.exe
+    Msvc,
+    /// GCC, including `cc` when it is GCC
+    Gcc,
+    /// Clang, including Apple Clang
+    Clang,
+    /// GCC targeting Windows
+    #[serde(rename = "mingw")]
+    MinGw,
+}
+
+impl fmt::Display for CompilerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Msvc => "MSVC",
+            Self::Gcc => "GCC",
+            Self::Clang => "Clang",
+            Self::MinGw => "MinGW",
+        })
+    }
+}
+
+/// A C compiler, what it reported about itself and why it was chosen
+#[derive(Debug, Clone, Serialize)]
+pub struct Compiler {
+    /// Compiler family
+    pub kind: CompilerKind,
+    /// Path to the compiler executable
+    pub path: PathBuf,
+    /// Arguments that select the compiler, e.g. `cc -target ...` for zig
+    pub args: Vec<String>,
+    /// Version, e.g. `13.2.0` or `19.38.33130`
+    pub version: Option<String>,
+    /// Target triple (`x86_64-linux-gnu`) or MSVC architecture (`x64`)
+    pub target: Option<String>,
+    /// Why this compiler is used
+    pub reason: String,
+    /// Whether the choice came from `CC`, which setuptools already reads
+    pub from_env: bool,
+    /// Whether it produces code for another architecture than the build host
+    pub cross: bool,
+}
+
+impl fmt::Display for Compiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(version) = &self.version {
+            write!(f, " {version}")?;
+        }
+        if let Some(target) = &self.target {
+            write!(f, " ({target})")?;
+        }
+        write!(f, " at {}", self.command_line())
+    }
+}
+
+impl Compiler {
+    /// Ask the compiler at `path` for its version and target
+    pub fn probe(path: &Path, reason: impl Into<String>) -> Self {
+        let (kind, version, target) = if is_msvc(path) {
+            // cl prints its banner to stderr when run without arguments
+            let banner = Command::new(path)
+                .output()
+                .map(|o| String::from_utf8_lossy(&o.stderr).to_string())
+                .unwrap_or_default();
+            let (version, target) = parse_msvc_banner(&banner);
+            (CompilerKind::Msvc, version, target)
+        } else {
+            let output = |arg: &str| {
+                Command::new(path)
+                    .arg(arg)
+                    .output()
+                    .ok()
+                    .filter(|o| o.status.success())
+                    .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+            };
+            let (mut kind, version) = parse_cc_version(&output("--version").unwrap_or_default());
+            let target = output("-dumpmachine").filter(|t| !t.is_empty());
+            if kind == CompilerKind::Gcc && target.as_ref().is_some_and(|t| t.contains("mingw")) {
+                kind = CompilerKind::MinGw;
+            }
+            (kind, version, target)
+        };
+        debug!("Probed {}: {kind} {version:?} {target:?}", path.display());
+
+        Self {
+            kind,
+            path: path.to_path_buf(),
+            args: vec![],
+            version,
+            target,
+            reason: reason.into(),
+            from_env: false,
+            cross: false,
+        }
+    }
+
+    /// The compiler as a `CC` value, e.g. `/usr/bin/zig cc -target aarch64-linux-gnu`
+    pub fn command_line(&self) -> String {
+        let path = self.path.to_string_lossy();
+        let path = if path.contains(char::is_whitespace) {
+            format!("\"{path}\"")
+        } else {
+            path.to_string()
+        };
+        std::iter::once(path)
+            .chain(self.args.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Make the `setup.py build_ext` command `cmd` use this compiler
+    pub fn apply_to(&self, cmd: &mut Command) {
+        match self.kind {
+            CompilerKind::MinGw if cfg!(windows) => {
+                cmd.arg("--compiler=mingw32");
+            }
+            // The build interpreter's LDSHARED names its own compiler, so
+            // cross builds link with the cross compiler explicitly
+            CompilerKind::Gcc | CompilerKind::Clang if self.cross => {
+                let cc = match env::var("CC") {
+                    Ok(cc) if self.from_env => cc,
+                    _ => self.command_line(),
+                };
+                if env::var_os("LDSHARED").is_none() {
+                    cmd.env("LDSHARED", format!("{cc} -shared"));
+                }
+                cmd.env("CC", cc);
+            }
+            CompilerKind::Gcc | CompilerKind::Clang if !self.from_env && !cfg!(windows) => {
+                cmd.env("CC", self.command_line());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_msvc(path: &Path) -> bool {
+    path.file_stem()
+        .is_some_and(|stem| stem.eq_ignore_ascii_case("cl"))
+}
+
+/// Version and architecture from cl.exe's banner, e.g.
+/// `Microsoft (R) C/C++ Optimizing Compiler Version 19.38.33130 for x64`
+pub fn parse_msvc_banner(banner: &str) -> (Option<String>, Option<String>) {
+    let Some(line) = banner.lines().find(|line| line.contains("Version")) else {
+        return (None, None);
+    };
+    let version = VERSION_REGEX.find(line).map(|m| m.as_str().to_string());
+    let target = line
+        .rsplit_once(" for ")
+        .map(|(_, arch)| arch.trim().to_string());
+    (version, target)
+}
+
+/// Compiler family and version from the first line of `cc --version`
+pub fn parse_cc_version(output: &str) -> (CompilerKind, Option<String>) {
+    let line = output.lines().next().unwrap_or_default();
+    let kind = if line.contains("clang") {
+        CompilerKind::Clang
+    } else {
+        CompilerKind::Gcc
+    };
+    // Skip the program name, which may itself contain digits (e.g. gcc-13)
+    let version = line
+        .split_once(' ')
+        .and_then(|(_, rest)| VERSION_REGEX.find(rest))
+        .map(|m| m.as_str().to_string());
+    (kind, version)
+}
+
+/// The compiler named by `CC`, skipping launchers such as ccache
+pub fn compiler_from_env(cc: &str) -> Option<PathBuf> {
+    let program = cc
+        .split_whitespace()
+        .find(|word| !COMPILER_LAUNCHERS.contains(word))?;
+    which(program).ok()
+}
+
+/// Pick the compiler setuptools would use, preferring `CC`
+fn select_compiler(tools: &BuildTools) -> Option<Compiler> {
+    if let Some((_, cc)) = tools.env_overrides.iter().find(|(name, _)| name == "CC") {
+        match compiler_from_env(cc) {
+            Some(path) => {
+                return Some(Compiler {
+                    from_env: true,
+                    ..Compiler::probe(&path, format!("CC is set to `{cc}`"))
+                })
+            }
+            None => debug!("CC={cc} does not name a compiler on PATH"),
+        }
+    }
+
+    let (path, reason) = if cfg!(windows) {
+        match (&tools.msvc, &tools.mingw) {
+            (Some(msvc), _) => (msvc, "MSVC matches the compiler of python.org builds"),
+            (None, Some(mingw)) => (mingw, "MSVC not found, falling back to MinGW"),
+            (None, None) => return None,
+        }
+    } else if cfg!(target_os = "macos") {
+        match (&tools.clang, &tools.gcc) {
+            (Some(clang), _) => (clang, "Clang is the default compiler on macOS"),
+            (None, Some(gcc)) => (gcc, "Clang not found, falling back to GCC"),
+            (None, None) => return None,
+        }
+    } else {
+        match (&tools.gcc, &tools.clang) {
+            (Some(gcc), _) => (gcc, "GCC is the default compiler on Linux"),
+            (None, Some(clang)) => (clang, "GCC not found, falling back to Clang"),
+            (None, None) => return None,
+        }
+    };
+    Some(Compiler::probe(path, reason))
+}
+
 /// Detect build tools available on the system
 pub fn detect_build_tools() -> BuildTools {
     let mut tools = BuildTools {
@@ -91,7 +353,14 @@ pub fn detect_build_tools() -> BuildTools {
         dlltool: None,
         vs: None,
         gcc: None,
+        clang: None,
         xcode: None,
+        env_overrides: COMPILER_ENV_VARS
+            .iter()
+            .filter_map(|name| Some((name.to_string(), env::var(name).ok()?)))
+            .filter(|(_, value)| !value.trim().is_empty())
+            .collect(),
+        selected: None,
     };
 
     // Detect MSVC
@@ -100,16 +369,6 @@ pub fn detect_build_tools() -> BuildTools {
             debug!("Found MSVC compiler: {}", path.display());
             tools.msvc = Some(path);
 
-            // Try to find Visual Studio installation
-            if let Ok(output) = Command::new("cl").arg("/?").output() {
-                let output_str = String::from_utf8_lossy(&output.stdout);
-                if let Some(line) = output_str.lines().next() {
-                    if line.contains("Microsoft") {
-                        debug!("MSVC version info: {line}");
-                    }
-                }
-            }
-
             // Try to find VS installation path
             if let Ok(output) = Command::new("where").arg("devenv.exe").output() {
                 if output.status.success() {
@@ -131,41 +390,45 @@ pub fn detect_build_tools() -> BuildTools {
         }
     }
 
-    // Detect MinGW
+    // On Windows, gcc is MinGW; elsewhere it is the native compiler
     match which("gcc") {
-        Ok(path) => {
+        Ok(path) if cfg!(windows) => {
             debug!("Found MinGW compiler: {}", path.display());
             tools.mingw = Some(path);
         }
-        Err(e) => {
-            debug!("MinGW compiler not found in PATH: {e}");
-        }
-    }
-
-    // Detect dlltool
-    match which("dlltool") {
         Ok(path) => {
-            debug!("Found dlltool: {}", path.display());
-            tools.dlltool = Some(path);
+            debug!("Found GCC compiler: {}", path.display());
+            tools.gcc = Some(path);
         }
         Err(e) => {
-            debug!("dlltool not found in PATH: {e}");
+            debug!("GCC compiler not found in PATH: {e}");
         }
     }
 
-    // For Unix systems (Linux and macOS), detect GCC
-    if cfg!(unix) {
-        match which("gcc") {
+    // Detect dlltool
+    if cfg!(windows) {
+        match which("dlltool") {
             Ok(path) => {
-                debug!("Found GCC compiler: {}", path.display());
-                tools.gcc = Some(path);
+                debug!("Found dlltool: {}", path.display());
+                tools.dlltool = Some(path);
             }
             Err(e) => {
-                debug!("GCC compiler not found in PATH: {e}");
+                debug!("dlltool not found in PATH: {e}");
             }
         }
     }
 
+    // Detect Clang
+    match which("clang") {
+        Ok(path) => {
+            debug!("Found Clang compiler: {}", path.display());
+            tools.clang = Some(path);
+        }
+        Err(e) => {
+            debug!("Clang compiler not found in PATH: {e}");
+        }
+    }
+
     // For macOS, detect Xcode Command Line Tools
     if cfg!(target_os = "macos") {
         // Check for xcode-select
@@ -191,9 +454,141 @@ pub fn detect_build_tools() -> BuildTools {
         }
     }
 
+    tools.selected = select_compiler(&tools);
+    if let Some(selected) = &tools.selected {
+        debug!("Selected compiler: {} ({})", selected, selected.reason);
+    }
     tools
 }
 
+/// Linux distribution family, used to suggest install commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distro {
+    /// Debian, Ubuntu and derivatives (apt)
+    Debian,
+    /// Fedora, RHEL, CentOS, Rocky and Alma Linux (dnf)
+    Fedora,
+    /// Arch Linux and derivatives (pacman)
+    Arch,
+    /// Alpine Linux (apk)
+    Alpine,
+    /// openSUSE and SLES (zypper)
+    Suse,
+    /// Anything else, including macOS and Windows
+    Unknown,
+}
+
+impl Distro {
+    /// Detect the distribution from `/etc/os-release`
+    pub fn detect() -> Self {
+        if !cfg!(target_os = "linux") {
+            return Self::Unknown;
+        }
+        fs::read_to_string("/etc/os-release")
+            .map(|content| Self::from_os_release(&content))
+            .unwrap_or(Self::Unknown)
+    }
+
+    /// Parse the `ID` and `ID_LIKE` fields of an os-release file
+    pub fn from_os_release(content: &str) -> Self {
+        let ids = content
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .filter(|(key, _)| matches!(key.trim(), "ID" | "ID_LIKE"))
+            .flat_map(|(_, value)| value.trim().trim_matches('"').split_whitespace())
+            .collect::<Vec<_>>();
+
+        for id in ids {
+            let distro = match id {
+                "debian" | "ubuntu" => Self::Debian,
+                "fedora" | "rhel" | "centos" | "rocky" | "almalinux" => Self::Fedora,
+                "arch" => Self::Arch,
+                "alpine" => Self::Alpine,
+                id if id.contains("suse") => Self::Suse,
+                _ => continue,
+            };
+            return distro;
+        }
+        Self::Unknown
+    }
+
+    /// Command that installs `packages` with the distribution's package manager
+    pub fn install_command(self, packages: &[&str]) -> Option<String> {
+        let manager = match self {
+            Self::Debian => "sudo apt-get install -y",
+            Self::Fedora => "sudo dnf install -y",
+            Self::Arch => "sudo pacman -S --needed",
+            Self::Alpine => "apk add",
+            Self::Suse => "sudo zypper install -y",
+            Self::Unknown => return None,
+        };
+        Some(format!("{} {}", manager, packages.join(" ")))
+    }
+
+    /// Packages providing a C compiler and linker
+    pub const fn compiler_packages(self) -> &'static [&'static str] {
+        match self {
+            Self::Debian => &["build-essential"],
+            Self::Fedora | Self::Suse => &["gcc"],
+            Self::Arch => &["base-devel"],
+            Self::Alpine => &["build-base"],
+            Self::Unknown => &[],
+        }
+    }
+
+    /// Package providing `Python.h` for Python `major.minor`, if known
+    pub fn python_dev_package(self, version: Option<(u32, u32)>) -> Option<String> {
+        let package = match (self, version) {
+            (Self::Debian, Some((major, minor))) => format!("python{major}.{minor}-dev"),
+            (Self::Debian | Self::Alpine, None) => "python3-dev".to_string(),
+            (Self::Alpine, Some((major, _))) => format!("python{major}-dev"),
+            (Self::Fedora, Some((major, minor))) => format!("python{major}.{minor}-devel"),
+            (Self::Fedora, None) => "python3-devel".to_string(),
+            (Self::Suse, Some((major, minor))) => format!("python{major}{minor}-devel"),
+            (Self::Suse, None) => "python3-devel".to_string(),
+            // Arch ships the headers with the interpreter
+            (Self::Arch, _) => "python".to_string(),
+            (Self::Unknown, _) => return None,
+        };
+        Some(package)
+    }
+}
+
+/// Check that `python` can have extensions built for it.
+///
+/// Fails with the install command for this system when `Python.h` is
+/// missing, and on Windows when the import library is; other link problems
+/// are logged as warnings.
+pub fn check_python_dev(python: &Path) -> Result<PythonInstallation> {
+    let info = PythonInstallation::probe(python)?;
+    debug!("{}: headers in {}", info, info.include.display());
+
+    if !info.has_header {
+        return Err(anyhow!(
+            "Python.h not found for Python {} at {} (looked in {}).\n\nTo fix: {}",
+            info.version,
+            info.executable.display(),
+            info.include.display(),
+            info.headers_fix(Distro::detect())
+        ));
+    }
+
+    let problems = info.link_problems();
+    if cfg!(windows) && !problems.is_empty() {
+        return Err(anyhow!(
+            "Cannot link extensions for Python {}.{} at {}: {}",
+            info.version.major,
+            info.version.minor,
+            info.executable.display(),
+            problems.join("; ")
+        ));
+    }
+    for problem in problems {
+        warn!("{problem}");
+    }
+    Ok(info)
+}
+
 /// Get installation instructions for build tools
 pub fn get_build_tools_installation_instructions() -> String {
     if cfg!(windows) {
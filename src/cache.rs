@@ -0,0 +1,305 @@
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use ring::digest::{Context as DigestContext, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::build_tools::{self, CompilerKind};
+use crate::config::Directive;
+use crate::cross::{find_cross_compiler, TargetPython};
+use crate::interpreter::{build_interpreter, PythonInstallation};
+use crate::lock::{FileLock, LockMode};
+use crate::python_version::PythonVersion;
+use crate::uv_compiler::CompileConfig;
+use crate::uv_env::UvEnv;
+
+/// File in the output directory that records what each module was built from
+pub const CACHE_FILE_NAME: &str = ".py2pyd-cache.json";
+
+/// Bumped when the fingerprint inputs change, invalidating older caches
+const CACHE_VERSION: u32 = 3;
+
+/// Fingerprints of the modules built into an output directory.
+///
+/// A module is fresh when its output exists and was built from the same
+/// source, settings, interpreter, Cython, C compiler and py2pyd version.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildCache {
+    version: u32,
+    /// Output path relative to the output directory, to fingerprint
+    entries: BTreeMap<PathBuf, String>,
+    /// Entries recorded (or, with `None`, invalidated) since loading
+    #[serde(skip)]
+    changes: BTreeMap<PathBuf, Option<String>>,
+    #[serde(skip)]
+    output_dir: PathBuf,
+}
+
+/// The interpreter, Cython and C compiler modules are compiled with, which
+/// fingerprints cover along with the settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Toolchain {
+    /// Installed interpreter the build environment is made from; `None` when
+    /// uv provides one
+    pub python: Option<PathBuf>,
+    /// Full version of the interpreter builds run with
+    pub python_version: Option<PythonVersion>,
+    /// Version of the Cython in the build environment
+    pub cython: Option<String>,
+    /// Family of the C compiler
+    pub compiler: Option<CompilerKind>,
+    /// Version the C compiler reported
+    pub compiler_version: Option<String>,
+    /// SHA-256 of the target's headers and sysconfig data, for cross builds
+    pub target_python: Option<String>,
+}
+
+impl Toolchain {
+    /// The toolchain of builds for `config` in `env`; without an environment
+    /// the Cython version is unknown
+    pub fn resolve(config: &CompileConfig, env: Option<&UvEnv>) -> Self {
+        let installation = match build_interpreter(config) {
+            Ok(installation) => Some(installation),
+            Err(e) => {
+                debug!("No installed interpreter to fingerprint: {e:#}");
+                None
+            }
+        };
+        // A Python uv downloaded is only known from the environment
+        let python_version = installation
+            .as_ref()
+            .map(|installation| installation.version)
+            .or_else(|| {
+                env.and_then(|env| PythonInstallation::probe(&env.python_path).ok())
+                    .map(|installation| installation.version)
+            });
+        // The compiler `setup.py` runs: the configured one, the cross
+        // compiler for the target, or the one build tool detection selects
+        let compiler = match (&config.compiler, &config.target) {
+            (Some(compiler), _) => Some(compiler.clone()),
+            (None, Some(target)) => target
+                .parse()
+                .and_then(|target| find_cross_compiler(&target))
+                .ok(),
+            (None, None) => build_tools::check_build_tools()
+                .ok()
+                .and_then(|tools| tools.selected),
+        };
+        let target_python = match (&config.target, &config.target_python) {
+            (Some(_), Some(dir)) => match target_python_digest(dir) {
+                Ok(digest) => Some(digest),
+                Err(e) => {
+                    debug!("Cannot fingerprint the target's Python: {e:#}");
+                    None
+                }
+            },
+            _ => None,
+        };
+        Self {
+            python: installation.map(|installation| installation.executable),
+            python_version,
+            cython: env.and_then(UvEnv::cython_version),
+            compiler: compiler.as_ref().map(|compiler| compiler.kind),
+            compiler_version: compiler.and_then(|compiler| compiler.version),
+            target_python,
+        }
+    }
+
+    /// Whether the interpreter and Cython versions are known, so that
+    /// fingerprints can tell whether a module is up to date
+    pub fn is_known(&self) -> bool {
+        self.python_version.is_some() && self.cython.is_some()
+    }
+}
+
+/// Settings that change the compiled module, in a stable order
+#[derive(Serialize)]
+struct FingerprintInputs<'a> {
+    py2pyd: &'a str,
+    toolchain: &'a Toolchain,
+    python_path: Option<&'a Path>,
+    python_version: Option<&'a str>,
+    target_dcc: Option<&'a str>,
+    conda_env: Option<&'a str>,
+    packages: BTreeSet<&'a str>,
+    optimize_level: u8,
+    encrypt_strings: bool,
+    keep_docstrings: bool,
+    cython_directives: &'a BTreeMap<String, Directive>,
+    lto: bool,
+    strip: bool,
+    debug_symbols: bool,
+    line_tracing: bool,
+    target: Option<&'a str>,
+    manylinux: Option<&'a str>,
+    repair: bool,
+    compile_args: &'a [String],
+    link_args: &'a [String],
+}
+
+/// SHA-256 of a module's source, the settings it is compiled with and the
+/// toolchain compiling it.
+///
+/// `config` should already have per-module overrides applied.
+pub fn fingerprint(source: &[u8], config: &CompileConfig, toolchain: &Toolchain) -> Result<String> {
+    let inputs = FingerprintInputs {
+        py2pyd: env!("CARGO_PKG_VERSION"),
+        toolchain,
+        python_path: config.python_path.as_deref(),
+        python_version: config.python_version.as_deref(),
+        target_dcc: config.target_dcc.as_deref(),
+        conda_env: config.conda_env.as_deref(),
+        packages: config.packages.iter().map(String::as_str).collect(),
+        optimize_level: config.optimize_level,
+        encrypt_strings: config.encrypt_strings,
+        keep_docstrings: config.keep_docstrings,
+        cython_directives: &config.cython_directives,
+        lto: config.lto,
+        strip: config.strip,
+        debug_symbols: config.debug_symbols,
+        line_tracing: config.line_tracing,
+        target: config.target.as_deref(),
+        manylinux: config.manylinux.as_deref(),
+        repair: config.repair,
+        compile_args: &config.compile_args,
+        link_args: &config.link_args,
+    };
+
+    let mut context = DigestContext::new(&SHA256);
+    context.update(&serde_json::to_vec(&inputs)?);
+    context.update(&[0]);
+    context.update(source);
+    Ok(to_hex(context))
+}
+
+/// SHA-256 of the headers and sysconfig data in a `--target-python` directory,
+/// with their relative paths
+fn target_python_digest(dir: &Path) -> Result<String> {
+    let python = TargetPython::from_dir(dir)?;
+    let mut files: Vec<PathBuf> = WalkDir::new(&python.include)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(std::result::Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(walkdir::DirEntry::into_path)
+        .collect();
+    files.extend(python.sysconfigdata);
+
+    let mut context = DigestContext::new(&SHA256);
+    for file in files {
+        let contents =
+            fs::read(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+        let name = file.strip_prefix(dir).unwrap_or(&file);
+        context.update(name.to_string_lossy().as_bytes());
+        context.update(&[0]);
+        context.update(&contents);
+        context.update(&[0]);
+    }
+    Ok(to_hex(context))
+}
+
+fn to_hex(context: DigestContext) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in context.finish().as_ref() {
+        write!(hex, "{byte:02x}").unwrap();
+    }
+    hex
+}
+
+/// [`fingerprint`] of the module at `path`, with its per-module overrides
+pub fn fingerprint_file(
+    path: &Path,
+    config: &CompileConfig,
+    toolchain: &Toolchain,
+) -> Result<String> {
+    let source =
+        fs::read(path).with_context(|| format!("Failed to read input file: {}", path.display()))?;
+    fingerprint(&source, &config.for_module(path), toolchain)
+}
+
+impl BuildCache {
+    /// Load the cache of `output_dir`; a missing or unreadable cache is empty
+    pub fn load(output_dir: &Path) -> Self {
+        let path = output_dir.join(CACHE_FILE_NAME);
+        let cache = match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<Self>(&content) {
+                Ok(cache) if cache.version == CACHE_VERSION => cache,
+                Ok(_) => {
+                    debug!("Ignoring build cache from another py2pyd version");
+                    Self::default()
+                }
+                Err(e) => {
+                    warn!("Ignoring unreadable build cache {}: {}", path.display(), e);
+                    Self::default()
+                }
+            },
+            Err(_) => Self::default(),
+        };
+
+        Self {
+            version: CACHE_VERSION,
+            output_dir: output_dir.to_path_buf(),
+            ..cache
+        }
+    }
+
+    fn key(&self, output: &Path) -> PathBuf {
+        output
+            .strip_prefix(&self.output_dir)
+            .unwrap_or(output)
+            .to_path_buf()
+    }
+
+    /// Whether `output` exists and was built with `fingerprint`
+    pub fn is_fresh(&self, output: &Path, fingerprint: &str) -> bool {
+        output.is_file()
+            && self.entries.get(&self.key(output)).map(String::as_str) == Some(fingerprint)
+    }
+
+    /// Remember that `output` was built with `fingerprint`
+    pub fn record(&mut self, output: &Path, fingerprint: String) {
+        let key = self.key(output);
+        self.entries.insert(key.clone(), fingerprint.clone());
+        self.changes.insert(key, Some(fingerprint));
+    }
+
+    /// Forget `output`, e.g. after its build failed
+    pub fn invalidate(&mut self, output: &Path) {
+        let key = self.key(output);
+        self.entries.remove(&key);
+        self.changes.insert(key, None);
+    }
+
+    /// Write the cache to the output directory.
+    ///
+    /// Only the entries changed since loading are written over what is on
+    /// disk, so builds into the same directory from other processes are kept.
+    pub fn save(&self) -> Result<()> {
+        let path = self.output_dir.join(CACHE_FILE_NAME);
+        let _lock = FileLock::acquire(
+            &self.output_dir.join(format!("{CACHE_FILE_NAME}.lock")),
+            LockMode::Exclusive,
+            &format!("the build cache in {}", self.output_dir.display()),
+        )?;
+
+        let mut merged = Self::load(&self.output_dir);
+        for (key, change) in &self.changes {
+            match change {
+                Some(fingerprint) => merged.entries.insert(key.clone(), fingerprint.clone()),
+                None => merged.entries.remove(key),
+            };
+        }
+
+        let temp = self
+            .output_dir
+            .join(format!("{CACHE_FILE_NAME}.{}", std::process::id()));
+        fs::write(&temp, serde_json::to_string_pretty(&merged)?)
+            .with_context(|| format!("Failed to write build cache: {}", temp.display()))?;
+        fs::rename(&temp, &path)
+            .with_context(|| format!("Failed to write build cache: {}", path.display()))
+    }
+}
@@ -4,8 +4,11 @@ use log::{debug, error, info, warn};
 use std::fs::{self, create_dir_all};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Instant;
 use walkdir::WalkDir;
 
+use crate::messages::Message;
+use crate::python_env::PythonEnvironment;
 use crate::transformer::TransformedModule;
 
 /// Compile a single Python file to a pyd file
@@ -14,6 +17,32 @@ pub fn compile_file(
     output_path: &Path,
     _: &str, // Unused but kept for backward compatibility
     optimize_level: u8,
+) -> Result<()> {
+    compile(input_path, output_path, optimize_level, None)
+}
+
+/// Compile a single Python file to a pyd file for the interpreter of `env`
+pub fn compile_file_with_env(
+    input_path: &Path,
+    output_path: &Path,
+    optimize_level: u8,
+    env: &PythonEnvironment,
+) -> Result<()> {
+    compile(
+        input_path,
+        output_path,
+        optimize_level,
+        Some(env.python_path()),
+    )
+}
+
+/// Compile one file, for `python` when given and otherwise whichever
+/// interpreter PyO3 finds
+fn compile(
+    input_path: &Path,
+    output_path: &Path,
+    optimize_level: u8,
+    python: Option<&Path>,
 ) -> Result<()> {
     info!(
         "Compiling {} to {}",
@@ -32,7 +61,7 @@ pub fn compile_file(
     create_rust_project(&transformed).with_context(|| "Failed to create Rust project")?;
 
     // Build the Rust project
-    build_rust_project(&transformed).with_context(|| "Failed to build Rust project")?;
+    build_rust_project(&transformed, python).with_context(|| "Failed to build Rust project")?;
 
     // Copy the compiled library to the output path
     copy_compiled_library(&transformed, output_path).with_context(|| {
@@ -57,6 +86,62 @@ pub fn batch_compile(
     _: &str, // Unused but kept for backward compatibility
     optimize_level: u8,
     recursive: bool,
+) -> Result<()> {
+    batch(
+        input_pattern,
+        output_dir,
+        optimize_level,
+        recursive,
+        None,
+        |_| {},
+    )
+}
+
+/// Batch compile multiple Python files to pyd files for the interpreter of `env`
+pub fn batch_compile_with_env(
+    input_pattern: &str,
+    output_dir: &Path,
+    optimize_level: u8,
+    recursive: bool,
+    env: &PythonEnvironment,
+) -> Result<()> {
+    batch_compile_with_messages(
+        input_pattern,
+        output_dir,
+        optimize_level,
+        recursive,
+        env,
+        |_| {},
+    )
+}
+
+/// Batch compile for the interpreter of `env`, reporting when each module
+/// starts and finishes
+pub fn batch_compile_with_messages(
+    input_pattern: &str,
+    output_dir: &Path,
+    optimize_level: u8,
+    recursive: bool,
+    env: &PythonEnvironment,
+    on_message: impl FnMut(Message),
+) -> Result<()> {
+    batch(
+        input_pattern,
+        output_dir,
+        optimize_level,
+        recursive,
+        Some(env.python_path()),
+        on_message,
+    )
+}
+
+fn batch(
+    input_pattern: &str,
+    output_dir: &Path,
+    optimize_level: u8,
+    recursive: bool,
+    python: Option<&Path>,
+    mut on_message: impl FnMut(Message),
 ) -> Result<()> {
     info!(
         "Batch compiling from {} to {}",
@@ -103,7 +188,19 @@ pub fn batch_compile(
         }
 
         // Compile the file
-        match compile_file(&input_path, &output_path, "", optimize_level) {
+        on_message(Message::ModuleStarted {
+            source: input_path.clone(),
+            output: output_path.clone(),
+        });
+        let started = Instant::now();
+        let result = compile(&input_path, &output_path, optimize_level, python);
+        on_message(Message::module_finished(
+            &input_path,
+            &output_path,
+            started.elapsed(),
+            result.as_ref().err(),
+        ));
+        match result {
             Ok(()) => {
                 success_count += 1;
             }
@@ -197,8 +294,8 @@ fn create_rust_project(transformed: &TransformedModule) -> Result<()> {
     Ok(())
 }
 
-/// Build a Rust project
-fn build_rust_project(transformed: &TransformedModule) -> Result<()> {
+/// Build a Rust project, against `python` when given
+fn build_rust_project(transformed: &TransformedModule, python: Option<&Path>) -> Result<()> {
     info!(
         "Building Rust project in {}",
         transformed.build_dir.display()
@@ -225,10 +322,15 @@ features = ["pyo3/extension-module"]
 
     // Use cargo directly to build the extension
     info!("Building with cargo...");
-    let status = Command::new("cargo")
+    let mut command = Command::new("cargo");
+    command
         .current_dir(&transformed.build_dir)
         .arg("build")
-        .arg("--release")
+        .arg("--release");
+    if let Some(python) = python {
+        command.env("PYO3_PYTHON", python);
+    }
+    let status = command
         .status()
         .with_context(|| "Failed to execute cargo build")?;
 
@@ -0,0 +1,549 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::uv_compiler::CompileConfig;
+use crate::uv_env::EnvBackend;
+
+/// Name of the standalone configuration file
+pub const CONFIG_FILE_NAME: &str = "py2pyd.toml";
+
+/// Name of the Python project file holding a `[tool.py2pyd]` table
+pub const PYPROJECT_FILE_NAME: &str = "pyproject.toml";
+
+/// How batch outputs are laid out in the output directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputLayout {
+    /// Keep the directory structure of the sources
+    #[default]
+    Mirror,
+    /// Put every module directly in the output directory
+    Flat,
+}
+
+/// A Cython compiler directive value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Directive {
+    /// `True` / `False`
+    Bool(bool),
+    /// An integer such as `language_level = 3`
+    Int(i64),
+    /// A string such as `binding = "cpdef"`
+    String(String),
+}
+
+impl Directive {
+    /// The value as a Python literal for `setup.py`
+    pub fn to_python(&self) -> String {
+        match self {
+            Self::Bool(true) => "True".to_string(),
+            Self::Bool(false) => "False".to_string(),
+            Self::Int(value) => value.to_string(),
+            Self::String(value) => python_string(value),
+        }
+    }
+}
+
+/// `value` as a double-quoted Python string literal, with backslashes, quotes
+/// and control characters escaped
+pub fn python_string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => literal.push_str("\\\\"),
+            '"' => literal.push_str("\\\""),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            // Rust's `\u{..}` escapes are not Python syntax; control
+            // characters are all below U+0100
+            c if c.is_control() => write!(literal, "\\x{:02x}", u32::from(c)).unwrap(),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Int(value) => write!(f, "{value}"),
+            Self::String(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl FromStr for Directive {
+    type Err = anyhow::Error;
+
+    /// Parse a value from the command line (`true`, `3` or any other string)
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "true" | "True" => Self::Bool(true),
+            "false" | "False" => Self::Bool(false),
+            _ => match s.parse::<i64>() {
+                Ok(value) => Self::Int(value),
+                Err(_) => Self::String(s.to_string()),
+            },
+        })
+    }
+}
+
+/// Parse a `name=value` directive from the command line
+pub fn parse_directive(s: &str) -> Result<(String, Directive)> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid directive '{}', expected NAME=VALUE", s))?;
+    Ok((name.trim().to_string(), value.trim().parse()?))
+}
+
+/// Settings for the modules matching a set of globs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ModuleOverride {
+    /// Globs of source files the override applies to, relative to the project root
+    pub modules: Vec<String>,
+    /// Optimization level (0-3)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optimize_level: Option<u8>,
+    /// Cython compiler directives, merged over the project-wide ones
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub directives: BTreeMap<String, Directive>,
+    /// Replace string literals with calls to a runtime decoder
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypt_strings: Option<bool>,
+    /// Leave docstrings as plain text when encrypting strings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_docstrings: Option<bool>,
+}
+
+impl ModuleOverride {
+    /// Whether the override applies to `path` (relative to the project root)
+    pub fn matches(&self, path: &Path) -> bool {
+        matches_any(&self.modules, path)
+    }
+
+    /// Apply the override on top of `config`
+    pub fn apply_to(&self, config: &mut CompileConfig) {
+        if let Some(level) = self.optimize_level {
+            config.optimize_level = level;
+        }
+        config.cython_directives.extend(self.directives.clone());
+        if let Some(encrypt) = self.encrypt_strings {
+            config.encrypt_strings = encrypt;
+        }
+        if let Some(keep) = self.keep_docstrings {
+            config.keep_docstrings = keep;
+        }
+    }
+}
+
+/// A named set of build settings selected with `--profile`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Profile {
+    /// Optimization level (0-3)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optimize_level: Option<u8>,
+    /// Link-time optimization
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lto: Option<bool>,
+    /// Strip symbols from the extension module
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strip: Option<bool>,
+    /// Emit debug symbols
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_symbols: Option<bool>,
+    /// Enable Cython line tracing for profilers and coverage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_tracing: Option<bool>,
+    /// Replace string literals with calls to a runtime decoder
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypt_strings: Option<bool>,
+    /// Leave docstrings as plain text when encrypting strings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_docstrings: Option<bool>,
+    /// Cython compiler directives, merged over the project-wide ones
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub directives: BTreeMap<String, Directive>,
+    /// Directory batch outputs are written to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+}
+
+/// Names of the profiles that exist without any configuration
+pub const BUILTIN_PROFILES: &[&str] = &["dev", "release", "debug"];
+
+impl Profile {
+    /// The built-in `dev`, `release` or `debug` profile
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            // Fast turnaround for local iteration
+            "dev" => Some(Self {
+                optimize_level: Some(1),
+                lto: Some(false),
+                strip: Some(false),
+                keep_docstrings: Some(true),
+                ..Default::default()
+            }),
+            "release" => Some(Self {
+                optimize_level: Some(3),
+                lto: Some(true),
+                strip: Some(true),
+                debug_symbols: Some(false),
+                line_tracing: Some(false),
+                ..Default::default()
+            }),
+            "debug" => Some(Self {
+                optimize_level: Some(0),
+                lto: Some(false),
+                strip: Some(false),
+                debug_symbols: Some(true),
+                line_tracing: Some(true),
+                keep_docstrings: Some(true),
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+
+    /// `self` with the fields set in `other` replacing its own
+    pub fn merged_with(&self, other: &Profile) -> Profile {
+        let mut directives = self.directives.clone();
+        directives.extend(other.directives.clone());
+        Profile {
+            optimize_level: other.optimize_level.or(self.optimize_level),
+            lto: other.lto.or(self.lto),
+            strip: other.strip.or(self.strip),
+            debug_symbols: other.debug_symbols.or(self.debug_symbols),
+            line_tracing: other.line_tracing.or(self.line_tracing),
+            encrypt_strings: other.encrypt_strings.or(self.encrypt_strings),
+            keep_docstrings: other.keep_docstrings.or(self.keep_docstrings),
+            directives,
+            output_dir: other.output_dir.clone().or_else(|| self.output_dir.clone()),
+        }
+    }
+
+    /// Apply the profile on top of `config`
+    pub fn apply_to(&self, config: &mut CompileConfig) {
+        if let Some(level) = self.optimize_level {
+            config.optimize_level = level;
+        }
+        if let Some(lto) = self.lto {
+            config.lto = lto;
+        }
+        if let Some(strip) = self.strip {
+            config.strip = strip;
+        }
+        if let Some(debug_symbols) = self.debug_symbols {
+            config.debug_symbols = debug_symbols;
+        }
+        if let Some(line_tracing) = self.line_tracing {
+            config.line_tracing = line_tracing;
+        }
+        if let Some(encrypt) = self.encrypt_strings {
+            config.encrypt_strings = encrypt;
+        }
+        if let Some(keep) = self.keep_docstrings {
+            config.keep_docstrings = keep;
+        }
+        config.cython_directives.extend(self.directives.clone());
+        if self.output_dir.is_some() {
+            config.output_dir.clone_from(&self.output_dir);
+        }
+    }
+}
+
+/// Look up a profile defined in `project` or built in.
+///
+/// A `[tool.py2pyd.profiles.release]` table only needs to list the settings
+/// it changes; the rest come from the built-in profile of the same name.
+pub fn find_profile(project: Option<&ProjectConfig>, name: &str) -> Result<Profile> {
+    let defined = project.and_then(|project| project.profiles.get(name));
+    match (Profile::builtin(name), defined) {
+        (Some(builtin), Some(defined)) => Ok(builtin.merged_with(defined)),
+        (Some(builtin), None) => Ok(builtin),
+        (None, Some(defined)) => Ok(defined.clone()),
+        (None, None) => {
+            let mut names: Vec<&str> = BUILTIN_PROFILES.to_vec();
+            if let Some(project) = project {
+                names.extend(project.profiles.keys().map(String::as_str));
+            }
+            names.sort_unstable();
+            names.dedup();
+            Err(anyhow!(
+                "Unknown profile '{}' (available: {})",
+                name,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+/// The `[tool.py2pyd]` table of `pyproject.toml`, or the contents of `py2pyd.toml`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Globs of source files to compile; everything when empty
+    pub include: Vec<String>,
+    /// Globs of source files (or directories) to skip
+    pub exclude: Vec<String>,
+    /// Python version to build for (e.g. "3.10")
+    pub python_version: Option<String>,
+    /// Python interpreter to build with
+    pub python_path: Option<PathBuf>,
+    /// Additional packages to install into the build environment
+    pub packages: Vec<String>,
+    /// Cython compiler directives (e.g. `language_level = 3`)
+    pub directives: BTreeMap<String, Directive>,
+    /// Optimization level (0-3)
+    pub optimize_level: Option<u8>,
+    /// Directory batch outputs are written to
+    pub output_dir: Option<PathBuf>,
+    /// Layout of batch outputs
+    pub output_layout: Option<OutputLayout>,
+    /// Replace string literals with calls to a runtime decoder
+    pub encrypt_strings: Option<bool>,
+    /// Leave docstrings as plain text when encrypting strings
+    pub keep_docstrings: Option<bool>,
+    /// Per-module-glob settings, applied in order
+    pub overrides: Vec<ModuleOverride>,
+    /// Skip batch modules that are already up to date
+    pub cache: Option<bool>,
+    /// DCC release to build for, e.g. `maya-2024`
+    pub dcc: Option<String>,
+    /// Build with `python-path` itself instead of a venv made from it
+    pub no_venv: Option<bool>,
+    /// Conda environment to build in, by name or prefix
+    pub conda_env: Option<String>,
+    /// Install build requirements into the conda environment itself
+    pub conda_install: Option<bool>,
+    /// Tool build environments are created with: `auto`, `uv` or `venv`
+    pub env_backend: Option<EnvBackend>,
+    /// Download uv when it is needed and missing
+    pub install_uv: Option<bool>,
+    /// Linux target triple to cross-compile for
+    pub target: Option<String>,
+    /// Directory with the target's Python headers and sysconfig data
+    pub target_python: Option<PathBuf>,
+    /// manylinux policy Linux modules must meet, e.g. `manylinux2014`
+    pub manylinux: Option<String>,
+    /// Vendor non-system libraries next to Linux modules
+    pub repair: Option<bool>,
+    /// Named profiles selected with `--profile`
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// File the configuration was read from
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+impl ProjectConfig {
+    /// Load a `py2pyd.toml` or the `[tool.py2pyd]` table of a `pyproject.toml`.
+    ///
+    /// Returns `Ok(None)` for a `pyproject.toml` without a `[tool.py2pyd]` table.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        debug!("Loading configuration from {}", path.display());
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read configuration file: {}", path.display()))?;
+        let document: toml::Table = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        let table = if path
+            .file_name()
+            .is_some_and(|name| name == PYPROJECT_FILE_NAME)
+        {
+            match document
+                .get("tool")
+                .and_then(|tool| tool.get("py2pyd"))
+                .cloned()
+            {
+                Some(table) => table,
+                None => return Ok(None),
+            }
+        } else {
+            toml::Value::Table(document)
+        };
+
+        let mut config: Self = table
+            .try_into()
+            .with_context(|| format!("Invalid py2pyd configuration in {}", path.display()))?;
+        config
+            .validate_globs()
+            .with_context(|| format!("Invalid py2pyd configuration in {}", path.display()))?;
+        config.source = Some(path.to_path_buf());
+        config.resolve_paths();
+        Ok(Some(config))
+    }
+
+    /// Find the configuration for `start` by searching it and its parents.
+    ///
+    /// In each directory `py2pyd.toml` wins over `pyproject.toml`; a
+    /// `pyproject.toml` without a `[tool.py2pyd]` table is skipped.
+    pub fn discover(start: &Path) -> Result<Option<Self>> {
+        let start = start.canonicalize().unwrap_or_else(|_| start.to_path_buf());
+
+        for dir in start.ancestors() {
+            for name in [CONFIG_FILE_NAME, PYPROJECT_FILE_NAME] {
+                let path = dir.join(name);
+                if !path.is_file() {
+                    continue;
+                }
+                if let Some(config) = Self::load(&path)? {
+                    info!("Using configuration from {}", path.display());
+                    return Ok(Some(config));
+                }
+            }
+        }
+
+        debug!("No py2pyd configuration found above {}", start.display());
+        Ok(None)
+    }
+
+    /// Directory the configuration file lives in
+    pub fn root(&self) -> Option<&Path> {
+        self.source.as_deref().and_then(Path::parent)
+    }
+
+    /// Fail on the first include, exclude or override glob that does not parse
+    fn validate_globs(&self) -> Result<()> {
+        let overrides = self
+            .overrides
+            .iter()
+            .map(|module| ("overrides.modules", &module.modules));
+        for (key, patterns) in [("include", &self.include), ("exclude", &self.exclude)]
+            .into_iter()
+            .chain(overrides)
+        {
+            for pattern in patterns {
+                glob_pattern(pattern)
+                    .with_context(|| format!("Invalid glob in {key}: {pattern:?}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Make relative paths relative to the configuration file
+    fn resolve_paths(&mut self) {
+        let Some(root) = self.root().map(Path::to_path_buf) else {
+            return;
+        };
+        let profile_dirs = self
+            .profiles
+            .values_mut()
+            .map(|profile| &mut profile.output_dir);
+        for path in [
+            &mut self.output_dir,
+            &mut self.python_path,
+            &mut self.target_python,
+        ]
+        .into_iter()
+        .chain(profile_dirs)
+        .flatten()
+        {
+            if path.is_relative() {
+                *path = root.join(&*path);
+            }
+        }
+    }
+
+    /// Apply the file settings on top of `config`.
+    ///
+    /// Call this before applying command line flags so that they win.
+    pub fn apply_to(&self, config: &mut CompileConfig) {
+        config.include.clone_from(&self.include);
+        config.exclude.clone_from(&self.exclude);
+        if self.python_version.is_some() {
+            config.python_version.clone_from(&self.python_version);
+        }
+        if self.python_path.is_some() {
+            config.python_path.clone_from(&self.python_path);
+        }
+        config.packages.extend(self.packages.iter().cloned());
+        config.cython_directives.extend(self.directives.clone());
+        if let Some(level) = self.optimize_level {
+            config.optimize_level = level;
+        }
+        if self.output_dir.is_some() {
+            config.output_dir.clone_from(&self.output_dir);
+        }
+        if let Some(layout) = self.output_layout {
+            config.output_layout = layout;
+        }
+        if let Some(encrypt) = self.encrypt_strings {
+            config.encrypt_strings = encrypt;
+        }
+        if let Some(keep) = self.keep_docstrings {
+            config.keep_docstrings = keep;
+        }
+        config.overrides.clone_from(&self.overrides);
+        if let Some(cache) = self.cache {
+            config.cache = cache;
+        }
+        if self.dcc.is_some() {
+            config.target_dcc.clone_from(&self.dcc);
+        }
+        if let Some(no_venv) = self.no_venv {
+            config.no_venv = no_venv;
+        }
+        if self.conda_env.is_some() {
+            config.conda_env.clone_from(&self.conda_env);
+        }
+        if let Some(install) = self.conda_install {
+            config.conda_install = install;
+        }
+        if let Some(backend) = self.env_backend {
+            config.env_backend = backend;
+        }
+        if let Some(install) = self.install_uv {
+            config.install_uv = install;
+        }
+        if self.target.is_some() {
+            config.target.clone_from(&self.target);
+        }
+        if self.target_python.is_some() {
+            config.target_python.clone_from(&self.target_python);
+        }
+        if self.manylinux.is_some() {
+            config.manylinux.clone_from(&self.manylinux);
+        }
+        if let Some(repair) = self.repair {
+            config.repair = repair;
+        }
+        config.project_root = self.root().map(Path::to_path_buf);
+    }
+}
+
+/// Whether `path` or one of its parent directories matches one of `patterns`
+pub fn matches_any(patterns: &[String], path: &Path) -> bool {
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+
+    patterns.iter().any(|pattern| {
+        // Configuration files are validated when loaded
+        let Ok(pattern) = glob_pattern(pattern) else {
+            return false;
+        };
+        path.ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| pattern.matches_path_with(ancestor, options))
+    })
+}
+
+/// Parse an include, exclude or override glob; a trailing `/` is ignored
+fn glob_pattern(pattern: &str) -> Result<glob::Pattern> {
+    Ok(glob::Pattern::new(pattern.trim_end_matches('/'))?)
+}
@@ -0,0 +1,337 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use walkdir::WalkDir;
+use which::which;
+
+use crate::build_tools::{compiler_from_env, Compiler, CompilerKind};
+use crate::elf::{self, ElfHeader};
+use crate::interpreter::PythonRequest;
+use crate::uv_compiler::CompileConfig;
+
+/// A Linux target to cross-compile for, e.g. `aarch64-linux-gnu`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrossTarget {
+    /// Architecture, normalized (`arm64` becomes `aarch64`)
+    pub arch: String,
+    /// ABI, e.g. `gnu`, `gnueabihf` or `musl`
+    pub abi: String,
+}
+
+impl FromStr for CrossTarget {
+    type Err = anyhow::Error;
+
+    /// Parse `arch-linux-abi` or `arch-vendor-linux-abi`
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split('-').collect();
+        let Some(linux) = parts.iter().position(|part| *part == "linux") else {
+            return Err(anyhow!(
+                "Unsupported target '{}': only Linux targets such as aarch64-linux-gnu are supported",
+                s
+            ));
+        };
+        let arch = match parts[0] {
+            "amd64" => "x86_64",
+            "arm64" => "aarch64",
+            "powerpc64le" => "ppc64le",
+            arch => arch,
+        };
+        let target = Self {
+            arch: arch.to_string(),
+            abi: parts.get(linux + 1).unwrap_or(&"gnu").to_string(),
+        };
+        if target.machine().is_none() {
+            return Err(anyhow!("Unsupported target architecture '{}'", parts[0]));
+        }
+        Ok(target)
+    }
+}
+
+impl fmt::Display for CrossTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-linux-{}", self.arch, self.abi)
+    }
+}
+
+impl CrossTarget {
+    /// ELF `e_machine` and whether the target is 64-bit
+    pub fn machine(&self) -> Option<(u16, bool)> {
+        let machine = match self.arch.as_str() {
+            "x86_64" => (elf::EM_X86_64, true),
+            "aarch64" => (elf::EM_AARCH64, true),
+            "arm" | "armv7" | "armv7l" | "armhf" => (elf::EM_ARM, false),
+            "i386" | "i586" | "i686" | "x86" => (elf::EM_386, false),
+            "riscv64" => (elf::EM_RISCV, true),
+            "ppc64le" => (elf::EM_PPC64, true),
+            "s390x" => (elf::EM_S390, true),
+            _ => return None,
+        };
+        Some(machine)
+    }
+
+    /// Architecture as GNU toolchains name it, e.g. `arm` for `armv7`
+    fn gnu_arch(&self) -> &str {
+        match self.arch.as_str() {
+            "armv7" | "armv7l" | "armhf" => "arm",
+            "ppc64le" => "powerpc64le",
+            arch => arch,
+        }
+    }
+
+    /// Target as `zig cc -target` expects it
+    pub fn zig_triple(&self) -> String {
+        let arch = match self.gnu_arch() {
+            "i386" | "i586" | "i686" => "x86",
+            arch => arch,
+        };
+        format!("{}-linux-{}", arch, self.abi)
+    }
+
+    /// Names of GCC cross compilers for this target, most common first
+    pub fn gcc_names(&self) -> Vec<String> {
+        let arch = self.gnu_arch();
+        vec![
+            format!("{}-linux-{}-gcc", arch, self.abi),
+            format!("{}-unknown-linux-{}-gcc", arch, self.abi),
+        ]
+    }
+
+    /// Check that the extension module at `path` was built for this target
+    pub fn check_output(&self, path: &Path) -> Result<()> {
+        let header = ElfHeader::read(path)?;
+        let (machine, is_64) = self.machine().unwrap_or_default();
+        if header.machine != machine || header.is_64 != is_64 {
+            return Err(anyhow!(
+                "{} was built for {}, not {}",
+                path.display(),
+                header,
+                self
+            ));
+        }
+        debug!("{} is {}", path.display(), header);
+        Ok(())
+    }
+}
+
+/// Find a C compiler for `target`: `CC`, then a GCC cross toolchain on
+/// `PATH`, then `zig cc`
+pub fn find_cross_compiler(target: &CrossTarget) -> Result<Compiler> {
+    let cross = |compiler: Compiler| Compiler {
+        cross: true,
+        ..compiler
+    };
+
+    if let Ok(cc) = env::var("CC") {
+        if let Some(path) = compiler_from_env(&cc) {
+            return Ok(cross(Compiler {
+                from_env: true,
+                ..Compiler::probe(&path, format!("CC is set to `{cc}`"))
+            }));
+        }
+    }
+
+    for name in target.gcc_names() {
+        if let Ok(path) = which(&name) {
+            return Ok(cross(Compiler::probe(
+                &path,
+                format!("{name} is the cross compiler for {target}"),
+            )));
+        }
+    }
+
+    if let Ok(zig) = which("zig") {
+        let version = Command::new(&zig)
+            .arg("version")
+            .output()
+            .ok()
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+        return Ok(Compiler {
+            kind: CompilerKind::Clang,
+            args: vec!["cc".to_string(), "-target".to_string(), target.zig_triple()],
+            version,
+            target: Some(target.zig_triple()),
+            reason: format!("zig cc targets {target}"),
+            from_env: false,
+            cross: true,
+            path: zig,
+        });
+    }
+
+    Err(anyhow!(
+        "No C compiler for {}. Install a cross toolchain (e.g. `sudo apt-get install gcc-{}`) \
+         or zig (https://ziglang.org/download/), or set CC",
+        target,
+        target.gcc_names()[0].trim_end_matches("-gcc")
+    ))
+}
+
+/// Python headers, and optionally sysconfig data, of the target interpreter
+#[derive(Debug, Clone)]
+pub struct TargetPython {
+    /// `major.minor` of the target Python
+    pub version: (u8, u8),
+    /// Directory holding `Python.h`
+    pub include: PathBuf,
+    /// The target's `_sysconfigdata_*.py`, with its compiler flags and suffixes
+    pub sysconfigdata: Option<PathBuf>,
+}
+
+impl TargetPython {
+    /// Find the headers and sysconfig data copied from the target system.
+    ///
+    /// `dir` is expected to hold `include/python3.X/Python.h` and, ideally,
+    /// `lib/python3.X/_sysconfigdata_*.py`.
+    pub fn from_dir(dir: &Path) -> Result<Self> {
+        let files: Vec<PathBuf> = WalkDir::new(dir)
+            .max_depth(4)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(std::result::Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(walkdir::DirEntry::into_path)
+            .collect();
+
+        let mut headers = files
+            .iter()
+            .filter(|path| path.file_name().is_some_and(|name| name == "Python.h"))
+            .filter_map(|path| {
+                let include = path.parent()?;
+                let version = parse_include_version(include.file_name()?.to_str()?)?;
+                Some((version, include.to_path_buf()))
+            });
+        let (version, include) = headers.next().ok_or_else(|| {
+            anyhow!(
+                "No include/python3.X/Python.h under {}; copy the headers from the target system",
+                dir.display()
+            )
+        })?;
+        if let Some((other, _)) = headers.find(|(other, _)| *other != version) {
+            return Err(anyhow!(
+                "Headers for Python {}.{} and {}.{} under {}; point --target-python at one of them",
+                version.0,
+                version.1,
+                other.0,
+                other.1,
+                dir.display()
+            ));
+        }
+
+        let sysconfigdata = files
+            .iter()
+            .find(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| {
+                        name.starts_with("_sysconfigdata_") && name.ends_with(".py")
+                    })
+            })
+            .cloned();
+        if sysconfigdata.is_none() {
+            info!(
+                "No _sysconfigdata_*.py under {}; using the build interpreter's compiler flags",
+                dir.display()
+            );
+        }
+
+        Ok(Self {
+            version,
+            include,
+            sysconfigdata,
+        })
+    }
+
+    /// Make `setup.py` in `build_dir`, run by `cmd`, read the target's sysconfig data
+    pub fn apply_to(&self, cmd: &mut Command, build_dir: &Path) -> Result<()> {
+        let Some(sysconfigdata) = &self.sysconfigdata else {
+            return Ok(());
+        };
+
+        // Only this module goes on the path, not the target's standard library
+        let dir = build_dir.join("target-sysconfig");
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+        let file_name = sysconfigdata.file_name().unwrap_or_default();
+        fs::copy(sysconfigdata, dir.join(file_name)).with_context(|| {
+            format!(
+                "Failed to copy sysconfig data from {}",
+                sysconfigdata.display()
+            )
+        })?;
+
+        let name = Path::new(file_name)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let python_path = match env::var_os("PYTHONPATH") {
+            Some(existing) => env::join_paths([dir.into_os_string(), existing])?,
+            None => dir.into_os_string(),
+        };
+        cmd.env("_PYTHON_SYSCONFIGDATA_NAME", name)
+            .env("PYTHONPATH", python_path);
+        Ok(())
+    }
+}
+
+/// `(3, 11)` from `python3.11`, also accepting ABI flags such as `python3.13t`
+fn parse_include_version(name: &str) -> Option<(u8, u8)> {
+    let version = name.strip_prefix("python")?;
+    let (major, minor) = version.split_once('.')?;
+    let minor = minor.trim_end_matches(|c: char| !c.is_ascii_digit());
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Check the cross-compilation settings of `config` and default the Python
+/// version to the target's
+pub fn configure(config: &mut CompileConfig) -> Result<()> {
+    let Some(target) = &config.target else {
+        if config.target_python.is_some() {
+            return Err(anyhow!("--target-python needs --target"));
+        }
+        return Ok(());
+    };
+    let target: CrossTarget = target.parse()?;
+    if cfg!(windows) {
+        return Err(anyhow!(
+            "Cross-compiling for {} needs a Linux or macOS build host",
+            target
+        ));
+    }
+
+    let dir = config.target_python.as_deref().ok_or_else(|| {
+        anyhow!(
+            "--target {} needs --target-python with the target's Python headers",
+            target
+        )
+    })?;
+    let python = TargetPython::from_dir(dir)?;
+    let (major, minor) = python.version;
+    match &config.python_version {
+        None => config.python_version = Some(format!("{major}.{minor}")),
+        Some(version) if version_matches(version, python.version) => {}
+        Some(version) => {
+            return Err(anyhow!(
+                "--python-version {} does not match the target's Python {}.{} in {}",
+                version,
+                major,
+                minor,
+                dir.display()
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Whether `version` ("3.11" or "3.11.4") is Python `major.minor`
+fn version_matches(version: &str, (major, minor): (u8, u8)) -> bool {
+    matches!(
+        version.parse(),
+        Ok(PythonRequest::Version { major: m, minor: Some(n), .. })
+            if m == u32::from(major) && n == u32::from(minor)
+    )
+}
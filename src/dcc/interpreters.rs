@@ -0,0 +1,214 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{host_platform, DccRegistry};
+use crate::interpreter::PythonInstallation;
+use crate::python_version::PythonVersion;
+
+/// Environment variable with extra install roots to search, like `PATH`
+pub const DCC_ROOTS_ENV: &str = "PY2PYD_DCC_ROOTS";
+
+/// An interpreter bundled with a DCC install, e.g. `mayapy`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DccInterpreter {
+    /// DCC id, e.g. `maya`
+    pub dcc: String,
+    /// Display name, e.g. `Autodesk Maya`
+    pub name: String,
+    /// Release read from the install path, e.g. `2024`
+    pub dcc_version: Option<String>,
+    /// The interpreter
+    pub path: PathBuf,
+    /// `major.minor.patch` it reported
+    pub python_version: String,
+    /// ABI tag, e.g. `cp310`
+    pub abi: String,
+    /// Whether `Python.h` is where the interpreter says
+    pub has_header: bool,
+    /// Whether Cython is importable in it
+    pub cython: bool,
+    /// Why its release's profile does not fit, e.g. another Python version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mismatch: Option<String>,
+}
+
+impl DccInterpreter {
+    /// `--dcc` value for this install, e.g. `maya-2024`
+    pub fn spec(&self) -> Option<String> {
+        self.dcc_version
+            .as_ref()
+            .map(|version| format!("{}-{}", self.dcc, version))
+    }
+
+    /// `major.minor` of its Python
+    pub fn python_major_minor(&self) -> &str {
+        match self.python_version.match_indices('.').nth(1) {
+            Some((index, _)) => &self.python_version[..index],
+            None => &self.python_version,
+        }
+    }
+}
+
+impl fmt::Display for DccInterpreter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} (Python {}, {})",
+            self.name,
+            self.dcc_version.as_deref().unwrap_or("(unknown release)"),
+            self.python_version,
+            self.abi
+        )
+    }
+}
+
+/// Extra install roots from `PY2PYD_DCC_ROOTS`
+pub fn env_roots() -> Vec<PathBuf> {
+    env::var_os(DCC_ROOTS_ENV)
+        .map(|roots| env::split_paths(&roots).collect())
+        .unwrap_or_default()
+}
+
+/// Paths matching each DCC's interpreter globs under its roots and `extra_roots`,
+/// with the DCC id; `only` limits the search to one DCC
+pub fn find_candidates(
+    registry: &DccRegistry,
+    extra_roots: &[PathBuf],
+    only: Option<&str>,
+) -> Vec<(String, PathBuf)> {
+    let mut seen = BTreeSet::new();
+    let mut candidates = vec![];
+    for (id, dcc) in &registry.dccs {
+        if only.is_some_and(|only| only != id) {
+            continue;
+        }
+        let Some(settings) = dcc.settings(host_platform()) else {
+            continue;
+        };
+        let roots = settings
+            .roots
+            .iter()
+            .filter_map(|root| expand_home(root))
+            .chain(extra_roots.iter().cloned());
+        for root in roots {
+            for pattern in &settings.interpreters {
+                let pattern = root.join(pattern);
+                let Ok(paths) = glob::glob(&pattern.to_string_lossy()) else {
+                    warn!("Invalid interpreter glob {}", pattern.display());
+                    continue;
+                };
+                for path in paths.flatten() {
+                    if !is_interpreter(&path) {
+                        continue;
+                    }
+                    // python3 is usually a link to python3.X
+                    let real = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+                    if seen.insert((id.clone(), real)) {
+                        candidates.push((id.clone(), path));
+                    }
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// Ask each candidate about its Python and match it with its release's profile.
+///
+/// Interpreters that fail to run are logged and left out.
+pub fn discover(
+    registry: &DccRegistry,
+    extra_roots: &[PathBuf],
+    only: Option<&str>,
+) -> Vec<DccInterpreter> {
+    find_candidates(registry, extra_roots, only)
+        .into_iter()
+        .filter_map(|(id, path)| match probe(registry, &id, &path) {
+            Ok(interpreter) => Some(interpreter),
+            Err(e) => {
+                warn!("Skipping {}: {:#}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Run the interpreter at `path`, found for DCC `id`, and describe it
+pub fn probe(registry: &DccRegistry, id: &str, path: &Path) -> Result<DccInterpreter> {
+    let dcc = registry
+        .dccs
+        .get(id)
+        .ok_or_else(|| anyhow!("Unknown DCC '{}'", id))?;
+    debug!("Probing {} interpreter {}", dcc.name, path.display());
+    let info = PythonInstallation::probe(path)?;
+    let dcc_version = match &dcc.version_pattern {
+        Some(pattern) => Regex::new(pattern)
+            .with_context(|| format!("Invalid version-pattern for {id}"))?
+            .captures(&path.to_string_lossy())
+            .and_then(|captures| captures.get(1))
+            .map(|version| version.as_str().to_string()),
+        None => None,
+    };
+
+    // The profile names a `major.minor` release; the interpreter reports a patch
+    let embeds = |python: &str| {
+        python
+            .parse::<PythonVersion>()
+            .is_ok_and(|python| python.same_minor(&info.version))
+    };
+    let mismatch = match dcc_version.as_ref().map(|v| (v, dcc.versions.get(v))) {
+        Some((version, None)) => Some(format!("no profile for {} {}", dcc.name, version)),
+        Some((version, Some(release))) if !embeds(&release.python) => Some(format!(
+            "the {} {} profile expects Python {}",
+            dcc.name, version, release.python
+        )),
+        _ => None,
+    };
+
+    Ok(DccInterpreter {
+        dcc: id.to_string(),
+        name: dcc.name.clone(),
+        dcc_version,
+        path: path.to_path_buf(),
+        python_version: info.version.to_string(),
+        abi: info.abi_tag(),
+        has_header: info.has_header,
+        cython: info.cython,
+        mismatch,
+    })
+}
+
+/// `~/x` relative to the home directory; other paths as they are
+fn expand_home(root: &str) -> Option<PathBuf> {
+    match root.strip_prefix('~') {
+        Some(rest) => dirs::home_dir().map(|home| home.join(rest.trim_start_matches('/'))),
+        None => Some(PathBuf::from(root)),
+    }
+}
+
+/// Whether `path` is an executable file rather than e.g. `python3.10-config`
+fn is_interpreter(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if name.ends_with("-config") || name.ends_with(".py") {
+        return false;
+    }
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.is_file()
+    }
+}
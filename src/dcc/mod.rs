@@ -0,0 +1,421 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::build_tools::{Compiler, CompilerKind};
+use crate::interpreter::PythonRequest;
+use crate::python_version::PythonVersion;
+use crate::uv_compiler::CompileConfig;
+
+mod interpreters;
+
+pub use interpreters::{
+    discover, env_roots, find_candidates, probe, DccInterpreter, DCC_ROOTS_ENV,
+};
+
+/// Profiles shipped with py2pyd
+const BUILTIN_PROFILES: &str = include_str!("profiles.toml");
+
+/// Environment variable naming a user profile file
+pub const DCC_PROFILES_ENV: &str = "PY2PYD_DCC_PROFILES";
+
+/// Settings for building on (or for) one operating system
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PlatformSettings {
+    /// Compiler family the host's Python was built with and modules must use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiler: Option<CompilerKind>,
+    /// manylinux policy the host's Linux releases support
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manylinux: Option<String>,
+    /// Extra compiler arguments
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub compile_args: Vec<String>,
+    /// Extra linker arguments
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub link_args: Vec<String>,
+    /// Directories the DCC is usually installed under; `~` is the home directory
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub roots: Vec<String>,
+    /// Globs of the bundled interpreter, relative to a root
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub interpreters: Vec<String>,
+}
+
+impl PlatformSettings {
+    /// `self` with the fields set in `other` replacing its own, and arguments appended
+    fn merged_with(&self, other: &PlatformSettings) -> PlatformSettings {
+        PlatformSettings {
+            compiler: other.compiler.or(self.compiler),
+            manylinux: other.manylinux.clone().or_else(|| self.manylinux.clone()),
+            compile_args: [self.compile_args.clone(), other.compile_args.clone()].concat(),
+            link_args: [self.link_args.clone(), other.link_args.clone()].concat(),
+            roots: [self.roots.clone(), other.roots.clone()].concat(),
+            interpreters: [self.interpreters.clone(), other.interpreters.clone()].concat(),
+        }
+    }
+}
+
+/// One release of a DCC and the Python it embeds
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DccVersion {
+    /// `major.minor` of the embedded Python
+    pub python: String,
+    /// ABI tag of the embedded Python; `cpXY` when not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abi: Option<String>,
+    /// VFX Reference Platform year the release follows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vfx_platform: Option<u16>,
+    /// Windows settings, over the DCC's
+    pub windows: PlatformSettings,
+    /// Linux settings, over the DCC's
+    pub linux: PlatformSettings,
+    /// macOS settings, over the DCC's
+    pub macos: PlatformSettings,
+}
+
+/// A DCC application and its releases
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Dcc {
+    /// Display name, e.g. `Autodesk Maya`
+    pub name: String,
+    /// Operating systems it runs on (`windows`, `linux`, `macos`); all when empty
+    pub platforms: Vec<String>,
+    /// Regex whose first group is the release in an interpreter's path
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_pattern: Option<String>,
+    /// Windows settings for every release
+    pub windows: PlatformSettings,
+    /// Linux settings for every release
+    pub linux: PlatformSettings,
+    /// macOS settings for every release
+    pub macos: PlatformSettings,
+    /// Releases by version, e.g. `2024` or `20.5`
+    pub versions: BTreeMap<String, DccVersion>,
+}
+
+impl Dcc {
+    /// Settings for every release on `platform` (`windows`, `linux` or `macos`)
+    pub fn settings(&self, platform: &str) -> Option<&PlatformSettings> {
+        match platform {
+            "windows" => Some(&self.windows),
+            "linux" => Some(&self.linux),
+            "macos" => Some(&self.macos),
+            _ => None,
+        }
+    }
+}
+
+/// DCC profiles by id, e.g. `maya`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DccRegistry {
+    /// DCCs by the id used in `--dcc id-version`
+    pub dccs: BTreeMap<String, Dcc>,
+}
+
+impl DccRegistry {
+    /// The profiles shipped with py2pyd
+    pub fn builtin() -> Self {
+        Self::from_toml(BUILTIN_PROFILES).expect("built-in DCC profiles are valid")
+    }
+
+    /// Parse profiles in the layout of the built-in `profiles.toml`
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let registry: Self = toml::from_str(content).with_context(|| "Invalid DCC profiles")?;
+        for (id, dcc) in &registry.dccs {
+            if let Some(pattern) = &dcc.version_pattern {
+                regex::Regex::new(pattern)
+                    .with_context(|| format!("Invalid version-pattern for {id}"))?;
+            }
+        }
+        Ok(registry)
+    }
+
+    /// The built-in profiles extended with the user's, if they have any
+    pub fn load() -> Result<Self> {
+        let mut registry = Self::builtin();
+        if let Some(path) = user_profiles_path().filter(|path| path.is_file()) {
+            debug!("Loading DCC profiles from {}", path.display());
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let user = Self::from_toml(&content)
+                .with_context(|| format!("Failed to parse {}", path.display()))?;
+            registry.extend(user);
+        }
+        Ok(registry)
+    }
+
+    /// Add the DCCs and releases of `other`, replacing releases that exist
+    pub fn extend(&mut self, other: DccRegistry) {
+        for (id, dcc) in other.dccs {
+            match self.dccs.get_mut(&id) {
+                Some(existing) => {
+                    if !dcc.name.is_empty() {
+                        existing.name = dcc.name;
+                    }
+                    if !dcc.platforms.is_empty() {
+                        existing.platforms = dcc.platforms;
+                    }
+                    if dcc.version_pattern.is_some() {
+                        existing.version_pattern = dcc.version_pattern;
+                    }
+                    existing.windows = existing.windows.merged_with(&dcc.windows);
+                    existing.linux = existing.linux.merged_with(&dcc.linux);
+                    existing.macos = existing.macos.merged_with(&dcc.macos);
+                    existing.versions.extend(dcc.versions);
+                }
+                None => {
+                    self.dccs.insert(id, dcc);
+                }
+            }
+        }
+    }
+
+    /// Look up `id-version`, e.g. `maya-2024` or `houdini-20.5`
+    pub fn resolve(&self, spec: &str) -> Result<DccTarget> {
+        let (id, version) = spec.rsplit_once('-').ok_or_else(|| {
+            anyhow!(
+                "Invalid DCC '{}', expected <dcc>-<version> such as maya-2024 (known: {})",
+                spec,
+                self.dccs.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })?;
+        let id = id.to_lowercase();
+        let dcc = self.dccs.get(&id).ok_or_else(|| {
+            anyhow!(
+                "Unknown DCC '{}' (known: {}); add it to {}",
+                id,
+                self.dccs.keys().cloned().collect::<Vec<_>>().join(", "),
+                user_profiles_path()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| DCC_PROFILES_ENV.to_string())
+            )
+        })?;
+        let release = dcc.versions.get(version).ok_or_else(|| {
+            anyhow!(
+                "Unknown {} version '{}' (known: {})",
+                dcc.name,
+                version,
+                dcc.versions.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })?;
+        if release.python.split('.').count() != 2 {
+            return Err(anyhow!(
+                "{} {} has Python '{}' in its profile, expected major.minor",
+                dcc.name,
+                version,
+                release.python
+            ));
+        }
+        Ok(DccTarget {
+            id,
+            name: dcc.name.clone(),
+            version: version.to_string(),
+            platforms: dcc.platforms.clone(),
+            python: release.python.clone(),
+            abi: release.abi.clone(),
+            vfx_platform: release.vfx_platform,
+            windows: dcc.windows.merged_with(&release.windows),
+            linux: dcc.linux.merged_with(&release.linux),
+            macos: dcc.macos.merged_with(&release.macos),
+        })
+    }
+}
+
+/// `PY2PYD_DCC_PROFILES`, or `~/.py2pyd/dcc.toml`
+pub fn user_profiles_path() -> Option<PathBuf> {
+    env::var_os(DCC_PROFILES_ENV)
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".py2pyd").join("dcc.toml")))
+}
+
+/// A resolved DCC release to build for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DccTarget {
+    /// DCC id, e.g. `maya`
+    pub id: String,
+    /// Display name, e.g. `Autodesk Maya`
+    pub name: String,
+    /// Release, e.g. `2024`
+    pub version: String,
+    /// Operating systems the DCC runs on; all when empty
+    pub platforms: Vec<String>,
+    /// `major.minor` of the embedded Python
+    pub python: String,
+    /// ABI tag, when it is not `cpXY`
+    pub abi: Option<String>,
+    /// VFX Reference Platform year
+    pub vfx_platform: Option<u16>,
+    /// Effective Windows settings
+    pub windows: PlatformSettings,
+    /// Effective Linux settings
+    pub linux: PlatformSettings,
+    /// Effective macOS settings
+    pub macos: PlatformSettings,
+}
+
+impl DccTarget {
+    /// ABI tag of the embedded Python, e.g. `cp310`
+    pub fn abi(&self) -> String {
+        self.abi
+            .clone()
+            .unwrap_or_else(|| format!("cp{}", self.python.replace('.', "")))
+    }
+
+    /// Settings for `platform` (`windows`, `linux` or `macos`)
+    pub fn settings(&self, platform: &str) -> Option<&PlatformSettings> {
+        match platform {
+            "windows" => Some(&self.windows),
+            "linux" => Some(&self.linux),
+            "macos" => Some(&self.macos),
+            _ => None,
+        }
+    }
+
+    /// Check that modules built by `config` can target this release, and set
+    /// its Python version and platform settings where `config` has none
+    pub fn apply_to(&self, config: &mut CompileConfig) -> Result<()> {
+        // Cross builds only produce Linux modules
+        let platform = if config.target.is_some() {
+            "linux"
+        } else {
+            host_platform()
+        };
+        if !self.platforms.is_empty() && !self.platforms.iter().any(|p| p == platform) {
+            return Err(anyhow!(
+                "{} does not run on {} (only {})",
+                self,
+                platform,
+                self.platforms.join(", ")
+            ));
+        }
+
+        match &config.python_version {
+            None => config.python_version = Some(self.python.clone()),
+            Some(version) if python_matches(version, &self.python) => {}
+            Some(version) => {
+                return Err(anyhow!(
+                    "--python-version {} does not match {}, which embeds Python {}",
+                    version,
+                    self,
+                    self.python
+                ))
+            }
+        }
+
+        let settings = self.settings(platform).cloned().unwrap_or_default();
+        if config.manylinux.is_none() && platform == "linux" {
+            config.manylinux = settings.manylinux;
+        }
+        config.compile_args.extend(settings.compile_args);
+        config.link_args.extend(settings.link_args);
+        Ok(())
+    }
+
+    /// Check that `compiler` builds modules this release can load
+    pub fn check_compiler(&self, compiler: &Compiler) -> Result<()> {
+        let platform = if compiler.cross {
+            "linux"
+        } else {
+            host_platform()
+        };
+        match self
+            .settings(platform)
+            .and_then(|settings| settings.compiler)
+        {
+            Some(kind) if kind != compiler.kind => Err(anyhow!(
+                "{} needs modules built with {} on {}, but {} was selected; set CC or install {}",
+                self,
+                kind,
+                platform,
+                compiler,
+                kind
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for DccTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} (Python {}, {})",
+            self.name,
+            self.version,
+            self.python,
+            self.abi()
+        )
+    }
+}
+
+/// `windows`, `linux` or `macos` for the machine py2pyd runs on
+pub fn host_platform() -> &'static str {
+    if cfg!(windows) {
+        "windows"
+    } else if cfg!(target_os = "macos") {
+        "macos"
+    } else {
+        "linux"
+    }
+}
+
+/// Whether `version` ("3.10" or "3.10.11") is Python `major_minor` ("3.10")
+fn python_matches(version: &str, major_minor: &str) -> bool {
+    match (version.parse(), major_minor.parse::<PythonVersion>()) {
+        (
+            Ok(PythonRequest::Version {
+                major,
+                minor: Some(minor),
+                ..
+            }),
+            Ok(python),
+        ) => python.same_minor(&PythonVersion::new(major, minor)),
+        _ => false,
+    }
+}
+
+/// Resolve `config.target_dcc`, if set, and apply it to `config`, recording
+/// the release in `config.dcc_target`; a config that has one already is left
+/// as it is.
+///
+/// Without `--python-path` (or a cross target), an installed interpreter of
+/// the release, e.g. `mayapy`, is built with directly instead of a venv.
+pub fn configure(config: &mut CompileConfig) -> Result<Option<DccTarget>> {
+    let Some(spec) = config.target_dcc.clone() else {
+        return Ok(None);
+    };
+    if config.dcc_target.is_some() {
+        return Ok(config.dcc_target.clone());
+    }
+    let registry = DccRegistry::load()?;
+    let target = registry.resolve(&spec)?;
+    target.apply_to(config)?;
+    info!("Building for {}", target);
+
+    if config.python_path.is_none() && config.target.is_none() && config.conda_env.is_none() {
+        let installed = discover(&registry, &env_roots(), Some(&target.id))
+            .into_iter()
+            .find(|found| {
+                found.dcc_version.as_deref() == Some(target.version.as_str())
+                    && found.mismatch.is_none()
+            });
+        if let Some(interpreter) = installed {
+            info!("Using {} at {}", interpreter, interpreter.path.display());
+            config.python_path = Some(interpreter.path);
+            config.no_venv = true;
+        }
+    }
+    config.dcc_target = Some(target.clone());
+    Ok(Some(target))
+}
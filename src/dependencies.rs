@@ -0,0 +1,201 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::parser::{is_stdlib_module_in, ImportEdge, ImportKind, ModuleGraph};
+use crate::python_version::PythonVersion;
+
+/// Reads top-level module names as JSON from stdin and reports which ones the
+/// interpreter can find, without importing them
+const PROBE_SCRIPT: &str = r#"
+import importlib.util, json, sys
+sys.path = [p for p in sys.path if p]
+found = {}
+for name in json.load(sys.stdin):
+    try:
+        found[name] = importlib.util.find_spec(name) is not None
+    except (ImportError, ValueError):
+        found[name] = False
+json.dump({"version": "%d.%d.%d" % sys.version_info[:3], "found": found}, sys.stdout)
+"#;
+
+#[derive(Deserialize)]
+struct ProbeOutput {
+    version: String,
+    found: HashMap<String, bool>,
+}
+
+/// Whether the imports of a project resolve in a target interpreter
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyReport {
+    /// Interpreter the imports were resolved with
+    pub interpreter: PathBuf,
+    /// Version of that interpreter
+    pub python_version: PythonVersion,
+    /// Top-level third-party packages and whether the interpreter finds them
+    pub packages: BTreeMap<String, bool>,
+    /// One diagnostic per import of a module the interpreter cannot find
+    pub missing: Vec<Diagnostic>,
+}
+
+impl DependencyReport {
+    /// Whether every unguarded import resolves
+    pub fn is_ok(&self) -> bool {
+        !self.missing.iter().any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// Classify an import for a target version.
+///
+/// Modules that left the standard library before `target` (e.g. `distutils`
+/// on 3.12) count as third-party, since something must install them.
+pub fn classify_import(edge: &ImportEdge, target: &PythonVersion) -> ImportKind {
+    match edge.kind {
+        ImportKind::Internal => ImportKind::Internal,
+        _ if is_stdlib_module_in(&edge.module, target) => ImportKind::Stdlib,
+        _ => ImportKind::ThirdParty,
+    }
+}
+
+/// Ask `interpreter` which of the top-level `modules` it can find
+pub fn probe_modules(
+    interpreter: &Path,
+    modules: &[&str],
+) -> Result<(PythonVersion, HashMap<String, bool>)> {
+    debug!(
+        "Probing {} for {} modules",
+        interpreter.display(),
+        modules.len()
+    );
+
+    let mut child = Command::new(interpreter)
+        .args(["-c", PROBE_SCRIPT])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run Python at {}", interpreter.display()))?;
+
+    let input = serde_json::to_vec(modules)?;
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("Failed to open stdin of {}", interpreter.display()))?
+        .write_all(&input)
+        .with_context(|| format!("Failed to write to {}", interpreter.display()))?;
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Failed to wait for {}", interpreter.display()))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to probe modules with {}:\n{}",
+            interpreter.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let probe: ProbeOutput = serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("Unexpected output from {}", interpreter.display()))?;
+    Ok((probe.version.parse()?, probe.found))
+}
+
+/// Check that every non-internal import of `graph` resolves in `interpreter`.
+///
+/// Standard library modules are checked too, since embedded interpreters
+/// sometimes ship without parts of it. Imports guarded by
+/// `except ImportError` are reported as warnings.
+pub fn check_dependencies(graph: &ModuleGraph, interpreter: &Path) -> Result<DependencyReport> {
+    info!(
+        "Checking dependencies of {} modules against {}",
+        graph.modules.len(),
+        interpreter.display()
+    );
+
+    let mut top_level: Vec<&str> = graph
+        .modules
+        .values()
+        .flat_map(|node| &node.imports)
+        .filter(|edge| edge.kind != ImportKind::Internal)
+        .filter_map(|edge| edge.module.split('.').next())
+        .collect();
+    top_level.sort_unstable();
+    top_level.dedup();
+
+    let (python_version, found) = probe_modules(interpreter, &top_level)?;
+    let is_found = |module: &str| {
+        let top_level = module.split('.').next().unwrap_or_default();
+        found.get(top_level).copied().unwrap_or(false)
+    };
+
+    let mut packages = BTreeMap::new();
+    let mut missing = Vec::new();
+    for node in graph.modules.values() {
+        for edge in &node.imports {
+            let kind = classify_import(edge, &python_version);
+            if kind == ImportKind::Internal {
+                continue;
+            }
+            let available = is_found(&edge.module);
+            if kind == ImportKind::ThirdParty {
+                let top_level = edge.module.split('.').next().unwrap_or_default();
+                packages.insert(top_level.to_string(), available);
+            }
+            if !available {
+                missing.push(missing_dependency(&node.path, edge, kind, &python_version));
+            }
+        }
+    }
+
+    missing.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    debug!("Found {} imports that do not resolve", missing.len());
+    Ok(DependencyReport {
+        interpreter: interpreter.to_path_buf(),
+        python_version,
+        packages,
+        missing,
+    })
+}
+
+fn missing_dependency(
+    path: &Path,
+    edge: &ImportEdge,
+    kind: ImportKind,
+    python_version: &PythonVersion,
+) -> Diagnostic {
+    let origin = match kind {
+        ImportKind::Stdlib => "standard library module",
+        _ => "module",
+    };
+    let explanation = if edge.guarded {
+        "the import is guarded by `except ImportError`, so the module falls back at runtime"
+    } else if edge.deferred {
+        "the import runs inside a function and fails when that function is called"
+    } else if kind == ImportKind::Stdlib {
+        "this interpreter ships without part of the standard library"
+    } else {
+        "install the package into the target environment or vendor it with the compiled module"
+    };
+
+    Diagnostic {
+        path: path.to_path_buf(),
+        line: edge.line,
+        column: edge.column,
+        code: "missing-dependency".to_string(),
+        severity: if edge.guarded {
+            Severity::Warning
+        } else {
+            Severity::Error
+        },
+        message: format!(
+            "{} `{}` cannot be found by the target Python {}",
+            origin, edge.module, python_version
+        ),
+        explanation: Some(explanation.to_string()),
+    }
+}
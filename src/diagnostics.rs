@@ -0,0 +1,125 @@
+use rustpython_parser::source_code::RandomLocator;
+use rustpython_parser::text_size::TextSize;
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Likely to misbehave after compilation
+    Warning,
+    /// Will fail to compile or run
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in a Python source file before compilation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// File the problem was found in
+    pub path: PathBuf,
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column number
+    pub column: usize,
+    /// Short rule identifier (e.g. `inspect-source`)
+    pub code: String,
+    /// Severity of the problem
+    pub severity: Severity,
+    /// One-line description of the problem
+    pub message: String,
+    /// Longer explanation of why it matters for compiled modules
+    pub explanation: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}[{}]: {}",
+            self.path.display(),
+            self.line,
+            self.column,
+            self.severity,
+            self.code,
+            self.message
+        )?;
+        if let Some(explanation) = &self.explanation {
+            write!(f, "\n  = note: {explanation}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts AST offsets into line/column positions and reads inline suppressions
+pub struct SourceMap<'a> {
+    path: &'a Path,
+    source: &'a str,
+    locator: RandomLocator<'a>,
+}
+
+impl<'a> SourceMap<'a> {
+    /// Create a source map for the given file contents
+    pub fn new(path: &'a Path, source: &'a str) -> Self {
+        Self {
+            path,
+            source,
+            locator: RandomLocator::new(source),
+        }
+    }
+
+    /// Build a diagnostic located at a byte offset
+    pub fn diagnostic(
+        &mut self,
+        offset: TextSize,
+        code: &str,
+        severity: Severity,
+        message: String,
+        explanation: Option<&str>,
+    ) -> Diagnostic {
+        let location = self.locator.locate(offset);
+        Diagnostic {
+            path: self.path.to_path_buf(),
+            line: location.row.get() as usize,
+            column: location.column.get() as usize,
+            code: code.to_string(),
+            severity,
+            message,
+            explanation: explanation.map(str::to_string),
+        }
+    }
+
+    /// Whether a `# py2pyd: ignore` comment on the given line silences `code`.
+    ///
+    /// A bare `# py2pyd: ignore` silences every rule; `# py2pyd: ignore[a, b]`
+    /// only the listed ones.
+    pub fn is_suppressed(&self, line: usize, code: &str) -> bool {
+        let Some(text) = self.source.lines().nth(line.saturating_sub(1)) else {
+            return false;
+        };
+        let Some(index) = text.find("# py2pyd: ignore") else {
+            return false;
+        };
+
+        let rest = &text[index + "# py2pyd: ignore".len()..];
+        match rest.strip_prefix('[') {
+            Some(list) => list
+                .split(']')
+                .next()
+                .unwrap_or_default()
+                .split(',')
+                .any(|item| item.trim() == code),
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,498 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::Serialize;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::{NamedTempFile, TempDir};
+
+use crate::build_tools::{detect_build_tools, Compiler, CompilerKind, Distro};
+use crate::interpreter::{find_installation, PythonInstallation, PythonRequest};
+use crate::uv_compiler::CompileConfig;
+use crate::uv_env::{locate_uv, EnvBackend};
+use crate::uv_install::{UV_OVERRIDE_ENV, UV_VERSION};
+
+/// Free space below which builds are expected to fail
+const MIN_FREE_SPACE: u64 = 100 * 1024 * 1024;
+
+/// Free space below which a build environment may not fit
+const LOW_FREE_SPACE: u64 = 1024 * 1024 * 1024;
+
+/// Smallest module that exercises the headers, compiler and linker
+const TRIVIAL_EXTENSION: &str = r#"#include <Python.h>
+
+static struct PyModuleDef module = {PyModuleDef_HEAD_INIT, "py2pyd_doctor", NULL, -1, NULL};
+
+PyMODINIT_FUNC PyInit_py2pyd_doctor(void) { return PyModule_Create(&module); }
+"#;
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    /// Everything is in order
+    Pass,
+    /// Builds may work, but something is missing or unusual
+    Warn,
+    /// Builds will fail until this is fixed
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pass => "pass",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+        })
+    }
+}
+
+/// One line of the `doctor` report
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorCheck {
+    /// What was checked, e.g. `uv` or `python 3.10`
+    pub name: String,
+    /// Whether it passed
+    pub status: CheckStatus,
+    /// What was found
+    pub detail: String,
+    /// How to fix a warning or failure on this system
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl DoctorCheck {
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: impl Into<String>, detail: impl Into<String>, fix: Option<String>) -> Self {
+        Self {
+            status: CheckStatus::Warn,
+            fix,
+            ..Self::pass(name, detail)
+        }
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, fix: Option<String>) -> Self {
+        Self {
+            status: CheckStatus::Fail,
+            fix,
+            ..Self::pass(name, detail)
+        }
+    }
+}
+
+/// Check everything a build needs: uv, the requested interpreters, their
+/// headers, a working compiler, Cython, disk space and write permissions.
+///
+/// `pythons` are extra interpreters to check, as paths or versions, on top of
+/// `--python-path` and `--python-version` in `config`.
+pub fn run_doctor(config: &CompileConfig, pythons: &[String]) -> Vec<DoctorCheck> {
+    let distro = Distro::detect();
+    info!("Running environment checks ({distro:?})");
+
+    let uv = locate_uv();
+    let mut checks = vec![check_uv(uv.as_deref(), config.env_backend)];
+
+    let mut requested: Vec<String> = config
+        .python_path
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .chain(config.python_version.clone())
+        .chain(pythons.iter().cloned())
+        .collect();
+    requested.dedup();
+
+    let mut interpreters = Vec::new();
+    if requested.is_empty() {
+        interpreters.extend(check_default_interpreter(&mut checks));
+    }
+    for spec in &requested {
+        let name = format!("python {spec}");
+        interpreters.extend(check_interpreter(&name, spec, &mut checks));
+    }
+
+    for (name, info) in &interpreters {
+        checks.push(check_headers(name, info, distro));
+        checks.push(check_link(name, info));
+        checks.push(check_cython(name, info));
+    }
+
+    // The compiler only needs to prove itself once
+    match interpreters.iter().find(|(_, info)| info.has_header) {
+        Some((_, info)) => checks.push(check_compiler(info, distro)),
+        None => checks.push(DoctorCheck::warn(
+            "compiler",
+            "not tested: no interpreter with development headers",
+            None,
+        )),
+    }
+
+    for (label, dir) in directories(config, uv.as_deref()) {
+        checks.push(check_disk_space(&label, &dir));
+        checks.push(check_writable(&label, &dir));
+    }
+    checks
+}
+
+fn check_uv(uv: Option<&Path>, backend: EnvBackend) -> DoctorCheck {
+    let fix = format!(
+        "pass --install-uv to download uv {UV_VERSION} (checksum-verified) into py2pyd's \
+         data directory, or set {UV_OVERRIDE_ENV} to an installed uv"
+    );
+    let Some(uv) = uv else {
+        return match backend {
+            EnvBackend::Uv => DoctorCheck::fail("uv", "not found", Some(fix.clone())),
+            _ => DoctorCheck::warn(
+                "uv",
+                "not found; builds use python -m venv and pip with an installed interpreter",
+                Some(fix.clone()),
+            ),
+        };
+    };
+
+    match command_output(Command::new(uv).arg("--version")) {
+        Ok(version) => DoctorCheck::pass("uv", format!("{} at {}", version, uv.display())),
+        Err(e) => DoctorCheck::fail(
+            "uv",
+            format!("{} does not run: {e:#}", uv.display()),
+            Some(fix.clone()),
+        ),
+    }
+}
+
+fn check_default_interpreter(
+    checks: &mut Vec<DoctorCheck>,
+) -> Option<(String, PythonInstallation)> {
+    match find_installation(&PythonRequest::Any) {
+        Ok(info) => {
+            checks.push(DoctorCheck::pass(
+                "python",
+                format!("{info} (from {})", info.source),
+            ));
+            Some(("python".to_string(), info))
+        }
+        Err(_) => {
+            checks.push(DoctorCheck::warn(
+                "python",
+                "no Python 3 on PATH or in uv, pyenv or conda installs; \
+                 builds need --python-path or --python-version",
+                None,
+            ));
+            None
+        }
+    }
+}
+
+/// Resolve `spec`, a path or a version, and ask it about itself
+fn check_interpreter(
+    name: &str,
+    spec: &str,
+    checks: &mut Vec<DoctorCheck>,
+) -> Option<(String, PythonInstallation)> {
+    let name = name.to_string();
+    let request = match spec.parse::<PythonRequest>() {
+        Ok(request) => request,
+        Err(e) => {
+            checks.push(DoctorCheck::fail(&name, format!("{e:#}"), None));
+            return None;
+        }
+    };
+
+    match find_installation(&request) {
+        Ok(info) => {
+            checks.push(DoctorCheck::pass(
+                &name,
+                format!("{info} (from {})", info.source),
+            ));
+            Some((name, info))
+        }
+        Err(_) if !matches!(request, PythonRequest::Path(_)) => {
+            checks.push(DoctorCheck::warn(
+                &name,
+                "not installed; uv downloads it on the first build",
+                Some(format!("uv python install {spec}")),
+            ));
+            None
+        }
+        Err(e) => {
+            checks.push(DoctorCheck::fail(&name, format!("{e:#}"), None));
+            None
+        }
+    }
+}
+
+fn check_headers(name: &str, info: &PythonInstallation, distro: Distro) -> DoctorCheck {
+    let name = format!("{name} headers");
+    if info.has_header {
+        return DoctorCheck::pass(name, format!("Python.h in {}", info.include.display()));
+    }
+
+    DoctorCheck::fail(
+        name,
+        format!("Python.h not found in {}", info.include.display()),
+        Some(info.headers_fix(distro)),
+    )
+}
+
+fn check_link(name: &str, info: &PythonInstallation) -> DoctorCheck {
+    let name = format!("{name} libpython");
+    let problems = info.link_problems();
+    if problems.is_empty() {
+        let detail = if cfg!(windows) {
+            format!("import library in {}", info.libdir.display())
+        } else if info.shared {
+            format!("{} in {}", info.ldlibrary, info.libdir.display())
+        } else {
+            "statically linked; extensions resolve symbols from the interpreter".to_string()
+        };
+        return DoctorCheck::pass(name, detail);
+    }
+
+    let detail = problems.join("; ");
+    if cfg!(windows) {
+        DoctorCheck::fail(
+            name,
+            detail,
+            Some(
+                "reinstall Python from python.org, whose installer includes the libraries"
+                    .to_string(),
+            ),
+        )
+    } else {
+        DoctorCheck::warn(name, detail, None)
+    }
+}
+
+fn check_cython(name: &str, info: &PythonInstallation) -> DoctorCheck {
+    let name = format!("{name} cython");
+    if info.cython {
+        DoctorCheck::pass(name, "installed")
+    } else {
+        // Builds install Cython into their own environment
+        DoctorCheck::warn(
+            name,
+            "not installed; builds download it from PyPI into the build environment",
+            Some(format!(
+                "{} -m pip install cython",
+                info.executable.display()
+            )),
+        )
+    }
+}
+
+fn compiler_fix(distro: Distro) -> Option<String> {
+    if cfg!(windows) {
+        Some("install Visual Studio Build Tools with the \"C++ build tools\" workload: https://visualstudio.microsoft.com/visual-cpp-build-tools/".to_string())
+    } else if cfg!(target_os = "macos") {
+        Some("xcode-select --install".to_string())
+    } else {
+        distro.install_command(distro.compiler_packages())
+    }
+}
+
+/// Compile, link and import a trivial extension with `info`'s headers
+fn check_compiler(info: &PythonInstallation, distro: Distro) -> DoctorCheck {
+    let Some(compiler) = detect_build_tools().selected else {
+        return DoctorCheck::fail("compiler", "no C compiler found", compiler_fix(distro));
+    };
+
+    match build_trivial_extension(&compiler, info) {
+        Ok(()) => DoctorCheck::pass(
+            "compiler",
+            format!(
+                "{} builds and imports an extension; {}",
+                compiler, compiler.reason
+            ),
+        ),
+        Err(e) => DoctorCheck::fail(
+            "compiler",
+            format!("{} cannot build an extension: {e:#}", compiler),
+            compiler_fix(distro),
+        ),
+    }
+}
+
+fn build_trivial_extension(compiler: &Compiler, info: &PythonInstallation) -> Result<()> {
+    let dir = TempDir::new().with_context(|| "Failed to create temporary directory")?;
+    let source = dir.path().join("py2pyd_doctor.c");
+    let output = dir.path().join(format!("py2pyd_doctor{}", info.ext_suffix));
+    fs::write(&source, TRIVIAL_EXTENSION)?;
+
+    let mut cmd = Command::new(&compiler.path);
+    if compiler.kind == CompilerKind::Msvc {
+        cmd.args(["/nologo", "/LD"])
+            .arg(format!("/I{}", info.include.display()))
+            .arg(&source)
+            .arg(format!("/Fe{}", output.display()))
+            .arg(format!("/Fo{}\\", dir.path().display()))
+            .arg("/link")
+            .arg(format!("/LIBPATH:{}", info.libdir.display()));
+    } else {
+        cmd.args(["-shared", "-fPIC"])
+            .arg("-I")
+            .arg(&info.include)
+            .arg(&source)
+            .arg("-o")
+            .arg(&output);
+        if cfg!(windows) {
+            cmd.arg("-L").arg(&info.libdir).arg(format!(
+                "-lpython{}{}",
+                info.version.major, info.version.minor
+            ));
+        } else if cfg!(target_os = "macos") {
+            cmd.args(["-undefined", "dynamic_lookup"]);
+        }
+    }
+    command_output(&mut cmd).with_context(|| "Compilation failed")?;
+
+    let import = format!(
+        "import sys; sys.path.insert(0, {:?}); import py2pyd_doctor",
+        dir.path().to_string_lossy()
+    );
+    command_output(Command::new(&info.executable).args(["-c", &import]))
+        .with_context(|| "The built extension does not import")?;
+    Ok(())
+}
+
+/// Directories builds write to, with a label for the report
+fn directories(config: &CompileConfig, uv: Option<&Path>) -> Vec<(String, PathBuf)> {
+    let mut dirs = vec![("temp".to_string(), env::temp_dir())];
+
+    let uv_cache = env::var_os("UV_CACHE_DIR")
+        .map(PathBuf::from)
+        .or_else(|| {
+            let uv = uv?;
+            command_output(Command::new(uv).args(["cache", "dir"]))
+                .ok()
+                .map(PathBuf::from)
+        })
+        .or_else(|| dirs::cache_dir().map(|dir| dir.join("uv")));
+    dirs.extend(uv_cache.map(|dir| ("uv cache".to_string(), dir)));
+
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(("py2pyd home".to_string(), home.join(".py2pyd")));
+    }
+    if let Some(output_dir) = &config.output_dir {
+        dirs.push(("output".to_string(), output_dir.clone()));
+    }
+    dirs
+}
+
+/// `path`, or its closest parent that exists
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|dir| dir.is_dir())
+}
+
+fn check_disk_space(label: &str, dir: &Path) -> DoctorCheck {
+    let name = format!("disk {label}");
+    let Some(existing) = existing_ancestor(dir) else {
+        return DoctorCheck::warn(name, format!("{} does not exist", dir.display()), None);
+    };
+
+    match available_space(existing) {
+        Ok(free) => {
+            let detail = format!("{} free in {}", format_size(free), dir.display());
+            let fix = || {
+                Some(format!(
+                    "free up space on the drive holding {}",
+                    dir.display()
+                ))
+            };
+            if free < MIN_FREE_SPACE {
+                DoctorCheck::fail(name, detail, fix())
+            } else if free < LOW_FREE_SPACE {
+                DoctorCheck::warn(name, detail, fix())
+            } else {
+                DoctorCheck::pass(name, detail)
+            }
+        }
+        Err(e) => {
+            debug!("Failed to measure free space in {}: {e:#}", dir.display());
+            DoctorCheck::warn(
+                name,
+                format!("could not measure free space in {}", dir.display()),
+                None,
+            )
+        }
+    }
+}
+
+/// Bytes available to unprivileged users on the file system holding `dir`
+fn available_space(dir: &Path) -> Result<u64> {
+    if cfg!(windows) {
+        let script = format!(
+            "(Get-Item -LiteralPath '{}').PSDrive.Free",
+            dir.display().to_string().replace('\'', "''")
+        );
+        let output = command_output(
+            Command::new("powershell")
+                .args(["-NoProfile", "-Command"])
+                .arg(script),
+        )?;
+        return Ok(output.parse()?);
+    }
+
+    // POSIX output: a header line, then "filesystem blocks used available ..."
+    let output = command_output(Command::new("df").arg("-Pk").arg(dir))?;
+    let available = output
+        .lines()
+        .nth(1)
+        .and_then(|line| line.split_whitespace().nth(3))
+        .ok_or_else(|| anyhow!("Unexpected df output: {}", output))?;
+    Ok(available.parse::<u64>()? * 1024)
+}
+
+/// `bytes` in GiB or MiB, for people
+pub fn format_size(bytes: u64) -> String {
+    const GIB: u64 = 1024 * 1024 * 1024;
+    if bytes >= GIB {
+        format!("{:.1} GiB", bytes as f64 / GIB as f64)
+    } else {
+        format!("{} MiB", bytes / (1024 * 1024))
+    }
+}
+
+fn check_writable(label: &str, dir: &Path) -> DoctorCheck {
+    let name = format!("write {label}");
+    let Some(existing) = existing_ancestor(dir) else {
+        return DoctorCheck::warn(name, format!("{} does not exist", dir.display()), None);
+    };
+
+    match NamedTempFile::new_in(existing) {
+        Ok(_) => DoctorCheck::pass(name, format!("{} is writable", existing.display())),
+        Err(e) => DoctorCheck::fail(
+            name,
+            format!("cannot write to {}: {e}", existing.display()),
+            Some(format!(
+                "make {} writable for this user, or point py2pyd elsewhere",
+                existing.display()
+            )),
+        ),
+    }
+}
+
+/// Run `cmd` and return its trimmed stdout, failing on a non-zero exit
+fn command_output(cmd: &mut Command) -> Result<String> {
+    let output = cmd.output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let message = if stderr.trim().is_empty() {
+            stdout
+        } else {
+            stderr
+        };
+        return Err(anyhow!("{}", message.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,379 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// `e_machine` of x86
+pub const EM_386: u16 = 3;
+/// `e_machine` of PowerPC64
+pub const EM_PPC64: u16 = 21;
+/// `e_machine` of IBM S/390
+pub const EM_S390: u16 = 22;
+/// `e_machine` of 32-bit ARM
+pub const EM_ARM: u16 = 40;
+/// `e_machine` of x86-64
+pub const EM_X86_64: u16 = 62;
+/// `e_machine` of AArch64
+pub const EM_AARCH64: u16 = 183;
+/// `e_machine` of RISC-V
+pub const EM_RISCV: u16 = 243;
+
+/// `sh_type` of the full symbol table, removed by `strip`
+const SHT_SYMTAB: u32 = 2;
+/// `sh_type` of a string table
+const SHT_STRTAB: u32 = 3;
+/// `sh_type` of the dynamic symbol table
+const SHT_DYNSYM: u32 = 11;
+/// `sh_type` of the dynamic linking table
+const SHT_DYNAMIC: u32 = 6;
+/// `sh_type` of the symbol versions needed from other objects
+const SHT_GNU_VERNEED: u32 = 0x6fff_fffe;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_RPATH: u64 = 15;
+const DT_RUNPATH: u64 = 29;
+
+/// The identification and machine fields of an ELF header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElfHeader {
+    /// 64-bit (`ELFCLASS64`) rather than 32-bit
+    pub is_64: bool,
+    /// Little-endian (`ELFDATA2LSB`) rather than big-endian
+    pub little_endian: bool,
+    /// Architecture, one of the `EM_*` constants
+    pub machine: u16,
+}
+
+impl ElfHeader {
+    /// Parse the start of an ELF file
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 20 || bytes[..4] != *b"\x7fELF" {
+            return Err(anyhow!("Not an ELF file"));
+        }
+        let is_64 = match bytes[4] {
+            1 => false,
+            2 => true,
+            class => return Err(anyhow!("Unknown ELF class {}", class)),
+        };
+        let little_endian = match bytes[5] {
+            1 => true,
+            2 => false,
+            data => return Err(anyhow!("Unknown ELF data encoding {}", data)),
+        };
+        let machine = [bytes[18], bytes[19]];
+        let machine = if little_endian {
+            u16::from_le_bytes(machine)
+        } else {
+            u16::from_be_bytes(machine)
+        };
+        Ok(Self {
+            is_64,
+            little_endian,
+            machine,
+        })
+    }
+
+    /// Read the header of the ELF file at `path`
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut bytes = [0; 20];
+        File::open(path)
+            .and_then(|mut file| file.read_exact(&mut bytes))
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&bytes).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+}
+
+impl fmt::Display for ElfHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}-bit, {}-endian)",
+            machine_name(self.machine),
+            if self.is_64 { 64 } else { 32 },
+            if self.little_endian { "little" } else { "big" }
+        )
+    }
+}
+
+/// Human-readable name of an `e_machine` value
+pub fn machine_name(machine: u16) -> String {
+    match machine {
+        EM_386 => "x86".to_string(),
+        EM_PPC64 => "ppc64".to_string(),
+        EM_S390 => "s390x".to_string(),
+        EM_ARM => "arm".to_string(),
+        EM_X86_64 => "x86_64".to_string(),
+        EM_AARCH64 => "aarch64".to_string(),
+        EM_RISCV => "riscv".to_string(),
+        other => format!("machine {other}"),
+    }
+}
+
+/// The dynamic linking information of an ELF shared object or executable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfFile {
+    /// Class, byte order and architecture
+    pub header: ElfHeader,
+    /// Shared libraries needed (`DT_NEEDED`), in link order
+    pub needed: Vec<String>,
+    /// `DT_RPATH` entries, e.g. `$ORIGIN`
+    pub rpath: Vec<String>,
+    /// `DT_RUNPATH` entries
+    pub runpath: Vec<String>,
+    /// Symbol versions needed from each library, e.g. `libc.so.6` to `GLIBC_2.17`
+    pub versions: BTreeMap<String, Vec<String>>,
+    /// Symbols other objects can link against, e.g. `PyInit_module`
+    pub exports: Vec<String>,
+    /// Whether the full symbol table (`.symtab`) is still present
+    pub has_symtab: bool,
+}
+
+impl ElfFile {
+    /// Parse the dynamic section and version requirements of an ELF file
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let header = ElfHeader::parse(bytes)?;
+        let reader = Reader::new(bytes, header.little_endian, header.is_64);
+        let sections = reader.sections()?;
+        let mut file = Self {
+            header,
+            needed: vec![],
+            rpath: vec![],
+            runpath: vec![],
+            versions: BTreeMap::new(),
+            exports: vec![],
+            has_symtab: sections.iter().any(|section| section.kind == SHT_SYMTAB),
+        };
+
+        for section in &sections {
+            let strings = sections
+                .get(section.link as usize)
+                .filter(|strings| strings.kind == SHT_STRTAB);
+            match (section.kind, strings) {
+                (SHT_DYNAMIC, Some(strings)) => reader.read_dynamic(section, strings, &mut file)?,
+                (SHT_GNU_VERNEED, Some(strings)) => {
+                    reader.read_verneed(section, strings, &mut file.versions)?;
+                }
+                (SHT_DYNSYM, Some(strings)) => {
+                    reader.read_exports(section, strings, &mut file.exports)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(file)
+    }
+
+    /// Read the ELF file at `path`
+    pub fn read(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&bytes).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+}
+
+/// The fields of a section header the parser needs
+struct Section {
+    kind: u32,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+}
+
+/// Bounds-checked reads in the byte order and word size of a binary
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+    is_64: bool,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8], little_endian: bool, is_64: bool) -> Self {
+        Self {
+            bytes,
+            little_endian,
+            is_64,
+        }
+    }
+
+    pub(crate) fn slice(&self, offset: u64, len: u64) -> Result<&'a [u8]> {
+        usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(len).ok())
+            .and_then(|(start, len)| self.bytes.get(start..start.checked_add(len)?))
+            .ok_or_else(|| anyhow!("Truncated ELF file"))
+    }
+
+    pub(crate) fn u8(&self, offset: u64) -> Result<u8> {
+        Ok(self.slice(offset, 1)?[0])
+    }
+
+    pub(crate) fn u16(&self, offset: u64) -> Result<u16> {
+        let bytes = self.slice(offset, 2)?.try_into()?;
+        Ok(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    pub(crate) fn u32(&self, offset: u64) -> Result<u32> {
+        let bytes = self.slice(offset, 4)?.try_into()?;
+        Ok(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    pub(crate) fn u64(&self, offset: u64) -> Result<u64> {
+        let bytes = self.slice(offset, 8)?.try_into()?;
+        Ok(if self.little_endian {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    }
+
+    /// An address-sized word: 8 bytes for 64-bit files, 4 for 32-bit ones
+    pub(crate) fn word(&self, offset: u64) -> Result<u64> {
+        if self.is_64 {
+            self.u64(offset)
+        } else {
+            self.u32(offset).map(u64::from)
+        }
+    }
+
+    pub(crate) fn word_size(&self) -> u64 {
+        if self.is_64 {
+            8
+        } else {
+            4
+        }
+    }
+
+    /// The NUL-terminated string at `offset`, at most `max_len` bytes long
+    pub(crate) fn c_string(&self, offset: u64, max_len: u64) -> Result<String> {
+        let len = max_len.min((self.bytes.len() as u64).saturating_sub(offset));
+        let bytes = self.slice(offset, len)?;
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+
+    /// The NUL-terminated string at `offset` in the string table `strings`
+    fn string(&self, strings: &Section, offset: u64) -> Result<String> {
+        self.c_string(
+            strings.offset.saturating_add(offset),
+            strings.size.saturating_sub(offset),
+        )
+    }
+
+    fn sections(&self) -> Result<Vec<Section>> {
+        let (offset, entry_size, count) = if self.is_64 {
+            (self.u64(0x28)?, self.u16(0x3a)?, self.u16(0x3c)?)
+        } else {
+            (u64::from(self.u32(0x20)?), self.u16(0x2e)?, self.u16(0x30)?)
+        };
+        (0..u64::from(count))
+            .map(|index| {
+                let base = offset.saturating_add(index * u64::from(entry_size));
+                if self.is_64 {
+                    Ok(Section {
+                        kind: self.u32(base + 4)?,
+                        offset: self.u64(base + 24)?,
+                        size: self.u64(base + 32)?,
+                        link: self.u32(base + 40)?,
+                        info: self.u32(base + 44)?,
+                    })
+                } else {
+                    Ok(Section {
+                        kind: self.u32(base + 4)?,
+                        offset: u64::from(self.u32(base + 16)?),
+                        size: u64::from(self.u32(base + 20)?),
+                        link: self.u32(base + 24)?,
+                        info: self.u32(base + 28)?,
+                    })
+                }
+            })
+            .collect()
+    }
+
+    fn read_dynamic(&self, section: &Section, strings: &Section, file: &mut ElfFile) -> Result<()> {
+        let entry_size = 2 * self.word_size();
+        for index in 0..section.size / entry_size {
+            let entry = section.offset.saturating_add(index * entry_size);
+            let tag = self.word(entry)?;
+            let value = self.word(entry + self.word_size())?;
+            let paths = |s: String| s.split(':').map(str::to_string).collect::<Vec<_>>();
+            match tag {
+                DT_NULL => break,
+                DT_NEEDED => file.needed.push(self.string(strings, value)?),
+                DT_RPATH => file.rpath.extend(paths(self.string(strings, value)?)),
+                DT_RUNPATH => file.runpath.extend(paths(self.string(strings, value)?)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Read `Elf_Verneed` entries, each followed by its `Elf_Vernaux` entries
+    fn read_verneed(
+        &self,
+        section: &Section,
+        strings: &Section,
+        versions: &mut BTreeMap<String, Vec<String>>,
+    ) -> Result<()> {
+        let mut entry = section.offset;
+        for _ in 0..section.info {
+            let count = self.u16(entry + 2)?;
+            let file = self.string(strings, u64::from(self.u32(entry + 4)?))?;
+            let names = versions.entry(file).or_default();
+
+            let mut aux = entry.saturating_add(u64::from(self.u32(entry + 8)?));
+            for _ in 0..count {
+                names.push(self.string(strings, u64::from(self.u32(aux + 8)?))?);
+                aux = aux.saturating_add(u64::from(self.u32(aux + 12)?));
+            }
+
+            let next = self.u32(entry + 12)?;
+            if next == 0 {
+                break;
+            }
+            entry = entry.saturating_add(u64::from(next));
+        }
+        Ok(())
+    }
+
+    /// Read the defined, default-visibility global symbols of `.dynsym`
+    fn read_exports(
+        &self,
+        section: &Section,
+        strings: &Section,
+        exports: &mut Vec<String>,
+    ) -> Result<()> {
+        let entry_size = if self.is_64 { 24 } else { 16 };
+        for index in 1..section.size / entry_size {
+            let entry = section.offset.saturating_add(index * entry_size);
+            let (info, other, section_index) = if self.is_64 {
+                (
+                    self.u8(entry + 4)?,
+                    self.u8(entry + 5)?,
+                    self.u16(entry + 6)?,
+                )
+            } else {
+                (
+                    self.u8(entry + 12)?,
+                    self.u8(entry + 13)?,
+                    self.u16(entry + 14)?,
+                )
+            };
+            // STB_GLOBAL or STB_WEAK, STV_DEFAULT, not SHN_UNDEF
+            let global = matches!(info >> 4, 1 | 2);
+            if global && other & 3 == 0 && section_index != 0 {
+                exports.push(self.string(strings, u64::from(self.u32(entry)?))?);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,592 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use ring::digest::{Context as DigestContext, SHA256};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tempfile::Builder;
+
+use crate::lock::{FileLock, LockMode};
+use crate::uv_env::EnvBackend;
+
+/// File in a cached environment's directory describing it; written last, so
+/// a directory without it was never finished
+pub const ENV_METADATA_FILE: &str = "py2pyd-env.json";
+
+/// Bumped when the key inputs or the layout change, invalidating older environments
+const ENV_CACHE_VERSION: u32 = 1;
+
+/// Prefix of the directories environments are built in before they are moved
+/// into place
+const TEMP_PREFIX: &str = ".tmp-";
+
+/// Half-built environments older than this are left over from a crashed run
+const STALE_BUILD_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What a cached environment is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvKind {
+    /// A virtual environment
+    Venv,
+    /// Packages installed into a directory on `PYTHONPATH` of an interpreter
+    /// used without a venv
+    Site,
+}
+
+/// What a cached environment is built from; environments are reused only for
+/// the same spec
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvSpec {
+    /// Venv or package directory
+    pub kind: EnvKind,
+    /// Interpreter it is created from, or what uv is asked for when it
+    /// downloads one (e.g. `uv:3.11`)
+    pub interpreter: String,
+    /// Python version of the interpreter, or the version asked for
+    pub python_version: Option<String>,
+    /// Tool that created it
+    pub backend: EnvBackend,
+    /// Installed requirements, sorted
+    pub packages: Vec<String>,
+}
+
+impl EnvSpec {
+    /// A spec for `packages` in any order
+    pub fn new(
+        kind: EnvKind,
+        interpreter: impl Into<String>,
+        python_version: Option<String>,
+        backend: EnvBackend,
+        packages: &[String],
+    ) -> Self {
+        let mut packages = packages.to_vec();
+        packages.sort();
+        packages.dedup();
+        Self {
+            kind,
+            interpreter: interpreter.into(),
+            python_version,
+            backend,
+            packages,
+        }
+    }
+
+    /// Short hash of the spec naming the environment's directory
+    pub fn key(&self) -> String {
+        let mut context = DigestContext::new(&SHA256);
+        context.update(&ENV_CACHE_VERSION.to_le_bytes());
+        context.update(&serde_json::to_vec(self).expect("EnvSpec serializes"));
+
+        let mut hex = String::with_capacity(16);
+        for byte in &context.finish().as_ref()[..8] {
+            write!(hex, "{byte:02x}").unwrap();
+        }
+        hex
+    }
+}
+
+/// A cached environment, as recorded in its [`ENV_METADATA_FILE`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedEnv {
+    /// Layout version
+    pub version: u32,
+    /// [`EnvSpec::key`] of `spec`
+    pub key: String,
+    /// What it was built from
+    pub spec: EnvSpec,
+    /// py2pyd version that built it
+    pub py2pyd: String,
+    /// When it was built, in seconds since the Unix epoch
+    pub created: u64,
+    /// When a build last used it, in seconds since the Unix epoch
+    pub last_used: u64,
+    /// Directory holding the metadata and `env`
+    #[serde(skip_deserializing)]
+    pub path: PathBuf,
+}
+
+impl CachedEnv {
+    /// The venv or package directory
+    pub fn env_dir(&self) -> PathBuf {
+        self.path.join("env")
+    }
+
+    /// The interpreter builds run: the venv's, or the one the packages are for
+    pub fn python(&self) -> PathBuf {
+        match self.spec.kind {
+            EnvKind::Venv => venv_python(&self.env_dir()),
+            EnvKind::Site => PathBuf::from(&self.spec.interpreter),
+        }
+    }
+
+    /// Time since a build last used it
+    pub fn idle(&self) -> Duration {
+        Duration::from_secs(now().saturating_sub(self.last_used))
+    }
+
+    /// Check that it can still be used: the directory is there, and the
+    /// interpreter runs and is still the recorded version
+    pub fn validate(&self) -> Result<()> {
+        if self.version != ENV_CACHE_VERSION || self.key != self.spec.key() {
+            return Err(anyhow!("made by another py2pyd version"));
+        }
+        let env_dir = self.env_dir();
+        if !env_dir.is_dir() {
+            return Err(anyhow!("{} is missing", env_dir.display()));
+        }
+
+        let python = self.python();
+        let output = Command::new(&python)
+            .args([
+                "-c",
+                "import sys; print('.'.join(map(str, sys.version_info[:3])))",
+            ])
+            .output()
+            .with_context(|| format!("{} does not run", python.display()))?;
+        if !output.status.success() {
+            return Err(anyhow!("{} does not run", python.display()));
+        }
+        let reported = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if let Some(version) = &self.spec.python_version {
+            if !version_matches(version, &reported) {
+                return Err(anyhow!(
+                    "{} is now Python {}, not {}",
+                    python.display(),
+                    reported,
+                    version
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the metadata, replacing the file atomically
+    fn save(&self) -> Result<()> {
+        let path = self.path.join(ENV_METADATA_FILE);
+        let temp = self
+            .path
+            .join(format!("{ENV_METADATA_FILE}.{}", std::process::id()));
+        fs::write(&temp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", temp.display()))?;
+        fs::rename(&temp, &path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+impl fmt::Display for EnvKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Venv => "venv",
+            Self::Site => "site",
+        })
+    }
+}
+
+impl fmt::Display for CachedEnv {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}  {} ({}) of {}",
+            self.key, self.spec.kind, self.spec.backend, self.spec.interpreter
+        )?;
+        if let Some(version) = &self.spec.python_version {
+            write!(f, ", Python {version}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A cached environment in use: a shared lock on it is held until this is
+/// dropped, so other processes do not remove it meanwhile
+#[derive(Debug)]
+pub struct LockedEnv {
+    env: CachedEnv,
+    lock: FileLock,
+}
+
+impl LockedEnv {
+    /// Stop using the environment, releasing the lock
+    pub fn release(self) -> CachedEnv {
+        self.env
+    }
+}
+
+impl Deref for LockedEnv {
+    type Target = CachedEnv;
+
+    fn deref(&self) -> &CachedEnv {
+        &self.env
+    }
+}
+
+/// The cached build environments in one directory, by default
+/// `<data dir>/py2pyd/envs`.
+///
+/// Each environment is built in a temporary directory next to the others and
+/// renamed into place when it is complete, so concurrent builds never see a
+/// half-built one. `<key>.lock` next to it is held exclusively while it is
+/// created or removed, and shared while builds use it.
+#[derive(Debug, Clone)]
+pub struct EnvCache {
+    root: PathBuf,
+}
+
+impl EnvCache {
+    /// The cache in `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The cache in py2pyd's data directory
+    pub fn open() -> Result<Self> {
+        let data_dir =
+            dirs::data_dir().ok_or_else(|| anyhow!("Failed to determine data directory"))?;
+        Ok(Self::new(data_dir.join("py2pyd").join("envs")))
+    }
+
+    /// Directory the environments are in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The lock file of the environment with `key`
+    pub fn lock_path(&self, key: &str) -> PathBuf {
+        self.root.join(format!("{key}.lock"))
+    }
+
+    /// The environment for `spec`, if it is cached and still valid.
+    ///
+    /// An invalid one is removed; a valid one is marked as used.
+    pub fn lookup(&self, spec: &EnvSpec) -> Result<Option<CachedEnv>> {
+        let key = spec.key();
+        let _lock = FileLock::acquire(
+            &self.lock_path(&key),
+            LockMode::Exclusive,
+            &format!("environment {key}"),
+        )?;
+        self.lookup_locked(spec)
+    }
+
+    /// [`EnvCache::lookup`], with the exclusive lock held
+    fn lookup_locked(&self, spec: &EnvSpec) -> Result<Option<CachedEnv>> {
+        let dir = self.root.join(spec.key());
+        let Some(env) = read_metadata(&dir) else {
+            return Ok(None);
+        };
+        if env.spec != *spec {
+            warn!(
+                "Cached environment {} does not match its key; rebuilding it",
+                dir.display()
+            );
+            remove_dir(&dir)?;
+            return Ok(None);
+        }
+        if let Err(e) = env.validate() {
+            warn!(
+                "Cached environment {} is unusable ({:#}); rebuilding it",
+                dir.display(),
+                e
+            );
+            remove_dir(&dir)?;
+            return Ok(None);
+        }
+        Ok(Some(touch(env)))
+    }
+
+    /// The environment for `spec` locked for use, if it is cached and valid;
+    /// nothing is built
+    pub fn find(&self, spec: &EnvSpec) -> Result<Option<LockedEnv>> {
+        let key = spec.key();
+        let lock = FileLock::acquire(
+            &self.lock_path(&key),
+            LockMode::Shared,
+            &format!("environment {key}"),
+        )?;
+        Ok(read_metadata(&self.root.join(&key))
+            .filter(|env| env.spec == *spec && env.validate().is_ok())
+            .map(|env| LockedEnv { env, lock }))
+    }
+
+    /// The environment for `spec`, built with `build` when it is not cached,
+    /// locked for use.
+    ///
+    /// `build` is given the `env` directory to create the environment in.
+    /// When another process is building the same environment, this waits for
+    /// it and uses the result.
+    pub fn get_or_create(
+        &self,
+        spec: &EnvSpec,
+        build: impl FnOnce(&Path) -> Result<()>,
+    ) -> Result<LockedEnv> {
+        let key = spec.key();
+        let lock_path = self.lock_path(&key);
+        let what = format!("environment {key}");
+        let dir = self.root.join(&key);
+        let mut build = Some(build);
+        loop {
+            // Reuse a valid environment (or the one just built) without
+            // waiting for other users
+            let built = build.is_none();
+            let lock = FileLock::acquire(&lock_path, LockMode::Shared, &what)?;
+            if let Some(env) = read_metadata(&dir) {
+                if env.spec == *spec && (built || env.validate().is_ok()) {
+                    if !built {
+                        info!("Reusing cached environment {}", dir.display());
+                    }
+                    return Ok(LockedEnv {
+                        env: touch(env),
+                        lock,
+                    });
+                }
+            }
+            drop(lock);
+
+            {
+                let _lock = FileLock::acquire(&lock_path, LockMode::Exclusive, &what)?;
+                if self.lookup_locked(spec)?.is_none() {
+                    let build = build
+                        .take()
+                        .ok_or_else(|| anyhow!("Cached environment {} keeps disappearing", key))?;
+                    self.build(spec, &dir, build)?;
+                }
+            }
+            // Removed again before the shared lock was taken; build it again
+        }
+    }
+
+    /// Build the environment for `spec` and move it to `dir`, with the
+    /// exclusive lock held
+    fn build(
+        &self,
+        spec: &EnvSpec,
+        dir: &Path,
+        build: impl FnOnce(&Path) -> Result<()>,
+    ) -> Result<()> {
+        fs::create_dir_all(&self.root)
+            .with_context(|| format!("Failed to create directory: {}", self.root.display()))?;
+        let temp_dir = Builder::new()
+            .prefix(TEMP_PREFIX)
+            .tempdir_in(&self.root)
+            .with_context(|| format!("Failed to create a directory in {}", self.root.display()))?;
+
+        let env = CachedEnv {
+            version: ENV_CACHE_VERSION,
+            key: spec.key(),
+            spec: spec.clone(),
+            py2pyd: env!("CARGO_PKG_VERSION").to_string(),
+            created: now(),
+            last_used: now(),
+            path: temp_dir.path().to_path_buf(),
+        };
+        build(&env.env_dir())?;
+        env.save()?;
+
+        // A leftover without metadata is not an environment anyone uses
+        if dir.exists() {
+            remove_dir(dir)?;
+        }
+        fs::rename(temp_dir.path(), dir)
+            .with_context(|| format!("Failed to move {} into place", dir.display()))?;
+        let _ = temp_dir.keep();
+        info!("Cached environment {}", dir.display());
+        Ok(())
+    }
+
+    /// All complete environments, most recently used first
+    pub fn list(&self) -> Result<Vec<CachedEnv>> {
+        let mut envs: Vec<CachedEnv> = self
+            .entries()?
+            .iter()
+            .filter_map(|dir| read_metadata(dir))
+            .collect();
+        envs.sort_by_key(|env| std::cmp::Reverse(env.last_used));
+        Ok(envs)
+    }
+
+    /// Remove the environment whose key starts with `key`, unless a build is
+    /// using it
+    pub fn remove(&self, key: &str) -> Result<CachedEnv> {
+        let mut matches: Vec<CachedEnv> = self
+            .list()?
+            .into_iter()
+            .filter(|env| !key.is_empty() && env.key.starts_with(key))
+            .collect();
+        let env = match matches.len() {
+            0 => return Err(anyhow!("No cached environment matches '{}'", key)),
+            1 => matches.remove(0),
+            _ => {
+                return Err(anyhow!(
+                    "'{}' matches several environments: {}",
+                    key,
+                    matches
+                        .iter()
+                        .map(|env| env.key.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            }
+        };
+
+        let lock_path = self.lock_path(&env.key);
+        let Some(_lock) = FileLock::try_acquire(&lock_path, LockMode::Exclusive)? else {
+            return Err(anyhow!(
+                "Environment {} is in use by another py2pyd process",
+                env.key
+            ));
+        };
+        remove_dir(&env.path)?;
+        Ok(env)
+    }
+
+    /// Stop using `env` and remove it, unless another process is using it too.
+    /// Returns whether it was removed.
+    pub fn remove_if_unused(&self, env: LockedEnv) -> Result<bool> {
+        let LockedEnv { env, lock } = env;
+        drop(lock);
+        let Some(_lock) = FileLock::try_acquire(&self.lock_path(&env.key), LockMode::Exclusive)?
+        else {
+            info!(
+                "Keeping {}; another py2pyd process is using it",
+                env.path.display()
+            );
+            return Ok(false);
+        };
+        if env.path.exists() {
+            remove_dir(&env.path)?;
+        }
+        Ok(true)
+    }
+
+    /// Remove broken and unfinished environments, and those not used for
+    /// `older_than` (or all with `Duration::ZERO`). Environments in use are
+    /// left alone. Returns what was removed.
+    pub fn prune(&self, older_than: Option<Duration>) -> Result<Vec<PathBuf>> {
+        let mut removed = vec![];
+        for dir in self.entries()? {
+            let name = dir.file_name().unwrap_or_default().to_string_lossy();
+            let _lock = if name.starts_with(TEMP_PREFIX) {
+                None
+            } else {
+                match FileLock::try_acquire(&self.lock_path(&name), LockMode::Exclusive)? {
+                    Some(lock) => Some(lock),
+                    None => {
+                        debug!("Skipping {}; it is in use", dir.display());
+                        continue;
+                    }
+                }
+            };
+
+            let reason = match read_metadata(&dir) {
+                Some(env) => match env.validate() {
+                    Err(e) => Some(format!("{e:#}")),
+                    Ok(()) if older_than.is_some_and(|age| env.idle() >= age) => {
+                        Some(format!("unused for {} days", env.idle().as_secs() / 86400))
+                    }
+                    Ok(()) => None,
+                },
+                None if !name.starts_with(TEMP_PREFIX) => Some("incomplete".into()),
+                // Possibly still being built by another process
+                None if modified_age(&dir) < STALE_BUILD_AGE => None,
+                None => Some("left from a failed build".into()),
+            };
+            if let Some(reason) = reason {
+                info!("Removing {}: {}", dir.display(), reason);
+                remove_dir(&dir)?;
+                removed.push(dir);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Directories in the cache
+    fn entries(&self) -> Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.root.display()))
+            }
+        };
+        Ok(entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect())
+    }
+}
+
+/// The interpreter of the venv at `venv`
+pub fn venv_python(venv: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv.join("Scripts").join("python.exe")
+    } else {
+        venv.join("bin").join("python")
+    }
+}
+
+/// Total size of the files under `path`, in bytes
+pub fn dir_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Whether `reported` (e.g. `3.11.7`) is the `requested` version (e.g. `3.11`),
+/// comparing whole components
+fn version_matches(requested: &str, reported: &str) -> bool {
+    let reported: Vec<&str> = reported.split('.').collect();
+    let requested: Vec<&str> = requested.split('.').collect();
+    requested.len() <= reported.len() && requested.iter().zip(&reported).all(|(a, b)| a == b)
+}
+
+/// The metadata in `dir`, if it is a complete environment
+fn read_metadata(dir: &Path) -> Option<CachedEnv> {
+    let path = dir.join(ENV_METADATA_FILE);
+    let content = fs::read_to_string(&path).ok()?;
+    match serde_json::from_str::<CachedEnv>(&content) {
+        Ok(env) => Some(CachedEnv {
+            path: dir.to_path_buf(),
+            ..env
+        }),
+        Err(e) => {
+            debug!("Ignoring unreadable {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// `env` marked as used now
+fn touch(mut env: CachedEnv) -> CachedEnv {
+    env.last_used = now();
+    if let Err(e) = env.save() {
+        debug!("Failed to mark {} as used: {:#}", env.path.display(), e);
+    }
+    env
+}
+
+fn remove_dir(dir: &Path) -> Result<()> {
+    fs::remove_dir_all(dir).with_context(|| format!("Failed to remove {}", dir.display()))
+}
+
+fn modified_age(path: &Path) -> Duration {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .unwrap_or_default()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
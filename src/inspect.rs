@@ -0,0 +1,431 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::elf::{self, ElfFile, Reader};
+use crate::manylinux::LinuxCompat;
+
+/// Prefix of the build metadata string py2pyd embeds in the modules it builds
+pub const BUILD_INFO_MARKER: &str = "PY2PYD_BUILD_INFO:";
+
+/// Metadata py2pyd embeds in the modules it builds
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuildInfo {
+    /// py2pyd version that built the module
+    pub py2pyd: String,
+    /// Module name the module was built as
+    pub module: String,
+    /// Python version the module was built for, when one was requested
+    pub python_version: Option<String>,
+    /// Built against the stable ABI
+    pub abi3: bool,
+    /// Optimization level (0-3)
+    pub optimize_level: u8,
+    /// Build profile
+    pub profile: Option<String>,
+    /// Cross-compilation target
+    pub target: Option<String>,
+    /// Build cache fingerprint of the source and settings
+    pub fingerprint: Option<String>,
+}
+
+impl BuildInfo {
+    /// C source defining the metadata as a string the linker keeps
+    pub fn to_c_source(&self) -> Result<String> {
+        let json = serde_json::to_string(self)?;
+        let escaped = format!("{BUILD_INFO_MARKER}{json}")
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"");
+        Ok(format!(
+            "#if defined(_WIN32)\n\
+             __declspec(dllexport)\n\
+             #elif defined(__GNUC__)\n\
+             __attribute__((used, visibility(\"default\")))\n\
+             #endif\n\
+             const char py2pyd_build_info[] = \"{escaped}\";\n"
+        ))
+    }
+
+    /// Find embedded metadata anywhere in a binary
+    pub fn find(bytes: &[u8]) -> Option<Self> {
+        let marker = BUILD_INFO_MARKER.as_bytes();
+        let start = bytes
+            .windows(marker.len())
+            .position(|window| window == marker)?
+            + marker.len();
+        let len = bytes[start..].iter().position(|b| *b == 0)?;
+        serde_json::from_slice(&bytes[start..start + len]).ok()
+    }
+}
+
+/// Container format of a compiled extension module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BinaryFormat {
+    /// Linux and other Unix shared objects
+    Elf,
+    /// Windows DLLs (`.pyd`)
+    Pe,
+    /// macOS bundles and dylibs
+    MachO,
+}
+
+impl fmt::Display for BinaryFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Elf => write!(f, "ELF"),
+            Self::Pe => write!(f, "PE"),
+            Self::MachO => write!(f, "Mach-O"),
+        }
+    }
+}
+
+/// What a compiled extension module is, read without loading it
+#[derive(Debug, Clone, Serialize)]
+pub struct BinaryInfo {
+    /// File that was inspected
+    pub path: PathBuf,
+    /// Container format
+    pub format: BinaryFormat,
+    /// Architectures, several for macOS universal binaries
+    pub architectures: Vec<String>,
+    /// Module names of the `PyInit_*` exports
+    pub init_symbols: Vec<String>,
+    /// Whether the module uses the stable ABI; `None` when it cannot be told
+    pub abi3: Option<bool>,
+    /// Python library the module links against, if any
+    pub python_library: Option<String>,
+    /// Shared libraries the module needs
+    pub needed: Vec<String>,
+    /// Metadata embedded by py2pyd
+    pub build_info: Option<BuildInfo>,
+    /// Whether debug symbols have been removed
+    pub stripped: bool,
+    /// manylinux platform tag, for ELF modules
+    pub manylinux: Option<String>,
+    /// Problems found, e.g. a file name not matching the init symbol
+    pub warnings: Vec<String>,
+}
+
+/// The format-specific facts `inspect` collects
+struct Parsed {
+    format: BinaryFormat,
+    architectures: Vec<String>,
+    exports: Vec<String>,
+    needed: Vec<String>,
+    stripped: bool,
+    manylinux: Option<String>,
+}
+
+/// Inspect the compiled extension module at `path`
+pub fn inspect(path: &Path) -> Result<BinaryInfo> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let parsed = parse(&bytes).with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let init_symbols: Vec<String> = parsed
+        .exports
+        .iter()
+        .filter_map(|name| name.strip_prefix("PyInit_"))
+        .map(str::to_string)
+        .collect();
+    let python_library = parsed
+        .needed
+        .iter()
+        .find(|name| is_python_library(name))
+        .cloned();
+    let build_info = BuildInfo::find(&bytes);
+
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let abi3 = build_info
+        .as_ref()
+        .map(|info| info.abi3)
+        .or_else(|| python_library.as_deref().and_then(abi3_from_library))
+        .or_else(|| abi3_from_file_name(&file_name));
+
+    // Python imports `name` by calling `PyInit_name`, with `name` taken up to the first dot
+    let (stem, suffix) = file_name.split_once('.').unwrap_or((&file_name, ""));
+    let mut warnings = vec![];
+    match init_symbols.as_slice() {
+        [] => warnings.push(format!(
+            "No PyInit_* export; {file_name} is not a Python extension module"
+        )),
+        names if !names.iter().any(|name| name == stem) => warnings.push(format!(
+            "File name '{stem}' does not match PyInit_{}; `import {stem}` will fail, rename it to {}.{suffix}",
+            names[0], names[0]
+        )),
+        _ => {}
+    }
+
+    Ok(BinaryInfo {
+        path: path.to_path_buf(),
+        format: parsed.format,
+        architectures: parsed.architectures,
+        init_symbols,
+        abi3,
+        python_library,
+        needed: parsed.needed,
+        build_info,
+        stripped: parsed.stripped,
+        manylinux: parsed.manylinux,
+        warnings,
+    })
+}
+
+fn parse(bytes: &[u8]) -> Result<Parsed> {
+    match bytes.get(..4) {
+        Some(b"\x7fELF") => parse_elf(bytes),
+        Some([b'M', b'Z', ..]) => parse_pe(bytes),
+        Some(
+            [0xfe, 0xed, 0xfa, 0xce | 0xcf]
+            | [0xce | 0xcf, 0xfa, 0xed, 0xfe]
+            | [0xca, 0xfe, 0xba, 0xbe],
+        ) => parse_macho(bytes),
+        _ => Err(anyhow!("Not an ELF, PE or Mach-O file")),
+    }
+}
+
+fn parse_elf(bytes: &[u8]) -> Result<Parsed> {
+    let file = ElfFile::parse(bytes)?;
+    Ok(Parsed {
+        format: BinaryFormat::Elf,
+        architectures: vec![elf::machine_name(file.header.machine)],
+        exports: file.exports.clone(),
+        needed: file.needed.clone(),
+        stripped: !file.has_symtab,
+        manylinux: Some(LinuxCompat::from_elf(&file, &[]).tag),
+    })
+}
+
+fn parse_pe(bytes: &[u8]) -> Result<Parsed> {
+    let reader = Reader::new(bytes, true, false);
+    let pe = u64::from(reader.u32(0x3c)?);
+    if reader.slice(pe, 4)? != b"PE\0\0" {
+        return Err(anyhow!("Missing PE signature"));
+    }
+    let coff = pe + 4;
+    let machine = reader.u16(coff)?;
+    let section_count = reader.u16(coff + 2)?;
+    let symbol_table = reader.u32(coff + 8)?;
+    let optional = coff + 20;
+    let optional_size = u64::from(reader.u16(coff + 16)?);
+    let directories = match reader.u16(optional)? {
+        0x10b => optional + 96,
+        0x20b => optional + 112,
+        magic => return Err(anyhow!("Unknown PE optional header magic {:#x}", magic)),
+    };
+
+    // (virtual address, virtual size, file offset) of each section
+    let sections: Vec<(u32, u32, u32)> = (0..u64::from(section_count))
+        .map(|index| {
+            let section = optional + optional_size + index * 40;
+            let virtual_size = reader.u32(section + 8)?.max(reader.u32(section + 16)?);
+            Ok((
+                reader.u32(section + 12)?,
+                virtual_size,
+                reader.u32(section + 20)?,
+            ))
+        })
+        .collect::<Result<_>>()?;
+    let offset = |rva: u32| -> Result<u64> {
+        sections
+            .iter()
+            .find(|(address, size, _)| rva >= *address && rva - address < *size)
+            .map(|(address, _, raw)| u64::from(rva - address) + u64::from(*raw))
+            .ok_or_else(|| anyhow!("Address {:#x} is outside every section", rva))
+    };
+    let directory = |index: u64| -> Result<(u32, u32)> {
+        let entry = directories + index * 8;
+        Ok((reader.u32(entry)?, reader.u32(entry + 4)?))
+    };
+
+    let mut exports = vec![];
+    let (export_rva, export_size) = directory(0)?;
+    if export_size > 0 {
+        let table = offset(export_rva)?;
+        let names = offset(reader.u32(table + 32)?)?;
+        for index in 0..u64::from(reader.u32(table + 24)?) {
+            let name = offset(reader.u32(names + index * 4)?)?;
+            exports.push(reader.c_string(name, 256)?);
+        }
+    }
+
+    let mut needed = vec![];
+    let (import_rva, import_size) = directory(1)?;
+    if import_size > 0 {
+        let mut descriptor = offset(import_rva)?;
+        loop {
+            let name_rva = reader.u32(descriptor + 12)?;
+            if name_rva == 0 {
+                break;
+            }
+            needed.push(reader.c_string(offset(name_rva)?, 256)?);
+            descriptor += 20;
+        }
+    }
+
+    let (_, debug_size) = directory(6)?;
+    let architecture = match machine {
+        0x14c => "x86".to_string(),
+        0x8664 => "x86_64".to_string(),
+        0xaa64 => "arm64".to_string(),
+        0x1c4 => "arm".to_string(),
+        other => format!("machine {other:#x}"),
+    };
+    Ok(Parsed {
+        format: BinaryFormat::Pe,
+        architectures: vec![architecture],
+        exports,
+        needed,
+        stripped: debug_size == 0 && symbol_table == 0,
+        manylinux: None,
+    })
+}
+
+/// Whether `bytes` start with the magic of a single-architecture Mach-O file
+fn is_thin_macho(bytes: &[u8]) -> bool {
+    matches!(
+        bytes.get(..4),
+        Some([0xfe, 0xed, 0xfa, 0xce | 0xcf] | [0xce | 0xcf, 0xfa, 0xed, 0xfe])
+    )
+}
+
+fn parse_macho(bytes: &[u8]) -> Result<Parsed> {
+    // Universal binaries hold one Mach-O file per architecture, big-endian
+    if bytes.starts_with(&[0xca, 0xfe, 0xba, 0xbe]) {
+        let reader = Reader::new(bytes, false, false);
+        let mut slices = vec![];
+        for index in 0..u64::from(reader.u32(4)?) {
+            let entry = 8 + index * 20;
+            let cpu = reader.u32(entry)?;
+            let offset = u64::from(reader.u32(entry + 8)?);
+            let size = u64::from(reader.u32(entry + 12)?);
+            let slice = reader.slice(offset, size)?;
+            if !is_thin_macho(slice) {
+                return Err(anyhow!(
+                    "Architecture {} of the universal binary is not a Mach-O file",
+                    index
+                ));
+            }
+            slices.push((cpu, slice));
+        }
+        let (_, first) = slices
+            .first()
+            .ok_or_else(|| anyhow!("Universal binary without architectures"))?;
+        let mut parsed = parse_macho(first)?;
+        parsed.architectures = slices.iter().map(|(cpu, _)| cpu_name(*cpu)).collect();
+        return Ok(parsed);
+    }
+
+    if !is_thin_macho(bytes) {
+        return Err(anyhow!("Not a Mach-O file"));
+    }
+    let little_endian = bytes[0] != 0xfe;
+    let reader = Reader::new(bytes, little_endian, bytes[3] == 0xcf || bytes[0] == 0xcf);
+    let is_64 = reader.word_size() == 8;
+    let cpu = reader.u32(4)?;
+    let command_count = reader.u32(16)?;
+
+    let mut offset = if is_64 { 32 } else { 28 };
+    let mut needed = vec![];
+    let mut exports = vec![];
+    let mut stripped = true;
+    for _ in 0..command_count {
+        let command = reader.u32(offset)?;
+        let size = u64::from(reader.u32(offset + 4)?);
+        match command {
+            // LC_LOAD_DYLIB, LC_LAZY_LOAD_DYLIB, LC_LOAD_WEAK_DYLIB, LC_REEXPORT_DYLIB, LC_LOAD_UPWARD_DYLIB
+            0xc | 0x20 | 0x8000_0018 | 0x8000_001f | 0x8000_0023 => {
+                let name = u64::from(reader.u32(offset + 8)?);
+                needed.push(reader.c_string(offset + name, size.saturating_sub(name))?);
+            }
+            // LC_SYMTAB
+            0x2 => {
+                let symbols = u64::from(reader.u32(offset + 8)?);
+                let count = u64::from(reader.u32(offset + 12)?);
+                let strings = u64::from(reader.u32(offset + 16)?);
+                let strings_size = u64::from(reader.u32(offset + 20)?);
+                let entry_size = if is_64 { 16 } else { 12 };
+                for index in 0..count {
+                    let entry = symbols + index * entry_size;
+                    let kind = reader.u8(entry + 4)?;
+                    let name = u64::from(reader.u32(entry)?);
+                    // Debugging (stab) entries and local symbols are what strip removes
+                    let is_stab = kind & 0xe0 != 0;
+                    let is_external = kind & 0x01 != 0;
+                    let is_defined = kind & 0x0e == 0x0e;
+                    if is_stab || (!is_external && is_defined) {
+                        stripped = false;
+                    } else if is_external && is_defined {
+                        let name =
+                            reader.c_string(strings + name, strings_size.saturating_sub(name))?;
+                        exports.push(name.strip_prefix('_').unwrap_or(&name).to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+        if size == 0 {
+            break;
+        }
+        offset += size;
+    }
+
+    Ok(Parsed {
+        format: BinaryFormat::MachO,
+        architectures: vec![cpu_name(cpu)],
+        exports,
+        needed,
+        stripped,
+        manylinux: None,
+    })
+}
+
+/// Name of a Mach-O `cputype`
+fn cpu_name(cpu: u32) -> String {
+    match cpu {
+        7 => "x86".to_string(),
+        0x0100_0007 => "x86_64".to_string(),
+        12 => "arm".to_string(),
+        0x0100_000c => "arm64".to_string(),
+        0x0100_0012 => "ppc64".to_string(),
+        other => format!("cpu {other:#x}"),
+    }
+}
+
+/// `libpython3.11.so.1.0`, `python311.dll`, `python3.dll` or a Python framework
+fn is_python_library(name: &str) -> bool {
+    let file = name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(name)
+        .to_lowercase();
+    file.starts_with("libpython")
+        || (file.starts_with("python") && file.ends_with(".dll"))
+        || name.contains("Python.framework")
+}
+
+/// `python3.dll` is the stable ABI; `python311.dll` is one version
+fn abi3_from_library(name: &str) -> Option<bool> {
+    let name = name.to_lowercase();
+    let version = name.strip_prefix("python")?.strip_suffix(".dll")?;
+    let version = version.trim_end_matches("_d");
+    Some(version == "3")
+}
+
+/// `.abi3.so` is the stable ABI; `.cpython-311-...` and `.cp311-...` are one version
+fn abi3_from_file_name(name: &str) -> Option<bool> {
+    if name.contains(".abi3.") {
+        Some(true)
+    } else if name.contains(".cpython-") || name.contains(".cp3") {
+        Some(false)
+    } else {
+        None
+    }
+}
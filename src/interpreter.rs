@@ -0,0 +1,783 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::build_tools::Distro;
+use crate::python_version::PythonVersion;
+use crate::uv_compiler::CompileConfig;
+use crate::uv_env::EnvBackend;
+
+/// Reports everything builds need to know about an interpreter, as JSON
+const PROBE_SCRIPT: &str = r#"
+import importlib.util, json, os, sys, sysconfig
+paths = sysconfig.get_paths()
+include = paths.get("include") or ""
+if os.name == "nt":
+    libdir = os.path.join(sys.base_prefix, "libs")
+else:
+    libdir = sysconfig.get_config_var("LIBDIR") or ""
+json.dump({
+    "executable": sys.executable,
+    "version": {"major": sys.version_info[0], "minor": sys.version_info[1], "patch": sys.version_info[2]},
+    "implementation": sys.implementation.name,
+    "abiflags": getattr(sys, "abiflags", ""),
+    "free_threaded": bool(sysconfig.get_config_var("Py_GIL_DISABLED")),
+    "ext_suffix": sysconfig.get_config_var("EXT_SUFFIX") or (".pyd" if os.name == "nt" else ".so"),
+    "soabi": sysconfig.get_config_var("SOABI"),
+    "include": include,
+    "platinclude": paths.get("platinclude") or include,
+    "has_header": os.path.isfile(os.path.join(include, "Python.h")),
+    "libdir": libdir,
+    "ldlibrary": sysconfig.get_config_var("LDLIBRARY") or "",
+    "shared": bool(sysconfig.get_config_var("Py_ENABLE_SHARED")),
+    "platform_tag": sysconfig.get_platform().replace("-", "_").replace(".", "_"),
+    "cython": importlib.util.find_spec("Cython") is not None,
+}, sys.stdout)
+"#;
+
+/// Python implementation, from `sys.implementation.name`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Implementation {
+    /// The reference implementation
+    #[default]
+    CPython,
+    /// PyPy
+    PyPy,
+    /// GraalPy
+    GraalPy,
+    /// Anything else
+    #[serde(other)]
+    Other,
+}
+
+impl fmt::Display for Implementation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::CPython => "CPython",
+            Self::PyPy => "PyPy",
+            Self::GraalPy => "GraalPy",
+            Self::Other => "Python",
+        })
+    }
+}
+
+/// Where an interpreter was found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InstallationSource {
+    /// A path given by the user
+    #[default]
+    Explicit,
+    /// `PATH`
+    Path,
+    /// uv's managed Python installs
+    Uv,
+    /// `$PYENV_ROOT/versions`
+    Pyenv,
+    /// A conda base or environment
+    Conda,
+}
+
+impl fmt::Display for InstallationSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Explicit => "explicit path",
+            Self::Path => "PATH",
+            Self::Uv => "uv",
+            Self::Pyenv => "pyenv",
+            Self::Conda => "conda",
+        })
+    }
+}
+
+/// An interpreter and what extensions built for it need, from one sysconfig probe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PythonInstallation {
+    /// `sys.executable`
+    pub executable: PathBuf,
+    /// `sys.version_info[:3]`
+    pub version: PythonVersion,
+    /// `sys.implementation.name`
+    pub implementation: Implementation,
+    /// `sys.abiflags`, e.g. `t` or `d`; empty on Windows
+    pub abiflags: String,
+    /// Whether the GIL is disabled (`Py_GIL_DISABLED`)
+    pub free_threaded: bool,
+    /// Suffix of extension modules, e.g. `.cpython-311-x86_64-linux-gnu.so`
+    pub ext_suffix: String,
+    /// `SOABI`, e.g. `cpython-311-x86_64-linux-gnu`
+    pub soabi: Option<String>,
+    /// `sysconfig.get_paths()['include']`
+    pub include: PathBuf,
+    /// `sysconfig.get_paths()['platinclude']`
+    pub platinclude: PathBuf,
+    /// Whether `Python.h` exists in `include`
+    pub has_header: bool,
+    /// Directory holding libpython (`libs` on Windows)
+    pub libdir: PathBuf,
+    /// File name of libpython, e.g. `libpython3.11.so`; empty on Windows
+    pub ldlibrary: String,
+    /// Whether Python was built with `--enable-shared`
+    pub shared: bool,
+    /// Wheel platform tag, e.g. `linux_x86_64` or `win_amd64`
+    pub platform_tag: String,
+    /// Whether Cython is importable
+    pub cython: bool,
+    /// Where the interpreter was found
+    #[serde(default)]
+    pub source: InstallationSource,
+}
+
+impl PythonInstallation {
+    /// Ask the interpreter at `python` about itself
+    pub fn probe(python: &Path) -> Result<Self> {
+        let output = Command::new(python)
+            .args(["-c", PROBE_SCRIPT])
+            .output()
+            .with_context(|| format!("Failed to run Python at {}", python.display()))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Failed to query Python at {}:\n{}",
+                python.display(),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("Unexpected output from {}", python.display()))
+    }
+
+    /// `include` and, when it differs, `platinclude`
+    pub fn include_dirs(&self) -> Vec<&Path> {
+        let mut dirs = vec![self.include.as_path()];
+        if self.platinclude != self.include && !self.platinclude.as_os_str().is_empty() {
+            dirs.push(&self.platinclude);
+        }
+        dirs
+    }
+
+    /// ABI tag of its extension modules, e.g. `cp311`, `cp313t` or `pypy310_pp73`
+    pub fn abi_tag(&self) -> String {
+        let soabi = self.soabi.clone().unwrap_or_else(|| {
+            // Windows has no SOABI before 3.13; EXT_SUFFIX is `.cp311-win_amd64.pyd`
+            self.ext_suffix
+                .trim_start_matches('.')
+                .split('.')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        });
+        let mut parts = soabi.split('-');
+        match (parts.next(), parts.next()) {
+            (Some("cpython"), Some(version)) => format!("cp{version}"),
+            (Some(tag), _) if tag.starts_with("cp") && tag.len() > 2 => tag.to_string(),
+            (Some(pypy), Some(pp)) if pypy.starts_with("pypy") => format!("{pypy}_{pp}"),
+            _ => {
+                let prefix = match self.implementation {
+                    Implementation::PyPy => "pp",
+                    _ => "cp",
+                };
+                let mut flags = self.abiflags.clone();
+                if self.free_threaded && !flags.contains('t') {
+                    flags.push('t');
+                }
+                format!(
+                    "{prefix}{}{}{flags}",
+                    self.version.major, self.version.minor
+                )
+            }
+        }
+    }
+
+    /// Problems with the libpython settings an extension is linked with.
+    ///
+    /// Windows extensions link against the import library in `libs`;
+    /// elsewhere they resolve symbols from the running interpreter, so a
+    /// missing libpython only matters for embedding.
+    pub fn link_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if cfg!(windows) {
+            let PythonVersion { major, minor, .. } = self.version;
+            let libraries = [
+                "python3.lib".to_string(),
+                format!("python{major}{minor}.lib"),
+            ];
+            if !libraries.iter().any(|lib| self.libdir.join(lib).is_file()) {
+                problems.push(format!(
+                    "{} not found in {}",
+                    libraries.join(" or "),
+                    self.libdir.display()
+                ));
+            }
+        } else if self.shared
+            && !self.ldlibrary.is_empty()
+            && !self.libdir.join(&self.ldlibrary).exists()
+        {
+            problems.push(format!(
+                "Python is built as a shared library, but {} is not in {}",
+                self.ldlibrary,
+                self.libdir.display()
+            ));
+        }
+        problems
+    }
+
+    /// How to install the missing headers on this system
+    pub fn headers_fix(&self, distro: Distro) -> String {
+        let PythonVersion { major, minor, .. } = self.version;
+        let install = if cfg!(windows) {
+            "reinstall Python from python.org, whose installer includes the headers and libraries"
+                .to_string()
+        } else if cfg!(target_os = "macos") {
+            "install Python from python.org or Homebrew, which ship the headers".to_string()
+        } else {
+            distro
+                .python_dev_package(Some((major, minor)))
+                .and_then(|package| distro.install_command(&[&package]))
+                .unwrap_or_else(|| {
+                    format!("install the development package for Python {major}.{minor}")
+                })
+        };
+        format!(
+            "{install}, or build with --python-version {major}.{minor} to use a uv-managed Python"
+        )
+    }
+}
+
+impl fmt::Display for PythonInstallation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}{} ({}) at {}",
+            self.implementation,
+            self.version,
+            if self.free_threaded {
+                " free-threaded"
+            } else {
+                ""
+            },
+            self.abi_tag(),
+            self.executable.display()
+        )
+    }
+}
+
+/// Which interpreter a build asks for: `3.11`, `3.13t`, `pypy3.10`, a path, or any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PythonRequest {
+    /// Any Python 3
+    Any,
+    /// A version, optionally of one implementation or free-threaded build
+    Version {
+        /// Major version
+        major: u32,
+        /// Minor version, or any
+        minor: Option<u32>,
+        /// Patch version, or any
+        patch: Option<u32>,
+        /// Implementation, or any
+        implementation: Option<Implementation>,
+        /// Whether a free-threaded build is required (`3.13t`)
+        free_threaded: bool,
+    },
+    /// An interpreter at a path
+    Path(PathBuf),
+}
+
+impl PythonRequest {
+    /// The request for `python_path` or, without one, `python_version`
+    pub fn from_config(python_path: Option<&Path>, python_version: Option<&str>) -> Result<Self> {
+        match (python_path, python_version) {
+            (Some(path), _) => Ok(Self::Path(path.to_path_buf())),
+            (None, Some(version)) => version.parse(),
+            (None, None) => Ok(Self::Any),
+        }
+    }
+
+    /// Whether `version` is the version asked for; paths and `Any` accept Python 3
+    pub fn matches_version(&self, version: &PythonVersion) -> bool {
+        match self {
+            Self::Version {
+                major,
+                minor,
+                patch,
+                ..
+            } => {
+                version.major == *major
+                    && minor.is_none_or(|minor| version.minor == minor)
+                    && patch.is_none_or(|patch| version.patch == Some(patch))
+            }
+            Self::Any | Self::Path(_) => version.major == 3,
+        }
+    }
+
+    /// Whether `installation` satisfies the request
+    pub fn matches(&self, installation: &PythonInstallation) -> bool {
+        match self {
+            Self::Version {
+                implementation,
+                free_threaded,
+                ..
+            } => {
+                self.matches_version(&installation.version)
+                    && implementation.is_none_or(|i| i == installation.implementation)
+                    && (!free_threaded || installation.free_threaded)
+            }
+            Self::Any => installation.version.major == 3,
+            Self::Path(_) => true,
+        }
+    }
+
+    /// `python3.11`-style executable names that may satisfy the request
+    fn executable_names(&self) -> Vec<String> {
+        let mut names = vec![];
+        if let Self::Version {
+            major,
+            minor: Some(minor),
+            implementation,
+            free_threaded,
+            ..
+        } = self
+        {
+            let base = match implementation {
+                Some(Implementation::PyPy) => "pypy",
+                _ => "python",
+            };
+            let t = if *free_threaded { "t" } else { "" };
+            names.push(format!("{base}{major}.{minor}{t}"));
+        }
+        if cfg!(windows) {
+            names.extend(["python".to_string(), "python3".to_string()]);
+        } else {
+            names.extend(["python3".to_string(), "python".to_string()]);
+        }
+        names
+    }
+
+    /// Whether a directory named like `cpython-3.11.7-linux-x86_64-gnu` or
+    /// `3.11.7` could hold the interpreter asked for
+    fn matches_dir_name(&self, name: &str) -> bool {
+        match self {
+            Self::Version { .. } => {
+                dir_version(name).is_none_or(|version| self.matches_version(&version))
+            }
+            Self::Any | Self::Path(_) => true,
+        }
+    }
+}
+
+/// The Python version in a directory name like `cpython-3.11.7-linux-x86_64-gnu`,
+/// `3.11.7` or `pypy3.10-7.3.12`
+fn dir_version(name: &str) -> Option<PythonVersion> {
+    name.split(['-', '_'])
+        .map(|part| part.trim_start_matches(|c: char| c.is_ascii_alphabetic()))
+        .find(|part| part.contains('.') && part.starts_with(|c: char| c.is_ascii_digit()))
+        .and_then(|part| part.parse().ok())
+}
+
+impl FromStr for PythonRequest {
+    type Err = anyhow::Error;
+
+    /// Parse `3`, `3.11`, `3.11.4`, `3.13t`, `cpython3.12`, `pypy3.10`,
+    /// `any`, or a path
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() || s == "any" {
+            return Ok(Self::Any);
+        }
+        if s.contains(['/', '\\']) || Path::new(s).exists() {
+            return Ok(Self::Path(PathBuf::from(s)));
+        }
+
+        let lower = s.to_lowercase();
+        let (implementation, version) = [
+            ("cpython", Some(Implementation::CPython)),
+            ("pypy", Some(Implementation::PyPy)),
+            ("graalpy", Some(Implementation::GraalPy)),
+            ("python", None),
+        ]
+        .into_iter()
+        .find_map(|(prefix, implementation)| {
+            lower
+                .strip_prefix(prefix)
+                .map(|rest| (implementation, rest.trim_start_matches(['-', '@'])))
+        })
+        .unwrap_or((None, lower.as_str()));
+        let (version, free_threaded) = match version.strip_suffix('t') {
+            Some(version) => (version, true),
+            None => (version, false),
+        };
+
+        let invalid = || {
+            anyhow!(
+                "Invalid Python request '{}', expected e.g. 3.11, 3.13t, pypy3.10 or a path",
+                s
+            )
+        };
+        let mut parts = version.split('.');
+        let mut next = || -> Result<Option<u32>> {
+            parts
+                .next()
+                .map(|part| part.parse::<u32>().map_err(|_| invalid()))
+                .transpose()
+        };
+        let major = next()?.ok_or_else(invalid)?;
+        let minor = next()?;
+        let patch = next()?;
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self::Version {
+            major,
+            minor,
+            patch,
+            implementation,
+            free_threaded,
+        })
+    }
+}
+
+impl fmt::Display for PythonRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Any => f.write_str("any Python 3"),
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Version {
+                major,
+                minor,
+                patch,
+                implementation,
+                free_threaded,
+            } => {
+                match implementation {
+                    Some(implementation) => write!(f, "{implementation} {major}")?,
+                    None => write!(f, "Python {major}")?,
+                }
+                if let Some(minor) = minor {
+                    write!(f, ".{minor}")?;
+                }
+                if let Some(patch) = patch {
+                    write!(f, ".{patch}")?;
+                }
+                if *free_threaded {
+                    f.write_str("t")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Executables that may satisfy `request`, in the order they are preferred:
+/// `PATH`, uv-managed installs, pyenv versions, then conda environments
+pub fn find_candidates(request: &PythonRequest) -> Vec<(PathBuf, InstallationSource)> {
+    if let PythonRequest::Path(path) = request {
+        return vec![(path.clone(), InstallationSource::Explicit)];
+    }
+
+    let mut candidates = vec![];
+    for name in request.executable_names() {
+        if let Ok(paths) = which::which_all(&name) {
+            candidates.extend(paths.map(|path| (path, InstallationSource::Path)));
+        }
+    }
+    for (root, source) in [
+        (uv_python_dir(), InstallationSource::Uv),
+        (pyenv_versions_dir(), InstallationSource::Pyenv),
+    ] {
+        let Some(root) = root else { continue };
+        let mut dirs: Vec<(Option<PythonVersion>, PathBuf)> = subdirectories(&root)
+            .into_iter()
+            .filter_map(|dir| {
+                let name = dir.file_name()?.to_string_lossy().to_string();
+                request
+                    .matches_dir_name(&name)
+                    .then(|| (dir_version(&name), dir))
+            })
+            .collect();
+        // Newest first
+        dirs.sort_by_key(|(version, _)| std::cmp::Reverse(*version));
+        candidates.extend(dirs.into_iter().map(|(_, dir)| (env_python(&dir), source)));
+    }
+    candidates.extend(
+        conda_prefixes()
+            .into_iter()
+            .map(|prefix| (env_python(&prefix), InstallationSource::Conda)),
+    );
+
+    // The same interpreter is often on PATH and in pyenv or conda
+    let mut seen = BTreeSet::new();
+    candidates.retain(|(path, _)| {
+        path.is_file() && seen.insert(fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+    });
+    candidates
+}
+
+/// Every installation satisfying `request`; candidates that fail to run are skipped
+pub fn find_installations(request: &PythonRequest) -> Vec<PythonInstallation> {
+    find_candidates(request)
+        .into_iter()
+        .filter_map(|(path, source)| probe_candidate(request, &path, source))
+        .collect()
+}
+
+/// The preferred installation satisfying `request`
+pub fn find_installation(request: &PythonRequest) -> Result<PythonInstallation> {
+    if let PythonRequest::Path(path) = request {
+        if !path.exists() {
+            return Err(anyhow!("Python interpreter not found: {}", path.display()));
+        }
+        return PythonInstallation::probe(path);
+    }
+    find_candidates(request)
+        .into_iter()
+        .find_map(|(path, source)| probe_candidate(request, &path, source))
+        .ok_or_else(|| {
+            anyhow!(
+                "No installed {} found on PATH or in uv, pyenv or conda installs",
+                request
+            )
+        })
+}
+
+/// Probe `path` and keep it if it satisfies `request`
+fn probe_candidate(
+    request: &PythonRequest,
+    path: &Path,
+    source: InstallationSource,
+) -> Option<PythonInstallation> {
+    match PythonInstallation::probe(path) {
+        Ok(mut installation) if request.matches(&installation) => {
+            installation.source = source;
+            Some(installation)
+        }
+        Ok(installation) => {
+            debug!("{} does not satisfy {}", installation, request);
+            None
+        }
+        Err(e) => {
+            debug!("Skipping {}: {:#}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// The interpreter of a venv, conda environment or install prefix
+pub fn env_python(prefix: &Path) -> PathBuf {
+    if cfg!(windows) {
+        prefix.join("python.exe")
+    } else {
+        prefix.join("bin").join("python3")
+    }
+}
+
+/// The conda environment `name`: `base`, an environment in a conda base's
+/// `envs`, or a prefix path
+pub fn find_conda_env(name: &str) -> Result<PathBuf> {
+    let path = Path::new(name);
+    if name.contains(['/', '\\']) || path.is_dir() {
+        if !path.join("conda-meta").is_dir() {
+            return Err(anyhow!("{} is not a conda environment", path.display()));
+        }
+        return Ok(path.to_path_buf());
+    }
+
+    let prefixes = conda_prefixes();
+    let found = if name == "base" {
+        // The base is the prefix whose envs are below it
+        prefixes
+            .iter()
+            .find(|prefix| prefix.join("envs").is_dir() || prefix.join("condabin").is_dir())
+    } else {
+        prefixes
+            .iter()
+            .find(|prefix| prefix.file_name().is_some_and(|n| n == name))
+    };
+    found.cloned().ok_or_else(|| {
+        let known: Vec<String> = prefixes
+            .iter()
+            .filter_map(|prefix| Some(prefix.file_name()?.to_string_lossy().to_string()))
+            .collect();
+        anyhow!(
+            "No conda environment '{}'{}",
+            name,
+            if known.is_empty() {
+                " (no conda installation found)".to_string()
+            } else {
+                format!("; found: {}", known.join(", "))
+            }
+        )
+    })
+}
+
+/// The pyenv version selected for `dir`: `PYENV_VERSION`, or the first
+/// entry of the nearest `.python-version`. `None` for pyenv's `system`.
+pub fn pyenv_version(dir: &Path) -> Option<String> {
+    let selected = env::var("PYENV_VERSION").ok().or_else(|| {
+        dir.ancestors()
+            .find_map(|dir| fs::read_to_string(dir.join(".python-version")).ok())
+    })?;
+    selected
+        .split([':', '\n', ' ', '\t'])
+        .map(str::trim)
+        .find(|name| !name.is_empty() && !name.starts_with('#'))
+        .filter(|name| *name != "system")
+        .map(str::to_string)
+}
+
+/// The interpreter of pyenv version `name`, if it is installed
+pub fn pyenv_python(name: &str) -> Option<PathBuf> {
+    let python = env_python(&pyenv_versions_dir()?.join(name));
+    python.is_file().then_some(python)
+}
+
+/// Check `--conda-env` and, when no interpreter was asked for, default to
+/// the pyenv version selected for the project
+pub fn configure(config: &mut CompileConfig) -> Result<()> {
+    if let Some(name) = &config.conda_env {
+        if config.python_path.is_some() {
+            return Err(anyhow!(
+                "--conda-env and --python-path both pick the interpreter"
+            ));
+        }
+        if config.target.is_some() {
+            return Err(anyhow!("--conda-env cannot be used with --target"));
+        }
+        let prefix = find_conda_env(name)?;
+        info!("Building in conda environment {}", prefix.display());
+        return Ok(());
+    }
+    if config.python_path.is_some() || config.python_version.is_some() || config.target.is_some() {
+        return Ok(());
+    }
+
+    let dir = match &config.project_root {
+        Some(root) => root.clone(),
+        None => env::current_dir()?,
+    };
+    let Some(version) = pyenv_version(&dir) else {
+        return Ok(());
+    };
+    match pyenv_python(&version) {
+        Some(python) => {
+            info!("Using pyenv version {} ({})", version, python.display());
+            config.python_path = Some(python);
+        }
+        None if matches!(version.parse(), Ok(PythonRequest::Version { .. })) => {
+            info!("Using Python {} selected by pyenv", version);
+            config.python_version = Some(version);
+        }
+        None => warn!("pyenv version '{}' is not installed; ignoring it", version),
+    }
+    Ok(())
+}
+
+/// The installed interpreter builds for `config` start from, found without
+/// creating an environment: the conda environment's, `python-path`, or one of
+/// `python-version`.
+///
+/// Fails when none is installed, or when uv would pick its default Python.
+pub fn build_interpreter(config: &CompileConfig) -> Result<PythonInstallation> {
+    if let Some(name) = &config.conda_env {
+        return PythonInstallation::probe(&env_python(&find_conda_env(name)?));
+    }
+    if config.python_path.is_none()
+        && config.python_version.is_none()
+        && config.env_backend.resolve(config.install_uv) == EnvBackend::Uv
+    {
+        return Err(anyhow!("uv picks its default Python"));
+    }
+    find_installation(&PythonRequest::from_config(
+        config.python_path.as_deref(),
+        config.python_version.as_deref(),
+    )?)
+}
+
+/// Where uv keeps the Pythons it installs (`UV_PYTHON_INSTALL_DIR`)
+fn uv_python_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("UV_PYTHON_INSTALL_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let data = if cfg!(windows) {
+        dirs::data_dir()
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|home| home.join(".local").join("share")))
+    };
+    data.map(|data| data.join("uv").join("python"))
+}
+
+/// `$PYENV_ROOT/versions`, or pyenv-win's
+fn pyenv_versions_dir() -> Option<PathBuf> {
+    let root = env::var_os("PYENV_ROOT").map(PathBuf::from).or_else(|| {
+        dirs::home_dir().map(|home| {
+            let root = home.join(".pyenv");
+            if cfg!(windows) {
+                root.join("pyenv-win")
+            } else {
+                root
+            }
+        })
+    })?;
+    Some(root.join("versions"))
+}
+
+/// The active conda environment, then each conda base and its environments
+pub fn conda_prefixes() -> Vec<PathBuf> {
+    let mut bases = vec![];
+    // conda's own executable is <base>/bin/conda or <base>\Scripts\conda.exe
+    if let Some(conda) = env::var_os("CONDA_EXE") {
+        bases.extend(
+            Path::new(&conda)
+                .parent()
+                .and_then(Path::parent)
+                .map(Path::to_path_buf),
+        );
+    }
+    if let Some(home) = dirs::home_dir() {
+        for name in ["miniforge3", "miniconda3", "anaconda3", "mambaforge"] {
+            bases.push(home.join(name));
+        }
+    }
+    if !cfg!(windows) {
+        bases.extend(["/opt/conda", "/opt/miniconda3", "/opt/miniforge3"].map(PathBuf::from));
+    }
+
+    let mut prefixes: Vec<PathBuf> = env::var_os("CONDA_PREFIX")
+        .map(PathBuf::from)
+        .into_iter()
+        .collect();
+    for base in bases
+        .into_iter()
+        .filter(|base| base.join("conda-meta").is_dir())
+    {
+        prefixes.push(base.clone());
+        prefixes.extend(subdirectories(&base.join("envs")));
+    }
+    let mut seen = BTreeSet::new();
+    prefixes.retain(|prefix| seen.insert(prefix.clone()));
+    prefixes
+}
+
+/// Directories in `dir`, sorted by name
+fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect()
+        })
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
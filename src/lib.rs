@@ -20,9 +20,30 @@
 //!
 //! - Compile single Python files to pyd/so extensions
 //! - Batch compile multiple Python files
-//! - Support for uv-based Python environment management
-//! - Automatic build tools detection (MSVC, MinGW, GCC, Xcode)
+//! - Support for uv-based Python environment management, with a `python -m venv` fallback
+//! - Opt-in, checksum-verified installation of uv for the host platform
+//! - Build environments cached by interpreter and packages, with `env list/prune/remove`
+//! - Cross-process file locks around shared environments, the uv install and the build cache
+//! - Owned `PythonEnvironment` handles for the legacy compiler, several at once
+//! - Automatic build tools detection (MSVC, MinGW, GCC, Clang, Xcode), honouring `CC`
 //! - Python AST parsing and transformation
+//! - Optional encryption of string literals in compiled modules
+//! - Cython-compatibility checks before compilation
+//! - Preflight check for syntax newer than the target Python version
+//! - Import dependency graphs with build order and cycle detection
+//! - Check that third-party imports resolve in the target interpreter
+//! - Project configuration in `pyproject.toml` (`[tool.py2pyd]`) or `py2pyd.toml`
+//! - Watch mode that recompiles changed modules in a long-lived build environment
+//! - Newline-delimited JSON messages for build scripts and CI
+//! - Opt-in build cache and dry-run plans for batch compilation
+//! - Cross-compilation of Linux extensions with `zig cc` or GCC cross toolchains
+//! - manylinux compatibility report and repair of Linux extension modules
+//! - DCC target profiles (Maya, Houdini, Blender, ...) that pick the Python and compiler settings
+//! - One interpreter resolver over explicit paths, `PATH`, uv, pyenv and conda installs
+//! - Builds inside existing conda environments and with the pyenv version selected for a project
+//! - Discovery of DCC-bundled interpreters (`mayapy`, `hython`, ...), usable without a venv
+//! - `inspect` of compiled ELF, PE and Mach-O modules without loading them
+//! - `doctor` checks of uv, interpreters, headers, compiler, disk space and permissions
 //!
 //! ## Example
 //!
@@ -46,26 +67,69 @@ use std::path::Path;
 
 // Re-export modules for library usage
 pub mod build_tools;
+pub mod cache;
 pub mod compiler;
+pub mod config;
+pub mod cross;
+pub mod dcc;
+pub mod dependencies;
+pub mod diagnostics;
+pub mod doctor;
+pub mod elf;
+pub mod env_cache;
+pub mod inspect;
+pub mod interpreter;
+pub mod lint;
+pub mod lock;
+pub mod manylinux;
+pub mod messages;
 pub mod parser;
+pub mod plan;
 pub mod python_env;
+pub mod python_version;
 pub mod transformer;
 pub mod turbo_downloader;
 pub mod uv_compiler;
 pub mod uv_env;
+pub mod uv_install;
+pub mod watch;
 
 // Re-export commonly used types
-pub use build_tools::{check_build_tools, detect_build_tools, BuildTools};
+pub use build_tools::{
+    check_build_tools, detect_build_tools, BuildTools, Compiler, CompilerKind, Distro,
+};
 pub use compiler::{
     batch_compile as compiler_batch_compile, compile_file as compiler_compile_file,
 };
+pub use config::{OutputLayout, ProjectConfig};
+pub use cross::{CrossTarget, TargetPython};
+pub use dcc::{DccInterpreter, DccRegistry, DccTarget};
+pub use dependencies::{check_dependencies, DependencyReport};
+pub use diagnostics::{Diagnostic, Severity};
+pub use doctor::{run_doctor, CheckStatus, DoctorCheck};
+pub use env_cache::{CachedEnv, EnvCache, EnvKind, EnvSpec, LockedEnv};
+pub use inspect::{inspect, BinaryFormat, BinaryInfo, BuildInfo};
+pub use interpreter::{
+    find_conda_env, find_installation, find_installations, Implementation, InstallationSource,
+    PythonInstallation, PythonRequest,
+};
+pub use lint::{lint_file, lint_source};
+pub use manylinux::{LinuxCompat, ManylinuxPolicy};
+pub use messages::{Message, MessageFormat, Reporter};
 pub use parser::{
-    extract_classes, extract_from_imports, extract_functions, extract_imports, extract_module_vars,
-    parse_file, parse_source,
+    check_target_version, extract_classes, extract_from_imports, extract_functions,
+    extract_imports, extract_module_vars, parse_file, parse_source, ModuleGraph,
+};
+pub use plan::{plan_batch, plan_batch_with_toolchain, BuildPlan};
+pub use python_env::PythonEnvironment;
+pub use python_version::PythonVersion;
+pub use transformer::{
+    encrypt_source, generate_cargo_toml, transform_ast, transform_file, StringEncryptionOptions,
+    TransformedModule,
 };
-pub use transformer::{generate_cargo_toml, transform_ast, transform_file, TransformedModule};
 pub use uv_compiler::CompileConfig;
-pub use uv_env::{UvEnv, UvEnvConfig};
+pub use uv_env::{EnvBackend, UvEnv, UvEnvConfig};
+pub use watch::{watch, WatchOptions};
 
 /// Compile a single Python file to a pyd/so extension using uv-based compilation.
 ///
@@ -289,6 +353,8 @@ mod tests {
         assert!(!config.keep_temp_files);
         assert!(config.target_dcc.is_none());
         assert!(config.packages.is_empty());
+        assert!(!config.encrypt_strings);
+        assert!(!config.keep_docstrings);
     }
 
     #[test]
@@ -0,0 +1,348 @@
+use anyhow::{Context, Result};
+use log::{debug, info};
+use rustpython_parser::ast::{self, Ranged};
+use rustpython_parser::text_size::TextSize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::diagnostics::{Diagnostic, Severity, SourceMap};
+use crate::parser::{walk_expr, walk_stmt, walk_suite, Visitor};
+
+/// A Cython-compatibility rule
+pub struct Rule {
+    /// Identifier used in reports and `# py2pyd: ignore[...]` comments
+    pub code: &'static str,
+    /// Why the construct is a problem once the module is compiled
+    pub explanation: &'static str,
+}
+
+/// `inspect.getsource` and friends
+pub const INSPECT_SOURCE: Rule = Rule {
+    code: "inspect-source",
+    explanation: "compiled modules ship no Python source, so inspect cannot retrieve it and raises OSError or TypeError",
+};
+
+/// Reliance on `__file__` to find files next to the source
+pub const FILE_RELATIVE_PATH: Rule = Rule {
+    code: "file-relative-path",
+    explanation: "after compilation __file__ points at the .pyd/.so, and data files next to the original .py must be shipped separately",
+};
+
+/// `sys._getframe`, `inspect.currentframe` and similar
+pub const FRAME_INTROSPECTION: Rule = Rule {
+    code: "frame-introspection",
+    explanation: "Cython functions do not create Python frames, so frame objects are missing or belong to the caller",
+};
+
+/// Writes through `locals()`
+pub const LOCALS_MUTATION: Rule = Rule {
+    code: "locals-mutation",
+    explanation: "Cython stores locals in C variables; writing to the dict returned by locals() never changes them",
+};
+
+/// Custom metaclasses and `__prepare__`
+pub const METACLASS: Rule = Rule {
+    code: "metaclass",
+    explanation: "Cython builds classes differently, so metaclasses relying on __prepare__ namespaces or function attributes may behave differently",
+};
+
+/// `__class__` references and zero-argument `super()` outside plain methods
+pub const CLASS_CELL: Rule = Rule {
+    code: "class-cell",
+    explanation: "Cython's __class__ cell is only set up for methods defined directly in a class body; nested functions, lambdas and comprehensions do not get it",
+};
+
+/// All rules checked by [`lint_source`]
+pub const RULES: &[&Rule] = &[
+    &INSPECT_SOURCE,
+    &FILE_RELATIVE_PATH,
+    &FRAME_INTROSPECTION,
+    &LOCALS_MUTATION,
+    &METACLASS,
+    &CLASS_CELL,
+];
+
+const SOURCE_FUNCTIONS: &[&str] = &[
+    "inspect.getsource",
+    "inspect.getsourcelines",
+    "inspect.getsourcefile",
+    "inspect.findsource",
+    "inspect.getcomments",
+];
+
+const FRAME_FUNCTIONS: &[&str] = &[
+    "sys._getframe",
+    "sys._current_frames",
+    "inspect.currentframe",
+    "inspect.stack",
+    "inspect.trace",
+];
+
+const LOCALS_MUTATORS: &[&str] = &[
+    "update",
+    "setdefault",
+    "pop",
+    "popitem",
+    "clear",
+    "__setitem__",
+];
+
+const HARMLESS_METACLASSES: &[&str] = &["type", "abc.ABCMeta"];
+
+/// Check a Python file for constructs that behave differently under Cython
+pub fn lint_file(path: &Path) -> Result<Vec<Diagnostic>> {
+    info!("Checking {} for Cython compatibility", path.display());
+
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read Python file: {}", path.display()))?;
+
+    lint_source(&source, path)
+}
+
+/// Check Python source for constructs that behave differently under Cython.
+///
+/// Findings on a line carrying `# py2pyd: ignore` or
+/// `# py2pyd: ignore[rule, ...]` are dropped.
+pub fn lint_source(source: &str, path: &Path) -> Result<Vec<Diagnostic>> {
+    let suite = crate::parser::parse_source(source)
+        .with_context(|| format!("Failed to parse Python file: {}", path.display()))?;
+
+    let mut linter = CythonLinter::default();
+    walk_suite(&mut linter, &suite);
+
+    let mut source_map = SourceMap::new(path, source);
+    let mut diagnostics = Vec::new();
+    for (offset, rule, message) in linter.findings {
+        let diagnostic = source_map.diagnostic(
+            offset,
+            rule.code,
+            Severity::Warning,
+            message,
+            Some(rule.explanation),
+        );
+        if source_map.is_suppressed(diagnostic.line, rule.code) {
+            debug!("Suppressed {} at line {}", rule.code, diagnostic.line);
+            continue;
+        }
+        diagnostics.push(diagnostic);
+    }
+
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    debug!("Found {} Cython compatibility issues", diagnostics.len());
+    Ok(diagnostics)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Class,
+    Function,
+}
+
+#[derive(Default)]
+struct CythonLinter {
+    aliases: HashMap<String, String>,
+    scopes: Vec<Scope>,
+    findings: Vec<(TextSize, &'static Rule, String)>,
+}
+
+impl CythonLinter {
+    fn report(&mut self, offset: TextSize, rule: &'static Rule, message: String) {
+        self.findings.push((offset, rule, message));
+    }
+
+    /// Resolve a name or attribute chain through the imports seen so far
+    fn qualified_name(&self, expr: &ast::Expr) -> Option<String> {
+        match expr {
+            ast::Expr::Name(name) => Some(
+                self.aliases
+                    .get(name.id.as_str())
+                    .cloned()
+                    .unwrap_or_else(|| name.id.to_string()),
+            ),
+            ast::Expr::Attribute(attribute) => self
+                .qualified_name(&attribute.value)
+                .map(|base| format!("{}.{}", base, attribute.attr)),
+            _ => None,
+        }
+    }
+
+    /// Inside a function whose direct parent is not the class body
+    fn in_nested_function_of_class(&self) -> bool {
+        let Some(class_index) = self.scopes.iter().rposition(|s| *s == Scope::Class) else {
+            return false;
+        };
+        self.scopes.len() > class_index + 2
+    }
+
+    fn with_scope<F: FnOnce(&mut Self)>(&mut self, scope: Scope, f: F) {
+        self.scopes.push(scope);
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn check_call(&mut self, call: &ast::ExprCall) {
+        if let Some(name) = self.qualified_name(&call.func) {
+            if SOURCE_FUNCTIONS.contains(&name.as_str()) {
+                self.report(
+                    call.range.start(),
+                    &INSPECT_SOURCE,
+                    format!("`{name}()` cannot read the source of compiled code"),
+                );
+            } else if FRAME_FUNCTIONS.contains(&name.as_str()) {
+                self.report(
+                    call.range.start(),
+                    &FRAME_INTROSPECTION,
+                    format!("`{name}()` relies on Python frames"),
+                );
+            } else if name == "super"
+                && call.args.is_empty()
+                && call.keywords.is_empty()
+                && self.in_nested_function_of_class()
+            {
+                self.report(
+                    call.range.start(),
+                    &CLASS_CELL,
+                    "zero-argument `super()` outside a method defined directly in the class body"
+                        .to_string(),
+                );
+            }
+        }
+
+        if let ast::Expr::Attribute(attribute) = call.func.as_ref() {
+            if is_locals_call(&attribute.value)
+                && LOCALS_MUTATORS.contains(&attribute.attr.as_str())
+            {
+                self.report(
+                    call.range.start(),
+                    &LOCALS_MUTATION,
+                    format!(
+                        "`locals().{}()` does not change local variables",
+                        attribute.attr
+                    ),
+                );
+            }
+        }
+    }
+}
+
+fn is_locals_call(expr: &ast::Expr) -> bool {
+    matches!(expr, ast::Expr::Call(call)
+        if call.args.is_empty()
+            && matches!(call.func.as_ref(), ast::Expr::Name(name) if name.id.as_str() == "locals"))
+}
+
+impl<'a> Visitor<'a> for CythonLinter {
+    fn visit_stmt(&mut self, stmt: &'a ast::Stmt) {
+        match stmt {
+            ast::Stmt::Import(import) => {
+                for alias in &import.names {
+                    let local = alias
+                        .asname
+                        .as_ref()
+                        .unwrap_or(&alias.name)
+                        .as_str()
+                        .split('.')
+                        .next()
+                        .unwrap_or_default()
+                        .to_string();
+                    let target = match &alias.asname {
+                        Some(_) => alias.name.to_string(),
+                        None => local.clone(),
+                    };
+                    self.aliases.insert(local, target);
+                }
+            }
+            ast::Stmt::ImportFrom(import) => {
+                let level = import.level.map_or(0, |level| level.to_u32());
+                if let (Some(module), 0) = (&import.module, level) {
+                    for alias in &import.names {
+                        let local = alias.asname.as_ref().unwrap_or(&alias.name);
+                        self.aliases
+                            .insert(local.to_string(), format!("{}.{}", module, alias.name));
+                    }
+                }
+            }
+            ast::Stmt::ClassDef(class_def) => {
+                for keyword in &class_def.keywords {
+                    if keyword.arg.as_ref().map(|arg| arg.as_str()) != Some("metaclass") {
+                        continue;
+                    }
+                    let name = self.qualified_name(&keyword.value);
+                    if !name
+                        .as_deref()
+                        .is_some_and(|name| HARMLESS_METACLASSES.contains(&name))
+                    {
+                        self.report(
+                            keyword.range.start(),
+                            &METACLASS,
+                            format!("class `{}` uses a custom metaclass", class_def.name),
+                        );
+                    }
+                }
+                self.with_scope(Scope::Class, |linter| walk_stmt(linter, stmt));
+                return;
+            }
+            ast::Stmt::FunctionDef(ast::StmtFunctionDef { name, .. })
+            | ast::Stmt::AsyncFunctionDef(ast::StmtAsyncFunctionDef { name, .. }) => {
+                if name.as_str() == "__prepare__" && self.scopes.last() == Some(&Scope::Class) {
+                    self.report(
+                        stmt.start(),
+                        &METACLASS,
+                        "metaclass defines `__prepare__`".to_string(),
+                    );
+                }
+                self.with_scope(Scope::Function, |linter| walk_stmt(linter, stmt));
+                return;
+            }
+            _ => {}
+        }
+
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'a ast::Expr) {
+        match expr {
+            ast::Expr::Call(call) => self.check_call(call),
+            ast::Expr::Name(name) if matches!(name.ctx, ast::ExprContext::Load) => {
+                match name.id.as_str() {
+                    "__file__" => self.report(
+                        name.range.start(),
+                        &FILE_RELATIVE_PATH,
+                        "`__file__` refers to the compiled extension, not the .py file".to_string(),
+                    ),
+                    "__class__" if self.in_nested_function_of_class() => {
+                        self.report(
+                            name.range.start(),
+                            &CLASS_CELL,
+                            "`__class__` referenced outside a method defined directly in the class body"
+                                .to_string(),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            ast::Expr::Subscript(subscript)
+                if !matches!(subscript.ctx, ast::ExprContext::Load)
+                    && is_locals_call(&subscript.value) =>
+            {
+                self.report(
+                    subscript.range.start(),
+                    &LOCALS_MUTATION,
+                    "assigning to `locals()[...]` does not change local variables".to_string(),
+                );
+            }
+            ast::Expr::Lambda(_)
+            | ast::Expr::ListComp(_)
+            | ast::Expr::SetComp(_)
+            | ast::Expr::DictComp(_)
+            | ast::Expr::GeneratorExp(_) => {
+                self.with_scope(Scope::Function, |linter| walk_expr(linter, expr));
+                return;
+            }
+            _ => {}
+        }
+
+        walk_expr(self, expr);
+    }
+}
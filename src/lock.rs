@@ -0,0 +1,176 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use std::env;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Environment variable with the seconds to wait for a lock held by another
+/// py2pyd process
+pub const LOCK_TIMEOUT_ENV: &str = "PY2PYD_LOCK_TIMEOUT";
+
+/// How long to wait for a lock without `PY2PYD_LOCK_TIMEOUT`; creating an
+/// environment can take a while when Python or packages are downloaded
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How often a busy lock is retried
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Whether other processes may hold the lock at the same time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Several holders, e.g. builds using an environment
+    Shared,
+    /// One holder, e.g. a process creating or deleting an environment
+    Exclusive,
+}
+
+/// An advisory lock on a file, shared between py2pyd processes and released
+/// when dropped (or when the process exits).
+///
+/// Exclusive holders write their PID into the file, so others can say who
+/// they are waiting for.
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+    mode: LockMode,
+}
+
+impl FileLock {
+    /// Lock `path` for `what`, waiting up to [`lock_timeout`]
+    pub fn acquire(path: &Path, mode: LockMode, what: &str) -> Result<Self> {
+        Self::acquire_with_timeout(path, mode, what, lock_timeout())
+    }
+
+    /// Lock `path` for `what`, waiting up to `timeout` for other holders
+    pub fn acquire_with_timeout(
+        path: &Path,
+        mode: LockMode,
+        what: &str,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let started = Instant::now();
+        let mut warned = false;
+        loop {
+            if let Some(lock) = Self::try_acquire(path, mode)? {
+                if warned {
+                    debug!("Acquired lock on {what} after {:?}", started.elapsed());
+                }
+                return Ok(lock);
+            }
+
+            let holder = describe_holder(path);
+            if started.elapsed() >= timeout {
+                return Err(anyhow!(
+                    "Timed out after {}s waiting for lock on {} held by {} ({}); \
+                     set {} to wait longer",
+                    timeout.as_secs(),
+                    what,
+                    holder,
+                    path.display(),
+                    LOCK_TIMEOUT_ENV
+                ));
+            }
+            if !warned {
+                warn!("Waiting for lock on {what} held by {holder}");
+                warned = true;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Lock `path` if no other process holds it in a conflicting mode
+    pub fn try_acquire(path: &Path, mode: LockMode) -> Result<Option<Self>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+
+        let locked = match mode {
+            LockMode::Shared => file.try_lock_shared(),
+            LockMode::Exclusive => file.try_lock(),
+        };
+        match locked {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+            }
+        }
+
+        let mut lock = Self {
+            file,
+            path: path.to_path_buf(),
+            mode,
+        };
+        if mode == LockMode::Exclusive {
+            lock.write_pid();
+        }
+        Ok(Some(lock))
+    }
+
+    /// The lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write_pid(&mut self) {
+        let result = self
+            .file
+            .set_len(0)
+            .and_then(|_| self.file.seek(SeekFrom::Start(0)))
+            .and_then(|_| write!(self.file, "{}", std::process::id()));
+        if let Err(e) = result {
+            debug!("Failed to write PID to {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Nobody holds it once it is released
+        if self.mode == LockMode::Exclusive {
+            let _ = self.file.set_len(0);
+        }
+        let _ = self.file.unlock();
+    }
+}
+
+/// Seconds from `PY2PYD_LOCK_TIMEOUT`, or [`DEFAULT_LOCK_TIMEOUT`]
+pub fn lock_timeout() -> Duration {
+    match env::var(LOCK_TIMEOUT_ENV) {
+        Ok(value) => match value.trim().parse::<u64>() {
+            Ok(seconds) => Duration::from_secs(seconds),
+            Err(_) => {
+                warn!("Ignoring invalid {LOCK_TIMEOUT_ENV}={value:?}; expected seconds");
+                DEFAULT_LOCK_TIMEOUT
+            }
+        },
+        Err(_) => DEFAULT_LOCK_TIMEOUT,
+    }
+}
+
+/// PID of the exclusive holder of the lock at `path`, if it recorded one
+pub fn lock_holder(path: &Path) -> Option<u32> {
+    let mut content = String::new();
+    File::open(path).ok()?.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
+
+/// `PID N`, or `another process` for shared holders
+fn describe_holder(path: &Path) -> String {
+    match lock_holder(path) {
+        Some(pid) => format!("PID {pid}"),
+        None => "another process".to_string(),
+    }
+}
@@ -9,13 +9,20 @@
 #![allow(clippy::derivable_impls)]
 #![allow(clippy::needless_return)]
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::{Parser, Subcommand};
 use env_logger::Env;
-use log::{info, warn};
+use log::{error, info, warn};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use py2pyd::{build_tools, compiler, python_env, uv_compiler};
+use py2pyd::config::{self, Directive};
+use py2pyd::{
+    build_tools, compiler, dependencies, doctor, env_cache, inspect, lint, parser, plan_batch,
+    run_doctor, uv_compiler, uv_env, watch, CheckStatus, Compiler, EnvCache, ManylinuxPolicy,
+    Message, ModuleGraph, ProjectConfig, PythonEnvironment, PythonVersion, Reporter, WatchOptions,
+};
+use py2pyd::{cross, dcc, interpreter};
 
 /// A tool to compile Python modules to pyd files
 #[derive(Parser)]
@@ -33,7 +40,8 @@ struct Cli {
     #[arg(long)]
     python_version: Option<String>,
 
-    /// Keep temporary files after compilation (default: false)
+    /// Keep temporary files after compilation, and cache the build
+    /// environment for later builds (default: false)
     #[arg(long)]
     keep_temp: bool,
 
@@ -45,6 +53,118 @@ struct Cli {
     #[arg(long)]
     packages: Option<String>,
 
+    /// Replace string literals with calls to a runtime decoder (uv compiler
+    /// only); f-strings, including literals inside their `{}` fields, stay
+    /// plain text
+    #[arg(long, overrides_with = "no_encrypt_strings")]
+    encrypt_strings: bool,
+
+    /// Leave string literals as they are, even when the configuration enables
+    /// encryption
+    #[arg(long, overrides_with = "encrypt_strings")]
+    no_encrypt_strings: bool,
+
+    /// Leave docstrings as plain text when encrypting strings
+    #[arg(long, overrides_with = "no_keep_docstrings")]
+    keep_docstrings: bool,
+
+    /// Encrypt docstrings too, even when the configuration keeps them
+    #[arg(long, overrides_with = "keep_docstrings")]
+    no_keep_docstrings: bool,
+
+    /// Configuration file (default: py2pyd.toml, or pyproject.toml with a
+    /// [tool.py2pyd] table, in the current directory or a parent)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Build profile: dev, release, debug or one defined in the configuration file
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Output format for results and progress: human, or newline-delimited
+    /// JSON (see docs/MESSAGE_FORMAT.md)
+    #[arg(long, default_value = "human", value_parser = ["human", "json"])]
+    message_format: String,
+
+    /// Skip batch modules already built from the same source, settings,
+    /// interpreter and Cython
+    #[arg(long, overrides_with = "no_cache")]
+    cache: bool,
+
+    /// Rebuild every module, even when the configuration enables the build cache
+    #[arg(long, overrides_with = "cache")]
+    no_cache: bool,
+
+    /// DCC release to build for, e.g. maya-2024 or houdini-20.5; picks its
+    /// Python version and compiler settings, and its interpreter when installed
+    #[arg(long)]
+    dcc: Option<String>,
+
+    /// Build with --python-path itself, e.g. mayapy, instead of a venv made
+    /// from it; build packages go in a separate directory on PYTHONPATH
+    #[arg(long, overrides_with = "venv")]
+    no_venv: bool,
+
+    /// Build in a venv, even when the configuration sets no-venv
+    #[arg(long, overrides_with = "no_venv")]
+    venv: bool,
+
+    /// Conda environment to build in, by name (e.g. base) or prefix; build
+    /// requirements go in a separate directory on PYTHONPATH
+    #[arg(long, value_name = "NAME")]
+    conda_env: Option<String>,
+
+    /// Install build requirements into the --conda-env environment itself
+    #[arg(long, overrides_with = "no_conda_install")]
+    conda_install: bool,
+
+    /// Keep build requirements out of the conda environment, even when the
+    /// configuration sets conda-install
+    #[arg(long, overrides_with = "conda_install")]
+    no_conda_install: bool,
+
+    /// How build environments are created: uv, python -m venv and pip, or
+    /// auto (uv when installed, otherwise venv)
+    #[arg(long, value_parser = ["auto", "uv", "venv"])]
+    env_backend: Option<String>,
+
+    /// Download uv when it is needed and missing, into py2pyd's data directory
+    /// after checking its published SHA-256
+    #[arg(long, overrides_with = "no_install_uv")]
+    install_uv: bool,
+
+    /// Never download uv, even when the configuration sets install-uv
+    #[arg(long, overrides_with = "install_uv")]
+    no_install_uv: bool,
+
+    /// Linux target to cross-compile for, e.g. aarch64-linux-gnu (uses CC,
+    /// <target>-gcc or zig cc)
+    #[arg(long)]
+    target: Option<String>,
+
+    /// Directory with the target's include/python3.X headers and, ideally,
+    /// its _sysconfigdata_*.py
+    #[arg(long)]
+    target_python: Option<PathBuf>,
+
+    /// Fail Linux builds that need a newer glibc or libstdc++ than this
+    /// manylinux policy allows, e.g. manylinux2014 or manylinux_2_28
+    #[arg(long)]
+    manylinux: Option<String>,
+
+    /// Copy non-system shared libraries next to Linux modules and link them
+    /// with an $ORIGIN RPATH
+    #[arg(long, overrides_with = "no_repair")]
+    repair: bool,
+
+    /// Leave Linux modules unrepaired, even when the configuration sets repair
+    #[arg(long, overrides_with = "repair")]
+    no_repair: bool,
+
+    /// Cython compiler directive, e.g. `boundscheck=false` (repeatable)
+    #[arg(long = "directive", value_name = "NAME=VALUE", value_parser = parse_directive_arg)]
+    directives: Vec<(String, Directive)>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -61,9 +181,9 @@ enum Commands {
         #[arg(short, long)]
         output: Option<PathBuf>,
 
-        /// Optimization level (0-3)
-        #[arg(short = 'O', long, default_value = "2")]
-        optimize: u8,
+        /// Optimization level (0-3) [default: 2]
+        #[arg(short = 'O', long)]
+        optimize: Option<u8>,
     },
     /// Batch compile multiple Python files to pyd files
     Batch {
@@ -71,20 +191,162 @@ enum Commands {
         #[arg(short, long)]
         input: String,
 
-        /// Output directory
+        /// Output directory (default: `output-dir` from the configuration file)
         #[arg(short, long)]
-        output: PathBuf,
+        output: Option<PathBuf>,
 
-        /// Optimization level (0-3)
-        #[arg(short = 'O', long, default_value = "2")]
-        optimize: u8,
+        /// Optimization level (0-3) [default: 2]
+        #[arg(short = 'O', long)]
+        optimize: Option<u8>,
 
         /// Recursive search
         #[arg(short, long)]
         recursive: bool,
+
+        /// Print what would be compiled instead of compiling (same as `plan`)
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show what `batch` would do without creating environments or compiling
+    Plan {
+        /// Input directory or glob pattern
+        #[arg(short, long)]
+        input: String,
+
+        /// Output directory (default: `output-dir` from the configuration file)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Optimization level (0-3) [default: 2]
+        #[arg(short = 'O', long)]
+        optimize: Option<u8>,
+
+        /// Recursive search
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// Compile a directory, then recompile modules whenever they change
+    Watch {
+        /// Input directory or Python file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output directory (default: `output-dir` from the configuration file)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Optimization level (0-3) [default: 2]
+        #[arg(short = 'O', long)]
+        optimize: Option<u8>,
+
+        /// Milliseconds without further changes before rebuilding
+        #[arg(long, default_value = "300")]
+        debounce: u64,
+    },
+    /// Check Python files for constructs that behave differently under Cython,
+    /// and for syntax newer than `--python-version` when it is given
+    Check {
+        /// Input file, directory or glob pattern
+        #[arg(short, long)]
+        input: String,
+
+        /// Recursive search
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// Print the import dependency graph of a project
+    Graph {
+        /// Project or package directory
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output format
+        #[arg(long, default_value = "json", value_parser = ["json", "dot"])]
+        format: String,
+
+        /// Write the graph to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Also draw stdlib and third-party modules (DOT only)
+        #[arg(long)]
+        external: bool,
+    },
+    /// Check that the imports of a project resolve in the target interpreter
+    /// (`--python-path`, e.g. a DCC's Python or a venv's python)
+    Deps {
+        /// Project directory or Python file
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+    /// Check that this machine can build extensions: uv, interpreters and
+    /// their headers, the C compiler, Cython, disk space and permissions
+    Doctor {
+        /// Another interpreter to check, as a path or a version (repeatable)
+        #[arg(long = "python", value_name = "PATH_OR_VERSION")]
+        pythons: Vec<String>,
+    },
+    /// Describe compiled extension modules (.so/.pyd) without loading them:
+    /// init symbols, ABI, architecture, needed libraries and build metadata
+    Inspect {
+        /// Extension modules to inspect
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// List interpreters bundled with installed DCCs (mayapy, hython,
+    /// Blender's and Nuke's Python), with their Python version and ABI
+    Interpreters {
+        /// Another directory DCCs are installed under (repeatable; also
+        /// PY2PYD_DCC_ROOTS)
+        #[arg(long = "root", value_name = "DIR")]
+        roots: Vec<PathBuf>,
+    },
+    /// Manage cached build environments (kept with --keep-temp and reused by
+    /// later builds with the same interpreter and packages)
+    Env {
+        #[command(subcommand)]
+        action: EnvAction,
+    },
+    /// Inspect the project configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum EnvAction {
+    /// List cached environments, most recently used first
+    List,
+    /// Remove broken and half-built environments, and optionally unused ones
+    Prune {
+        /// Also remove environments no build has used for this many days
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+
+        /// Remove every cached environment
+        #[arg(long, conflicts_with = "older_than")]
+        all: bool,
+    },
+    /// Remove cached environments by key (or a unique prefix of one), unless
+    /// a build is using them
+    Remove {
+        /// Keys as printed by `env list`
+        #[arg(required = true)]
+        keys: Vec<String>,
     },
 }
 
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the effective configuration after merging the file and command line flags
+    Show,
+}
+
+fn parse_directive_arg(s: &str) -> std::result::Result<(String, Directive), String> {
+    config::parse_directive(s).map_err(|e| e.to_string())
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
@@ -97,13 +359,34 @@ fn main() -> Result<()> {
     };
     env_logger::init_from_env(env);
 
-    // Check for required build tools
-    info!("Checking for required build tools...");
-    let build_tools =
-        build_tools::check_build_tools().with_context(|| "Failed to check build tools")?;
+    let mut reporter = Reporter::new(cli.message_format.parse()?);
+    let result =
+        load_project_config(&cli).and_then(|project| run(&cli, project.as_ref(), &mut reporter));
+    reporter.finish(cli.command.name(), &result);
+    result
+}
 
-    info!("Build tools found:\n{}", build_tools.get_tools_info());
+impl Commands {
+    /// Name of the subcommand, as reported in the summary message
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Compile { .. } => "compile",
+            Self::Batch { .. } => "batch",
+            Self::Plan { .. } => "plan",
+            Self::Watch { .. } => "watch",
+            Self::Check { .. } => "check",
+            Self::Graph { .. } => "graph",
+            Self::Deps { .. } => "deps",
+            Self::Doctor { .. } => "doctor",
+            Self::Inspect { .. } => "inspect",
+            Self::Interpreters { .. } => "interpreters",
+            Self::Env { .. } => "env",
+            Self::Config { .. } => "config",
+        }
+    }
+}
 
+fn run(cli: &Cli, project: Option<&ProjectConfig>, reporter: &mut Reporter) -> Result<()> {
     // Execute command
     match &cli.command {
         Commands::Compile {
@@ -111,11 +394,16 @@ fn main() -> Result<()> {
             output,
             optimize,
         } => {
+            let mut config = compile_config(cli, project, *optimize)?;
+            config.compiler = check_build_tools(&config)?;
             let output = output.clone().unwrap_or_else(|| {
                 // If no output path is specified, generate a file with the same name as the input file
                 // but with the appropriate extension for the current platform (.pyd on Windows, .so on others)
                 let file_name = input.file_name().unwrap_or_default();
-                let mut output_path = PathBuf::from(file_name);
+                let mut output_path = match &config.output_dir {
+                    Some(dir) => dir.join(file_name),
+                    None => PathBuf::from(file_name),
+                };
 
                 // Use the appropriate extension based on the platform
                 if cfg!(windows) {
@@ -126,73 +414,30 @@ fn main() -> Result<()> {
 
                 output_path
             });
-
             info!("Compiling {} to {}", input.display(), output.display());
-            info!("Optimization level: {optimize}");
-
-            // Parse additional packages
-            let packages = cli
-                .packages
-                .as_ref()
-                .map(|p| {
-                    p.split(',')
-                        .map(|s| s.trim().to_string())
-                        .collect::<Vec<_>>()
-                })
-                .unwrap_or_default();
+            info!("Optimization level: {}", config.optimize_level);
 
-            if cli.use_uv {
+            reporter.emit(Message::ModuleStarted {
+                source: input.clone(),
+                output: output.clone(),
+            });
+            let started = Instant::now();
+            let result = if cli.use_uv {
                 // Use the uv-based compiler
-                let config = uv_compiler::CompileConfig {
-                    python_path: cli.python_path.as_deref().map(PathBuf::from),
-                    python_version: cli.python_version.clone(),
-                    optimize_level: *optimize,
-                    keep_temp_files: cli.keep_temp,
-                    target_dcc: None,
-                    packages,
-                };
-
                 uv_compiler::compile_file(input, &output, &config)
-                    .with_context(|| format!("Failed to compile {}", input.display()))?;
             } else {
                 // Use the old compiler
-                // Initialize Python environment
-                info!("Initializing Python environment...");
-                python_env::initialize_python_env(
-                    cli.python_path.as_deref(),
-                    cli.python_version.as_deref(),
-                )
-                .with_context(|| "Failed to initialize Python environment")?;
-
-                // Set Python environment variables
-                python_env::set_python_env_vars()
-                    .with_context(|| "Failed to set Python environment variables")?;
-
-                // Display Python path
-                let python_path =
-                    python_env::get_python_path().with_context(|| "Failed to get Python path")?;
-                info!("Using Python interpreter: {}", python_path.display());
-
-                compile_file(input, &output, *optimize)
-                    .with_context(|| format!("Failed to compile {}", input.display()))?;
-
-                // Clean up virtual environment if not keeping it
-                if cli.keep_temp {
-                    let venv_path = python_env::get_venv_path()?;
-                    info!("Keeping virtual environment at: {}", venv_path.display());
-                    info!(
-                        "You can activate it with: {}\\Scripts\\activate",
-                        venv_path.display()
-                    );
-                } else {
-                    info!("Cleaning up temporary virtual environment...");
-                    if let Err(e) = python_env::cleanup_venv() {
-                        warn!("Failed to clean up virtual environment: {e}");
-                    } else {
-                        info!("Virtual environment cleaned up successfully");
-                    }
-                }
-            }
+                with_legacy_env(cli, &config, |env| {
+                    compiler::compile_file_with_env(input, &output, config.optimize_level, env)
+                })
+            };
+            reporter.emit(Message::module_finished(
+                input,
+                &output,
+                started.elapsed(),
+                result.as_ref().err(),
+            ));
+            result.with_context(|| format!("Failed to compile {}", input.display()))?;
 
             info!("Successfully compiled to {}", output.display());
         }
@@ -201,92 +446,692 @@ fn main() -> Result<()> {
             output,
             optimize,
             recursive,
+            dry_run,
         } => {
+            let mut config = compile_config(cli, project, *optimize)?;
+            let output = output_dir(output.as_ref(), &config)?;
+            if *dry_run {
+                return run_plan(input, &output, &config, *recursive, reporter);
+            }
+
+            config.compiler = check_build_tools(&config)?;
             info!("Batch compiling from {} to {}", input, output.display());
-            info!("Optimization level: {optimize}");
-
-            // Parse additional packages
-            let packages = cli
-                .packages
-                .as_ref()
-                .map(|p| {
-                    p.split(',')
-                        .map(|s| s.trim().to_string())
-                        .collect::<Vec<_>>()
-                })
-                .unwrap_or_default();
+            info!("Optimization level: {}", config.optimize_level);
 
             if cli.use_uv {
                 // Use the uv-based compiler
-                let config = uv_compiler::CompileConfig {
-                    python_path: cli.python_path.as_deref().map(PathBuf::from),
-                    python_version: cli.python_version.clone(),
-                    optimize_level: *optimize,
-                    keep_temp_files: cli.keep_temp,
-                    target_dcc: None,
-                    packages,
-                };
-
-                uv_compiler::batch_compile(input, output, &config, *recursive)
-                    .with_context(|| "Failed to batch compile")?;
+                uv_compiler::batch_compile_with_messages(
+                    input,
+                    &output,
+                    &config,
+                    *recursive,
+                    |message| reporter.emit(message),
+                )
+                .with_context(|| "Failed to batch compile")?;
             } else {
                 // Use the old compiler
-                // Initialize Python environment
-                info!("Initializing Python environment...");
-                python_env::initialize_python_env(
-                    cli.python_path.as_deref(),
-                    cli.python_version.as_deref(),
-                )
-                .with_context(|| "Failed to initialize Python environment")?;
-
-                // Set Python environment variables
-                python_env::set_python_env_vars()
-                    .with_context(|| "Failed to set Python environment variables")?;
-
-                // Display Python path
-                let python_path =
-                    python_env::get_python_path().with_context(|| "Failed to get Python path")?;
-                info!("Using Python interpreter: {}", python_path.display());
-
-                batch_compile(input, output, *optimize, *recursive)
-                    .with_context(|| "Failed to batch compile")?;
-
-                // Clean up virtual environment if not keeping it
-                if cli.keep_temp {
-                    let venv_path = python_env::get_venv_path()?;
-                    info!("Keeping virtual environment at: {}", venv_path.display());
-                    info!(
-                        "You can activate it with: {}\\Scripts\\activate",
-                        venv_path.display()
-                    );
-                } else {
-                    info!("Cleaning up temporary virtual environment...");
-                    if let Err(e) = python_env::cleanup_venv() {
-                        warn!("Failed to clean up virtual environment: {e}");
-                    } else {
-                        info!("Virtual environment cleaned up successfully");
-                    }
-                }
+                with_legacy_env(cli, &config, |env| {
+                    compiler::batch_compile_with_messages(
+                        input,
+                        &output,
+                        config.optimize_level,
+                        *recursive,
+                        env,
+                        |message| reporter.emit(message),
+                    )
+                })
+                .with_context(|| "Failed to batch compile")?;
             }
 
             info!("Successfully batch compiled");
         }
+        Commands::Plan {
+            input,
+            output,
+            optimize,
+            recursive,
+        } => {
+            let config = compile_config(cli, project, *optimize)?;
+            let output = output_dir(output.as_ref(), &config)?;
+            run_plan(input, &output, &config, *recursive, reporter)?;
+        }
+        Commands::Watch {
+            input,
+            output,
+            optimize,
+            debounce,
+        } => {
+            let mut config = compile_config(cli, project, *optimize)?;
+            config.compiler = check_build_tools(&config)?;
+            let output = output_dir(output.as_ref(), &config)?;
+            let options = WatchOptions {
+                debounce: Duration::from_millis(*debounce),
+                ..Default::default()
+            };
+
+            if !reporter.is_json() {
+                println!(
+                    "Watching {} for changes, writing to {} (Ctrl+C to stop)",
+                    input.display(),
+                    output.display()
+                );
+            }
+            watch::watch(input, &output, &config, &options, |message| {
+                reporter.emit(message);
+            })?;
+        }
+        Commands::Check { input, recursive } => {
+            let config = compile_config(cli, project, None)?;
+            run_check(
+                input,
+                *recursive,
+                config.python_version.as_deref(),
+                reporter,
+            )?;
+        }
+        Commands::Graph {
+            input,
+            format,
+            output,
+            external,
+        } => {
+            let graph = ModuleGraph::from_directory(input)
+                .with_context(|| format!("Failed to build import graph for {}", input.display()))?;
+
+            let rendered = match format.as_str() {
+                "dot" => graph.to_dot(*external),
+                _ => graph.to_json()? + "\n",
+            };
+            reporter.modules += graph.modules.len();
+
+            match output {
+                Some(path) => {
+                    std::fs::write(path, rendered)
+                        .with_context(|| format!("Failed to write graph to {}", path.display()))?;
+                    info!("Wrote import graph to {}", path.display());
+                }
+                None if reporter.is_json() => {
+                    let graph = match format.as_str() {
+                        "dot" => serde_json::Value::String(rendered),
+                        _ => serde_json::from_str(&rendered)?,
+                    };
+                    reporter.emit(Message::Graph {
+                        format: format.clone(),
+                        graph,
+                    });
+                }
+                None => print!("{rendered}"),
+            }
+        }
+        Commands::Deps { input } => {
+            let config = compile_config(cli, project, None)?;
+            run_deps(input, config.python_path.as_deref(), reporter)?;
+        }
+        Commands::Doctor { pythons } => {
+            let config = compile_config(cli, project, None)?;
+            run_doctor_command(&config, pythons, reporter)?;
+        }
+        Commands::Inspect { files } => run_inspect(files, reporter)?,
+        Commands::Interpreters { roots } => run_interpreters(roots, reporter)?,
+        Commands::Env { action } => run_env(action, reporter)?,
+        Commands::Config {
+            action: ConfigAction::Show,
+        } => {
+            let config = compile_config(cli, project, None)?;
+            let source = project.and_then(|p| p.source.clone());
+            if reporter.is_json() {
+                reporter.emit(Message::Config {
+                    source,
+                    config: serde_json::to_value(&config)?,
+                });
+                return Ok(());
+            }
+
+            match source {
+                Some(source) => println!("# Configuration file: {}", source.display()),
+                None => println!("# No configuration file found"),
+            }
+            print!(
+                "{}",
+                toml::to_string_pretty(&config)
+                    .with_context(|| "Failed to render configuration")?
+            );
+        }
     }
 
     Ok(())
 }
 
-fn compile_file(input: &Path, output: &Path, optimize: u8) -> Result<()> {
-    // This will be implemented in the compiler module
-    compiler::compile_file(input, output, "generic", optimize)
+/// Run `build` in the Python environment used by the old compiler
+fn with_legacy_env(
+    cli: &Cli,
+    config: &uv_compiler::CompileConfig,
+    build: impl FnOnce(&PythonEnvironment) -> Result<()>,
+) -> Result<()> {
+    // Initialize Python environment
+    info!("Initializing Python environment...");
+    let env = PythonEnvironment::new(
+        config.python_path.as_deref(),
+        config.python_version.as_deref(),
+        config.install_uv,
+    )
+    .with_context(|| "Failed to initialize Python environment")?;
+    env.check()
+        .with_context(|| "Python environment does not work")?;
+    info!("Using Python interpreter: {}", env.python_path().display());
+
+    build(&env)?;
+
+    // Clean up virtual environment if not keeping it
+    if cli.keep_temp {
+        if let Some(venv_path) = env.venv_path() {
+            info!("Keeping virtual environment at: {}", venv_path.display());
+            info!(
+                "You can activate it with: {}\\Scripts\\activate",
+                venv_path.display()
+            );
+        }
+    } else {
+        info!("Cleaning up temporary virtual environment...");
+        if let Err(e) = env.cleanup() {
+            warn!("Failed to clean up virtual environment: {e}");
+        }
+    }
+    Ok(())
+}
+
+fn load_project_config(cli: &Cli) -> Result<Option<ProjectConfig>> {
+    match &cli.config {
+        Some(path) => ProjectConfig::load(path)?
+            .map(Some)
+            .ok_or_else(|| anyhow!("No [tool.py2pyd] table in {}", path.display())),
+        None => ProjectConfig::discover(&std::env::current_dir()?),
+    }
 }
 
-fn batch_compile(
-    input_pattern: &str,
-    output_dir: &Path,
-    optimize: u8,
+/// Merge defaults, the configuration file, the selected profile and command
+/// line flags, in that order
+fn compile_config(
+    cli: &Cli,
+    project: Option<&ProjectConfig>,
+    optimize: Option<u8>,
+) -> Result<uv_compiler::CompileConfig> {
+    let mut config = uv_compiler::CompileConfig::default();
+    if let Some(project) = project {
+        project.apply_to(&mut config);
+    }
+    if let Some(name) = &cli.profile {
+        info!("Using profile '{name}'");
+        config::find_profile(project, name)?.apply_to(&mut config);
+        config.profile = Some(name.clone());
+    }
+
+    if let Some(path) = &cli.python_path {
+        config.python_path = Some(PathBuf::from(path));
+    }
+    if let Some(version) = &cli.python_version {
+        config.python_version = Some(version.clone());
+    }
+    if let Some(level) = optimize {
+        config.optimize_level = level;
+    }
+    if let Some(packages) = &cli.packages {
+        config.packages = packages.split(',').map(|s| s.trim().to_string()).collect();
+    }
+    if cli.dcc.is_some() {
+        config.target_dcc.clone_from(&cli.dcc);
+    }
+    if cli.conda_env.is_some() {
+        config.conda_env.clone_from(&cli.conda_env);
+    }
+    if cli.target.is_some() {
+        config.target.clone_from(&cli.target);
+    }
+    if cli.target_python.is_some() {
+        config.target_python.clone_from(&cli.target_python);
+    }
+    if let Some(policy) = &cli.manylinux {
+        config.manylinux = Some(policy.clone());
+    }
+    dcc::configure(&mut config)?;
+    if let Some(policy) = &config.manylinux {
+        policy.parse::<ManylinuxPolicy>()?;
+    }
+    set_flag(&mut config.repair, cli.repair, cli.no_repair);
+    set_flag(&mut config.no_venv, cli.no_venv, cli.venv);
+    set_flag(
+        &mut config.conda_install,
+        cli.conda_install,
+        cli.no_conda_install,
+    );
+    if let Some(backend) = &cli.env_backend {
+        config.env_backend = backend.parse()?;
+    }
+    set_flag(&mut config.install_uv, cli.install_uv, cli.no_install_uv);
+    config.keep_temp_files = cli.keep_temp;
+    set_flag(&mut config.cache, cli.cache, cli.no_cache);
+    set_flag(
+        &mut config.encrypt_strings,
+        cli.encrypt_strings,
+        cli.no_encrypt_strings,
+    );
+    set_flag(
+        &mut config.keep_docstrings,
+        cli.keep_docstrings,
+        cli.no_keep_docstrings,
+    );
+    config
+        .cython_directives
+        .extend(cli.directives.iter().cloned());
+    cross::configure(&mut config)?;
+    interpreter::configure(&mut config)?;
+    Ok(config)
+}
+
+/// Overwrite `setting` when the flag (`on`) or its `--no-` counterpart (`off`)
+/// was given; clap keeps only the last of the two
+fn set_flag(setting: &mut bool, on: bool, off: bool) {
+    if on {
+        *setting = true;
+    } else if off {
+        *setting = false;
+    }
+}
+
+/// The `--output` directory, falling back to `output-dir` from the configuration
+fn output_dir(output: Option<&PathBuf>, config: &uv_compiler::CompileConfig) -> Result<PathBuf> {
+    output
+        .or(config.output_dir.as_ref())
+        .cloned()
+        .ok_or_else(|| {
+            anyhow!("No output directory: pass --output or set output-dir in py2pyd.toml")
+        })
+}
+
+/// Check for build tools and Python headers, and return the compiler builds
+/// should use
+fn check_build_tools(config: &uv_compiler::CompileConfig) -> Result<Option<Compiler>> {
+    // Cross builds use the target's headers, checked by `cross::configure`
+    if let Some(target) = &config.target {
+        let compiler = cross::find_cross_compiler(&target.parse()?)?;
+        info!("Cross-compiling with {} ({})", compiler, compiler.reason);
+        if let Some(target) = &config.dcc_target {
+            target.check_compiler(&compiler)?;
+        }
+        return Ok(Some(compiler));
+    }
+
+    info!("Checking for required build tools...");
+    let build_tools =
+        build_tools::check_build_tools().with_context(|| "Failed to check build tools")?;
+
+    info!("Build tools found:\n{}", build_tools.get_tools_info());
+
+    // Without an installed interpreter uv downloads one, which ships its headers
+    let python = match &config.conda_env {
+        Some(name) => Some(interpreter::env_python(&interpreter::find_conda_env(name)?)),
+        None => uv_env::find_python(
+            config.python_path.as_deref(),
+            config.python_version.as_deref(),
+        ),
+    };
+    if let Some(python) = python {
+        build_tools::check_python_dev(&python)?;
+    }
+    if let (Some(target), Some(compiler)) = (&config.dcc_target, &build_tools.selected) {
+        target.check_compiler(compiler)?;
+    }
+    Ok(build_tools.selected)
+}
+
+fn run_plan(
+    input: &str,
+    output: &Path,
+    config: &uv_compiler::CompileConfig,
     recursive: bool,
+    reporter: &mut Reporter,
 ) -> Result<()> {
-    // This will be implemented in the compiler module
-    compiler::batch_compile(input_pattern, output_dir, "generic", optimize, recursive)
+    let plan = plan_batch(input, output, config, recursive)?;
+    reporter.modules += plan.modules.len();
+    if reporter.is_json() {
+        reporter.emit(Message::Plan(plan));
+        return Ok(());
+    }
+
+    let interpreter = match (&plan.interpreter, &plan.python_version) {
+        (Some(path), Some(version)) => format!("{} (Python {version})", path.display()),
+        (Some(path), None) => path.display().to_string(),
+        (None, Some(version)) => format!("Python {version}, provided by uv"),
+        (None, None) => "uv's default Python".to_string(),
+    };
+    println!("Interpreter: {interpreter}");
+    if let Some(cython) = &plan.cython {
+        println!("Cython:      {cython}");
+    }
+    println!("Packages:    {}", plan.packages.join(", "));
+    println!("Output:      {}", plan.output_dir.display());
+    println!();
+    for module in &plan.modules {
+        println!(
+            "  {}  {}  ->  {}{}",
+            module.source.display(),
+            module.module,
+            module.output.display(),
+            if module.cached { "  (up to date)" } else { "" }
+        );
+    }
+    for source in &plan.excluded {
+        println!("  {}  (excluded)", source.display());
+    }
+    println!();
+    println!(
+        "{} module(s): {} to build, {} up to date, {} excluded",
+        plan.modules.len(),
+        plan.to_build(),
+        plan.modules.len() - plan.to_build(),
+        plan.excluded.len()
+    );
+    Ok(())
+}
+
+fn run_doctor_command(
+    config: &uv_compiler::CompileConfig,
+    pythons: &[String],
+    reporter: &mut Reporter,
+) -> Result<()> {
+    let checks = run_doctor(config, pythons);
+    let failed = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .count();
+    let warnings = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Warn)
+        .count();
+    reporter.modules += checks.len();
+
+    if reporter.is_json() {
+        for check in checks {
+            reporter.emit(Message::Doctor(check));
+        }
+    } else {
+        let width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for check in &checks {
+            println!(
+                "{:<4}  {:<width$}  {}",
+                check.status, check.name, check.detail
+            );
+            if let Some(fix) = &check.fix {
+                println!("{:<4}  {:<width$}  fix: {}", "", "", fix);
+            }
+        }
+        println!();
+        println!(
+            "{} check(s): {} passed, {} warning(s), {} failed",
+            checks.len(),
+            checks.len() - failed - warnings,
+            warnings,
+            failed
+        );
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} check(s) failed", failed);
+    }
+    Ok(())
+}
+
+fn run_interpreters(roots: &[PathBuf], reporter: &mut Reporter) -> Result<()> {
+    let registry = dcc::DccRegistry::load()?;
+    let roots: Vec<PathBuf> = dcc::env_roots().into_iter().chain(roots.to_vec()).collect();
+    let interpreters = dcc::discover(&registry, &roots, None);
+    reporter.modules += interpreters.len();
+    if reporter.is_json() {
+        for interpreter in interpreters {
+            reporter.emit(Message::Interpreter(interpreter));
+        }
+        return Ok(());
+    }
+
+    if interpreters.is_empty() {
+        println!(
+            "No DCC interpreters found; add install directories with --root or PY2PYD_DCC_ROOTS"
+        );
+        return Ok(());
+    }
+    for interpreter in &interpreters {
+        println!("{interpreter}");
+        println!("  path:    {}", interpreter.path.display());
+        match interpreter
+            .spec()
+            .filter(|_| interpreter.mismatch.is_none())
+        {
+            Some(spec) => println!("  build:   py2pyd --dcc {spec} ..."),
+            None => println!(
+                "  build:   py2pyd --python-path {} --no-venv ...",
+                interpreter.path.display()
+            ),
+        }
+        if !interpreter.has_header {
+            println!("  warning: Python.h not found; install the DCC's developer kit");
+        }
+        if let Some(mismatch) = &interpreter.mismatch {
+            println!("  warning: {mismatch}");
+        }
+    }
+    Ok(())
+}
+
+fn run_env(action: &EnvAction, reporter: &mut Reporter) -> Result<()> {
+    let cache = EnvCache::open()?;
+    let removed = match action {
+        EnvAction::List => {
+            let envs = cache.list()?;
+            reporter.modules += envs.len();
+            if envs.is_empty() && !reporter.is_json() {
+                println!("No cached environments in {}", cache.root().display());
+            }
+            for env in envs {
+                let size = env_cache::dir_size(&env.path);
+                if reporter.is_json() {
+                    reporter.emit(Message::Environment { env, size });
+                    continue;
+                }
+                println!("{env}");
+                if !env.spec.packages.is_empty() {
+                    println!("  packages:  {}", env.spec.packages.join(" "));
+                }
+                println!("  path:      {}", env.path.display());
+                println!(
+                    "  size:      {}, last used {} days ago",
+                    doctor::format_size(size),
+                    env.idle().as_secs() / 86400
+                );
+            }
+            return Ok(());
+        }
+        EnvAction::Prune { older_than, all } => {
+            let older_than = match (older_than, all) {
+                (_, true) => Some(Duration::ZERO),
+                (Some(days), false) => Some(Duration::from_secs(days * 86400)),
+                (None, false) => None,
+            };
+            cache.prune(older_than)?
+        }
+        EnvAction::Remove { keys } => keys
+            .iter()
+            .map(|key| cache.remove(key).map(|env| env.path))
+            .collect::<Result<_>>()?,
+    };
+
+    reporter.modules += removed.len();
+    if !reporter.is_json() && removed.is_empty() {
+        println!("Nothing to remove");
+    }
+    for path in removed {
+        if reporter.is_json() {
+            reporter.emit(Message::EnvironmentRemoved { path });
+        } else {
+            println!("Removed {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn run_inspect(files: &[PathBuf], reporter: &mut Reporter) -> Result<()> {
+    let mut failed = 0;
+    for file in files {
+        let info = match inspect(file) {
+            Ok(info) => info,
+            Err(e) => {
+                error!("{e:#}");
+                failed += 1;
+                continue;
+            }
+        };
+        reporter.modules += 1;
+        if reporter.is_json() {
+            reporter.emit(Message::Inspect(Box::new(info)));
+            continue;
+        }
+
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        let list = |items: &[String]| {
+            if items.is_empty() {
+                "none".to_string()
+            } else {
+                items.join(", ")
+            }
+        };
+        println!("{}", info.path.display());
+        println!("  format:     {}", info.format);
+        println!("  arch:       {}", info.architectures.join(", "));
+        println!("  init:       {}", list(&info.init_symbols));
+        println!("  abi3:       {}", info.abi3.map_or("unknown", yes_no));
+        println!(
+            "  python lib: {}",
+            info.python_library
+                .as_deref()
+                .unwrap_or("none (symbols come from the interpreter)")
+        );
+        println!("  needs:      {}", list(&info.needed));
+        println!("  stripped:   {}", yes_no(info.stripped));
+        if let Some(tag) = &info.manylinux {
+            println!("  manylinux:  {tag}");
+        }
+        if let Some(build) = &info.build_info {
+            let python = build.python_version.as_deref().unwrap_or("any");
+            println!(
+                "  built by:   py2pyd {} (Python {}, -O{}{}{})",
+                build.py2pyd,
+                python,
+                build.optimize_level,
+                build
+                    .profile
+                    .as_ref()
+                    .map(|p| format!(", profile {p}"))
+                    .unwrap_or_default(),
+                build
+                    .target
+                    .as_ref()
+                    .map(|t| format!(", target {t}"))
+                    .unwrap_or_default()
+            );
+        }
+        for warning in &info.warnings {
+            println!("  warning:    {warning}");
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} file(s) could not be inspected", failed);
+    }
+    Ok(())
+}
+
+fn run_check(
+    input: &str,
+    recursive: bool,
+    python_version: Option<&str>,
+    reporter: &mut Reporter,
+) -> Result<()> {
+    let target = python_version
+        .map(str::parse::<PythonVersion>)
+        .transpose()
+        .with_context(|| "Invalid --python-version")?;
+
+    let files = if Path::new(input).is_file() {
+        vec![PathBuf::from(input)]
+    } else {
+        uv_compiler::collect_python_files(input, recursive)
+            .with_context(|| format!("Failed to collect Python files from pattern: {input}"))?
+    };
+
+    let mut diagnostics = Vec::new();
+    for file in &files {
+        let mut file_diagnostics = lint::lint_file(file)?;
+        if let Some(target) = &target {
+            file_diagnostics.extend(parser::check_target_version_file(file, target)?);
+            file_diagnostics.sort_by_key(|d| (d.line, d.column));
+        }
+        diagnostics.extend(file_diagnostics);
+    }
+
+    let issues = diagnostics.len();
+    for diagnostic in diagnostics {
+        reporter.emit(Message::Diagnostic(diagnostic));
+    }
+    reporter.modules += files.len();
+    if !reporter.is_json() {
+        println!("Checked {} file(s), found {} issue(s)", files.len(), issues);
+    }
+
+    if issues > 0 {
+        anyhow::bail!("{} compatibility issue(s) found", issues);
+    }
+    Ok(())
+}
+
+fn run_deps(input: &Path, python_path: Option<&Path>, reporter: &mut Reporter) -> Result<()> {
+    let interpreter = match python_path {
+        Some(path) => path.to_path_buf(),
+        None => {
+            interpreter::find_installation(&interpreter::PythonRequest::Any)
+                .with_context(|| "No Python interpreter found; pass --python-path")?
+                .executable
+        }
+    };
+
+    let graph = if input.is_file() {
+        let root = input.parent().unwrap_or_else(|| Path::new("."));
+        ModuleGraph::build(root, &[input.to_path_buf()])?
+    } else {
+        ModuleGraph::from_directory(input)?
+    };
+    let report = dependencies::check_dependencies(&graph, &interpreter)?;
+
+    reporter.modules += graph.modules.len();
+    for diagnostic in &report.missing {
+        reporter.emit(Message::Diagnostic(diagnostic.clone()));
+    }
+    if reporter.is_json() {
+        reporter.emit(Message::Dependencies {
+            interpreter: report.interpreter.clone(),
+            python_version: report.python_version,
+            packages: report.packages.clone(),
+        });
+    } else {
+        println!(
+            "Python {} at {}: {} third-party package(s), {} missing import(s)",
+            report.python_version,
+            report.interpreter.display(),
+            report.packages.len(),
+            report.missing.len()
+        );
+    }
+
+    if !report.is_ok() {
+        anyhow::bail!("Some imports do not resolve in {}", interpreter.display());
+    }
+    Ok(())
 }
@@ -0,0 +1,487 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::{BTreeSet, VecDeque};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use which::which;
+
+use crate::elf::{self, ElfFile, ElfHeader};
+
+/// Libraries every manylinux system provides, so modules may link them
+const SYSTEM_LIBRARIES: &[&str] = &[
+    "libc.so.6",
+    "libm.so.6",
+    "libdl.so.2",
+    "librt.so.1",
+    "libpthread.so.0",
+    "libutil.so.1",
+    "libnsl.so.1",
+    "libresolv.so.2",
+    "libcrypt.so.1",
+    "libgcc_s.so.1",
+    "libstdc++.so.6",
+    "libX11.so.6",
+    "libXext.so.6",
+    "libXrender.so.1",
+    "libICE.so.6",
+    "libSM.so.6",
+    "libGL.so.1",
+    "libgobject-2.0.so.0",
+    "libgthread-2.0.so.0",
+    "libglib-2.0.so.0",
+];
+
+/// Known policies: name, legacy alias, newest GLIBC 2.x and GLIBCXX 3.4.x
+const POLICIES: &[(&str, Option<&str>, u32, u32)] = &[
+    ("manylinux_2_5", Some("manylinux1"), 5, 8),
+    ("manylinux_2_12", Some("manylinux2010"), 12, 13),
+    ("manylinux_2_17", Some("manylinux2014"), 17, 19),
+    ("manylinux_2_24", None, 24, 22),
+    ("manylinux_2_28", None, 28, 25),
+    ("manylinux_2_31", None, 31, 28),
+    ("manylinux_2_34", None, 34, 29),
+    ("manylinux_2_35", None, 35, 30),
+    ("manylinux_2_39", None, 39, 33),
+];
+
+/// A manylinux policy: the newest glibc and libstdc++ symbol versions it allows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManylinuxPolicy {
+    /// PEP 600 name, e.g. `manylinux_2_17`
+    pub name: String,
+    /// Legacy name, e.g. `manylinux2014`
+    pub alias: Option<String>,
+    /// Newest `GLIBC_2.x` allowed, as `x`
+    pub glibc: u32,
+    /// Newest `GLIBCXX_3.4.x` allowed, as `x`; unknown for policies not in the table
+    pub glibcxx: Option<u32>,
+}
+
+impl ManylinuxPolicy {
+    /// The oldest known policy allowing GLIBC `2.glibc` and GLIBCXX `3.4.glibcxx`
+    fn oldest_allowing(glibc: u32, glibcxx: u32, legacy_arch: bool) -> Self {
+        POLICIES
+            .iter()
+            .filter(|(_, _, policy_glibc, _)| legacy_arch || *policy_glibc >= 17)
+            .find(|(_, _, policy_glibc, policy_glibcxx)| {
+                *policy_glibc >= glibc && *policy_glibcxx >= glibcxx
+            })
+            .map(Self::from_table)
+            .unwrap_or_else(|| Self::generic(glibc.max(17)))
+    }
+
+    fn from_table(&(name, alias, glibc, glibcxx): &(&str, Option<&str>, u32, u32)) -> Self {
+        Self {
+            name: name.to_string(),
+            alias: alias.map(str::to_string),
+            glibc,
+            glibcxx: Some(glibcxx),
+        }
+    }
+
+    /// `manylinux_2_{glibc}`, for glibc releases newer than the table
+    fn generic(glibc: u32) -> Self {
+        Self {
+            name: format!("manylinux_2_{glibc}"),
+            alias: None,
+            glibc,
+            glibcxx: None,
+        }
+    }
+}
+
+impl FromStr for ManylinuxPolicy {
+    type Err = anyhow::Error;
+
+    /// Parse `manylinux2014`, `manylinux_2_17` or `manylinux_2_17_x86_64`
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(policy) = POLICIES
+            .iter()
+            .find(|(name, alias, _, _)| s == *name || Some(s) == *alias)
+        {
+            return Ok(Self::from_table(policy));
+        }
+        let glibc = s
+            .strip_prefix("manylinux_2_")
+            .and_then(|rest| rest.split('_').next())
+            .and_then(|minor| minor.parse().ok())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown manylinux policy '{}', expected e.g. manylinux2014 or manylinux_2_28",
+                    s
+                )
+            })?;
+        Ok(POLICIES
+            .iter()
+            .find(|(_, _, policy_glibc, _)| *policy_glibc == glibc)
+            .map(Self::from_table)
+            .unwrap_or_else(|| Self::generic(glibc)))
+    }
+}
+
+impl fmt::Display for ManylinuxPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.alias {
+            Some(alias) => write!(f, "{} ({})", self.name, alias),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// What a Linux extension module needs from the system it is loaded on
+#[derive(Debug, Clone, Serialize)]
+pub struct LinuxCompat {
+    /// Newest `GLIBC_*` symbol version needed, e.g. `2.17`
+    pub glibc: Option<String>,
+    /// Newest `GLIBCXX_*` symbol version needed, e.g. `3.4.19`
+    pub glibcxx: Option<String>,
+    /// Shared libraries the module needs
+    pub needed: Vec<String>,
+    /// Needed libraries manylinux systems are not guaranteed to have
+    pub external: Vec<String>,
+    /// Libraries copied next to the module by `repair`
+    pub vendored: Vec<PathBuf>,
+    /// Oldest manylinux policy the symbol versions fit
+    #[serde(serialize_with = "serialize_policy")]
+    pub policy: ManylinuxPolicy,
+    /// Platform tag, e.g. `manylinux_2_17_x86_64`
+    pub tag: String,
+}
+
+fn serialize_policy<S: serde::Serializer>(
+    policy: &ManylinuxPolicy,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&policy.name)
+}
+
+impl LinuxCompat {
+    /// Compatibility of a module, given the vendored libraries loaded with it
+    pub fn from_elf(module: &ElfFile, vendored: &[(PathBuf, ElfFile)]) -> Self {
+        let files = || std::iter::once(module).chain(vendored.iter().map(|(_, lib)| lib));
+        let newest = |prefix: &str| {
+            files()
+                .flat_map(|file| file.versions.values().flatten())
+                .filter_map(|version| parse_symbol_version(version, prefix))
+                .max()
+        };
+        let glibc = newest("GLIBC_");
+        let glibcxx = newest("GLIBCXX_");
+
+        let vendored_names: BTreeSet<&str> = vendored
+            .iter()
+            .filter_map(|(path, _)| path.file_name()?.to_str())
+            .collect();
+        let external = files()
+            .flat_map(|file| &file.needed)
+            .filter(|name| !is_system_library(name) && !vendored_names.contains(name.as_str()))
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        // Only x86 had manylinux policies before manylinux2014
+        let legacy_arch = matches!(module.header.machine, elf::EM_X86_64 | elf::EM_386);
+        let policy = ManylinuxPolicy::oldest_allowing(
+            glibc.as_ref().and_then(|v| v.get(1).copied()).unwrap_or(0),
+            glibcxx
+                .as_ref()
+                .and_then(|v| v.get(2).copied())
+                .unwrap_or(0),
+            legacy_arch,
+        );
+        let tag = format!("{}_{}", policy.name, platform_arch(&module.header));
+
+        let dotted = |version: Vec<u32>| {
+            version
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(".")
+        };
+        Self {
+            glibc: glibc.map(dotted),
+            glibcxx: glibcxx.map(dotted),
+            needed: module.needed.clone(),
+            external,
+            vendored: vendored.iter().map(|(path, _)| path.clone()).collect(),
+            policy,
+            tag,
+        }
+    }
+
+    /// Fail unless the module loads on every system of `policy`
+    pub fn check_policy(&self, policy: &ManylinuxPolicy) -> Result<()> {
+        // Component `index` of a dotted version, e.g. 17 of 2.17
+        let component = |version: &Option<String>, index: usize| {
+            version
+                .as_deref()
+                .and_then(|v| v.split('.').nth(index)?.parse::<u32>().ok())
+        };
+        let mut problems = vec![];
+        if component(&self.glibc, 1).is_some_and(|needed| needed > policy.glibc) {
+            problems.push(format!(
+                "GLIBC_{}",
+                self.glibc.as_deref().unwrap_or_default()
+            ));
+        }
+        if let (Some(needed), Some(allowed)) = (component(&self.glibcxx, 2), policy.glibcxx) {
+            if needed > allowed {
+                problems.push(format!(
+                    "GLIBCXX_{}",
+                    self.glibcxx.as_deref().unwrap_or_default()
+                ));
+            }
+        }
+        let fix = if problems.is_empty() {
+            "build with --repair to vendor them".to_string()
+        } else {
+            format!(
+                "build on an older system, e.g. in a quay.io/pypa/{} container",
+                policy.alias.as_deref().unwrap_or(&policy.name)
+            )
+        };
+        problems.extend(self.external.iter().cloned());
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "Module is not {} compatible: it needs {}; {}",
+            policy,
+            problems.join(", "),
+            fix
+        ))
+    }
+}
+
+impl fmt::Display for LinuxCompat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.tag)?;
+        if let Some(alias) = &self.policy.alias {
+            write!(f, " ({alias})")?;
+        }
+        if let Some(glibc) = &self.glibc {
+            write!(f, ", GLIBC {glibc}")?;
+        }
+        if let Some(glibcxx) = &self.glibcxx {
+            write!(f, ", GLIBCXX {glibcxx}")?;
+        }
+        if !self.needed.is_empty() {
+            write!(f, ", needs {}", self.needed.join(", "))?;
+        }
+        if !self.vendored.is_empty() {
+            write!(f, ", vendored {}", self.vendored.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// `[2, 17]` from `GLIBC_2.17` with prefix `GLIBC_`; `None` for e.g. `GLIBC_PRIVATE`
+fn parse_symbol_version(version: &str, prefix: &str) -> Option<Vec<u32>> {
+    version
+        .strip_prefix(prefix)?
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// Whether manylinux systems provide `name`, including the dynamic loader and
+/// the interpreter's own libpython
+pub fn is_system_library(name: &str) -> bool {
+    SYSTEM_LIBRARIES.contains(&name)
+        || name.starts_with("ld-linux")
+        || name.starts_with("ld64.so")
+        || name.starts_with("libpython3")
+}
+
+/// Architecture as platform tags spell it
+fn platform_arch(header: &ElfHeader) -> String {
+    match header.machine {
+        elf::EM_386 => "i686".to_string(),
+        elf::EM_ARM => "armv7l".to_string(),
+        elf::EM_PPC64 if header.little_endian => "ppc64le".to_string(),
+        elf::EM_RISCV => "riscv64".to_string(),
+        machine => elf::machine_name(machine),
+    }
+}
+
+/// Report what the module at `path` needs, vendoring its non-system libraries
+/// first if `repair` is set
+pub fn check(path: &Path, repair: bool) -> Result<LinuxCompat> {
+    let module = ElfFile::read(path)?;
+    let vendored = if repair {
+        repair_module(path, &module)?
+    } else {
+        vec![]
+    };
+    Ok(LinuxCompat::from_elf(&module, &vendored))
+}
+
+/// Copy the non-system libraries the module at `path` needs, and the ones they
+/// need in turn, next to it. The module must find them through an `$ORIGIN`
+/// RPATH, which builds with `--repair` are linked with.
+pub fn repair(path: &Path) -> Result<Vec<PathBuf>> {
+    let module = ElfFile::read(path)?;
+    Ok(repair_module(path, &module)?
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect())
+}
+
+fn repair_module(path: &Path, module: &ElfFile) -> Result<Vec<(PathBuf, ElfFile)>> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut queue: VecDeque<String> = module
+        .needed
+        .iter()
+        .filter(|name| !is_system_library(name))
+        .cloned()
+        .collect();
+    if queue.is_empty() {
+        return Ok(vec![]);
+    }
+    ensure_origin_rpath(path, module)?;
+
+    let search_path = library_search_path(module, dir);
+    let mut seen = BTreeSet::new();
+    let mut vendored = vec![];
+    while let Some(name) = queue.pop_front() {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        let dest = dir.join(&name);
+        // A copy from an earlier repair is only used when the original is gone
+        let source = find_library(&name, &search_path, &module.header)
+            .or_else(|| dest.is_file().then(|| dest.clone()))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Cannot find {}, needed by {}; add its directory to LD_LIBRARY_PATH",
+                    name,
+                    path.display()
+                )
+            })?;
+        if source != dest {
+            info!("Vendoring {} next to {}", source.display(), path.display());
+            fs::copy(&source, &dest).with_context(|| {
+                format!("Failed to copy {} to {}", source.display(), dest.display())
+            })?;
+        }
+
+        let library = ElfFile::read(&dest)?;
+        queue.extend(
+            library
+                .needed
+                .iter()
+                .filter(|name| !is_system_library(name))
+                .cloned(),
+        );
+        vendored.push((dest, library));
+    }
+    Ok(vendored)
+}
+
+/// Make sure the module searches its own directory for libraries, using
+/// patchelf if it was not linked that way
+fn ensure_origin_rpath(path: &Path, module: &ElfFile) -> Result<()> {
+    let is_origin = |entry: &String| entry == "$ORIGIN" || entry == "${ORIGIN}";
+    if module.rpath.iter().chain(&module.runpath).any(is_origin) {
+        return Ok(());
+    }
+    let patchelf = which("patchelf").map_err(|_| {
+        anyhow!(
+            "{} has no $ORIGIN RPATH; rebuild it with --repair or install patchelf",
+            path.display()
+        )
+    })?;
+    debug!("Setting the RPATH of {} with patchelf", path.display());
+    let status = Command::new(patchelf)
+        .args(["--force-rpath", "--set-rpath", "$ORIGIN"])
+        .arg(path)
+        .status()
+        .with_context(|| "Failed to run patchelf")?;
+    if !status.success() {
+        return Err(anyhow!(
+            "patchelf failed to set the RPATH of {}",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Directories the dynamic loader would search for the module's libraries,
+/// except the module's own
+fn library_search_path(module: &ElfFile, dir: &Path) -> Vec<PathBuf> {
+    // DT_RPATH is ignored when DT_RUNPATH is present
+    let entries = if module.runpath.is_empty() {
+        &module.rpath
+    } else {
+        &module.runpath
+    };
+    let origin = dir.to_string_lossy();
+    let mut dirs: Vec<PathBuf> = entries
+        .iter()
+        .map(|entry| {
+            PathBuf::from(
+                entry
+                    .replace("${ORIGIN}", &origin)
+                    .replace("$ORIGIN", &origin),
+            )
+        })
+        .collect();
+    if let Some(paths) = env::var_os("LD_LIBRARY_PATH") {
+        dirs.extend(env::split_paths(&paths));
+    }
+    dirs.extend(ld_so_conf_dirs(Path::new("/etc/ld.so.conf")));
+    dirs.extend(
+        ["/lib64", "/usr/lib64", "/lib", "/usr/lib", "/usr/local/lib"]
+            .iter()
+            .map(PathBuf::from),
+    );
+
+    let own = dir.canonicalize().ok();
+    dirs.retain(|candidate| candidate.canonicalize().ok() != own);
+    dirs
+}
+
+/// Directories listed in an `ld.so.conf`, following `include` lines
+fn ld_so_conf_dirs(conf: &Path) -> Vec<PathBuf> {
+    let Ok(content) = fs::read_to_string(conf) else {
+        return vec![];
+    };
+    let mut dirs = vec![];
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(pattern) = line.strip_prefix("include") {
+            let pattern = conf.parent().unwrap_or(Path::new("/")).join(pattern.trim());
+            for included in glob::glob(&pattern.to_string_lossy())
+                .into_iter()
+                .flatten()
+                .flatten()
+            {
+                dirs.extend(ld_so_conf_dirs(&included));
+            }
+        } else if !line.is_empty() {
+            dirs.push(PathBuf::from(line));
+        }
+    }
+    dirs
+}
+
+/// The first `name` in `search_path` built for the module's architecture
+fn find_library(name: &str, search_path: &[PathBuf], module: &ElfHeader) -> Option<PathBuf> {
+    search_path
+        .iter()
+        .map(|dir| dir.join(name))
+        .find(|candidate| {
+            ElfHeader::read(candidate).is_ok_and(|header| {
+                header.machine == module.machine && header.is_64 == module.is_64
+            })
+        })
+}
@@ -0,0 +1,288 @@
+use anyhow::{anyhow, Result};
+use log::{error, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::dcc::DccInterpreter;
+use crate::diagnostics::Diagnostic;
+use crate::doctor::DoctorCheck;
+use crate::env_cache::CachedEnv;
+use crate::inspect::BinaryInfo;
+use crate::plan::BuildPlan;
+use crate::python_version::PythonVersion;
+
+/// Version of the JSON message schema; bumped on incompatible changes.
+///
+/// See `docs/MESSAGE_FORMAT.md` for the schema.
+pub const MESSAGE_FORMAT_VERSION: u32 = 1;
+
+/// How the command line reports progress and results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Text for people
+    #[default]
+    Human,
+    /// Newline-delimited JSON, one [`Message`] per line
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!(
+                "Unknown message format '{}', expected human or json",
+                s
+            )),
+        }
+    }
+}
+
+/// A machine-readable event, serialized with a `reason` tag
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum Message {
+    /// A module started compiling
+    ModuleStarted {
+        /// Python source being compiled
+        source: PathBuf,
+        /// Extension module being written
+        output: PathBuf,
+    },
+    /// A module was skipped because its output is up to date
+    ModuleFresh {
+        /// Python source of the module
+        source: PathBuf,
+        /// Existing extension module
+        output: PathBuf,
+    },
+    /// A module finished compiling, successfully or not
+    ModuleFinished {
+        /// Python source that was compiled
+        source: PathBuf,
+        /// Extension module that was written
+        output: PathBuf,
+        /// Whether the build succeeded
+        success: bool,
+        /// Time the build took, in milliseconds
+        duration_ms: u64,
+        /// Why the build failed
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// A problem found in a source file
+    Diagnostic(Diagnostic),
+    /// Packages found (or not) in the target interpreter
+    Dependencies {
+        /// Interpreter the imports were resolved with
+        interpreter: PathBuf,
+        /// Version of that interpreter
+        python_version: PythonVersion,
+        /// Top-level third-party packages and whether they were found
+        packages: BTreeMap<String, bool>,
+    },
+    /// An import graph printed by `graph`
+    Graph {
+        /// `json` or `dot`
+        format: String,
+        /// The graph as a JSON object, or DOT source as a string
+        graph: serde_json::Value,
+    },
+    /// What `plan` or `batch --dry-run` would do
+    Plan(BuildPlan),
+    /// One result of `doctor`
+    Doctor(DoctorCheck),
+    /// What a compiled extension module is, from `inspect`
+    Inspect(Box<BinaryInfo>),
+    /// An interpreter bundled with a DCC, from `interpreters`
+    Interpreter(DccInterpreter),
+    /// A cached build environment, from `env list`
+    Environment {
+        /// The environment and what it was built from
+        #[serde(flatten)]
+        env: CachedEnv,
+        /// Disk space it takes, in bytes
+        size: u64,
+    },
+    /// A cached build environment removed by `env remove` or `env prune`
+    EnvironmentRemoved {
+        /// Its directory
+        path: PathBuf,
+    },
+    /// The effective configuration printed by `config show`
+    Config {
+        /// Configuration file, if one was found
+        source: Option<PathBuf>,
+        /// The merged settings
+        config: serde_json::Value,
+    },
+    /// The last message of every command
+    Summary {
+        /// Subcommand that ran
+        command: String,
+        /// Whether the command succeeded
+        success: bool,
+        /// Modules compiled or files checked
+        modules: usize,
+        /// Modules that failed to compile
+        failed: usize,
+        /// Diagnostics reported
+        diagnostics: usize,
+        /// Time the command took, in milliseconds
+        duration_ms: u64,
+        /// Why the command failed
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    version: u32,
+    #[serde(flatten)]
+    message: &'a Message,
+}
+
+impl Message {
+    /// A [`Message::ModuleFinished`] for the result of compiling `source`
+    pub fn module_finished(
+        source: &Path,
+        output: &Path,
+        duration: Duration,
+        error: Option<&anyhow::Error>,
+    ) -> Self {
+        Self::ModuleFinished {
+            source: source.to_path_buf(),
+            output: output.to_path_buf(),
+            success: error.is_none(),
+            duration_ms: millis(duration),
+            error: error.map(|e| format!("{e:#}")),
+        }
+    }
+
+    /// The message as one line of JSON, including the schema version
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&Envelope {
+            version: MESSAGE_FORMAT_VERSION,
+            message: self,
+        })?)
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Writes messages in the selected format and counts what was reported
+pub struct Reporter<W: Write = io::Stdout> {
+    format: MessageFormat,
+    out: W,
+    started: Instant,
+    /// Modules compiled or files checked so far
+    pub modules: usize,
+    /// Modules that failed to compile so far
+    pub failed: usize,
+    /// Diagnostics reported so far
+    pub diagnostics: usize,
+}
+
+impl Reporter {
+    /// A reporter writing to stdout
+    pub fn new(format: MessageFormat) -> Self {
+        Self::with_writer(format, io::stdout())
+    }
+}
+
+impl<W: Write> Reporter<W> {
+    /// A reporter writing to `out`
+    pub fn with_writer(format: MessageFormat, out: W) -> Self {
+        Self {
+            format,
+            out,
+            started: Instant::now(),
+            modules: 0,
+            failed: 0,
+            diagnostics: 0,
+        }
+    }
+
+    /// Whether messages are written as JSON
+    pub fn is_json(&self) -> bool {
+        self.format == MessageFormat::Json
+    }
+
+    /// Record and write a message.
+    ///
+    /// In human format only module results and diagnostics are written;
+    /// commands print their other output themselves.
+    pub fn emit(&mut self, message: Message) {
+        match &message {
+            Message::ModuleFinished { success, .. } => {
+                self.modules += 1;
+                if !success {
+                    self.failed += 1;
+                }
+            }
+            Message::Diagnostic(_) => self.diagnostics += 1,
+            _ => {}
+        }
+
+        if let Err(e) = self.write(&message) {
+            warn!("Failed to write message: {e}");
+        }
+    }
+
+    fn write(&mut self, message: &Message) -> Result<()> {
+        if self.is_json() {
+            writeln!(self.out, "{}", message.to_json()?)?;
+            // Consumers read events as they happen
+            self.out.flush()?;
+            return Ok(());
+        }
+
+        match message {
+            Message::ModuleFinished {
+                source,
+                success,
+                duration_ms,
+                error,
+                ..
+            } => {
+                let status = if *success { "ok" } else { "failed" };
+                let seconds = *duration_ms as f64 / 1000.0;
+                writeln!(self.out, "{status:<6} {} ({seconds:.1}s)", source.display())?;
+                if let Some(message) = error {
+                    error!("{message}");
+                }
+            }
+            Message::ModuleFresh { source, .. } => {
+                writeln!(self.out, "fresh  {}", source.display())?;
+            }
+            Message::Diagnostic(diagnostic) => writeln!(self.out, "{diagnostic}")?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Emit the [`Message::Summary`] for `command` and return the writer
+    pub fn finish(mut self, command: &str, result: &Result<()>) -> W {
+        let summary = Message::Summary {
+            command: command.to_string(),
+            success: result.is_ok() && self.failed == 0,
+            modules: self.modules,
+            failed: self.failed,
+            diagnostics: self.diagnostics,
+            duration_ms: millis(self.started.elapsed()),
+            error: result.as_ref().err().map(|e| format!("{e:#}")),
+        };
+        self.emit(summary);
+        self.out
+    }
+}
@@ -0,0 +1,601 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use rustpython_parser::ast::{self, Ranged};
+use rustpython_parser::source_code::RandomLocator;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::stdlib::is_stdlib_module;
+use super::{parse_source, walk_stmt, walk_suite, Visitor};
+
+/// Where an imported module comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImportKind {
+    /// A module of the project itself
+    Internal,
+    /// A package installed from elsewhere
+    ThirdParty,
+    /// A module of the Python standard library
+    Stdlib,
+}
+
+/// One module imported by another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImportEdge {
+    /// Absolute dotted name of the imported module
+    pub module: String,
+    /// 1-based line of the import statement
+    pub line: usize,
+    /// 1-based column of the import statement
+    pub column: usize,
+    /// Where the imported module comes from
+    pub kind: ImportKind,
+    /// Whether the import only runs when a function is called
+    pub deferred: bool,
+    /// Whether an `except ImportError` handler guards the import
+    pub guarded: bool,
+}
+
+/// A Python module of the project
+#[derive(Debug, Clone, Serialize)]
+pub struct ModuleNode {
+    /// Absolute dotted module name
+    pub name: String,
+    /// Source file of the module
+    pub path: PathBuf,
+    /// Whether the module is a package `__init__.py`
+    pub is_package: bool,
+    /// Imports outside `if TYPE_CHECKING:` blocks, one per imported module
+    pub imports: Vec<ImportEdge>,
+}
+
+/// Import dependencies between the modules of a project.
+///
+/// Imports deferred into a function body are recorded but do not count as
+/// dependencies, since they do not run when the module is imported.
+#[derive(Debug, Clone)]
+pub struct ModuleGraph {
+    /// Directory module names are relative to
+    pub root: PathBuf,
+    /// Modules keyed by dotted name
+    pub modules: BTreeMap<String, ModuleNode>,
+}
+
+#[derive(Serialize)]
+struct GraphReport<'a> {
+    root: &'a Path,
+    modules: Vec<&'a ModuleNode>,
+    build_order: Vec<&'a str>,
+    cycles: Vec<Vec<&'a str>>,
+}
+
+impl ModuleGraph {
+    /// Build the graph for every Python file below `dir`.
+    ///
+    /// If `dir` is itself a package, module names start at the first parent
+    /// directory that is not, so absolute imports of the package resolve.
+    pub fn from_directory(dir: &Path) -> Result<Self> {
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve directory: {}", dir.display()))?;
+        let pattern = dir.to_string_lossy();
+        let files = crate::uv_compiler::collect_python_files(&pattern, true)
+            .with_context(|| format!("Failed to collect Python files from {}", dir.display()))?;
+
+        Self::build(&source_root(&dir), &files)
+    }
+
+    /// Build the graph for `files`, naming modules relative to `root`
+    pub fn build(root: &Path, files: &[PathBuf]) -> Result<Self> {
+        info!(
+            "Building import graph for {} files under {}",
+            files.len(),
+            root.display()
+        );
+
+        let mut parsed = Vec::new();
+        for path in files {
+            let (name, is_package) = module_name(root, path);
+            let source = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read Python file: {}", path.display()))?;
+            let suite = parse_source(&source)
+                .with_context(|| format!("Failed to parse Python file: {}", path.display()))?;
+            parsed.push((name, is_package, path.clone(), source, suite));
+        }
+
+        let names: BTreeSet<String> = parsed.iter().map(|(name, ..)| name.clone()).collect();
+        let resolver = Resolver::new(&names);
+
+        let mut modules = BTreeMap::new();
+        for (name, is_package, path, source, suite) in parsed {
+            let imports = resolver.module_imports(&name, is_package, &source, &suite);
+            debug!("{} imports {} modules", name, imports.len());
+            modules.insert(
+                name.clone(),
+                ModuleNode {
+                    name,
+                    path,
+                    is_package,
+                    imports,
+                },
+            );
+        }
+
+        let graph = Self {
+            root: root.to_path_buf(),
+            modules,
+        };
+        for cycle in graph.cycles() {
+            warn!("Circular import between: {}", cycle.join(", "));
+        }
+        Ok(graph)
+    }
+
+    /// Internal modules imported by `module`
+    pub fn dependencies<'a>(&'a self, module: &str) -> impl Iterator<Item = &'a str> + 'a {
+        self.modules
+            .get(module)
+            .into_iter()
+            .flat_map(|node| &node.imports)
+            .filter(|edge| edge.kind == ImportKind::Internal && !edge.deferred)
+            .map(|edge| edge.module.as_str())
+            .filter(|name| self.modules.contains_key(*name))
+    }
+
+    /// Modules ordered so that each comes after the modules it imports.
+    ///
+    /// Modules in an import cycle are kept next to each other.
+    pub fn build_order(&self) -> Vec<&str> {
+        self.strongly_connected_components()
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Groups of modules that import each other, directly or indirectly
+    pub fn cycles(&self) -> Vec<Vec<&str>> {
+        self.strongly_connected_components()
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.dependencies(component[0]).any(|d| d == component[0])
+            })
+            .collect()
+    }
+
+    /// Tarjan's algorithm; components are returned dependencies first
+    fn strongly_connected_components(&self) -> Vec<Vec<&str>> {
+        struct State<'a> {
+            index: HashMap<&'a str, usize>,
+            low_link: HashMap<&'a str, usize>,
+            stack: Vec<&'a str>,
+            on_stack: BTreeSet<&'a str>,
+            components: Vec<Vec<&'a str>>,
+        }
+
+        fn connect<'a>(graph: &'a ModuleGraph, state: &mut State<'a>, module: &'a str) {
+            let index = state.index.len();
+            state.index.insert(module, index);
+            state.low_link.insert(module, index);
+            state.stack.push(module);
+            state.on_stack.insert(module);
+
+            for dependency in graph.dependencies(module) {
+                if !state.index.contains_key(dependency) {
+                    connect(graph, state, dependency);
+                    let low = state.low_link[module].min(state.low_link[dependency]);
+                    state.low_link.insert(module, low);
+                } else if state.on_stack.contains(dependency) {
+                    let low = state.low_link[module].min(state.index[dependency]);
+                    state.low_link.insert(module, low);
+                }
+            }
+
+            if state.low_link[module] == state.index[module] {
+                let mut component = Vec::new();
+                while let Some(member) = state.stack.pop() {
+                    state.on_stack.remove(member);
+                    component.push(member);
+                    if member == module {
+                        break;
+                    }
+                }
+                component.sort_unstable();
+                state.components.push(component);
+            }
+        }
+
+        let mut state = State {
+            index: HashMap::new(),
+            low_link: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: BTreeSet::new(),
+            components: Vec::new(),
+        };
+        for module in self.modules.keys() {
+            if !state.index.contains_key(module.as_str()) {
+                connect(self, &mut state, module);
+            }
+        }
+        state.components
+    }
+
+    /// Serialize modules, imports, build order and cycles as JSON
+    pub fn to_json(&self) -> Result<String> {
+        let report = GraphReport {
+            root: &self.root,
+            modules: self.modules.values().collect(),
+            build_order: self.build_order(),
+            cycles: self.cycles(),
+        };
+        serde_json::to_string_pretty(&report).with_context(|| "Failed to serialize import graph")
+    }
+
+    /// Render the graph in Graphviz DOT format.
+    ///
+    /// Edges inside import cycles are drawn in red and imports deferred into
+    /// functions are dashed. Stdlib and third-party modules are only drawn
+    /// when `include_external` is set.
+    pub fn to_dot(&self, include_external: bool) -> String {
+        let cyclic: BTreeSet<&str> = self.cycles().into_iter().flatten().collect();
+        let component_of: HashMap<&str, usize> = self
+            .strongly_connected_components()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, component)| component.into_iter().map(move |m| (m, i)))
+            .collect();
+
+        let mut dot = String::from("digraph imports {\n    rankdir=LR;\n    node [shape=box];\n");
+        for name in self.modules.keys() {
+            let color = if cyclic.contains(name.as_str()) {
+                ", color=red"
+            } else {
+                ""
+            };
+            writeln!(dot, "    \"{name}\" [label=\"{name}\"{color}];").unwrap();
+        }
+
+        let mut external = BTreeMap::new();
+        for node in self.modules.values() {
+            for edge in &node.imports {
+                let internal = edge.kind == ImportKind::Internal;
+                if internal && !self.modules.contains_key(&edge.module) {
+                    continue;
+                }
+                if !internal {
+                    if !include_external {
+                        continue;
+                    }
+                    external.insert(edge.module.as_str(), edge.kind);
+                }
+
+                let same_cycle = internal
+                    && !edge.deferred
+                    && cyclic.contains(edge.module.as_str())
+                    && component_of.get(node.name.as_str())
+                        == component_of.get(edge.module.as_str());
+                let style = if same_cycle {
+                    " [color=red]"
+                } else if edge.deferred {
+                    " [style=dashed]"
+                } else {
+                    ""
+                };
+                writeln!(
+                    dot,
+                    "    \"{}\" -> \"{}\"{};",
+                    node.name, edge.module, style
+                )
+                .unwrap();
+            }
+        }
+
+        for (name, kind) in external {
+            let style = match kind {
+                ImportKind::Stdlib => "shape=ellipse, color=gray",
+                _ => "shape=ellipse, style=dashed",
+            };
+            writeln!(dot, "    \"{name}\" [{style}];").unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// The first ancestor of `dir` (or `dir` itself) that is not a package
+fn source_root(dir: &Path) -> PathBuf {
+    let mut root = dir.to_path_buf();
+    while root.join("__init__.py").is_file() {
+        match root.parent() {
+            Some(parent) => root = parent.to_path_buf(),
+            None => break,
+        }
+    }
+    root
+}
+
+/// Dotted name `path` is imported as, found by walking up its packages
+pub fn dotted_module_name(path: &Path) -> String {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let root = source_root(path.parent().unwrap_or_else(|| Path::new("")));
+    module_name(&root, &path).0
+}
+
+/// Dotted module name of `path` relative to `root`, and whether it is a package
+fn module_name(root: &Path, path: &Path) -> (String, bool) {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let mut parts: Vec<String> = relative
+        .with_extension("")
+        .components()
+        .filter_map(|component| match component {
+            std::path::Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+
+    let is_package = parts.last().is_some_and(|last| last == "__init__") && parts.len() > 1;
+    if is_package {
+        parts.pop();
+    }
+    (parts.join("."), is_package)
+}
+
+struct Resolver<'a> {
+    modules: &'a BTreeSet<String>,
+    top_level: BTreeSet<&'a str>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(modules: &'a BTreeSet<String>) -> Self {
+        let top_level = modules
+            .iter()
+            .filter_map(|name| name.split('.').next())
+            .collect();
+        Self { modules, top_level }
+    }
+
+    fn classify(&self, module: &str) -> ImportKind {
+        let top_level = module.split('.').next().unwrap_or_default();
+        if self.top_level.contains(top_level) {
+            ImportKind::Internal
+        } else if is_stdlib_module(module) {
+            ImportKind::Stdlib
+        } else {
+            ImportKind::ThirdParty
+        }
+    }
+
+    /// The longest prefix of `module` that is a project module
+    fn resolve(&self, module: &str) -> String {
+        let mut candidate = module;
+        loop {
+            if self.modules.contains(candidate) {
+                return candidate.to_string();
+            }
+            match candidate.rfind('.') {
+                Some(index) => candidate = &candidate[..index],
+                None => return module.to_string(),
+            }
+        }
+    }
+
+    /// The target of `from <base> import <name>`: a submodule if one exists
+    fn resolve_from(&self, base: &str, name: &str) -> String {
+        let submodule = if base.is_empty() {
+            name.to_string()
+        } else {
+            format!("{base}.{name}")
+        };
+        if self.modules.contains(&submodule) {
+            submodule
+        } else {
+            self.resolve(base)
+        }
+    }
+
+    fn module_imports(
+        &self,
+        name: &str,
+        is_package: bool,
+        source: &str,
+        suite: &ast::Suite,
+    ) -> Vec<ImportEdge> {
+        let mut collector = ImportCollector::default();
+        walk_suite(&mut collector, suite);
+
+        let mut locator = RandomLocator::new(source);
+        let mut imports: Vec<ImportEdge> = Vec::new();
+        let mut add = |module: String, kind: ImportKind, site: &ImportSite| {
+            if module == name || module.is_empty() {
+                return;
+            }
+            let location = locator.locate(site.stmt.start());
+            let edge = ImportEdge {
+                module,
+                line: location.row.get() as usize,
+                column: location.column.get() as usize,
+                kind,
+                deferred: site.deferred,
+                guarded: site.guarded,
+            };
+            match imports.iter_mut().find(|i| i.module == edge.module) {
+                // Keep the import that is most likely to run and to fail
+                Some(existing)
+                    if (existing.deferred, existing.guarded) > (edge.deferred, edge.guarded) =>
+                {
+                    *existing = edge;
+                }
+                Some(_) => {}
+                None => imports.push(edge),
+            }
+        };
+
+        for site in &collector.sites {
+            match site.stmt {
+                ast::Stmt::Import(import) => {
+                    for alias in &import.names {
+                        let kind = self.classify(&alias.name);
+                        let module = match kind {
+                            ImportKind::Internal => self.resolve(&alias.name),
+                            _ => alias.name.to_string(),
+                        };
+                        add(module, kind, site);
+                    }
+                }
+                ast::Stmt::ImportFrom(import) => {
+                    let level = import.level.map_or(0, |level| level.to_u32()) as usize;
+                    let module = import.module.as_ref().map(|m| m.to_string());
+
+                    if level == 0 {
+                        let Some(module) = module else {
+                            continue;
+                        };
+                        let kind = self.classify(&module);
+                        if kind != ImportKind::Internal {
+                            add(module, kind, site);
+                            continue;
+                        }
+                        for alias in &import.names {
+                            add(self.resolve_from(&module, &alias.name), kind, site);
+                        }
+                        continue;
+                    }
+
+                    // Relative import: start from the importing module's package
+                    let mut package: Vec<&str> =
+                        name.split('.').filter(|p| !p.is_empty()).collect();
+                    if !is_package {
+                        package.pop();
+                    }
+                    if level - 1 > package.len() {
+                        warn!("Relative import beyond top-level package in {}", name);
+                        continue;
+                    }
+                    package.truncate(package.len() - (level - 1));
+
+                    let mut base = package.join(".");
+                    if let Some(module) = module {
+                        if !base.is_empty() {
+                            base.push('.');
+                        }
+                        base.push_str(&module);
+                    }
+                    for alias in &import.names {
+                        add(
+                            self.resolve_from(&base, &alias.name),
+                            ImportKind::Internal,
+                            site,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        imports.sort_by_key(|import| import.line);
+        imports
+    }
+}
+
+/// An import statement and the context it runs in
+struct ImportSite<'a> {
+    stmt: &'a ast::Stmt,
+    deferred: bool,
+    guarded: bool,
+}
+
+/// Collects the import statements of a module that run outside type checkers
+#[derive(Default)]
+struct ImportCollector<'a> {
+    functions: usize,
+    guards: usize,
+    sites: Vec<ImportSite<'a>>,
+}
+
+impl<'a> Visitor<'a> for ImportCollector<'a> {
+    fn visit_stmt(&mut self, stmt: &'a ast::Stmt) {
+        match stmt {
+            ast::Stmt::Import(_) | ast::Stmt::ImportFrom(_) => {
+                self.sites.push(ImportSite {
+                    stmt,
+                    deferred: self.functions > 0,
+                    guarded: self.guards > 0,
+                });
+                return;
+            }
+            ast::Stmt::FunctionDef(_) | ast::Stmt::AsyncFunctionDef(_) => {
+                self.functions += 1;
+                walk_stmt(self, stmt);
+                self.functions -= 1;
+                return;
+            }
+            // `if TYPE_CHECKING:` blocks never run
+            ast::Stmt::If(if_stmt) if is_type_checking(&if_stmt.test) => {
+                self.visit_body(&if_stmt.orelse);
+                return;
+            }
+            ast::Stmt::Try(ast::StmtTry {
+                body,
+                handlers,
+                orelse,
+                finalbody,
+                ..
+            })
+            | ast::Stmt::TryStar(ast::StmtTryStar {
+                body,
+                handlers,
+                orelse,
+                finalbody,
+                ..
+            }) if catches_import_error(handlers) => {
+                self.guards += 1;
+                self.visit_body(body);
+                self.guards -= 1;
+                for handler in handlers {
+                    let ast::ExceptHandler::ExceptHandler(handler) = handler;
+                    self.visit_body(&handler.body);
+                }
+                self.visit_body(orelse);
+                self.visit_body(finalbody);
+                return;
+            }
+            _ => {}
+        }
+
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, _expr: &'a ast::Expr) {}
+}
+
+fn is_type_checking(test: &ast::Expr) -> bool {
+    match test {
+        ast::Expr::Name(name) => name.id.as_str() == "TYPE_CHECKING",
+        ast::Expr::Attribute(attribute) => attribute.attr.as_str() == "TYPE_CHECKING",
+        _ => false,
+    }
+}
+
+/// Whether one of the handlers catches a failed import
+fn catches_import_error(handlers: &[ast::ExceptHandler]) -> bool {
+    fn catches(expr: &ast::Expr) -> bool {
+        match expr {
+            ast::Expr::Name(ast::ExprName { id, .. })
+            | ast::Expr::Attribute(ast::ExprAttribute { attr: id, .. }) => matches!(
+                id.as_str(),
+                "ImportError" | "ModuleNotFoundError" | "Exception" | "BaseException"
+            ),
+            ast::Expr::Tuple(tuple) => tuple.elts.iter().any(catches),
+            _ => false,
+        }
+    }
+
+    handlers.iter().any(|handler| {
+        let ast::ExceptHandler::ExceptHandler(handler) = handler;
+        handler.type_.as_deref().map_or(true, catches)
+    })
+}
@@ -4,6 +4,17 @@ use rustpython_parser::{ast, Parse};
 use std::fs;
 use std::path::Path;
 
+mod imports;
+mod stdlib;
+mod target_version;
+mod visitor;
+pub use imports::{dotted_module_name, ImportEdge, ImportKind, ModuleGraph, ModuleNode};
+pub use stdlib::{is_stdlib_module, is_stdlib_module_in, STDLIB_MODULES};
+pub use target_version::{
+    check_target_version, check_target_version_file, SyntaxFeature, FEATURES,
+};
+pub use visitor::{walk_arguments, walk_expr, walk_pattern, walk_stmt, walk_suite, Visitor};
+
 /// Parse a Python file into an AST
 pub fn parse_file(path: &Path) -> Result<ast::Suite> {
     info!("Parsing Python file: {}", path.display());
@@ -0,0 +1,293 @@
+use crate::python_version::PythonVersion;
+
+/// Top-level modules of the Python standard library in any version since 3.7
+pub const STDLIB_MODULES: &[&str] = &[
+    "__future__",
+    "_thread",
+    "abc",
+    "aifc",
+    "annotationlib",
+    "antigravity",
+    "argparse",
+    "array",
+    "ast",
+    "asynchat",
+    "asyncio",
+    "asyncore",
+    "atexit",
+    "audioop",
+    "base64",
+    "bdb",
+    "binascii",
+    "binhex",
+    "bisect",
+    "builtins",
+    "bz2",
+    "cProfile",
+    "calendar",
+    "cgi",
+    "cgitb",
+    "chunk",
+    "cmath",
+    "cmd",
+    "code",
+    "codecs",
+    "codeop",
+    "collections",
+    "colorsys",
+    "compileall",
+    "compression",
+    "concurrent",
+    "configparser",
+    "contextlib",
+    "contextvars",
+    "copy",
+    "copyreg",
+    "crypt",
+    "csv",
+    "ctypes",
+    "curses",
+    "dataclasses",
+    "datetime",
+    "dbm",
+    "decimal",
+    "difflib",
+    "dis",
+    "distutils",
+    "doctest",
+    "dummy_threading",
+    "email",
+    "encodings",
+    "ensurepip",
+    "enum",
+    "errno",
+    "faulthandler",
+    "fcntl",
+    "filecmp",
+    "fileinput",
+    "fnmatch",
+    "formatter",
+    "fractions",
+    "ftplib",
+    "functools",
+    "gc",
+    "genericpath",
+    "getopt",
+    "getpass",
+    "gettext",
+    "glob",
+    "graphlib",
+    "grp",
+    "gzip",
+    "hashlib",
+    "heapq",
+    "hmac",
+    "html",
+    "http",
+    "idlelib",
+    "imaplib",
+    "imghdr",
+    "imp",
+    "importlib",
+    "inspect",
+    "io",
+    "ipaddress",
+    "itertools",
+    "json",
+    "keyword",
+    "lib2to3",
+    "linecache",
+    "locale",
+    "logging",
+    "lzma",
+    "macpath",
+    "mailbox",
+    "mailcap",
+    "marshal",
+    "math",
+    "mimetypes",
+    "mmap",
+    "modulefinder",
+    "msilib",
+    "msvcrt",
+    "multiprocessing",
+    "netrc",
+    "nis",
+    "nntplib",
+    "nt",
+    "ntpath",
+    "nturl2path",
+    "numbers",
+    "opcode",
+    "operator",
+    "optparse",
+    "os",
+    "ossaudiodev",
+    "parser",
+    "pathlib",
+    "pdb",
+    "pickle",
+    "pickletools",
+    "pipes",
+    "pkgutil",
+    "platform",
+    "plistlib",
+    "poplib",
+    "posix",
+    "posixpath",
+    "pprint",
+    "profile",
+    "pstats",
+    "pty",
+    "pwd",
+    "py_compile",
+    "pyclbr",
+    "pydoc",
+    "pydoc_data",
+    "pyexpat",
+    "queue",
+    "quopri",
+    "random",
+    "re",
+    "readline",
+    "reprlib",
+    "resource",
+    "rlcompleter",
+    "runpy",
+    "sched",
+    "secrets",
+    "select",
+    "selectors",
+    "shelve",
+    "shlex",
+    "shutil",
+    "signal",
+    "site",
+    "smtpd",
+    "smtplib",
+    "sndhdr",
+    "socket",
+    "socketserver",
+    "spwd",
+    "sqlite3",
+    "sre_compile",
+    "sre_constants",
+    "sre_parse",
+    "ssl",
+    "stat",
+    "statistics",
+    "string",
+    "stringprep",
+    "struct",
+    "subprocess",
+    "sunau",
+    "symbol",
+    "symtable",
+    "sys",
+    "sysconfig",
+    "syslog",
+    "tabnanny",
+    "tarfile",
+    "telnetlib",
+    "tempfile",
+    "termios",
+    "textwrap",
+    "this",
+    "threading",
+    "time",
+    "timeit",
+    "tkinter",
+    "token",
+    "tokenize",
+    "tomllib",
+    "trace",
+    "traceback",
+    "tracemalloc",
+    "tty",
+    "turtle",
+    "turtledemo",
+    "types",
+    "typing",
+    "unicodedata",
+    "unittest",
+    "urllib",
+    "uu",
+    "uuid",
+    "venv",
+    "warnings",
+    "wave",
+    "weakref",
+    "webbrowser",
+    "winreg",
+    "winsound",
+    "wsgiref",
+    "xdrlib",
+    "xml",
+    "xmlrpc",
+    "zipapp",
+    "zipfile",
+    "zipimport",
+    "zlib",
+    "zoneinfo",
+];
+
+/// Modules added or removed since Python 3.7: `(module, added in, removed in)`
+const VERSION_CHANGES: &[(&str, Option<PythonVersion>, Option<PythonVersion>)] = &[
+    ("annotationlib", Some(PythonVersion::new(3, 14)), None),
+    ("compression", Some(PythonVersion::new(3, 14)), None),
+    ("graphlib", Some(PythonVersion::new(3, 9)), None),
+    ("tomllib", Some(PythonVersion::new(3, 11)), None),
+    ("zoneinfo", Some(PythonVersion::new(3, 9)), None),
+    ("macpath", None, Some(PythonVersion::new(3, 8))),
+    ("dummy_threading", None, Some(PythonVersion::new(3, 9))),
+    ("formatter", None, Some(PythonVersion::new(3, 10))),
+    ("parser", None, Some(PythonVersion::new(3, 10))),
+    ("symbol", None, Some(PythonVersion::new(3, 10))),
+    ("binhex", None, Some(PythonVersion::new(3, 11))),
+    ("asynchat", None, Some(PythonVersion::new(3, 12))),
+    ("asyncore", None, Some(PythonVersion::new(3, 12))),
+    ("distutils", None, Some(PythonVersion::new(3, 12))),
+    ("imp", None, Some(PythonVersion::new(3, 12))),
+    ("smtpd", None, Some(PythonVersion::new(3, 12))),
+    ("aifc", None, Some(PythonVersion::new(3, 13))),
+    ("audioop", None, Some(PythonVersion::new(3, 13))),
+    ("cgi", None, Some(PythonVersion::new(3, 13))),
+    ("cgitb", None, Some(PythonVersion::new(3, 13))),
+    ("chunk", None, Some(PythonVersion::new(3, 13))),
+    ("crypt", None, Some(PythonVersion::new(3, 13))),
+    ("imghdr", None, Some(PythonVersion::new(3, 13))),
+    ("lib2to3", None, Some(PythonVersion::new(3, 13))),
+    ("mailcap", None, Some(PythonVersion::new(3, 13))),
+    ("msilib", None, Some(PythonVersion::new(3, 13))),
+    ("nis", None, Some(PythonVersion::new(3, 13))),
+    ("nntplib", None, Some(PythonVersion::new(3, 13))),
+    ("ossaudiodev", None, Some(PythonVersion::new(3, 13))),
+    ("pipes", None, Some(PythonVersion::new(3, 13))),
+    ("sndhdr", None, Some(PythonVersion::new(3, 13))),
+    ("spwd", None, Some(PythonVersion::new(3, 13))),
+    ("sunau", None, Some(PythonVersion::new(3, 13))),
+    ("telnetlib", None, Some(PythonVersion::new(3, 13))),
+    ("uu", None, Some(PythonVersion::new(3, 13))),
+    ("xdrlib", None, Some(PythonVersion::new(3, 13))),
+];
+
+/// Whether `module` (a dotted name) belongs to the standard library
+pub fn is_stdlib_module(module: &str) -> bool {
+    let top_level = module.split('.').next().unwrap_or_default();
+    STDLIB_MODULES.binary_search(&top_level).is_ok()
+}
+
+/// Whether `module` (a dotted name) ships with the standard library of `version`
+pub fn is_stdlib_module_in(module: &str, version: &PythonVersion) -> bool {
+    if !is_stdlib_module(module) {
+        return false;
+    }
+
+    let top_level = module.split('.').next().unwrap_or_default();
+    let Some((_, added, removed)) = VERSION_CHANGES.iter().find(|(m, ..)| *m == top_level) else {
+        return true;
+    };
+    let added = added.map_or(true, |added| version.at_least(added.major, added.minor));
+    let removed = removed.is_some_and(|removed| version.at_least(removed.major, removed.minor));
+    added && !removed
+}
@@ -0,0 +1,430 @@
+use anyhow::{Context, Result};
+use log::{debug, info};
+use rustpython_parser::ast::{self, Ranged};
+use rustpython_parser::lexer::lex_starts_at;
+use rustpython_parser::text_size::TextSize;
+use rustpython_parser::{Mode, Tok};
+use std::fs;
+use std::path::Path;
+
+use super::{walk_arguments, walk_expr, walk_stmt, walk_suite, Visitor};
+use crate::diagnostics::{Diagnostic, Severity, SourceMap};
+use crate::python_version::PythonVersion;
+
+/// A syntax feature that only exists from a given Python version on
+pub struct SyntaxFeature {
+    /// Identifier used in reports and `# py2pyd: ignore[...]` comments
+    pub code: &'static str,
+    /// Human-readable name of the construct
+    pub description: &'static str,
+    /// First Python version that accepts the construct
+    pub minimum: PythonVersion,
+}
+
+/// Assignment expressions (`:=`, PEP 572)
+pub const ASSIGNMENT_EXPRESSION: SyntaxFeature = SyntaxFeature {
+    code: "assignment-expression",
+    description: "assignment expression (`:=`)",
+    minimum: PythonVersion::new(3, 8),
+};
+
+/// Positional-only parameters (`/`, PEP 570)
+pub const POSITIONAL_ONLY_PARAMETERS: SyntaxFeature = SyntaxFeature {
+    code: "positional-only-parameters",
+    description: "positional-only parameters (`/`)",
+    minimum: PythonVersion::new(3, 8),
+};
+
+/// Arbitrary decorator expressions (PEP 614)
+pub const RELAXED_DECORATORS: SyntaxFeature = SyntaxFeature {
+    code: "relaxed-decorators",
+    description: "decorator that is not a dotted name or call",
+    minimum: PythonVersion::new(3, 9),
+};
+
+/// Subscripting builtin collections at runtime (`list[int]`, PEP 585)
+pub const BUILTIN_GENERICS: SyntaxFeature = SyntaxFeature {
+    code: "builtin-generics",
+    description: "subscripted builtin type (`list[int]`) evaluated at runtime",
+    minimum: PythonVersion::new(3, 9),
+};
+
+/// `match` statements (PEP 634)
+pub const MATCH_STATEMENT: SyntaxFeature = SyntaxFeature {
+    code: "match-statement",
+    description: "`match` statement",
+    minimum: PythonVersion::new(3, 10),
+};
+
+/// `X | Y` type unions evaluated at runtime (PEP 604)
+pub const UNION_OPERATOR: SyntaxFeature = SyntaxFeature {
+    code: "union-operator",
+    description: "`X | Y` type union evaluated at runtime",
+    minimum: PythonVersion::new(3, 10),
+};
+
+/// Parenthesized context managers
+pub const PARENTHESIZED_CONTEXT_MANAGERS: SyntaxFeature = SyntaxFeature {
+    code: "parenthesized-context-managers",
+    description: "parenthesized context managers",
+    minimum: PythonVersion::new(3, 10),
+};
+
+/// `except*` clauses (PEP 654)
+pub const EXCEPTION_GROUPS: SyntaxFeature = SyntaxFeature {
+    code: "exception-groups",
+    description: "`except*` clause",
+    minimum: PythonVersion::new(3, 11),
+};
+
+/// Star expressions in subscripts and annotations (PEP 646)
+pub const VARIADIC_GENERICS: SyntaxFeature = SyntaxFeature {
+    code: "variadic-generics",
+    description: "star expression in a subscript or annotation",
+    minimum: PythonVersion::new(3, 11),
+};
+
+/// `type` statements and type parameter lists (PEP 695)
+pub const TYPE_PARAMETERS: SyntaxFeature = SyntaxFeature {
+    code: "type-parameters",
+    description: "`type` statement or type parameter list",
+    minimum: PythonVersion::new(3, 12),
+};
+
+/// All features checked by [`check_target_version`]
+pub const FEATURES: &[&SyntaxFeature] = &[
+    &ASSIGNMENT_EXPRESSION,
+    &POSITIONAL_ONLY_PARAMETERS,
+    &RELAXED_DECORATORS,
+    &BUILTIN_GENERICS,
+    &MATCH_STATEMENT,
+    &UNION_OPERATOR,
+    &PARENTHESIZED_CONTEXT_MANAGERS,
+    &EXCEPTION_GROUPS,
+    &VARIADIC_GENERICS,
+    &TYPE_PARAMETERS,
+];
+
+const BUILTIN_TYPES: &[&str] = &[
+    "bool",
+    "bytearray",
+    "bytes",
+    "complex",
+    "dict",
+    "float",
+    "frozenset",
+    "int",
+    "list",
+    "object",
+    "set",
+    "str",
+    "tuple",
+    "type",
+];
+
+const GENERIC_BUILTINS: &[&str] = &["dict", "frozenset", "list", "set", "tuple", "type"];
+
+/// Check a Python file for syntax newer than the target version
+pub fn check_target_version_file(path: &Path, target: &PythonVersion) -> Result<Vec<Diagnostic>> {
+    info!("Checking {} against Python {}", path.display(), target);
+
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read Python file: {}", path.display()))?;
+
+    check_target_version(&source, path, target)
+}
+
+/// Check Python source for syntax features that need a newer Python than `target`.
+///
+/// Every finding is reported as an error carrying the minimum version of the
+/// construct. Findings on a line carrying `# py2pyd: ignore[feature]` are dropped.
+pub fn check_target_version(
+    source: &str,
+    path: &Path,
+    target: &PythonVersion,
+) -> Result<Vec<Diagnostic>> {
+    let suite = super::parse_source(source)
+        .with_context(|| format!("Failed to parse Python file: {}", path.display()))?;
+
+    let mut checker = VersionChecker {
+        source,
+        target: *target,
+        future_annotations: has_future_annotations(&suite),
+        in_function: false,
+        findings: Vec::new(),
+    };
+    walk_suite(&mut checker, &suite);
+
+    let mut source_map = SourceMap::new(path, source);
+    let mut diagnostics = Vec::new();
+    for (offset, feature) in checker.findings {
+        let explanation = format!(
+            "build for Python {} or newer, or rewrite the construct for Python {}",
+            feature.minimum, target
+        );
+        let diagnostic = source_map.diagnostic(
+            offset,
+            feature.code,
+            Severity::Error,
+            format!(
+                "{} requires Python {}, but the target is Python {}",
+                feature.description, feature.minimum, target
+            ),
+            Some(&explanation),
+        );
+        if source_map.is_suppressed(diagnostic.line, feature.code) {
+            debug!("Suppressed {} at line {}", feature.code, diagnostic.line);
+            continue;
+        }
+        diagnostics.push(diagnostic);
+    }
+
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    debug!(
+        "Found {} constructs too new for Python {}",
+        diagnostics.len(),
+        target
+    );
+    Ok(diagnostics)
+}
+
+/// Whether the module starts with `from __future__ import annotations`
+fn has_future_annotations(suite: &ast::Suite) -> bool {
+    suite.iter().any(|stmt| {
+        matches!(stmt, ast::Stmt::ImportFrom(import)
+            if import.module.as_ref().is_some_and(|m| m.as_str() == "__future__")
+                && import.names.iter().any(|alias| alias.name.as_str() == "annotations"))
+    })
+}
+
+/// `name`, `a.b.c` or a call of either: the only decorators before PEP 614
+fn is_legacy_decorator(expr: &ast::Expr) -> bool {
+    fn is_dotted_name(expr: &ast::Expr) -> bool {
+        match expr {
+            ast::Expr::Name(_) => true,
+            ast::Expr::Attribute(attribute) => is_dotted_name(&attribute.value),
+            _ => false,
+        }
+    }
+
+    match expr {
+        ast::Expr::Call(call) => is_dotted_name(&call.func),
+        _ => is_dotted_name(expr),
+    }
+}
+
+/// Flatten `a | b | c` into its operands
+fn union_operands<'a>(expr: &'a ast::Expr, operands: &mut Vec<&'a ast::Expr>) {
+    match expr {
+        ast::Expr::BinOp(binop) if matches!(binop.op, ast::Operator::BitOr) => {
+            union_operands(&binop.left, operands);
+            union_operands(&binop.right, operands);
+        }
+        _ => operands.push(expr),
+    }
+}
+
+/// Whether an `|` expression is a type union rather than a bitwise or
+fn is_type_union(expr: &ast::Expr) -> bool {
+    let mut operands = Vec::new();
+    union_operands(expr, &mut operands);
+
+    let looks_like_type = |operand: &&ast::Expr| {
+        matches!(
+            operand,
+            ast::Expr::Name(_) | ast::Expr::Attribute(_) | ast::Expr::Subscript(_)
+        ) || matches!(operand, ast::Expr::Constant(constant)
+            if matches!(constant.value, ast::Constant::None | ast::Constant::Str(_)))
+    };
+    let is_builtin_type = |operand: &&ast::Expr| match operand {
+        ast::Expr::Name(name) => BUILTIN_TYPES.contains(&name.id.as_str()),
+        ast::Expr::Constant(constant) => matches!(constant.value, ast::Constant::None),
+        ast::Expr::Subscript(subscript) => matches!(subscript.value.as_ref(),
+            ast::Expr::Name(name) if GENERIC_BUILTINS.contains(&name.id.as_str())),
+        _ => false,
+    };
+
+    operands.iter().all(looks_like_type) && operands.iter().any(is_builtin_type)
+}
+
+fn is_starred(expr: &ast::Expr) -> bool {
+    match expr {
+        ast::Expr::Starred(_) => true,
+        ast::Expr::Tuple(tuple) => tuple
+            .elts
+            .iter()
+            .any(|elt| matches!(elt, ast::Expr::Starred(_))),
+        _ => false,
+    }
+}
+
+struct VersionChecker<'s> {
+    source: &'s str,
+    target: PythonVersion,
+    future_annotations: bool,
+    in_function: bool,
+    findings: Vec<(TextSize, &'static SyntaxFeature)>,
+}
+
+impl VersionChecker<'_> {
+    fn report(&mut self, offset: TextSize, feature: &'static SyntaxFeature) {
+        if !self
+            .target
+            .at_least(feature.minimum.major, feature.minimum.minor)
+        {
+            self.findings.push((offset, feature));
+        }
+    }
+
+    fn check_decorators(&mut self, decorators: &[ast::Expr]) {
+        for decorator in decorators {
+            if !is_legacy_decorator(decorator) {
+                self.report(decorator.start(), &RELAXED_DECORATORS);
+            }
+        }
+    }
+
+    fn with_function_scope<F: FnOnce(&mut Self)>(&mut self, in_function: bool, f: F) {
+        let outer = std::mem::replace(&mut self.in_function, in_function);
+        f(self);
+        self.in_function = outer;
+    }
+
+    /// Whether the items of a `with` statement are wrapped in one pair of parentheses.
+    ///
+    /// The AST does not record this, so the statement header is re-lexed.
+    fn has_parenthesized_items(&self, stmt: &ast::Stmt, items: &[ast::WithItem]) -> bool {
+        let needs_check = items.len() > 1 || items.iter().any(|item| item.optional_vars.is_some());
+        let Some(last) = items.last().filter(|_| needs_check) else {
+            return false;
+        };
+        let items_end = last
+            .optional_vars
+            .as_ref()
+            .map_or_else(|| last.context_expr.end(), |vars| vars.end());
+
+        let start = stmt.start();
+        let mut depth = 0usize;
+        for token in lex_starts_at(&self.source[start.to_usize()..], Mode::Module, start) {
+            let Ok((tok, range)) = token else {
+                return false;
+            };
+            match tok {
+                Tok::Async | Tok::With if depth == 0 => {}
+                Tok::Lpar => depth += 1,
+                Tok::Rpar if depth == 1 => return range.start() >= items_end,
+                Tok::Rpar => depth = depth.saturating_sub(1),
+                // The header does not start with a parenthesis
+                _ if depth == 0 => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+}
+
+impl<'a> Visitor<'a> for VersionChecker<'_> {
+    fn visit_stmt(&mut self, stmt: &'a ast::Stmt) {
+        match stmt {
+            ast::Stmt::FunctionDef(ast::StmtFunctionDef {
+                decorator_list,
+                type_params,
+                ..
+            })
+            | ast::Stmt::AsyncFunctionDef(ast::StmtAsyncFunctionDef {
+                decorator_list,
+                type_params,
+                ..
+            }) => {
+                self.check_decorators(decorator_list);
+                if !type_params.is_empty() {
+                    self.report(stmt.start(), &TYPE_PARAMETERS);
+                }
+                self.with_function_scope(true, |checker| walk_stmt(checker, stmt));
+                return;
+            }
+            ast::Stmt::ClassDef(class_def) => {
+                self.check_decorators(&class_def.decorator_list);
+                if !class_def.type_params.is_empty() {
+                    self.report(stmt.start(), &TYPE_PARAMETERS);
+                }
+                // Class bodies evaluate their annotations even inside functions
+                self.with_function_scope(false, |checker| walk_stmt(checker, stmt));
+                return;
+            }
+            ast::Stmt::AnnAssign(ann_assign) if self.in_function => {
+                // Annotations of local variables are never evaluated
+                self.visit_expr(&ann_assign.target);
+                if let Some(value) = &ann_assign.value {
+                    self.visit_expr(value);
+                }
+                return;
+            }
+            ast::Stmt::TypeAlias(_) => self.report(stmt.start(), &TYPE_PARAMETERS),
+            ast::Stmt::Match(_) => self.report(stmt.start(), &MATCH_STATEMENT),
+            ast::Stmt::TryStar(try_star) => {
+                if let Some(handler) = try_star.handlers.first() {
+                    self.report(handler.start(), &EXCEPTION_GROUPS);
+                }
+            }
+            ast::Stmt::With(ast::StmtWith { items, .. })
+            | ast::Stmt::AsyncWith(ast::StmtAsyncWith { items, .. })
+                if self.has_parenthesized_items(stmt, items) =>
+            {
+                self.report(stmt.start(), &PARENTHESIZED_CONTEXT_MANAGERS);
+            }
+            _ => {}
+        }
+
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'a ast::Expr) {
+        match expr {
+            ast::Expr::NamedExpr(named) => {
+                self.report(named.range.start(), &ASSIGNMENT_EXPRESSION);
+            }
+            ast::Expr::BinOp(binop)
+                if matches!(binop.op, ast::Operator::BitOr) && is_type_union(expr) =>
+            {
+                // Report the whole union once rather than each nested operand
+                self.report(binop.range.start(), &UNION_OPERATOR);
+                return;
+            }
+            ast::Expr::Subscript(subscript) => {
+                if is_starred(&subscript.slice) {
+                    self.report(subscript.slice.start(), &VARIADIC_GENERICS);
+                }
+                if matches!(subscript.ctx, ast::ExprContext::Load)
+                    && matches!(subscript.value.as_ref(),
+                        ast::Expr::Name(name) if GENERIC_BUILTINS.contains(&name.id.as_str()))
+                {
+                    // Nested generics such as `list[tuple[int]]` are reported once
+                    self.report(subscript.range.start(), &BUILTIN_GENERICS);
+                    return;
+                }
+            }
+            _ => {}
+        }
+
+        walk_expr(self, expr);
+    }
+
+    fn visit_annotation(&mut self, expr: &'a ast::Expr) {
+        // `*args: *Ts` is a grammar change, so it matters even when unevaluated
+        if matches!(expr, ast::Expr::Starred(_)) {
+            self.report(expr.start(), &VARIADIC_GENERICS);
+        }
+        // With postponed evaluation annotations are only stored as strings
+        if !self.future_annotations {
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_arguments(&mut self, arguments: &'a ast::Arguments) {
+        if let Some(first) = arguments.posonlyargs.first() {
+            self.report(first.def.start(), &POSITIONAL_ONLY_PARAMETERS);
+        }
+        walk_arguments(self, arguments);
+    }
+}
@@ -0,0 +1,448 @@
+use rustpython_parser::ast;
+
+/// A borrowing visitor over a Python AST.
+///
+/// Every method has a default implementation that simply walks into the
+/// node's children, so implementors only override the nodes they care about
+/// and call the matching `walk_*` function to keep descending.
+pub trait Visitor<'a> {
+    /// Visit a statement
+    fn visit_stmt(&mut self, stmt: &'a ast::Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    /// Visit an expression
+    fn visit_expr(&mut self, expr: &'a ast::Expr) {
+        walk_expr(self, expr);
+    }
+
+    /// Visit an annotation (argument, return or variable annotation)
+    fn visit_annotation(&mut self, expr: &'a ast::Expr) {
+        self.visit_expr(expr);
+    }
+
+    /// Visit a `match` case pattern
+    fn visit_pattern(&mut self, pattern: &'a ast::Pattern) {
+        walk_pattern(self, pattern);
+    }
+
+    /// Visit the arguments of a function or lambda
+    fn visit_arguments(&mut self, arguments: &'a ast::Arguments) {
+        walk_arguments(self, arguments);
+    }
+
+    /// Visit a block of statements
+    fn visit_body(&mut self, body: &'a [ast::Stmt]) {
+        for stmt in body {
+            self.visit_stmt(stmt);
+        }
+    }
+}
+
+/// Walk a whole module
+pub fn walk_suite<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, suite: &'a ast::Suite) {
+    visitor.visit_body(suite);
+}
+
+/// Walk the children of a statement
+pub fn walk_stmt<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a ast::Stmt) {
+    match stmt {
+        ast::Stmt::FunctionDef(ast::StmtFunctionDef {
+            args,
+            body,
+            decorator_list,
+            returns,
+            type_params,
+            ..
+        })
+        | ast::Stmt::AsyncFunctionDef(ast::StmtAsyncFunctionDef {
+            args,
+            body,
+            decorator_list,
+            returns,
+            type_params,
+            ..
+        }) => {
+            for decorator in decorator_list {
+                visitor.visit_expr(decorator);
+            }
+            walk_type_params(visitor, type_params);
+            visitor.visit_arguments(args);
+            if let Some(returns) = returns {
+                visitor.visit_annotation(returns);
+            }
+            visitor.visit_body(body);
+        }
+        ast::Stmt::ClassDef(class_def) => {
+            for decorator in &class_def.decorator_list {
+                visitor.visit_expr(decorator);
+            }
+            walk_type_params(visitor, &class_def.type_params);
+            for base in &class_def.bases {
+                visitor.visit_expr(base);
+            }
+            for keyword in &class_def.keywords {
+                visitor.visit_expr(&keyword.value);
+            }
+            visitor.visit_body(&class_def.body);
+        }
+        ast::Stmt::Return(ast::StmtReturn { value, .. }) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        ast::Stmt::Delete(ast::StmtDelete { targets, .. }) => {
+            for target in targets {
+                visitor.visit_expr(target);
+            }
+        }
+        ast::Stmt::Assign(ast::StmtAssign { targets, value, .. }) => {
+            for target in targets {
+                visitor.visit_expr(target);
+            }
+            visitor.visit_expr(value);
+        }
+        ast::Stmt::TypeAlias(ast::StmtTypeAlias {
+            name,
+            type_params,
+            value,
+            ..
+        }) => {
+            visitor.visit_expr(name);
+            walk_type_params(visitor, type_params);
+            visitor.visit_annotation(value);
+        }
+        ast::Stmt::AugAssign(ast::StmtAugAssign { target, value, .. }) => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(value);
+        }
+        ast::Stmt::AnnAssign(ast::StmtAnnAssign {
+            target,
+            annotation,
+            value,
+            ..
+        }) => {
+            visitor.visit_expr(target);
+            visitor.visit_annotation(annotation);
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        ast::Stmt::For(ast::StmtFor {
+            target,
+            iter,
+            body,
+            orelse,
+            ..
+        })
+        | ast::Stmt::AsyncFor(ast::StmtAsyncFor {
+            target,
+            iter,
+            body,
+            orelse,
+            ..
+        }) => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(iter);
+            visitor.visit_body(body);
+            visitor.visit_body(orelse);
+        }
+        ast::Stmt::While(ast::StmtWhile {
+            test, body, orelse, ..
+        })
+        | ast::Stmt::If(ast::StmtIf {
+            test, body, orelse, ..
+        }) => {
+            visitor.visit_expr(test);
+            visitor.visit_body(body);
+            visitor.visit_body(orelse);
+        }
+        ast::Stmt::With(ast::StmtWith { items, body, .. })
+        | ast::Stmt::AsyncWith(ast::StmtAsyncWith { items, body, .. }) => {
+            for item in items {
+                visitor.visit_expr(&item.context_expr);
+                if let Some(vars) = &item.optional_vars {
+                    visitor.visit_expr(vars);
+                }
+            }
+            visitor.visit_body(body);
+        }
+        ast::Stmt::Match(ast::StmtMatch { subject, cases, .. }) => {
+            visitor.visit_expr(subject);
+            for case in cases {
+                visitor.visit_pattern(&case.pattern);
+                if let Some(guard) = &case.guard {
+                    visitor.visit_expr(guard);
+                }
+                visitor.visit_body(&case.body);
+            }
+        }
+        ast::Stmt::Raise(ast::StmtRaise { exc, cause, .. }) => {
+            if let Some(exc) = exc {
+                visitor.visit_expr(exc);
+            }
+            if let Some(cause) = cause {
+                visitor.visit_expr(cause);
+            }
+        }
+        ast::Stmt::Try(ast::StmtTry {
+            body,
+            handlers,
+            orelse,
+            finalbody,
+            ..
+        })
+        | ast::Stmt::TryStar(ast::StmtTryStar {
+            body,
+            handlers,
+            orelse,
+            finalbody,
+            ..
+        }) => {
+            visitor.visit_body(body);
+            for handler in handlers {
+                let ast::ExceptHandler::ExceptHandler(handler) = handler;
+                if let Some(type_) = &handler.type_ {
+                    visitor.visit_expr(type_);
+                }
+                visitor.visit_body(&handler.body);
+            }
+            visitor.visit_body(orelse);
+            visitor.visit_body(finalbody);
+        }
+        ast::Stmt::Assert(ast::StmtAssert { test, msg, .. }) => {
+            visitor.visit_expr(test);
+            if let Some(msg) = msg {
+                visitor.visit_expr(msg);
+            }
+        }
+        ast::Stmt::Expr(ast::StmtExpr { value, .. }) => {
+            visitor.visit_expr(value);
+        }
+        ast::Stmt::Import(_)
+        | ast::Stmt::ImportFrom(_)
+        | ast::Stmt::Global(_)
+        | ast::Stmt::Nonlocal(_)
+        | ast::Stmt::Pass(_)
+        | ast::Stmt::Break(_)
+        | ast::Stmt::Continue(_) => {}
+    }
+}
+
+/// Walk the children of an expression
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, expr: &'a ast::Expr) {
+    match expr {
+        ast::Expr::BoolOp(ast::ExprBoolOp { values, .. }) => {
+            for value in values {
+                visitor.visit_expr(value);
+            }
+        }
+        ast::Expr::NamedExpr(ast::ExprNamedExpr { target, value, .. }) => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(value);
+        }
+        ast::Expr::BinOp(ast::ExprBinOp { left, right, .. }) => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        ast::Expr::UnaryOp(ast::ExprUnaryOp { operand, .. }) => {
+            visitor.visit_expr(operand);
+        }
+        ast::Expr::Lambda(ast::ExprLambda { args, body, .. }) => {
+            visitor.visit_arguments(args);
+            visitor.visit_expr(body);
+        }
+        ast::Expr::IfExp(ast::ExprIfExp {
+            test, body, orelse, ..
+        }) => {
+            visitor.visit_expr(test);
+            visitor.visit_expr(body);
+            visitor.visit_expr(orelse);
+        }
+        ast::Expr::Dict(ast::ExprDict { keys, values, .. }) => {
+            for (key, value) in keys.iter().zip(values) {
+                if let Some(key) = key {
+                    visitor.visit_expr(key);
+                }
+                visitor.visit_expr(value);
+            }
+        }
+        ast::Expr::Set(ast::ExprSet { elts, .. })
+        | ast::Expr::List(ast::ExprList { elts, .. })
+        | ast::Expr::Tuple(ast::ExprTuple { elts, .. }) => {
+            for elt in elts {
+                visitor.visit_expr(elt);
+            }
+        }
+        ast::Expr::ListComp(ast::ExprListComp {
+            elt, generators, ..
+        })
+        | ast::Expr::SetComp(ast::ExprSetComp {
+            elt, generators, ..
+        })
+        | ast::Expr::GeneratorExp(ast::ExprGeneratorExp {
+            elt, generators, ..
+        }) => {
+            walk_comprehensions(visitor, generators);
+            visitor.visit_expr(elt);
+        }
+        ast::Expr::DictComp(ast::ExprDictComp {
+            key,
+            value,
+            generators,
+            ..
+        }) => {
+            walk_comprehensions(visitor, generators);
+            visitor.visit_expr(key);
+            visitor.visit_expr(value);
+        }
+        ast::Expr::Await(ast::ExprAwait { value, .. })
+        | ast::Expr::YieldFrom(ast::ExprYieldFrom { value, .. })
+        | ast::Expr::Attribute(ast::ExprAttribute { value, .. })
+        | ast::Expr::Starred(ast::ExprStarred { value, .. }) => {
+            visitor.visit_expr(value);
+        }
+        ast::Expr::Yield(ast::ExprYield { value, .. }) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        ast::Expr::Compare(ast::ExprCompare {
+            left, comparators, ..
+        }) => {
+            visitor.visit_expr(left);
+            for comparator in comparators {
+                visitor.visit_expr(comparator);
+            }
+        }
+        ast::Expr::Call(ast::ExprCall {
+            func,
+            args,
+            keywords,
+            ..
+        }) => {
+            visitor.visit_expr(func);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+            for keyword in keywords {
+                visitor.visit_expr(&keyword.value);
+            }
+        }
+        ast::Expr::FormattedValue(ast::ExprFormattedValue {
+            value, format_spec, ..
+        }) => {
+            visitor.visit_expr(value);
+            if let Some(format_spec) = format_spec {
+                visitor.visit_expr(format_spec);
+            }
+        }
+        ast::Expr::JoinedStr(ast::ExprJoinedStr { values, .. }) => {
+            for value in values {
+                visitor.visit_expr(value);
+            }
+        }
+        ast::Expr::Subscript(ast::ExprSubscript { value, slice, .. }) => {
+            visitor.visit_expr(value);
+            visitor.visit_expr(slice);
+        }
+        ast::Expr::Slice(ast::ExprSlice {
+            lower, upper, step, ..
+        }) => {
+            for part in [lower, upper, step].into_iter().flatten() {
+                visitor.visit_expr(part);
+            }
+        }
+        ast::Expr::Constant(_) | ast::Expr::Name(_) => {}
+    }
+}
+
+/// Walk the children of a `match` pattern
+pub fn walk_pattern<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, pattern: &'a ast::Pattern) {
+    match pattern {
+        ast::Pattern::MatchValue(ast::PatternMatchValue { value, .. }) => {
+            visitor.visit_expr(value);
+        }
+        ast::Pattern::MatchSequence(ast::PatternMatchSequence { patterns, .. })
+        | ast::Pattern::MatchOr(ast::PatternMatchOr { patterns, .. }) => {
+            for pattern in patterns {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        ast::Pattern::MatchMapping(ast::PatternMatchMapping { keys, patterns, .. }) => {
+            for key in keys {
+                visitor.visit_expr(key);
+            }
+            for pattern in patterns {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        ast::Pattern::MatchClass(ast::PatternMatchClass {
+            cls,
+            patterns,
+            kwd_patterns,
+            ..
+        }) => {
+            visitor.visit_expr(cls);
+            for pattern in patterns.iter().chain(kwd_patterns) {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        ast::Pattern::MatchAs(ast::PatternMatchAs { pattern, .. }) => {
+            if let Some(pattern) = pattern {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        ast::Pattern::MatchSingleton(_) | ast::Pattern::MatchStar(_) => {}
+    }
+}
+
+/// Walk the defaults and annotations of a function signature
+pub fn walk_arguments<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, arguments: &'a ast::Arguments) {
+    let with_defaults = arguments
+        .posonlyargs
+        .iter()
+        .chain(&arguments.args)
+        .chain(&arguments.kwonlyargs);
+
+    for arg in with_defaults {
+        if let Some(default) = &arg.default {
+            visitor.visit_expr(default);
+        }
+        if let Some(annotation) = &arg.def.annotation {
+            visitor.visit_annotation(annotation);
+        }
+    }
+
+    for arg in [&arguments.vararg, &arguments.kwarg].into_iter().flatten() {
+        if let Some(annotation) = &arg.annotation {
+            visitor.visit_annotation(annotation);
+        }
+    }
+}
+
+fn walk_comprehensions<'a, V: Visitor<'a> + ?Sized>(
+    visitor: &mut V,
+    generators: &'a [ast::Comprehension],
+) {
+    for generator in generators {
+        visitor.visit_expr(&generator.target);
+        visitor.visit_expr(&generator.iter);
+        for condition in &generator.ifs {
+            visitor.visit_expr(condition);
+        }
+    }
+}
+
+fn walk_type_params<'a, V: Visitor<'a> + ?Sized>(
+    visitor: &mut V,
+    type_params: &'a [ast::TypeParam],
+) {
+    for type_param in type_params {
+        if let ast::TypeParam::TypeVar(type_var) = type_param {
+            if let Some(bound) = &type_var.bound {
+                visitor.visit_annotation(bound);
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use log::debug;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::cache::{fingerprint_file, BuildCache, Toolchain};
+use crate::parser::dotted_module_name;
+use crate::uv_compiler::{
+    batch_output_path, build_env_packages, collect_python_files, find_build_env, CompileConfig,
+};
+
+/// What batch compilation would do with one input
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedModule {
+    /// Python source
+    pub source: PathBuf,
+    /// Dotted name the module is imported as
+    pub module: String,
+    /// Extension module that would be written
+    pub output: PathBuf,
+    /// Whether the build cache would skip the module
+    pub cached: bool,
+}
+
+/// What batch compilation would do, worked out without building anything
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildPlan {
+    /// Installed interpreter the build environment would be created from;
+    /// `None` when uv would provide one
+    pub interpreter: Option<PathBuf>,
+    /// Its full version, or the requested one when uv would provide it
+    pub python_version: Option<String>,
+    /// Cython in the cached build environment, when there is one
+    pub cython: Option<String>,
+    /// Packages the build environment would get
+    pub packages: Vec<String>,
+    /// Directory outputs would be written to
+    pub output_dir: PathBuf,
+    /// Modules that would be compiled or found up to date
+    pub modules: Vec<PlannedModule>,
+    /// Inputs skipped by `include`/`exclude`
+    pub excluded: Vec<PathBuf>,
+}
+
+impl BuildPlan {
+    /// Modules that would actually be compiled
+    pub fn to_build(&self) -> usize {
+        self.modules.iter().filter(|m| !m.cached).count()
+    }
+}
+
+/// Work out what `batch_compile` would do with the same arguments.
+///
+/// Only reads the sources, the build cache and a cached build environment:
+/// no environment is created and no compiler runs. Without a cached
+/// environment the Cython version is unknown, so no module is reported as up
+/// to date.
+pub fn plan_batch(
+    input_pattern: &str,
+    output_dir: &Path,
+    config: &CompileConfig,
+    recursive: bool,
+) -> Result<BuildPlan> {
+    let config = &*config.with_dcc_profile()?;
+    let build_env = if config.cache {
+        find_build_env(config)?
+    } else {
+        None
+    };
+    let toolchain = Toolchain::resolve(config, build_env.as_ref());
+    plan_batch_with_toolchain(input_pattern, output_dir, config, recursive, &toolchain)
+}
+
+/// [`plan_batch`] for builds with `toolchain`
+pub fn plan_batch_with_toolchain(
+    input_pattern: &str,
+    output_dir: &Path,
+    config: &CompileConfig,
+    recursive: bool,
+    toolchain: &Toolchain,
+) -> Result<BuildPlan> {
+    let config = &*config.with_dcc_profile()?;
+    let python_files = collect_python_files(input_pattern, recursive)
+        .with_context(|| format!("Failed to collect Python files from pattern: {input_pattern}"))?;
+    let cache = (config.cache && toolchain.is_known()).then(|| BuildCache::load(output_dir));
+
+    let mut modules = Vec::new();
+    let mut excluded = Vec::new();
+    for source in python_files {
+        if !config.is_selected(&source) {
+            excluded.push(source);
+            continue;
+        }
+
+        let output = batch_output_path(
+            Path::new(input_pattern),
+            &source,
+            output_dir,
+            config.output_layout,
+        );
+        let cached = match &cache {
+            Some(cache) => fingerprint_file(&source, config, toolchain)
+                .is_ok_and(|fingerprint| cache.is_fresh(&output, &fingerprint)),
+            None => false,
+        };
+        modules.push(PlannedModule {
+            module: dotted_module_name(&source),
+            source,
+            output,
+            cached,
+        });
+    }
+    debug!(
+        "Planned {} modules, {} excluded",
+        modules.len(),
+        excluded.len()
+    );
+
+    Ok(BuildPlan {
+        interpreter: toolchain.python.clone(),
+        python_version: toolchain
+            .python_version
+            .map(|version| version.to_string())
+            .or_else(|| config.python_version.clone()),
+        cython: toolchain.cython.clone(),
+        packages: build_env_packages(config),
+        output_dir: output_dir.to_path_buf(),
+        modules,
+        excluded,
+    })
+}
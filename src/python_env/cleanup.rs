@@ -1,29 +1,30 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use log::{debug, info};
-use std::fs;
 use std::path::PathBuf;
 
-/// Clean up the virtual environment
-pub fn cleanup_venv() -> Result<()> {
-    let venv_dir = super::get_venv_dir()?;
+use super::PythonEnvironment;
+use crate::env_cache::EnvCache;
 
-    if venv_dir.exists() {
-        info!("Cleaning up virtual environment at {}", venv_dir.display());
-        fs::remove_dir_all(&venv_dir).with_context(|| {
-            format!(
-                "Failed to remove virtual environment at {}",
-                venv_dir.display()
-            )
-        })?;
-        debug!("Virtual environment cleaned up successfully");
-    } else {
-        debug!("No virtual environment to clean up");
+impl PythonEnvironment {
+    /// The virtual environment, when one was created rather than an
+    /// installed interpreter used
+    pub fn venv_path(&self) -> Option<PathBuf> {
+        self.venv.as_ref().map(|venv| venv.env_dir())
     }
 
-    Ok(())
-}
+    /// Remove the virtual environment, if one was created.
+    ///
+    /// It is left alone while another py2pyd process is using it.
+    pub fn cleanup(self) -> Result<()> {
+        let Some(venv) = self.venv else {
+            debug!("No virtual environment to clean up");
+            return Ok(());
+        };
 
-/// Get the path to the virtual environment
-pub fn get_venv_path() -> Result<PathBuf> {
-    super::get_venv_dir()
+        let path = venv.path.clone();
+        if EnvCache::open()?.remove_if_unused(venv)? {
+            info!("Cleaned up virtual environment at {}", path.display());
+        }
+        Ok(())
+    }
 }
@@ -1,57 +1,27 @@
-use anyhow::{anyhow, Context, Result};
-use log::{debug, info};
+use anyhow::Result;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
-/// Create a virtual environment with a specific Python version using uv
-pub fn create_venv_with_uv_and_version(uv_path: &Path, python_version: &str) -> Result<PathBuf> {
-    let venv_dir = super::get_venv_dir()?;
-
-    // Check if the virtual environment already exists
-    if venv_dir.exists() {
-        debug!(
-            "Virtual environment already exists at {}",
-            venv_dir.display()
-        );
-        return Ok(venv_dir);
-    }
-
-    // Create the parent directory if it doesn't exist
-    if let Some(parent) = venv_dir.parent() {
-        std::fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
-    }
-
-    // Create the virtual environment with specific Python version
-    info!(
-        "Creating virtual environment with Python {} at {}",
-        python_version,
-        venv_dir.display()
-    );
+use crate::env_cache::LockedEnv;
 
-    // In uv 0.7.6, the command is "uv venv create --python X.Y [path]"
-    let status = Command::new(uv_path)
-        .arg("venv")
-        .arg("create")
-        .arg("--python")
-        .arg(python_version)
-        .arg(venv_dir.to_str().unwrap())
-        .status()
-        .with_context(|| {
-            format!("Failed to execute uv venv create with Python {python_version}")
-        })?;
-
-    if !status.success() {
-        return Err(anyhow!(
-            "Failed to create virtual environment with Python {}",
-            python_version
-        ));
-    }
+/// Create (or reuse) a virtual environment with a specific Python version
+/// using uv, returning its directory
+///
+/// No lock is held on the venv, so another process running `env prune` or
+/// `env remove` can delete it while it is in use; prefer
+/// [`create_venv_with_uv_and_version_locked`].
+pub fn create_venv_with_uv_and_version(uv_path: &Path, python_version: &str) -> Result<PathBuf> {
+    Ok(create_venv_with_uv_and_version_locked(uv_path, python_version)?.env_dir())
+}
 
-    info!(
-        "Virtual environment created successfully with Python {} at {}",
-        python_version,
-        venv_dir.display()
-    );
-    Ok(venv_dir)
+/// Create (or reuse) a virtual environment with a specific Python version
+/// using uv
+///
+/// The venv lives in [`CachedEnv::env_dir`](crate::env_cache::CachedEnv::env_dir)
+/// and cannot be pruned or removed by another process until the returned value
+/// is dropped.
+pub fn create_venv_with_uv_and_version_locked(
+    uv_path: &Path,
+    python_version: &str,
+) -> Result<LockedEnv> {
+    super::create_cached_venv(uv_path, Some(python_version))
 }
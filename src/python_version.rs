@@ -0,0 +1,88 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A Python version such as `3.10` or `3.11.4`
+///
+/// Equality, ordering and hashing are exact, so `3.11`, `3.11.0` and
+/// `3.11.4` all differ. Use [`PythonVersion::same_minor`] to check a reported
+/// version against a requested `major.minor` one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PythonVersion {
+    /// Major version (always 3 in practice)
+    pub major: u32,
+    /// Minor version
+    pub minor: u32,
+    /// Patch version, if known
+    pub patch: Option<u32>,
+}
+
+impl PythonVersion {
+    /// Create a `major.minor` version
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch: None,
+        }
+    }
+
+    /// This version without the patch number
+    pub const fn major_minor(&self) -> Self {
+        Self::new(self.major, self.minor)
+    }
+
+    /// Whether both versions are the same `major.minor` release, ignoring
+    /// patch numbers
+    pub const fn same_minor(&self, other: &Self) -> bool {
+        self.major == other.major && self.minor == other.minor
+    }
+
+    /// Whether this version is at least `major.minor`
+    pub const fn at_least(&self, major: u32, minor: u32) -> bool {
+        self.major > major || (self.major == major && self.minor >= minor)
+    }
+}
+
+impl FromStr for PythonVersion {
+    type Err = anyhow::Error;
+
+    /// Parse `3.10`, `3.10.4` or `Python 3.10.4`; a bare major version such as
+    /// `3` names no release to check against, so it is rejected
+    fn from_str(s: &str) -> Result<Self> {
+        let version = s.trim().trim_start_matches("Python").trim();
+        let mut parts = version.split('.');
+
+        let parse = |part: Option<&str>| -> Result<Option<u32>> {
+            part.map(|p| {
+                // Ignore suffixes such as "rc1" or "+"
+                let digits: String = p.chars().take_while(char::is_ascii_digit).collect();
+                digits
+                    .parse::<u32>()
+                    .map_err(|_| anyhow!("Invalid Python version: {}", s))
+            })
+            .transpose()
+        };
+
+        let major = parse(parts.next())?.ok_or_else(|| anyhow!("Invalid Python version: {}", s))?;
+        let minor = parse(parts.next())?
+            .ok_or_else(|| anyhow!("Python version {} needs a minor version, e.g. 3.11", s))?;
+        let patch = parse(parts.next())?;
+
+        Ok(Self {
+            major,
+            minor,
+            patch,
+        })
+    }
+}
+
+impl fmt::Display for PythonVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.patch {
+            Some(patch) => write!(f, "{}.{}.{}", self.major, self.minor, patch),
+            None => write!(f, "{}.{}", self.major, self.minor),
+        }
+    }
+}
@@ -4,6 +4,12 @@ use rustpython_parser::ast;
 use std::fmt::Write;
 use std::path::{Path, PathBuf};
 
+mod string_encryption;
+pub use string_encryption::{
+    decrypt_string, encrypt_source, encrypt_string, generate_key, StringEncryptionOptions,
+    DECODER_NAME,
+};
+
 /// Represents a transformed Python module
 pub struct TransformedModule {
     pub module_name: String,
@@ -0,0 +1,348 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use rustpython_parser::ast::{self, Ranged};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::parser::{walk_expr, walk_stmt, walk_suite, Visitor};
+
+/// Name of the runtime decoder injected into encrypted modules
+pub const DECODER_NAME: &str = "_py2pyd_str";
+
+/// Options for replacing string literals with runtime-decoded constants
+pub struct StringEncryptionOptions {
+    /// Key used to encrypt the literals of this build
+    pub key: Vec<u8>,
+
+    /// Leave docstrings as plain text so `__doc__` and `help()` keep working
+    pub keep_docstrings: bool,
+}
+
+impl StringEncryptionOptions {
+    /// Create options with a freshly generated per-build key
+    pub fn new(keep_docstrings: bool) -> Self {
+        Self {
+            key: generate_key(),
+            keep_docstrings,
+        }
+    }
+}
+
+/// Generate a random 32-byte key
+pub fn generate_key() -> Vec<u8> {
+    let mut key = Vec::with_capacity(32);
+    key.extend_from_slice(Uuid::new_v4().as_bytes());
+    key.extend_from_slice(Uuid::new_v4().as_bytes());
+    key
+}
+
+/// Encrypt a string with the given key.
+///
+/// Each byte is XORed with `key[i % len] + i / len`, which is exactly what
+/// the injected Python decoder undoes at runtime.
+pub fn encrypt_string(value: &str, key: &[u8]) -> Vec<u8> {
+    apply_keystream(value.as_bytes(), key)
+}
+
+/// Decrypt bytes produced by [`encrypt_string`]
+pub fn decrypt_string(data: &[u8], key: &[u8]) -> Result<String> {
+    String::from_utf8(apply_keystream(data, key))
+        .map_err(|e| anyhow!("Decrypted string is not valid UTF-8: {}", e))
+}
+
+fn apply_keystream(data: &[u8], key: &[u8]) -> Vec<u8> {
+    if key.is_empty() {
+        return data.to_vec();
+    }
+
+    data.iter()
+        .enumerate()
+        .map(|(i, b)| b ^ key[i % key.len()].wrapping_add((i / key.len()) as u8))
+        .collect()
+}
+
+/// Replace the string literals of a Python module with calls to a runtime decoder.
+///
+/// The literals are encrypted into a table at the top of the module and every
+/// occurrence becomes `_py2pyd_str(<index>)`. f-strings, including literals
+/// inside their replacement fields, annotations, `match` patterns and
+/// (optionally) docstrings are left untouched. Line numbers are preserved
+/// whenever the line above the decoder is blank or a comment other than an
+/// encoding declaration.
+pub fn encrypt_source(source: &str, options: &StringEncryptionOptions) -> Result<String> {
+    info!("Encrypting string literals");
+
+    let suite = crate::parser::parse_source(source)
+        .with_context(|| "Failed to parse source for string encryption")?;
+
+    let (prelude_stmt, encrypt_module_docstring) = prelude_position(&suite, options);
+
+    let mut collector = LiteralCollector {
+        keep_docstrings: options.keep_docstrings,
+        skipped: HashSet::new(),
+        literals: Vec::new(),
+    };
+    if !encrypt_module_docstring {
+        if let Some(docstring) = docstring_of(&suite) {
+            collector.skipped.insert(docstring.range.start().to_usize());
+        }
+    }
+    walk_suite(&mut collector, &suite);
+
+    collector.literals.sort_by_key(|(range, _)| range.start());
+
+    if collector.literals.is_empty() {
+        debug!("No string literals to encrypt");
+        return Ok(source.to_string());
+    }
+
+    // Assign table slots, sharing one slot between identical literals
+    let mut slots: HashMap<&str, usize> = HashMap::new();
+    let mut table: Vec<&str> = Vec::new();
+    let mut replacements = Vec::with_capacity(collector.literals.len());
+    for (range, value) in &collector.literals {
+        let slot = *slots.entry(value).or_insert_with(|| {
+            table.push(value);
+            table.len() - 1
+        });
+
+        // Keep multi-line literals on the same number of lines
+        let original = &source[range.start().to_usize()..range.end().to_usize()];
+        let newlines = "\n".repeat(original.matches('\n').count());
+        replacements.push((
+            range.start().to_usize(),
+            range.end().to_usize(),
+            format!("{DECODER_NAME}({slot}{newlines})"),
+        ));
+    }
+
+    let prelude = generate_decoder(&table, &options.key);
+
+    // Work out where the decoder goes
+    let (insert_at, replace_until) = match prelude_stmt {
+        Some(stmt) => {
+            let line_start = line_start_of(source, stmt.start().to_usize());
+            let previous_end = previous_stmt_end(&suite, stmt);
+            match previous_line(source, line_start) {
+                Some(prev)
+                    if prev >= previous_end && is_disposable_line(source, prev, line_start) =>
+                {
+                    (prev, line_start)
+                }
+                _ => (line_start, line_start),
+            }
+        }
+        None => (source.len(), source.len()),
+    };
+
+    let mut result = String::with_capacity(source.len() + prelude.len());
+    let mut cursor = 0;
+    let mut prelude_written = false;
+    for (start, end, replacement) in replacements {
+        if !prelude_written && start >= insert_at {
+            write_prelude(
+                &mut result,
+                source,
+                &mut cursor,
+                insert_at,
+                replace_until,
+                &prelude,
+            );
+            prelude_written = true;
+        }
+        result.push_str(&source[cursor..start]);
+        result.push_str(&replacement);
+        cursor = end;
+    }
+    if !prelude_written {
+        write_prelude(
+            &mut result,
+            source,
+            &mut cursor,
+            insert_at,
+            replace_until,
+            &prelude,
+        );
+    }
+    result.push_str(&source[cursor..]);
+
+    debug!(
+        "Encrypted {} string literals into {} table entries",
+        collector.literals.len(),
+        table.len()
+    );
+    Ok(result)
+}
+
+fn write_prelude(
+    result: &mut String,
+    source: &str,
+    cursor: &mut usize,
+    insert_at: usize,
+    replace_until: usize,
+    prelude: &str,
+) {
+    result.push_str(&source[*cursor..insert_at]);
+    if !result.is_empty() && !result.ends_with('\n') {
+        result.push('\n');
+    }
+    result.push_str(prelude);
+    result.push('\n');
+    *cursor = replace_until;
+}
+
+/// Generate the single-line decoder and its encrypted table
+fn generate_decoder(table: &[&str], key: &[u8]) -> String {
+    let mut entries = String::new();
+    for value in table {
+        write!(entries, "{}, ", bytes_literal(&encrypt_string(value, key))).unwrap();
+    }
+
+    format!(
+        "_py2pyd_key, _py2pyd_table, _py2pyd_cache = {key}, ({entries}), {{}}; \
+         {DECODER_NAME} = lambda i: _py2pyd_cache[i] if i in _py2pyd_cache else \
+         _py2pyd_cache.setdefault(i, bytes(b ^ ((_py2pyd_key[j % len(_py2pyd_key)] + j // len(_py2pyd_key)) & 255) \
+         for j, b in enumerate(_py2pyd_table[i])).decode('utf-8'))",
+        key = bytes_literal(key),
+        entries = entries.trim_end(),
+    )
+}
+
+fn bytes_literal(data: &[u8]) -> String {
+    let mut literal = String::with_capacity(data.len() * 4 + 3);
+    literal.push_str("b'");
+    for byte in data {
+        write!(literal, "\\x{byte:02x}").unwrap();
+    }
+    literal.push('\'');
+    literal
+}
+
+/// Find the statement the decoder must be inserted before.
+///
+/// The decoder has to follow `from __future__` imports, and a kept module
+/// docstring must stay the first statement. Returns the statement (if any)
+/// and whether the module docstring comes after the decoder.
+fn prelude_position<'a>(
+    suite: &'a ast::Suite,
+    options: &StringEncryptionOptions,
+) -> (Option<&'a ast::Stmt>, bool) {
+    let has_docstring = docstring_of(suite).is_some();
+    let mut index = 0;
+    if has_docstring {
+        index = 1;
+    }
+    let mut has_future = false;
+    while let Some(ast::Stmt::ImportFrom(import)) = suite.get(index) {
+        if import.module.as_deref() != Some("__future__") {
+            break;
+        }
+        has_future = true;
+        index += 1;
+    }
+
+    if has_docstring && !has_future && !options.keep_docstrings {
+        return (suite.first(), true);
+    }
+
+    (suite.get(index), false)
+}
+
+fn docstring_of(body: &[ast::Stmt]) -> Option<&ast::ExprConstant> {
+    match body.first() {
+        Some(ast::Stmt::Expr(ast::StmtExpr { value, .. })) => match value.as_ref() {
+            ast::Expr::Constant(
+                constant @ ast::ExprConstant {
+                    value: ast::Constant::Str(_),
+                    ..
+                },
+            ) => Some(constant),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn previous_stmt_end(suite: &ast::Suite, stmt: &ast::Stmt) -> usize {
+    suite
+        .iter()
+        .take_while(|s| !std::ptr::eq(*s, stmt))
+        .last()
+        .map_or(0, |s| s.end().to_usize())
+}
+
+fn line_start_of(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+fn previous_line(source: &str, line_start: usize) -> Option<usize> {
+    if line_start == 0 {
+        return None;
+    }
+    Some(line_start_of(source, line_start - 1))
+}
+
+/// Whether the line at `start..end` can be replaced by the decoder without
+/// changing behaviour.
+///
+/// A PEP 263 `coding[:=]` comment in the first two lines decides how the file
+/// is decoded, so it is never replaced.
+fn is_disposable_line(source: &str, start: usize, end: usize) -> bool {
+    let trimmed = source[start..end].trim();
+    if trimmed.is_empty() {
+        return true;
+    }
+    if !trimmed.starts_with('#') || trimmed.starts_with("#!") {
+        return false;
+    }
+    let line_index = source[..start].matches('\n').count();
+    !(line_index < 2 && (trimmed.contains("coding:") || trimmed.contains("coding=")))
+}
+
+/// Collects the string literals that should be encrypted
+struct LiteralCollector<'a> {
+    keep_docstrings: bool,
+    skipped: HashSet<usize>,
+    literals: Vec<(ast::text_size::TextRange, &'a str)>,
+}
+
+impl<'a> Visitor<'a> for LiteralCollector<'a> {
+    fn visit_stmt(&mut self, stmt: &'a ast::Stmt) {
+        if self.keep_docstrings {
+            let body = match stmt {
+                ast::Stmt::FunctionDef(def) => Some(&def.body),
+                ast::Stmt::AsyncFunctionDef(def) => Some(&def.body),
+                ast::Stmt::ClassDef(def) => Some(&def.body),
+                _ => None,
+            };
+            if let Some(docstring) = body.and_then(|body| docstring_of(body)) {
+                self.skipped.insert(docstring.range.start().to_usize());
+            }
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'a ast::Expr) {
+        match expr {
+            ast::Expr::Constant(ast::ExprConstant {
+                value: ast::Constant::Str(value),
+                range,
+                ..
+            }) => {
+                if !self.skipped.contains(&range.start().to_usize()) {
+                    self.literals.push((*range, value));
+                }
+            }
+            // f-string parts must stay literal
+            ast::Expr::JoinedStr(_) => {}
+            _ => walk_expr(self, expr),
+        }
+    }
+
+    // Annotations may be evaluated lazily or as source text
+    fn visit_annotation(&mut self, _expr: &'a ast::Expr) {}
+
+    // Patterns only accept literal values
+    fn visit_pattern(&mut self, _pattern: &'a ast::Pattern) {}
+}
@@ -1,14 +1,34 @@
 use anyhow::{anyhow, Context, Result};
 use log::{debug, info, warn};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt::Write;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::time::Instant;
 use tempfile::TempDir;
 
-use crate::uv_env::{UvEnv, UvEnvConfig};
+use crate::build_tools::Compiler;
+use crate::cache::{fingerprint_file, BuildCache, Toolchain};
+use crate::config::{matches_any, python_string, Directive, ModuleOverride, OutputLayout};
+use crate::cross::{find_cross_compiler, CrossTarget, TargetPython};
+use crate::dcc::{self, DccTarget};
+use crate::inspect::BuildInfo;
+use crate::interpreter::find_conda_env;
+use crate::manylinux;
+use crate::messages::Message;
+use crate::parser::check_target_version;
+use crate::python_version::PythonVersion;
+use crate::transformer::{encrypt_source, StringEncryptionOptions};
+use crate::uv_env::{EnvBackend, UvEnv, UvEnvConfig};
+
+/// C file defining the build metadata, compiled into every module
+const BUILD_INFO_SOURCE: &str = "py2pyd_build_info.c";
 
 /// Configuration for compiling a Python module to a pyd file
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct CompileConfig {
     /// Path to the Python interpreter to use
     pub python_path: Option<PathBuf>,
@@ -22,11 +42,101 @@ pub struct CompileConfig {
     /// Whether to keep temporary files
     pub keep_temp_files: bool,
 
-    /// Target environment (for future use)
+    /// DCC release to build for, e.g. `maya-2024` (see `dcc::DccRegistry`);
+    /// its profile is applied when compiling, or by [`dcc::configure`]
     pub target_dcc: Option<String>,
 
+    /// The `target_dcc` release, once its profile has been applied
+    #[serde(skip)]
+    pub dcc_target: Option<DccTarget>,
+
     /// Additional packages to install
     pub packages: Vec<String>,
+
+    /// Replace string literals with calls to a runtime decoder
+    pub encrypt_strings: bool,
+
+    /// Leave docstrings as plain text when encrypting strings
+    pub keep_docstrings: bool,
+
+    /// Cython compiler directives
+    pub cython_directives: BTreeMap<String, Directive>,
+
+    /// Globs of files batch compilation is limited to; everything when empty
+    pub include: Vec<String>,
+
+    /// Globs of files (or directories) batch compilation skips
+    pub exclude: Vec<String>,
+
+    /// Default output directory for batch compilation
+    pub output_dir: Option<PathBuf>,
+
+    /// Layout of batch outputs
+    pub output_layout: OutputLayout,
+
+    /// Per-module-glob settings, applied in order
+    pub overrides: Vec<ModuleOverride>,
+
+    /// Build profile the settings came from
+    pub profile: Option<String>,
+
+    /// Link-time optimization
+    pub lto: bool,
+
+    /// Strip symbols from the extension module
+    pub strip: bool,
+
+    /// Emit debug symbols
+    pub debug_symbols: bool,
+
+    /// Enable Cython line tracing (`linetrace` and `CYTHON_TRACE`)
+    pub line_tracing: bool,
+
+    /// Skip batch modules whose output was built from the same source,
+    /// settings and toolchain; off by default
+    pub cache: bool,
+
+    /// Linux target triple to cross-compile for, e.g. `aarch64-linux-gnu`
+    pub target: Option<String>,
+
+    /// Directory with the target's Python headers and sysconfig data
+    pub target_python: Option<PathBuf>,
+
+    /// manylinux policy Linux modules must meet, e.g. `manylinux2014`
+    pub manylinux: Option<String>,
+
+    /// Copy non-system libraries next to Linux modules, found via an `$ORIGIN` RPATH
+    pub repair: bool,
+
+    /// Build with `python_path` itself, e.g. `mayapy`, instead of a venv made from it
+    pub no_venv: bool,
+
+    /// Conda environment to build in, by name or prefix
+    pub conda_env: Option<String>,
+
+    /// Install build requirements into the conda environment itself rather
+    /// than a separate directory on `PYTHONPATH`
+    pub conda_install: bool,
+
+    /// Tool build environments are created with
+    pub env_backend: EnvBackend,
+
+    /// Download uv when the environment backend needs it and it is missing
+    pub install_uv: bool,
+
+    /// Extra C compiler arguments, e.g. from a DCC profile
+    pub compile_args: Vec<String>,
+
+    /// Extra linker arguments, e.g. from a DCC profile
+    pub link_args: Vec<String>,
+
+    /// Directory globs are relative to (usually where the config file lives)
+    #[serde(skip)]
+    pub project_root: Option<PathBuf>,
+
+    /// C compiler passed to setuptools; its own default when `None`
+    #[serde(skip)]
+    pub compiler: Option<Compiler>,
 }
 
 impl Default for CompileConfig {
@@ -37,9 +147,122 @@ impl Default for CompileConfig {
             optimize_level: 2,
             keep_temp_files: false,
             target_dcc: None,
+            dcc_target: None,
             packages: vec![],
+            encrypt_strings: false,
+            keep_docstrings: false,
+            cython_directives: BTreeMap::new(),
+            include: vec![],
+            exclude: vec![],
+            output_dir: None,
+            output_layout: OutputLayout::Mirror,
+            overrides: vec![],
+            profile: None,
+            lto: false,
+            strip: false,
+            debug_symbols: false,
+            line_tracing: false,
+            cache: false,
+            target: None,
+            target_python: None,
+            manylinux: None,
+            repair: false,
+            no_venv: false,
+            conda_env: None,
+            conda_install: false,
+            env_backend: EnvBackend::Auto,
+            install_uv: false,
+            compile_args: vec![],
+            link_args: vec![],
+            project_root: None,
+            compiler: None,
+        }
+    }
+}
+
+impl CompileConfig {
+    /// The configuration with the `target_dcc` profile applied, unless it has
+    /// been already
+    pub fn with_dcc_profile(&self) -> Result<Cow<'_, CompileConfig>> {
+        if self.target_dcc.is_none() || self.dcc_target.is_some() {
+            return Ok(Cow::Borrowed(self));
+        }
+        let mut config = self.clone();
+        dcc::configure(&mut config)?;
+        Ok(Cow::Owned(config))
+    }
+
+    /// Whether modules are built for the stable ABI (`abi3`); Cython's line
+    /// tracing needs the full C API
+    pub fn abi3(&self) -> bool {
+        !self.line_tracing
+    }
+
+    /// `path` relative to the project root, for matching globs
+    fn glob_path(&self, path: &Path) -> PathBuf {
+        let Some(root) = &self.project_root else {
+            return path.strip_prefix(".").unwrap_or(path).to_path_buf();
+        };
+        match (path.canonicalize(), root.canonicalize()) {
+            (Ok(path), Ok(root)) => path
+                .strip_prefix(&root)
+                .map(Path::to_path_buf)
+                .unwrap_or(path),
+            _ => path.to_path_buf(),
         }
     }
+
+    /// Whether batch compilation should pick up `path` given `include`/`exclude`
+    pub fn is_selected(&self, path: &Path) -> bool {
+        let path = self.glob_path(path);
+        (self.include.is_empty() || matches_any(&self.include, &path))
+            && !matches_any(&self.exclude, &path)
+    }
+
+    /// The configuration for one module, with matching overrides applied
+    pub fn for_module(&self, path: &Path) -> CompileConfig {
+        let mut config = self.clone();
+        let glob_path = self.glob_path(path);
+        for module_override in &self.overrides {
+            if module_override.matches(&glob_path) {
+                debug!(
+                    "Applying override {:?} to {}",
+                    module_override.modules,
+                    path.display()
+                );
+                module_override.apply_to(&mut config);
+            }
+        }
+        config
+    }
+}
+
+/// Fail with every construct in `source` that is too new for `python_version`
+fn check_syntax_for_target(source: &str, path: &Path, python_version: &str) -> Result<()> {
+    let target = match python_version.parse::<PythonVersion>() {
+        Ok(target) => target,
+        Err(e) => {
+            warn!("Skipping target version check: {}", e);
+            return Ok(());
+        }
+    };
+
+    let diagnostics = check_target_version(source, path, &target)?;
+    if diagnostics.is_empty() {
+        return Ok(());
+    }
+
+    let report = diagnostics
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+    Err(anyhow!(
+        "{} uses syntax not supported by Python {}:\n{}",
+        path.display(),
+        target,
+        report
+    ))
 }
 
 /// Compile a Python file to a pyd file using uv
@@ -49,7 +272,167 @@ pub fn compile_file(input_path: &Path, output_path: &Path, config: &CompileConfi
         input_path.display(),
         output_path.display()
     );
+    let config = config.with_dcc_profile()?;
+    let config = &config.for_module(input_path);
+    let source_code = read_source(input_path, config)?;
+
+    let uv_env = create_build_env(config)?;
+    let toolchain = Toolchain::resolve(config, Some(&uv_env));
+    build_extension(
+        input_path,
+        source_code,
+        output_path,
+        config,
+        &uv_env,
+        &toolchain,
+    )
+}
 
+/// Compile a Python file using an existing build environment.
+///
+/// Use this with [`create_build_env`] to compile several modules, or the same
+/// module repeatedly, without setting up a new environment each time.
+pub fn compile_file_with_env(
+    input_path: &Path,
+    output_path: &Path,
+    config: &CompileConfig,
+    uv_env: &UvEnv,
+) -> Result<()> {
+    info!(
+        "Compiling {} to {} with {}",
+        input_path.display(),
+        output_path.display(),
+        uv_env.venv_path.display()
+    );
+    let config = config.with_dcc_profile()?;
+    let toolchain = Toolchain::resolve(&config, Some(uv_env));
+    compile_in_env(input_path, output_path, &config, uv_env, &toolchain)
+}
+
+/// Compile one module in `uv_env`, whose toolchain is already resolved
+fn compile_in_env(
+    input_path: &Path,
+    output_path: &Path,
+    config: &CompileConfig,
+    uv_env: &UvEnv,
+    toolchain: &Toolchain,
+) -> Result<()> {
+    let config = &config.for_module(input_path);
+    let source_code = read_source(input_path, config)?;
+
+    build_extension(
+        input_path,
+        source_code,
+        output_path,
+        config,
+        uv_env,
+        toolchain,
+    )
+}
+
+/// Packages installed into the build environment
+pub fn build_env_packages(config: &CompileConfig) -> Vec<String> {
+    let mut packages = vec![
+        "setuptools>=60.0.0".to_string(),
+        "wheel>=0.37.0".to_string(),
+        "cython>=3.0.0".to_string(),
+    ];
+
+    // Add user-specified packages
+    packages.extend(config.packages.clone());
+    packages
+}
+
+/// Create a uv virtual environment with Cython and the configured packages
+pub fn create_build_env(config: &CompileConfig) -> Result<UvEnv> {
+    let config = &*config.with_dcc_profile()?;
+    let packages = build_env_packages(config);
+
+    if config.no_venv {
+        let python = config
+            .python_path
+            .as_deref()
+            .ok_or_else(|| anyhow!("--no-venv needs --python-path (or a --dcc install)"))?;
+        let uv_env = UvEnv::for_interpreter(
+            python,
+            &packages,
+            config.keep_temp_files,
+            config.env_backend,
+        )
+        .with_context(|| format!("Failed to prepare {}", python.display()))?;
+        info!("Using Python interpreter: {}", python.display());
+        return Ok(uv_env);
+    }
+
+    let uv_config = env_config(config, packages)?;
+    let uv_env = UvEnv::create(&uv_config).with_context(|| match &uv_config.conda_env {
+        Some(prefix) => format!("Failed to prepare conda environment {}", prefix.display()),
+        None => "Failed to create the build environment".to_string(),
+    })?;
+
+    info!("Build environment at: {}", uv_env.venv_path.display());
+    info!("Using Python interpreter: {}", uv_env.python_path.display());
+    Ok(uv_env)
+}
+
+/// The build environment [`create_build_env`] would reuse for `config`, if it
+/// is cached; nothing is created or installed
+pub fn find_build_env(config: &CompileConfig) -> Result<Option<UvEnv>> {
+    let config = &*config.with_dcc_profile()?;
+    let packages = build_env_packages(config);
+    if config.no_venv {
+        let Some(python) = &config.python_path else {
+            return Ok(None);
+        };
+        return UvEnv::find_cached_for_interpreter(
+            python,
+            &packages,
+            config.keep_temp_files,
+            config.env_backend,
+        );
+    }
+    UvEnv::find_cached(&env_config(config, packages)?)
+}
+
+/// Settings for the build environment of `config`, with `packages` installed
+fn env_config(config: &CompileConfig, packages: Vec<String>) -> Result<UvEnvConfig> {
+    let conda_env = config
+        .conda_env
+        .as_deref()
+        .map(find_conda_env)
+        .transpose()?;
+    Ok(UvEnvConfig {
+        python_path: config.python_path.clone(),
+        python_version: config.python_version.clone(),
+        keep_venv: config.keep_temp_files,
+        packages,
+        conda_env,
+        install_in_env: config.conda_install,
+        backend: config.env_backend,
+        install_uv: config.install_uv,
+    })
+}
+
+/// Read a module and reject syntax the target interpreter cannot handle
+fn read_source(input_path: &Path, config: &CompileConfig) -> Result<String> {
+    let source_code = fs::read_to_string(input_path)
+        .with_context(|| format!("Failed to read input file: {}", input_path.display()))?;
+
+    if let Some(version) = &config.python_version {
+        check_syntax_for_target(&source_code, input_path, version)?;
+    }
+    Ok(source_code)
+}
+
+/// Cythonize and build one module in `uv_env`, then copy it to `output_path`
+fn build_extension(
+    input_path: &Path,
+    mut source_code: String,
+    output_path: &Path,
+    config: &CompileConfig,
+    uv_env: &UvEnv,
+    toolchain: &Toolchain,
+) -> Result<()> {
     // Create a temporary directory for the build
     let temp_dir = TempDir::new().with_context(|| "Failed to create temporary directory")?;
 
@@ -71,54 +454,83 @@ pub fn compile_file(input_path: &Path, output_path: &Path, config: &CompileConfi
         .and_then(|s| s.to_str())
         .ok_or_else(|| anyhow!("Invalid input file name"))?;
 
-    // Read the Python source code
-    let source_code = fs::read_to_string(input_path)
-        .with_context(|| format!("Failed to read input file: {}", input_path.display()))?;
+    // Hide string literals from the compiled binary, using a fresh key per build
+    if config.encrypt_strings {
+        let options = StringEncryptionOptions::new(config.keep_docstrings);
+        source_code = encrypt_source(&source_code, &options)
+            .with_context(|| format!("Failed to encrypt strings in {}", input_path.display()))?;
+    }
+
+    // Cross builds compile against the target's headers with a cross compiler
+    let target: Option<CrossTarget> = config.target.as_deref().map(str::parse).transpose()?;
+    let target_python = match (&target, &config.target_python) {
+        (Some(_), Some(dir)) => Some(TargetPython::from_dir(dir)?),
+        _ => None,
+    };
+    let cross_compiler;
+    let compiler = match (&target, &config.compiler) {
+        (Some(_), Some(compiler)) if compiler.cross => Some(compiler),
+        (Some(target), _) => {
+            cross_compiler = find_cross_compiler(target)?;
+            Some(&cross_compiler)
+        }
+        (None, compiler) => compiler.as_ref(),
+    };
 
     // Create the setup.py file
     let setup_py_path = temp_dir_path.join("setup.py");
-    let setup_py_content = generate_setup_py(module_name, &source_code, config);
+    let setup_py_content = generate_setup_py(
+        module_name,
+        &source_code,
+        config,
+        target_python
+            .as_ref()
+            .map(|python| python.include.as_path()),
+    );
     fs::write(&setup_py_path, setup_py_content)
         .with_context(|| format!("Failed to write setup.py to {}", setup_py_path.display()))?;
 
+    // Record how the module was built, for `py2pyd inspect`
+    let build_info = BuildInfo {
+        py2pyd: env!("CARGO_PKG_VERSION").to_string(),
+        module: module_name.to_string(),
+        python_version: config.python_version.clone(),
+        abi3: config.abi3(),
+        optimize_level: config.optimize_level,
+        profile: config.profile.clone(),
+        target: config.target.clone(),
+        fingerprint: fingerprint_file(input_path, config, toolchain).ok(),
+    };
+    let build_info_path = temp_dir_path.join(BUILD_INFO_SOURCE);
+    fs::write(&build_info_path, build_info.to_c_source()?).with_context(|| {
+        format!(
+            "Failed to write build metadata to {}",
+            build_info_path.display()
+        )
+    })?;
+
     // Copy the Python source file to the temp directory
     let source_path = temp_dir_path.join(format!("{module_name}.py"));
     fs::write(&source_path, source_code)
         .with_context(|| format!("Failed to write source file to {}", source_path.display()))?;
 
-    // Create a uv virtual environment
-    let mut packages = vec![
-        "setuptools>=60.0.0".to_string(),
-        "wheel>=0.37.0".to_string(),
-        "cython>=3.0.0".to_string(),
-    ];
-
-    // Add user-specified packages
-    packages.extend(config.packages.clone());
-
-    let uv_config = UvEnvConfig {
-        python_path: config.python_path.clone(),
-        python_version: config.python_version.clone(),
-        keep_venv: config.keep_temp_files,
-        packages,
-    };
-
-    let uv_env =
-        UvEnv::create(&uv_config).with_context(|| "Failed to create uv virtual environment")?;
-
-    info!(
-        "Created uv virtual environment at: {}",
-        uv_env.venv_path.display()
-    );
-    info!("Using Python interpreter: {}", uv_env.python_path.display());
-
     // Build the extension module
     info!("Building extension module...");
-    let status = Command::new(&uv_env.python_path)
-        .current_dir(&temp_dir_path)
+    let mut cmd = uv_env.python_command();
+    cmd.current_dir(&temp_dir_path)
         .arg("setup.py")
         .arg("build_ext")
-        .arg("--inplace")
+        .arg("--inplace");
+    if let Some(compiler) = compiler {
+        debug!("Building with {compiler}");
+        compiler.apply_to(&mut cmd);
+    }
+    if let Some(target_python) = &target_python {
+        target_python.apply_to(&mut cmd, &temp_dir_path)?;
+    }
+    let status = cmd
+        // Keep stdout for py2pyd's own output, e.g. `--message-format json`
+        .stdout(std::io::stderr())
         .status()
         .with_context(|| "Failed to execute Python setup.py build_ext")?;
 
@@ -161,6 +573,28 @@ pub fn compile_file(input_path: &Path, output_path: &Path, config: &CompileConfi
         )
     })?;
 
+    if let Some(target) = &target {
+        target.check_output(output_path)?;
+    }
+
+    // Report the glibc and libraries Linux modules need from the systems they run on
+    if cfg!(target_os = "linux") || target.is_some() {
+        let compat = manylinux::check(output_path, config.repair)?;
+        info!("{}: {}", output_path.display(), compat);
+        if !compat.external.is_empty() {
+            warn!(
+                "{} needs {}, which manylinux systems may not have; build with --repair to vendor them",
+                output_path.display(),
+                compat.external.join(", ")
+            );
+        }
+        if let Some(policy) = &config.manylinux {
+            compat
+                .check_policy(&policy.parse()?)
+                .with_context(|| format!("{} would not load everywhere", output_path.display()))?;
+        }
+    }
+
     info!(
         "Successfully compiled {} to {}",
         input_path.display(),
@@ -176,6 +610,18 @@ pub fn batch_compile(
     config: &CompileConfig,
     recursive: bool,
 ) -> Result<()> {
+    batch_compile_with_messages(input_pattern, output_dir, config, recursive, |_| {})
+}
+
+/// Batch compile, reporting when each module starts and finishes
+pub fn batch_compile_with_messages(
+    input_pattern: &str,
+    output_dir: &Path,
+    config: &CompileConfig,
+    recursive: bool,
+    mut on_message: impl FnMut(Message),
+) -> Result<()> {
+    let config = &*config.with_dcc_profile()?;
     info!(
         "Batch compiling from {} to {}",
         input_pattern,
@@ -196,32 +642,91 @@ pub fn batch_compile(
 
     info!("Found {} Python files to compile", python_files.len());
 
+    // Fingerprints need the environment's Cython, so with the cache on the
+    // modules share one environment
+    let build_env = config.cache.then(|| create_build_env(config)).transpose()?;
+    let toolchain = build_env
+        .as_ref()
+        .map(|env| Toolchain::resolve(config, Some(env)));
+    let mut cache = match &toolchain {
+        Some(toolchain) if toolchain.is_known() => Some(BuildCache::load(output_dir)),
+        Some(_) => {
+            warn!("Not using the build cache: the Python or Cython version is unknown");
+            None
+        }
+        None => None,
+    };
+
     // Compile each Python file
     let mut success_count = 0;
     let mut failure_count = 0;
+    let mut fresh_count = 0;
 
     for input_path in python_files {
-        // Determine the output path
-        let relative_path = input_path
-            .strip_prefix(Path::new(input_pattern))
-            .unwrap_or(&input_path);
-        let mut output_path = output_dir.join(relative_path);
-
-        // Use the appropriate extension based on the platform
-        if cfg!(windows) {
-            output_path.set_extension("pyd");
-        } else {
-            output_path.set_extension("so");
+        if !config.is_selected(&input_path) {
+            debug!("Skipping excluded file: {}", input_path.display());
+            continue;
         }
 
+        // Determine the output path
+        let output_path = batch_output_path(
+            Path::new(input_pattern),
+            &input_path,
+            output_dir,
+            config.output_layout,
+        );
+
         // Create parent directories if needed
         if let Some(parent) = output_path.parent() {
             fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
         }
 
+        // Skip modules built from the same source and settings
+        let fingerprint = match (&cache, &toolchain) {
+            (Some(_), Some(toolchain)) => fingerprint_file(&input_path, config, toolchain).ok(),
+            _ => None,
+        };
+        if let (Some(cache), Some(fingerprint)) = (&cache, &fingerprint) {
+            if cache.is_fresh(&output_path, fingerprint) {
+                debug!("{} is up to date", output_path.display());
+                on_message(Message::ModuleFresh {
+                    source: input_path,
+                    output: output_path,
+                });
+                fresh_count += 1;
+                continue;
+            }
+        }
+
         // Compile the file
-        match compile_file(&input_path, &output_path, config) {
+        on_message(Message::ModuleStarted {
+            source: input_path.clone(),
+            output: output_path.clone(),
+        });
+        let started = Instant::now();
+        let result = match (&build_env, &toolchain) {
+            (Some(env), Some(toolchain)) => {
+                compile_in_env(&input_path, &output_path, config, env, toolchain)
+            }
+            _ => compile_file(&input_path, &output_path, config),
+        };
+        on_message(Message::module_finished(
+            &input_path,
+            &output_path,
+            started.elapsed(),
+            result.as_ref().err(),
+        ));
+        if let Some(cache) = &mut cache {
+            match (&result, fingerprint) {
+                (Ok(()), Some(fingerprint)) => cache.record(&output_path, fingerprint),
+                _ => cache.invalidate(&output_path),
+            }
+            if let Err(e) = cache.save() {
+                warn!("{e:#}");
+            }
+        }
+        match result {
             Ok(()) => {
                 success_count += 1;
             }
@@ -232,7 +737,9 @@ pub fn batch_compile(
         }
     }
 
-    info!("Batch compilation complete: {success_count} succeeded, {failure_count} failed");
+    info!(
+        "Batch compilation complete: {success_count} succeeded, {failure_count} failed, {fresh_count} up to date"
+    );
 
     if failure_count > 0 {
         warn!("Some files failed to compile");
@@ -241,8 +748,30 @@ pub fn batch_compile(
     Ok(())
 }
 
+/// Where batch compilation writes the module compiled from `input_path`
+pub fn batch_output_path(
+    input_root: &Path,
+    input_path: &Path,
+    output_dir: &Path,
+    layout: OutputLayout,
+) -> PathBuf {
+    let relative_path = match layout {
+        OutputLayout::Mirror => input_path.strip_prefix(input_root).unwrap_or(input_path),
+        OutputLayout::Flat => Path::new(input_path.file_name().unwrap_or_default()),
+    };
+    let mut output_path = output_dir.join(relative_path);
+
+    // Use the appropriate extension based on the platform
+    if cfg!(windows) {
+        output_path.set_extension("pyd");
+    } else {
+        output_path.set_extension("so");
+    }
+    output_path
+}
+
 /// Collect Python files matching a pattern
-fn collect_python_files(pattern: &str, recursive: bool) -> Result<Vec<PathBuf>> {
+pub fn collect_python_files(pattern: &str, recursive: bool) -> Result<Vec<PathBuf>> {
     let mut python_files = Vec::new();
 
     // Check if the pattern is a directory
@@ -293,8 +822,9 @@ fn collect_python_files(pattern: &str, recursive: bool) -> Result<Vec<PathBuf>>
 /// Generate a setup.py file for building the extension module
 fn generate_setup_py(
     module_name: &str,
-    _source_code: &str,      // Unused but kept for potential future use
-    _config: &CompileConfig, // Unused but kept for potential future use
+    _source_code: &str, // Unused but kept for potential future use
+    config: &CompileConfig,
+    target_include: Option<&Path>,
 ) -> String {
     let mut setup_py = String::new();
 
@@ -302,27 +832,142 @@ fn generate_setup_py(
     setup_py.push_str("from setuptools.command.build_ext import build_ext\n");
     setup_py.push_str("import sys\n\n");
 
+    // Compiler and linker flags depend on the compiler setuptools picks
+    let (msvc_flag, gcc_flag) = match config.optimize_level {
+        0 => ("/Od", "-O0"),
+        1 => ("/O1", "-O1"),
+        2 => ("/O2", "-O2"),
+        _ => ("/Ox", "-O3"),
+    };
+    let mut msvc_compile = vec![msvc_flag];
+    let mut msvc_link = vec![];
+    let mut gcc_compile = vec![gcc_flag];
+    let mut gcc_link = vec![];
+    if config.lto {
+        msvc_compile.push("/GL");
+        msvc_link.push("/LTCG");
+        gcc_compile.push("-flto");
+        gcc_link.push("-flto");
+    }
+    if config.debug_symbols {
+        msvc_compile.push("/Zi");
+        msvc_link.push("/DEBUG");
+        gcc_compile.push("-g");
+    }
+    // Profile arguments are written for the compiler the platform uses
+    for arg in &config.compile_args {
+        msvc_compile.push(arg);
+        gcc_compile.push(arg);
+    }
+    for arg in &config.link_args {
+        msvc_link.push(arg);
+        gcc_link.push(arg);
+    }
+    let quoted = |flags: &[&str]| {
+        flags
+            .iter()
+            .map(|flag| python_string(flag))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
     // Add custom build_ext class to support ABI3
     setup_py.push_str("class ABI3BuildExt(build_ext):\n");
     setup_py.push_str("    def build_extension(self, ext):\n");
-    setup_py.push_str("        ext.py_limited_api = True\n");
+    if config.abi3() {
+        setup_py.push_str("        ext.py_limited_api = True\n");
+    }
+    setup_py.push_str("        if self.compiler.compiler_type == 'msvc':\n");
+    writeln!(
+        setup_py,
+        "            ext.extra_compile_args = [{}]",
+        quoted(&msvc_compile)
+    )
+    .unwrap();
+    writeln!(
+        setup_py,
+        "            ext.extra_link_args = [{}]",
+        quoted(&msvc_link)
+    )
+    .unwrap();
+    setup_py.push_str("        else:\n");
+    writeln!(
+        setup_py,
+        "            ext.extra_compile_args = [{}]",
+        quoted(&gcc_compile)
+    )
+    .unwrap();
+    writeln!(
+        setup_py,
+        "            ext.extra_link_args = [{}]",
+        quoted(&gcc_link)
+    )
+    .unwrap();
+    if config.repair && (cfg!(target_os = "linux") || config.target.is_some()) {
+        // DT_RPATH, unlike DT_RUNPATH, also applies to the vendored libraries' own dependencies
+        writeln!(
+            setup_py,
+            "            ext.extra_link_args += [{}]",
+            quoted(&["-Wl,-rpath,$ORIGIN", "-Wl,--disable-new-dtags"])
+        )
+        .unwrap();
+    }
+    if config.strip {
+        // Apple's linker has no -s; -x drops local symbols instead
+        setup_py.push_str(
+            "            ext.extra_link_args.append('-Wl,-x' if sys.platform == 'darwin' else '-s')\n",
+        );
+    }
     setup_py.push_str("        super().build_extension(ext)\n\n");
 
     // Setup the extension module
+    setup_py.push_str("ext = Extension(\n");
+    writeln!(setup_py, "    '{module_name}',").unwrap();
+    writeln!(
+        setup_py,
+        "    sources=['{module_name}.py', '{BUILD_INFO_SOURCE}'],"
+    )
+    .unwrap();
+
+    // Enable ABI3 compatibility
+    if config.abi3() {
+        setup_py.push_str("    py_limited_api=True,\n");
+        setup_py.push_str("    define_macros=[('Py_LIMITED_API', '0x03070000')],\n");
+    }
+    if let Some(include) = target_include {
+        // Searched before the build interpreter's own headers
+        writeln!(
+            setup_py,
+            "    include_dirs=[{}],",
+            python_string(&include.to_string_lossy())
+        )
+        .unwrap();
+    }
+    setup_py.push_str(")\n");
+    if config.line_tracing {
+        setup_py.push_str(
+            "ext.define_macros += [('CYTHON_TRACE', '1'), ('CYTHON_TRACE_NOGIL', '1')]\n",
+        );
+    }
+
+    // Read by Cython's build_ext when it cythonizes the sources
+    let mut cython_directives = config.cython_directives.clone();
+    if config.line_tracing {
+        cython_directives
+            .entry("linetrace".to_string())
+            .or_insert(Directive::Bool(true));
+    }
+    let directives = cython_directives
+        .iter()
+        .map(|(name, value)| format!("{}: {}", python_string(name), value.to_python()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(setup_py, "ext.cython_directives = {{{directives}}}\n").unwrap();
+
     setup_py.push_str("setup(\n");
     writeln!(setup_py, "    name='{module_name}',").unwrap();
     setup_py.push_str("    version='0.1',\n");
-    setup_py.push_str("    ext_modules=[Extension(\n");
-    writeln!(setup_py, "        '{module_name}',").unwrap();
-    writeln!(setup_py, "        sources=['{module_name}.py'],").unwrap();
-
-    // Add custom include paths if needed in the future
-    // Currently not used
-
-    // Enable ABI3 compatibility
-    setup_py.push_str("        py_limited_api=True,\n");
-    setup_py.push_str("        define_macros=[('Py_LIMITED_API', '0x03070000')],\n");
-    setup_py.push_str("    )],\n");
+    setup_py.push_str("    ext_modules=[ext],\n");
 
     // Use custom build_ext class
     setup_py.push_str("    cmdclass={'build_ext': ABI3BuildExt},\n");
@@ -1,11 +1,17 @@
 use anyhow::{anyhow, Context, Result};
-use log::{info, warn};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 use std::env;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::str::FromStr;
 use tempfile::TempDir;
-use uuid::Uuid;
+
+use crate::env_cache::{venv_python, EnvCache, EnvKind, EnvSpec, LockedEnv};
+use crate::interpreter::{env_python, find_installation, PythonInstallation, PythonRequest};
+use crate::uv_install::{install_uv, managed_uv, UV_OVERRIDE_ENV};
 
 /// Configuration for a uv virtual environment
 pub struct UvEnvConfig {
@@ -20,6 +26,19 @@ pub struct UvEnvConfig {
 
     /// Additional packages to install
     pub packages: Vec<String>,
+
+    /// Prefix of a conda environment to build in instead of a venv
+    pub conda_env: Option<PathBuf>,
+
+    /// Install packages into the conda environment itself rather than a
+    /// separate directory on `PYTHONPATH`
+    pub install_in_env: bool,
+
+    /// Tool that creates the environment and installs packages into it
+    pub backend: EnvBackend,
+
+    /// Download uv when the backend needs it and it is missing
+    pub install_uv: bool,
 }
 
 impl Default for UvEnvConfig {
@@ -29,10 +48,65 @@ impl Default for UvEnvConfig {
             python_version: None,
             keep_venv: false,
             packages: vec![],
+            conda_env: None,
+            install_in_env: false,
+            backend: EnvBackend::Auto,
+            install_uv: false,
+        }
+    }
+}
+
+/// How build environments are created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvBackend {
+    /// uv when it is installed, otherwise `venv`
+    #[default]
+    Auto,
+    /// `uv venv` and `uv pip`; can download Python versions
+    Uv,
+    /// `python -m venv` and pip, with an installed interpreter
+    Venv,
+}
+
+impl EnvBackend {
+    /// `Auto` resolved to `Uv` when uv is installed (or may be installed),
+    /// otherwise `Venv`
+    pub fn resolve(self, install_uv: bool) -> Self {
+        match self {
+            Self::Auto if install_uv || locate_uv().is_some() => Self::Uv,
+            Self::Auto => Self::Venv,
+            backend => backend,
         }
     }
 }
 
+impl FromStr for EnvBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "uv" => Ok(Self::Uv),
+            "venv" => Ok(Self::Venv),
+            _ => Err(anyhow!(
+                "Unknown environment backend '{}', expected auto, uv or venv",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EnvBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Uv => "uv",
+            Self::Venv => "venv",
+        })
+    }
+}
+
 /// A uv virtual environment
 pub struct UvEnv {
     /// Path to the virtual environment
@@ -41,139 +115,299 @@ pub struct UvEnv {
     /// Path to the Python interpreter in the virtual environment
     pub python_path: PathBuf,
 
+    /// uv, when packages are installed with it rather than pip
+    uv_path: Option<PathBuf>,
+
+    /// Directory packages were installed into for an interpreter used without
+    /// a venv; put on `PYTHONPATH`
+    site_dir: Option<PathBuf>,
+
     /// Temporary directory holding the virtual environment (if any)
     temp_dir: Option<TempDir>,
+
+    /// Cached environment in use, locked against removal until this is dropped
+    cached: Option<LockedEnv>,
 }
 
 impl UvEnv {
     /// Create a new uv virtual environment
     pub fn create(config: &UvEnvConfig) -> Result<Self> {
-        // Check if uv is installed
-        let uv_path = find_uv_executable()?;
-        info!("Found uv at: {}", uv_path.display());
-
-        // Create a temporary directory for the virtual environment
-        let temp_dir = if config.keep_venv {
-            // Create a directory in the user's home directory
-            let home_dir =
-                dirs::home_dir().ok_or_else(|| anyhow!("Failed to get home directory"))?;
-            let venv_dir = home_dir
-                .join(".py2pyd")
-                .join("venvs")
-                .join(Uuid::new_v4().to_string());
-            fs::create_dir_all(&venv_dir)
-                .with_context(|| format!("Failed to create directory: {}", venv_dir.display()))?;
-            None
-        } else {
-            // Create a temporary directory
-            Some(TempDir::new().with_context(|| "Failed to create temporary directory")?)
+        if let Some(prefix) = &config.conda_env {
+            return Self::for_conda_env(prefix, config);
+        }
+
+        let uv_path = match config.backend.resolve(config.install_uv) {
+            EnvBackend::Venv => None,
+            _ => {
+                let uv_path = find_uv_executable(config.install_uv)?;
+                info!("Found uv at: {}", uv_path.display());
+                Some(uv_path)
+            }
         };
 
-        // Get the path to the virtual environment
-        let venv_path = if let Some(ref temp_dir) = temp_dir {
-            temp_dir.path().to_path_buf()
-        } else {
-            let home_dir =
-                dirs::home_dir().ok_or_else(|| anyhow!("Failed to get home directory"))?;
-            home_dir
-                .join(".py2pyd")
-                .join("venvs")
-                .join(Uuid::new_v4().to_string())
+        // Without uv nothing is downloaded, so the interpreter must be installed
+        let installed = match (installed_python(config)?, &uv_path) {
+            (None, None) => Some(
+                find_installation(&PythonRequest::from_config(
+                    None,
+                    config.python_version.as_deref(),
+                )?)
+                .with_context(|| {
+                    "The venv backend needs an installed interpreter; install one, \
+                     or use --env-backend uv to download it"
+                })?,
+            ),
+            (installed, _) => installed,
         };
 
-        info!(
-            "Creating uv virtual environment at: {}",
-            venv_path.display()
-        );
+        if !config.keep_venv {
+            let temp_dir =
+                TempDir::new().with_context(|| "Failed to create temporary directory")?;
+            let venv_path = temp_dir.path().to_path_buf();
+            build_venv(&venv_path, uv_path.as_deref(), installed.as_ref(), config)?;
+            return Ok(Self {
+                python_path: venv_python(&venv_path),
+                venv_path,
+                uv_path,
+                site_dir: None,
+                temp_dir: Some(temp_dir),
+                cached: None,
+            });
+        }
 
-        // Build the command to create the virtual environment
-        let mut cmd = Command::new(&uv_path);
-        cmd.arg("venv");
+        // Reuse the environment built earlier from the same interpreter and packages
+        let spec = venv_spec(installed.as_ref(), uv_path.is_some(), config);
+        let cached = EnvCache::open()?.get_or_create(&spec, |venv_path| {
+            build_venv(venv_path, uv_path.as_deref(), installed.as_ref(), config)
+        })?;
+        Ok(Self {
+            venv_path: cached.env_dir(),
+            python_path: cached.python(),
+            uv_path,
+            site_dir: None,
+            temp_dir: None,
+            cached: Some(cached),
+        })
+    }
 
-        // Add Python version if specified
-        if let Some(ref version) = config.python_version {
-            cmd.arg("--python");
-            cmd.arg(version);
-        } else if let Some(ref python_path) = config.python_path {
-            cmd.arg("--python");
-            cmd.arg(python_path);
+    /// The environment [`UvEnv::create`] would reuse for `config`, if it is
+    /// cached; nothing is created, installed or downloaded.
+    ///
+    /// Only environments kept with `keep_venv`, and conda environments that
+    /// get the packages installed into them, can be found.
+    pub fn find_cached(config: &UvEnvConfig) -> Result<Option<Self>> {
+        if let Some(prefix) = &config.conda_env {
+            let python = env_python(prefix);
+            if !config.install_in_env {
+                return Self::find_cached_for_interpreter(
+                    &python,
+                    &config.packages,
+                    config.keep_venv,
+                    config.backend,
+                );
+            }
+            return Ok(Some(Self {
+                venv_path: prefix.clone(),
+                python_path: python,
+                uv_path: None,
+                site_dir: None,
+                temp_dir: None,
+                cached: None,
+            }));
+        }
+        if !config.keep_venv {
+            return Ok(None);
         }
 
-        // Add the path to the virtual environment
-        cmd.arg(&venv_path);
-
-        // Run the command
-        let status = cmd.status().with_context(|| "Failed to execute uv venv")?;
+        let uv = config.backend.resolve(config.install_uv) == EnvBackend::Uv;
+        let installed = match installed_python(config)? {
+            None if !uv => find_installation(&PythonRequest::from_config(
+                None,
+                config.python_version.as_deref(),
+            )?)
+            .ok(),
+            installed => installed,
+        };
+        let spec = venv_spec(installed.as_ref(), uv, config);
+        Ok(EnvCache::open()?.find(&spec)?.map(|cached| Self {
+            venv_path: cached.env_dir(),
+            python_path: cached.python(),
+            uv_path: None,
+            site_dir: None,
+            temp_dir: None,
+            cached: Some(cached),
+        }))
+    }
 
-        if !status.success() {
-            return Err(anyhow!("Failed to create uv virtual environment"));
+    /// Build in the conda environment at `prefix`, with the packages installed
+    /// into it or, by default, into a separate directory on `PYTHONPATH`.
+    ///
+    /// Works without uv; packages are installed with the environment's pip then.
+    pub fn for_conda_env(prefix: &Path, config: &UvEnvConfig) -> Result<Self> {
+        let python = env_python(prefix);
+        let installation = PythonInstallation::probe(&python).with_context(|| {
+            format!("No usable Python in conda environment {}", prefix.display())
+        })?;
+        if let Some(version) = &config.python_version {
+            let request: PythonRequest = version.parse()?;
+            if !request.matches(&installation) {
+                return Err(anyhow!(
+                    "Conda environment {} has Python {}, not the requested {}",
+                    prefix.display(),
+                    installation.version,
+                    request
+                ));
+            }
         }
+        info!(
+            "Using conda environment {}: {}",
+            prefix.display(),
+            installation
+        );
 
-        // Get the path to the Python interpreter in the virtual environment
-        let python_path = if cfg!(windows) {
-            venv_path.join("Scripts").join("python.exe")
-        } else {
-            venv_path.join("bin").join("python")
+        if !config.install_in_env {
+            return Self::for_interpreter(
+                &python,
+                &config.packages,
+                config.keep_venv,
+                config.backend,
+            );
+        }
+        let env = Self {
+            venv_path: prefix.to_path_buf(),
+            python_path: python,
+            uv_path: installer_uv(config.backend),
+            site_dir: None,
+            temp_dir: None,
+            cached: None,
         };
+        if !config.packages.is_empty() {
+            info!(
+                "Installing packages into {}: {:?}",
+                prefix.display(),
+                config.packages
+            );
+            env.pip_install(&config.packages)?;
+        }
+        Ok(env)
+    }
 
-        if !python_path.exists() {
+    /// Use the interpreter at `python` as it is, e.g. a DCC's `mayapy`, with
+    /// `packages` installed into a separate directory on `PYTHONPATH`
+    pub fn for_interpreter(
+        python: &Path,
+        packages: &[String],
+        keep: bool,
+        backend: EnvBackend,
+    ) -> Result<Self> {
+        if !python.is_file() {
             return Err(anyhow!(
-                "Python interpreter not found in virtual environment"
+                "Python interpreter not found: {}",
+                python.display()
             ));
         }
 
-        // Install required packages
-        if !config.packages.is_empty() {
-            info!("Installing packages: {:?}", config.packages);
-
-            let mut cmd = Command::new(&uv_path);
-            cmd.arg("pip");
-            cmd.arg("install");
-
-            // Add packages
-            for package in &config.packages {
-                cmd.arg(package);
+        let uv_path = installer_uv(backend);
+        let install = |site_dir: &Path| -> Result<()> {
+            fs::create_dir_all(site_dir)
+                .with_context(|| format!("Failed to create directory: {}", site_dir.display()))?;
+            if !packages.is_empty() {
+                info!(
+                    "Installing packages for {}: {:?}",
+                    python.display(),
+                    packages
+                );
+                pip_install(uv_path.as_deref(), python, Some(site_dir), packages)?;
             }
+            Ok(())
+        };
 
-            // Set the virtual environment
-            cmd.env("VIRTUAL_ENV", &venv_path);
+        let (site_dir, temp_dir, cached) = if keep {
+            // Reuse the packages installed earlier for this interpreter
+            let spec = site_spec(python, uv_path.is_some(), packages);
+            let cached = EnvCache::open()?.get_or_create(&spec, install)?;
+            (cached.env_dir(), None, Some(cached))
+        } else {
+            let temp_dir =
+                TempDir::new().with_context(|| "Failed to create temporary directory")?;
+            install(temp_dir.path())?;
+            (temp_dir.path().to_path_buf(), Some(temp_dir), None)
+        };
 
-            // Add the virtual environment's bin directory to PATH
-            let path_var = if cfg!(windows) { "Path" } else { "PATH" };
-            let mut paths = env::var(path_var).unwrap_or_default();
-            let bin_dir = if cfg!(windows) {
-                venv_path.join("Scripts")
-            } else {
-                venv_path.join("bin")
-            };
-            paths = format!(
-                "{}{}{}",
-                bin_dir.to_string_lossy(),
-                if cfg!(windows) { ";" } else { ":" },
-                paths
-            );
-            cmd.env(path_var, paths);
+        Ok(Self {
+            venv_path: site_dir.clone(),
+            python_path: python.to_path_buf(),
+            uv_path,
+            site_dir: Some(site_dir),
+            temp_dir,
+            cached,
+        })
+    }
 
-            // Run the command
-            let status = cmd
-                .status()
-                .with_context(|| "Failed to execute uv pip install")?;
+    /// The package directory [`UvEnv::for_interpreter`] would reuse for
+    /// `python`, if it is cached; nothing is installed
+    pub fn find_cached_for_interpreter(
+        python: &Path,
+        packages: &[String],
+        keep: bool,
+        backend: EnvBackend,
+    ) -> Result<Option<Self>> {
+        if !keep {
+            return Ok(None);
+        }
+        let uv_path = installer_uv(backend);
+        let spec = site_spec(python, uv_path.is_some(), packages);
+        Ok(EnvCache::open()?.find(&spec)?.map(|cached| Self {
+            venv_path: cached.env_dir(),
+            python_path: python.to_path_buf(),
+            uv_path,
+            site_dir: Some(cached.env_dir()),
+            temp_dir: None,
+            cached: Some(cached),
+        }))
+    }
 
-            if !status.success() {
-                return Err(anyhow!("Failed to install packages"));
+    /// Version of the Cython installed in the environment
+    pub fn cython_version(&self) -> Option<String> {
+        match self.run_script("import Cython; print(Cython.__version__)") {
+            Ok(version) => Some(version.trim().to_string()),
+            Err(e) => {
+                debug!("No Cython in {}: {:#}", self.venv_path.display(), e);
+                None
             }
         }
+    }
 
-        Ok(Self {
-            venv_path,
-            python_path,
-            temp_dir,
-        })
+    /// A command running the environment's interpreter
+    pub fn python_command(&self) -> Command {
+        let mut cmd = Command::new(&self.python_path);
+        if let Some(site_dir) = &self.site_dir {
+            let mut paths = vec![site_dir.clone()];
+            if let Some(existing) = env::var_os("PYTHONPATH") {
+                paths.extend(env::split_paths(&existing));
+            }
+            if let Ok(joined) = env::join_paths(paths) {
+                cmd.env("PYTHONPATH", joined);
+            }
+        }
+        cmd
+    }
+
+    /// Install into the environment, or the package directory of an
+    /// interpreter used without a venv
+    fn pip_install(&self, packages: &[String]) -> Result<()> {
+        pip_install(
+            self.uv_path.as_deref(),
+            &self.python_path,
+            self.site_dir.as_deref(),
+            packages,
+        )
     }
 
     /// Run a Python script in the virtual environment
     pub fn run_script(&self, script: &str) -> Result<String> {
-        let output = Command::new(&self.python_path)
+        let output = self
+            .python_command()
             .arg("-c")
             .arg(script)
             .output()
@@ -190,7 +424,8 @@ impl UvEnv {
 
     /// Run a Python module in the virtual environment
     pub fn run_module(&self, module: &str, args: &[&str]) -> Result<()> {
-        let status = Command::new(&self.python_path)
+        let status = self
+            .python_command()
             .arg("-m")
             .arg(module)
             .args(args)
@@ -206,89 +441,281 @@ impl UvEnv {
 
     /// Install a package in the virtual environment
     pub fn install_package(&self, package: &str) -> Result<()> {
-        let uv_path = find_uv_executable()?;
+        self.pip_install(&[package.to_string()])
+            .with_context(|| format!("Failed to install package: {package}"))
+    }
+}
 
-        let status = Command::new(&uv_path)
-            .arg("pip")
-            .arg("install")
-            .arg(package)
-            .env("VIRTUAL_ENV", &self.venv_path)
-            .status()
-            .with_context(|| format!("Failed to install package: {package}"))?;
+/// The cache entry of a venv made from `installed`, or by uv from the config's
+/// Python version when nothing installed matches
+fn venv_spec(installed: Option<&PythonInstallation>, uv: bool, config: &UvEnvConfig) -> EnvSpec {
+    let (interpreter, python_version) = match installed {
+        Some(installation) => (
+            fs::canonicalize(&installation.executable)
+                .unwrap_or_else(|_| installation.executable.clone())
+                .to_string_lossy()
+                .to_string(),
+            Some(installation.version.to_string()),
+        ),
+        None => (
+            format!(
+                "uv:{}",
+                config.python_version.as_deref().unwrap_or("default")
+            ),
+            config.python_version.clone(),
+        ),
+    };
+    let backend = if uv { EnvBackend::Uv } else { EnvBackend::Venv };
+    EnvSpec::new(
+        EnvKind::Venv,
+        interpreter,
+        python_version,
+        backend,
+        &config.packages,
+    )
+}
 
-        if !status.success() {
-            return Err(anyhow!("Failed to install package: {}", package));
+/// The cache entry of the package directory for an interpreter used without a venv
+fn site_spec(python: &Path, uv: bool, packages: &[String]) -> EnvSpec {
+    let interpreter = fs::canonicalize(python).unwrap_or_else(|_| python.to_path_buf());
+    let backend = if uv { EnvBackend::Uv } else { EnvBackend::Venv };
+    EnvSpec::new(
+        EnvKind::Site,
+        interpreter.to_string_lossy(),
+        None,
+        backend,
+        packages,
+    )
+}
+
+/// Create a venv at `venv_path` with `uv venv` when `uv_path` is given,
+/// otherwise `python -m venv`, and install the config's packages into it
+fn build_venv(
+    venv_path: &Path,
+    uv_path: Option<&Path>,
+    installed: Option<&PythonInstallation>,
+    config: &UvEnvConfig,
+) -> Result<()> {
+    let mut cmd = match (uv_path, installed) {
+        (Some(uv_path), _) => {
+            info!(
+                "Creating uv virtual environment at: {}",
+                venv_path.display()
+            );
+            let mut cmd = Command::new(uv_path);
+            cmd.arg("venv");
+
+            // Prefer an installed interpreter; uv downloads a version it can't find
+            let python = match (installed, &config.python_version) {
+                (Some(installation), _) => Some(installation.executable.clone().into_os_string()),
+                (None, Some(version)) => {
+                    info!("Letting uv download Python {version}");
+                    Some(version.into())
+                }
+                (None, None) => None,
+            };
+            if let Some(python) = python {
+                cmd.arg("--python");
+                cmd.arg(python);
+            }
+            cmd
+        }
+        (None, Some(installation)) => {
+            info!(
+                "Creating virtual environment at {} with {}",
+                venv_path.display(),
+                installation
+            );
+            let mut cmd = Command::new(&installation.executable);
+            cmd.args(["-m", "venv"]);
+            cmd
         }
+        (None, None) => return Err(anyhow!("No interpreter to create a venv with")),
+    };
 
-        Ok(())
+    // Add the path to the virtual environment
+    cmd.arg(venv_path);
+
+    // Run the command; its output goes to stderr so stdout stays
+    // reserved for py2pyd's own output (e.g. `--message-format json`)
+    let status = cmd
+        .stdout(std::io::stderr())
+        .status()
+        .with_context(|| "Failed to create the virtual environment")?;
+
+    if !status.success() {
+        return Err(anyhow!(
+            "Failed to create virtual environment{}",
+            if uv_path.is_some() {
+                ""
+            } else {
+                " (on Debian and Ubuntu, `python -m venv` needs the python3-venv package)"
+            }
+        ));
     }
-}
 
-/// Find the uv executable
-fn find_uv_executable() -> Result<PathBuf> {
-    // Try to find uv in PATH
-    if let Ok(path) = which::which("uv") {
-        return Ok(path);
+    let python_path = venv_python(venv_path);
+    if !python_path.exists() {
+        return Err(anyhow!(
+            "Python interpreter not found in virtual environment"
+        ));
     }
 
-    // Try common installation locations
-    let common_paths = if cfg!(windows) {
-        vec![
-            r"C:\Users\hallo\.cargo\bin\uv.exe",
-            r"C:\Program Files\uv\uv.exe",
-            r"C:\uv\uv.exe",
-        ]
-    } else {
-        vec![
-            "/usr/bin/uv",
-            "/usr/local/bin/uv",
-            "/opt/uv/bin/uv",
-            "/home/hallo/.cargo/bin/uv",
-        ]
-    };
+    // Install required packages
+    if !config.packages.is_empty() {
+        info!("Installing packages: {:?}", config.packages);
+        pip_install(uv_path, &python_path, None, &config.packages)?;
+    }
+    Ok(())
+}
 
-    for path_str in common_paths {
-        let path = PathBuf::from(path_str);
-        if path.exists() {
-            return Ok(path);
+/// Install `packages` for `python`, into `site_dir` when it is used without a
+/// venv: with `uv pip` when uv is given, otherwise with the interpreter's own pip
+fn pip_install(
+    uv_path: Option<&Path>,
+    python: &Path,
+    site_dir: Option<&Path>,
+    packages: &[String],
+) -> Result<()> {
+    let mut cmd = match uv_path {
+        Some(uv_path) => {
+            let mut cmd = Command::new(uv_path);
+            cmd.args(["pip", "install", "--python"]);
+            cmd.arg(python);
+            cmd
         }
+        None => {
+            let mut cmd = Command::new(python);
+            cmd.args(["-m", "pip", "install", "--disable-pip-version-check"]);
+            cmd
+        }
+    };
+    if let Some(site_dir) = site_dir {
+        cmd.arg("--target").arg(site_dir);
     }
+    cmd.args(packages);
+
+    let status = cmd
+        .stdout(std::io::stderr())
+        .status()
+        .with_context(|| "Failed to execute pip install")?;
+    if !status.success() {
+        return Err(anyhow!(
+            "Failed to install packages: {}",
+            packages.join(" ")
+        ));
+    }
+    Ok(())
+}
 
-    // If uv is not found, try to install it
-    warn!("uv not found, attempting to install it");
-    install_uv()?;
+/// uv to install packages with for `backend`; pip when `None`
+fn installer_uv(backend: EnvBackend) -> Option<PathBuf> {
+    match backend {
+        EnvBackend::Venv => None,
+        EnvBackend::Auto | EnvBackend::Uv => locate_uv(),
+    }
+}
 
-    // Try to find uv again
-    which::which("uv").with_context(|| "Failed to find uv executable after installation")
+/// Find the uv executable; installing it when missing needs `install`
+fn find_uv_executable(install: bool) -> Result<PathBuf> {
+    if let Some(path) = locate_uv() {
+        return Ok(path);
+    }
+    if !install {
+        return Err(anyhow!(
+            "uv not found; install it, pass --install-uv to download it, \
+             or use --env-backend venv"
+        ));
+    }
+
+    warn!("uv not found, installing it");
+    install_uv()
 }
 
-/// Install uv (latest version - 0.7.6 as of last update)
-fn install_uv() -> Result<()> {
-    if cfg!(windows) {
-        // On Windows, use PowerShell to install uv
-        let status = Command::new("powershell")
-            .arg("-ExecutionPolicy")
-            .arg("ByPass")
-            .arg("-Command")
-            .arg("irm https://astral.sh/uv/install.ps1 | iex")
-            .status()
-            .with_context(|| "Failed to execute PowerShell command to install uv")?;
+/// The installed interpreter a venv would be created with for `python_path`
+/// or `python_version`.
+///
+/// `None` when uv would download a managed Python instead.
+pub fn find_python(python_path: Option<&Path>, python_version: Option<&str>) -> Option<PathBuf> {
+    let request = PythonRequest::from_config(python_path, python_version).ok()?;
+    find_installation(&request)
+        .ok()
+        .map(|installation| installation.executable)
+}
 
-        if !status.success() {
-            return Err(anyhow!("Failed to install uv"));
+/// The installed interpreter for the config's path or version; `None` when
+/// nothing was asked for or the version is not installed
+fn installed_python(config: &UvEnvConfig) -> Result<Option<PythonInstallation>> {
+    if let Some(ref path) = config.python_path {
+        let installation = PythonInstallation::probe(path)?;
+        if let Some(ref version) = config.python_version {
+            let request: PythonRequest = version.parse()?;
+            if !request.matches(&installation) {
+                return Err(anyhow!(
+                    "--python-path {} is Python {}, not the requested {}",
+                    path.display(),
+                    installation.version,
+                    request
+                ));
+            }
         }
-    } else {
-        // On Unix, use curl to install uv
-        let status = Command::new("sh")
-            .arg("-c")
-            .arg("curl -LsSf https://astral.sh/uv/install.sh | sh")
-            .status()
-            .with_context(|| "Failed to execute shell command to install uv")?;
+        return Ok(Some(installation));
+    }
 
-        if !status.success() {
-            return Err(anyhow!("Failed to install uv"));
+    let Some(ref version) = config.python_version else {
+        return Ok(None);
+    };
+    let request: PythonRequest = version.parse()?;
+    match find_installation(&request) {
+        Ok(installation) => {
+            info!("Using {} (from {})", installation, installation.source);
+            Ok(Some(installation))
+        }
+        Err(e) => {
+            debug!("{e:#}");
+            Ok(None)
         }
     }
+}
 
-    Ok(())
+/// Look for an installed uv executable without installing it: `PY2PYD_UV`,
+/// `PATH`, the managed install, then uv's and cargo's install directories
+pub fn locate_uv() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(UV_OVERRIDE_ENV) {
+        let path = PathBuf::from(path);
+        if path.is_file() {
+            return Some(path);
+        }
+        warn!(
+            "{} points to {}, which does not exist",
+            UV_OVERRIDE_ENV,
+            path.display()
+        );
+        return None;
+    }
+
+    // Try to find uv in PATH
+    if let Ok(path) = which::which("uv") {
+        return Some(path);
+    }
+    if let Some(path) = managed_uv() {
+        return Some(path);
+    }
+
+    // Try common installation locations
+    let exe = if cfg!(windows) { "uv.exe" } else { "uv" };
+    let mut common_paths: Vec<PathBuf> = vec![];
+    if let Some(dir) = env::var_os("UV_INSTALL_DIR") {
+        common_paths.push(PathBuf::from(dir).join(exe));
+    }
+    if let Some(home) = dirs::home_dir() {
+        common_paths.push(home.join(".local").join("bin").join(exe));
+        common_paths.push(home.join(".cargo").join("bin").join(exe));
+    }
+    if !cfg!(windows) {
+        common_paths.extend(
+            ["/usr/local/bin/uv", "/usr/bin/uv", "/opt/homebrew/bin/uv"].map(PathBuf::from),
+        );
+    }
+    common_paths.into_iter().find(|path| path.is_file())
 }
@@ -0,0 +1,259 @@
+use anyhow::{anyhow, Context, Result};
+use flate2::read::GzDecoder;
+use log::{debug, info};
+use ring::digest::{Context as DigestContext, SHA256};
+use std::env;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use zip::ZipArchive;
+
+use crate::lock::{FileLock, LockMode};
+use crate::turbo_downloader::smart_download_file;
+
+/// uv release installed by `--install-uv`
+pub const UV_VERSION: &str = "0.7.6";
+
+/// Environment variable with the uv executable to use instead of looking for one
+pub const UV_OVERRIDE_ENV: &str = "PY2PYD_UV";
+
+/// uv's release target for the machine py2pyd runs on, e.g. `x86_64-unknown-linux-gnu`
+pub fn host_target() -> Result<&'static str> {
+    uv_target(
+        env::consts::OS,
+        env::consts::ARCH,
+        cfg!(target_env = "musl"),
+    )
+}
+
+/// uv's release target for `os` and `arch` as in [`std::env::consts`]
+pub fn uv_target(os: &str, arch: &str, musl: bool) -> Result<&'static str> {
+    Ok(match (os, arch, musl) {
+        ("linux", "x86_64", false) => "x86_64-unknown-linux-gnu",
+        ("linux", "x86_64", true) => "x86_64-unknown-linux-musl",
+        ("linux", "aarch64", false) => "aarch64-unknown-linux-gnu",
+        ("linux", "aarch64", true) => "aarch64-unknown-linux-musl",
+        ("linux", "x86", _) => "i686-unknown-linux-gnu",
+        ("linux", "powerpc64", _) => "powerpc64le-unknown-linux-gnu",
+        ("linux", "s390x", _) => "s390x-unknown-linux-gnu",
+        ("macos", "x86_64", _) => "x86_64-apple-darwin",
+        ("macos", "aarch64", _) => "aarch64-apple-darwin",
+        ("windows", "x86_64", _) => "x86_64-pc-windows-msvc",
+        ("windows", "aarch64", _) => "aarch64-pc-windows-msvc",
+        ("windows", "x86", _) => "i686-pc-windows-msvc",
+        _ => {
+            return Err(anyhow!(
+                "uv does not publish binaries for {} on {}; install it yourself and set {}",
+                os,
+                arch,
+                UV_OVERRIDE_ENV
+            ))
+        }
+    })
+}
+
+/// Release archive for `target`: a `.zip` on Windows, a `.tar.gz` elsewhere
+pub fn archive_name(target: &str) -> String {
+    let extension = if target.contains("windows") {
+        "zip"
+    } else {
+        "tar.gz"
+    };
+    format!("uv-{target}.{extension}")
+}
+
+/// Where `archive` of uv `version` is downloaded from; its checksum is at `<url>.sha256`
+pub fn download_url(version: &str, archive: &str) -> String {
+    format!("https://github.com/astral-sh/uv/releases/download/{version}/{archive}")
+}
+
+/// Directory the managed uv of [`UV_VERSION`] is installed into
+pub fn managed_dir() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir().ok_or_else(|| anyhow!("Failed to determine data directory"))?;
+    Ok(data_dir.join("py2pyd").join("uv").join(UV_VERSION))
+}
+
+/// The managed uv, if it is installed
+pub fn managed_uv() -> Option<PathBuf> {
+    let uv = managed_dir().ok()?.join(executable_name());
+    uv.is_file().then_some(uv)
+}
+
+/// The SHA-256 in a `.sha256` file (`<hex>  <file name>`)
+pub fn parse_checksum(text: &str) -> Result<String> {
+    text.split_whitespace()
+        .next()
+        .filter(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(str::to_lowercase)
+        .ok_or_else(|| anyhow!("Malformed checksum file: {:?}", text.trim()))
+}
+
+/// Hex SHA-256 of the file at `path`
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut context = DigestContext::new(&SHA256);
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+    }
+
+    let mut hex = String::with_capacity(64);
+    for byte in context.finish().as_ref() {
+        write!(hex, "{byte:02x}").unwrap();
+    }
+    Ok(hex)
+}
+
+/// Download uv [`UV_VERSION`] for this machine, check it against the published
+/// SHA-256 and install it into [`managed_dir`]. Returns the executable.
+pub fn install_uv() -> Result<PathBuf> {
+    if let Some(uv) = managed_uv() {
+        debug!("uv already installed at {}", uv.display());
+        return Ok(uv);
+    }
+
+    let dir = managed_dir()?;
+    let parent = dir
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid uv directory {}", dir.display()))?;
+    // Wait for another process installing it, rather than downloading it twice
+    let _lock = FileLock::acquire(
+        &parent.join(format!("{UV_VERSION}.lock")),
+        LockMode::Exclusive,
+        &format!("the uv {UV_VERSION} install"),
+    )?;
+    if let Some(uv) = managed_uv() {
+        debug!("uv installed at {} by another process", uv.display());
+        return Ok(uv);
+    }
+
+    let target = host_target()?;
+    let archive = archive_name(target);
+    let url = download_url(UV_VERSION, &archive);
+
+    // Unpacked next to the final directory, so moving it there is a rename
+    let temp_dir = TempDir::new_in(parent)
+        .with_context(|| format!("Failed to create a directory in {}", parent.display()))?;
+    let archive_path = temp_dir.path().join(&archive);
+    let checksum_path = temp_dir.path().join(format!("{archive}.sha256"));
+
+    info!("Downloading uv {UV_VERSION} from {url}");
+    smart_download_file(&url, &archive_path)
+        .with_context(|| format!("Failed to download uv from {url}"))?;
+    smart_download_file(&format!("{url}.sha256"), &checksum_path)
+        .with_context(|| format!("Failed to download the checksum of {archive}"))?;
+
+    let expected = parse_checksum(&fs::read_to_string(&checksum_path)?)?;
+    let actual = sha256_file(&archive_path)?;
+    if actual != expected {
+        return Err(anyhow!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            archive,
+            expected,
+            actual
+        ));
+    }
+    debug!("{archive} matches its published SHA-256 {expected}");
+
+    let unpacked = temp_dir.path().join("uv");
+    extract_executables(&archive_path, &unpacked)?;
+    let uv = unpacked.join(executable_name());
+    if !uv.is_file() {
+        return Err(anyhow!(
+            "{} does not contain {}",
+            archive,
+            executable_name()
+        ));
+    }
+
+    // A directory without the executable is left from an interrupted install
+    if dir.exists() {
+        fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {}", dir.display()))?;
+    }
+    fs::rename(&unpacked, &dir)
+        .with_context(|| format!("Failed to install uv into {}", dir.display()))?;
+    let uv = dir.join(executable_name());
+    info!("uv {UV_VERSION} installed at {}", uv.display());
+    Ok(uv)
+}
+
+/// Copy the executables (`uv` and `uvx`) out of a release archive into `dest`,
+/// ignoring the directory they are in
+pub fn extract_executables(archive: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest)
+        .with_context(|| format!("Failed to create directory: {}", dest.display()))?;
+    let file =
+        File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?;
+    let name = archive.to_string_lossy();
+
+    if name.ends_with(".zip") {
+        let mut zip = ZipArchive::new(file)
+            .with_context(|| format!("Failed to read zip file: {}", archive.display()))?;
+        for i in 0..zip.len() {
+            let mut entry = zip
+                .by_index(i)
+                .with_context(|| format!("Failed to read file {i} in zip"))?;
+            if entry.is_file() {
+                let path = PathBuf::from(entry.name());
+                write_executable(&path, &mut entry, dest)?;
+            }
+        }
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        let mut tar = tar::Archive::new(GzDecoder::new(file));
+        let entries = tar
+            .entries()
+            .with_context(|| format!("Failed to read {}", archive.display()))?;
+        for entry in entries {
+            let mut entry =
+                entry.with_context(|| format!("Failed to read {}", archive.display()))?;
+            if entry.header().entry_type().is_file() {
+                let path = entry.path()?.into_owned();
+                write_executable(&path, &mut entry, dest)?;
+            }
+        }
+    } else {
+        return Err(anyhow!("Unsupported archive {}", archive.display()));
+    }
+    Ok(())
+}
+
+/// Write the archive entry `path` into `dest` if it is `uv` or `uvx`
+fn write_executable(path: &Path, reader: &mut impl Read, dest: &Path) -> Result<()> {
+    let Some(file_name) = path.file_name() else {
+        return Ok(());
+    };
+    let stem = Path::new(file_name).file_stem().unwrap_or_default();
+    if stem != "uv" && stem != "uvx" {
+        return Ok(());
+    }
+
+    let out = dest.join(file_name);
+    let mut file =
+        File::create(&out).with_context(|| format!("Failed to create file: {}", out.display()))?;
+    io::copy(reader, &mut file)
+        .with_context(|| format!("Failed to write file: {}", out.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&out, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+/// `uv.exe` on Windows, `uv` elsewhere
+fn executable_name() -> &'static str {
+    if cfg!(windows) {
+        "uv.exe"
+    } else {
+        "uv"
+    }
+}
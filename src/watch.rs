@@ -0,0 +1,202 @@
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::messages::Message;
+use crate::uv_compiler::{self, batch_output_path, CompileConfig};
+
+/// How often the input tree is scanned for changes
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long the input tree must stay unchanged before rebuilding
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Timing of the watch loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchOptions {
+    /// How often the input tree is scanned for changes
+    pub poll_interval: Duration,
+    /// How long the input tree must stay unchanged before rebuilding, so a
+    /// burst of saves triggers one rebuild
+    pub debounce: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            debounce: DEFAULT_DEBOUNCE,
+        }
+    }
+}
+
+/// What a file looked like when it was last scanned
+type Fingerprint = (SystemTime, u64);
+
+/// Finds Python files below a directory that changed since the last scan
+pub struct Watcher {
+    root: PathBuf,
+    snapshot: BTreeMap<PathBuf, Fingerprint>,
+}
+
+impl Watcher {
+    /// Start watching `root`, a directory or a single Python file
+    pub fn new(root: &Path) -> Result<Self> {
+        let mut watcher = Self {
+            root: root.to_path_buf(),
+            snapshot: BTreeMap::new(),
+        };
+        watcher.snapshot = watcher.scan()?;
+        debug!(
+            "Watching {} Python files in {}",
+            watcher.snapshot.len(),
+            root.display()
+        );
+        Ok(watcher)
+    }
+
+    /// Python files currently known to the watcher
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.snapshot.keys().map(PathBuf::as_path)
+    }
+
+    fn scan(&self) -> Result<BTreeMap<PathBuf, Fingerprint>> {
+        if !self.root.exists() {
+            anyhow::bail!("{} no longer exists", self.root.display());
+        }
+
+        // Files can disappear between listing and reading their metadata
+        // while an editor saves, so unreadable entries are skipped
+        Ok(walkdir::WalkDir::new(&self.root)
+            .into_iter()
+            .filter_map(std::result::Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "py"))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                let modified = metadata.modified().ok()?;
+                Some((entry.into_path(), (modified, metadata.len())))
+            })
+            .collect())
+    }
+
+    /// Files created or modified since the last scan
+    pub fn poll(&mut self) -> Result<BTreeSet<PathBuf>> {
+        let current = self.scan()?;
+
+        let changed = current
+            .iter()
+            .filter(|(path, fingerprint)| self.snapshot.get(*path) != Some(fingerprint))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for removed in self.snapshot.keys().filter(|p| !current.contains_key(*p)) {
+            debug!("{} was removed", removed.display());
+        }
+
+        self.snapshot = current;
+        Ok(changed)
+    }
+
+    /// Block until files change, then until nothing changed for the debounce
+    /// period, and return every file that changed in between
+    pub fn wait_for_changes(&mut self, options: &WatchOptions) -> Result<BTreeSet<PathBuf>> {
+        let mut changed = BTreeSet::new();
+        let mut last_change = Instant::now();
+
+        loop {
+            thread::sleep(options.poll_interval);
+
+            let new_changes = self.poll()?;
+            if !new_changes.is_empty() {
+                changed.extend(new_changes);
+                last_change = Instant::now();
+            } else if !changed.is_empty() && last_change.elapsed() >= options.debounce {
+                return Ok(changed);
+            }
+        }
+    }
+}
+
+/// Whether `output` is missing or older than `source`
+pub fn needs_rebuild(source: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(source), modified(output)) {
+        (Some(source), Some(output)) => source > output,
+        _ => true,
+    }
+}
+
+/// Compile `input` into `output_dir`, then recompile modules as they change.
+///
+/// Outputs that are already newer than their sources are not rebuilt on
+/// start. One build environment is created up front and reused for every
+/// rebuild. `on_message` is called when each module starts and finishes;
+/// failed builds do not stop the loop, which only returns on errors such as
+/// the input directory disappearing.
+pub fn watch(
+    input: &Path,
+    output_dir: &Path,
+    config: &CompileConfig,
+    options: &WatchOptions,
+    mut on_message: impl FnMut(Message),
+) -> Result<()> {
+    let root = if input.is_dir() {
+        input
+    } else {
+        input.parent().unwrap_or_else(|| Path::new(""))
+    };
+    fs::create_dir_all(output_dir).with_context(|| {
+        format!(
+            "Failed to create output directory: {}",
+            output_dir.display()
+        )
+    })?;
+
+    let mut watcher = Watcher::new(input)?;
+    let output_for =
+        |source: &Path| batch_output_path(root, source, output_dir, config.output_layout);
+
+    info!("Creating the build environment...");
+    let uv_env = uv_compiler::create_build_env(config)?;
+
+    let mut rebuild = |sources: Vec<PathBuf>| {
+        for source in sources {
+            if !config.is_selected(&source) {
+                debug!("Skipping excluded file: {}", source.display());
+                continue;
+            }
+
+            let output = output_for(&source);
+            on_message(Message::ModuleStarted {
+                source: source.clone(),
+                output: output.clone(),
+            });
+            let started = Instant::now();
+            let result = uv_compiler::compile_file_with_env(&source, &output, config, &uv_env);
+            on_message(Message::module_finished(
+                &source,
+                &output,
+                started.elapsed(),
+                result.as_ref().err(),
+            ));
+        }
+    };
+
+    let stale: Vec<PathBuf> = watcher
+        .files()
+        .filter(|source| needs_rebuild(source, &output_for(source)))
+        .map(Path::to_path_buf)
+        .collect();
+    info!("{} module(s) out of date", stale.len());
+    rebuild(stale);
+
+    loop {
+        let changed = watcher.wait_for_changes(options)?;
+        info!("{} module(s) changed", changed.len());
+        rebuild(changed.into_iter().collect());
+    }
+}
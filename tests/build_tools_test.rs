@@ -2,8 +2,25 @@
 //!
 //! These tests verify build tools detection functionality.
 
+mod common;
+
+use std::path::PathBuf;
+
+use py2pyd::build_tools::{check_python_dev, parse_cc_version, parse_msvc_banner};
+use py2pyd::{BuildTools, Compiler, CompilerKind, Distro, PythonInstallation};
+
+/// Python 3.11.4, with or without its headers
+fn python_info(has_header: bool) -> PythonInstallation {
+    PythonInstallation {
+        has_header,
+        ..common::installation((3, 11, 4))
+    }
+}
+
 #[cfg(test)]
 mod build_tools_tests {
+    use super::*;
+
     /// Test that detect_build_tools doesn't panic
     #[test]
     fn test_detect_build_tools_no_panic() {
@@ -97,6 +114,46 @@ mod build_tools_tests {
         );
     }
 
+    /// Test that Clang alone, or a compiler selected from `CC`, counts as a
+    /// build tool
+    #[test]
+    fn test_has_any_tools_clang_and_selected() {
+        let none = BuildTools {
+            msvc: None,
+            mingw: None,
+            dlltool: None,
+            vs: None,
+            gcc: None,
+            clang: None,
+            xcode: None,
+            env_overrides: vec![],
+            selected: None,
+        };
+        assert!(!none.has_any_tools());
+
+        let clang = BuildTools {
+            clang: Some(PathBuf::from("/usr/bin/clang")),
+            ..none
+        };
+        assert!(clang.has_any_tools());
+
+        let selected = BuildTools {
+            clang: None,
+            selected: Some(Compiler {
+                kind: CompilerKind::Gcc,
+                path: PathBuf::from("/opt/cross/bin/cc"),
+                args: vec![],
+                version: None,
+                target: None,
+                reason: "CC is set".to_string(),
+                from_env: true,
+                cross: false,
+            }),
+            ..clang
+        };
+        assert!(selected.has_any_tools());
+    }
+
     /// Test that MSVC detection works on Windows
     #[test]
     #[cfg(windows)]
@@ -188,6 +245,101 @@ mod build_tools_tests {
         }
     }
 
+    /// Test that the compiler builds will use is reported with a reason
+    #[test]
+    fn test_selected_compiler() {
+        let tools = py2pyd::detect_build_tools();
+        let Some(selected) = &tools.selected else {
+            return;
+        };
+
+        assert!(!selected.reason.is_empty());
+        assert!(tools
+            .get_tools_info()
+            .contains(&format!("Using: {selected}")));
+        if !cfg!(windows) {
+            assert!(tools.mingw.is_none(), "gcc is only MinGW on Windows");
+        }
+    }
+
+    /// Test version and target parsing for GCC, Clang and MSVC
+    #[test]
+    fn test_parse_compiler_versions() {
+        assert_eq!(
+            parse_cc_version("gcc (Ubuntu 13.2.0-23ubuntu4) 13.2.0\nCopyright (C) 2023"),
+            (CompilerKind::Gcc, Some("13.2.0".to_string()))
+        );
+        assert_eq!(
+            parse_cc_version("gcc-12 (GCC) 12.3.1 20230508"),
+            (CompilerKind::Gcc, Some("12.3.1".to_string()))
+        );
+        assert_eq!(
+            parse_cc_version("Ubuntu clang version 18.1.3 (1ubuntu1)\nTarget: x86_64-pc-linux-gnu"),
+            (CompilerKind::Clang, Some("18.1.3".to_string()))
+        );
+        assert_eq!(
+            parse_cc_version("Apple clang version 15.0.0 (clang-1500.1.0.2.5)"),
+            (CompilerKind::Clang, Some("15.0.0".to_string()))
+        );
+
+        let banner = "Microsoft (R) C/C++ Optimizing Compiler Version 19.38.33130 for x64\r\nCopyright (C) Microsoft Corporation.  All rights reserved.\r\n";
+        assert_eq!(
+            parse_msvc_banner(banner),
+            (Some("19.38.33130".to_string()), Some("x64".to_string()))
+        );
+        assert_eq!(parse_msvc_banner(""), (None, None));
+    }
+
+    /// Test the fixes suggested for missing headers and libpython
+    #[test]
+    fn test_python_dev_problems() {
+        let info = python_info(false);
+        let fix = info.headers_fix(Distro::Debian);
+        assert!(fix.contains("--python-version 3.11"));
+        if cfg!(target_os = "linux") {
+            assert!(fix.starts_with("sudo apt-get install -y python3.11-dev"));
+            assert!(info
+                .headers_fix(Distro::Fedora)
+                .contains("python3.11-devel"));
+        }
+
+        let problems = info.link_problems();
+        assert_eq!(problems.len(), 1);
+        if !cfg!(windows) {
+            assert!(problems[0].contains("libpython3.11.so"));
+            let static_python = PythonInstallation {
+                shared: false,
+                ..python_info(true)
+            };
+            assert!(static_python.link_problems().is_empty());
+        }
+    }
+
+    /// Test that an interpreter without Python.h fails the check up front
+    #[test]
+    #[cfg(unix)]
+    fn test_check_python_dev_missing_headers() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let python = temp_dir.path().join("python3");
+        let json = serde_json::to_string(&python_info(false))?;
+        std::fs::write(&python, format!("#!/bin/sh\necho '{json}'\n"))?;
+        std::fs::set_permissions(&python, std::fs::Permissions::from_mode(0o755))?;
+
+        let error = check_python_dev(&python).unwrap_err().to_string();
+        assert!(error.contains("Python.h not found for Python 3.11.4"));
+        assert!(error.contains("/usr/include/python3.11"));
+        assert!(error.contains("--python-version 3.11"));
+
+        // A real interpreter answers the probe
+        if let Some(python) = common::find_python() {
+            let info = PythonInstallation::probe(&python)?;
+            assert_eq!(info.version.major, 3);
+        }
+        Ok(())
+    }
+
     /// Test that multiple calls return consistent results
     #[test]
     fn test_detection_consistency() {
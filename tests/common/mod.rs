@@ -0,0 +1,60 @@
+//! Helpers shared by the integration tests
+//!
+//! Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use std::env;
+use std::path::PathBuf;
+
+use py2pyd::{Implementation, InstallationSource, PythonInstallation, PythonVersion};
+
+/// A CPython `major.minor.patch` on Linux as the probe would report it, with
+/// headers but no libpython, for tests that do not run Python
+pub fn installation((major, minor, patch): (u32, u32, u32)) -> PythonInstallation {
+    PythonInstallation {
+        executable: PathBuf::from(format!("/usr/bin/python{major}.{minor}")),
+        version: PythonVersion {
+            major,
+            minor,
+            patch: Some(patch),
+        },
+        implementation: Implementation::CPython,
+        abiflags: String::new(),
+        free_threaded: false,
+        ext_suffix: format!(".cpython-{major}{minor}-x86_64-linux-gnu.so"),
+        soabi: Some(format!("cpython-{major}{minor}-x86_64-linux-gnu")),
+        include: PathBuf::from(format!("/usr/include/python{major}.{minor}")),
+        platinclude: PathBuf::from(format!("/usr/include/python{major}.{minor}")),
+        has_header: true,
+        libdir: PathBuf::from("/nonexistent/lib"),
+        ldlibrary: format!("libpython{major}.{minor}.so"),
+        shared: true,
+        platform_tag: "linux_x86_64".to_string(),
+        cython: false,
+        source: InstallationSource::Path,
+    }
+}
+
+/// A Python 3 interpreter on `PATH`, for tests that run Python.
+///
+/// Tests return early without one. The reason is printed, and on CI (`CI`
+/// set) the test fails instead, so it cannot pass without running.
+pub fn find_python() -> Option<PathBuf> {
+    let python = ["python3", "python"]
+        .iter()
+        .find_map(|name| which::which(name).ok());
+    if python.is_none() {
+        assert!(
+            env::var_os("CI").is_none(),
+            "No Python 3 interpreter on PATH on CI"
+        );
+        skip("no Python 3 interpreter on PATH");
+    }
+    python
+}
+
+/// Report that a test returns early, without checking anything, because of
+/// `reason`
+pub fn skip(reason: &str) {
+    eprintln!("skipping: {reason}");
+}
@@ -0,0 +1,305 @@
+//! Unit tests for the project configuration file
+//!
+//! These tests verify that `py2pyd.toml` and the `[tool.py2pyd]` table of
+//! `pyproject.toml` are discovered and parsed, and that their settings,
+//! including per-module overrides and named profiles, end up in the compile
+//! configuration.
+
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+use py2pyd::config::{find_profile, parse_directive, python_string, Directive, Profile};
+use py2pyd::{CompileConfig, EnvBackend, OutputLayout, ProjectConfig};
+
+const PYPROJECT: &str = r#"
+[project]
+name = "my-tool"
+
+[tool.py2pyd]
+python-version = "3.10"
+optimize-level = 3
+output-dir = "build"
+output-layout = "flat"
+include = ["src/**/*.py"]
+exclude = ["src/**/tests"]
+
+[tool.py2pyd.directives]
+language_level = 3
+boundscheck = false
+
+[[tool.py2pyd.overrides]]
+modules = ["src/tool/debug/*.py"]
+optimize-level = 0
+directives = { boundscheck = true }
+"#;
+
+fn write(dir: &Path, name: &str, contents: &str) -> Result<()> {
+    let path = dir.join(name);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    /// Test loading the [tool.py2pyd] table of pyproject.toml
+    #[test]
+    fn test_load_pyproject() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        write(temp_dir.path(), "pyproject.toml", PYPROJECT)?;
+
+        let config = ProjectConfig::load(&temp_dir.path().join("pyproject.toml"))?.unwrap();
+
+        assert_eq!(config.python_version.as_deref(), Some("3.10"));
+        assert_eq!(config.optimize_level, Some(3));
+        assert_eq!(config.output_layout, Some(OutputLayout::Flat));
+        assert_eq!(config.output_dir, Some(temp_dir.path().join("build")));
+        assert_eq!(config.directives["language_level"], Directive::Int(3));
+        assert_eq!(config.directives["boundscheck"], Directive::Bool(false));
+        assert_eq!(config.overrides.len(), 1);
+        assert_eq!(config.root(), Some(temp_dir.path()));
+        Ok(())
+    }
+
+    /// Test that pyproject.toml without a [tool.py2pyd] table is ignored
+    #[test]
+    fn test_discover() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let nested = temp_dir.path().join("src/tool");
+        fs::create_dir_all(&nested)?;
+
+        write(
+            temp_dir.path(),
+            "pyproject.toml",
+            "[project]\nname = \"x\"\n",
+        )?;
+        assert!(ProjectConfig::load(&temp_dir.path().join("pyproject.toml"))?.is_none());
+        assert!(ProjectConfig::discover(&nested)?.is_none());
+
+        write(temp_dir.path(), "pyproject.toml", PYPROJECT)?;
+        let config = ProjectConfig::discover(&nested)?.unwrap();
+        assert_eq!(config.optimize_level, Some(3));
+
+        // py2pyd.toml wins over pyproject.toml in the same directory
+        write(temp_dir.path(), "py2pyd.toml", "optimize-level = 1\n")?;
+        let config = ProjectConfig::discover(&nested)?.unwrap();
+        assert_eq!(config.optimize_level, Some(1));
+        assert!(config.source.unwrap().ends_with("py2pyd.toml"));
+        Ok(())
+    }
+
+    /// Test that misspelled keys are rejected
+    #[test]
+    fn test_unknown_key() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        write(temp_dir.path(), "py2pyd.toml", "optimise-level = 1\n")?;
+
+        let error = ProjectConfig::load(&temp_dir.path().join("py2pyd.toml")).unwrap_err();
+        assert!(format!("{error:#}").contains("optimise-level"));
+        Ok(())
+    }
+
+    /// Test that invalid globs are rejected with the key and pattern
+    #[test]
+    fn test_invalid_glob() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("py2pyd.toml");
+
+        write(
+            temp_dir.path(),
+            "py2pyd.toml",
+            "exclude = [\"src/[tests\"]\n",
+        )?;
+        let error = format!("{:#}", ProjectConfig::load(&path).unwrap_err());
+        assert!(error.contains("exclude") && error.contains("src/[tests"));
+
+        write(
+            temp_dir.path(),
+            "py2pyd.toml",
+            "[[overrides]]\nmodules = [\"src/***.py\"]\noptimize-level = 0\n",
+        )?;
+        let error = format!("{:#}", ProjectConfig::load(&path).unwrap_err());
+        assert!(error.contains("overrides.modules") && error.contains("src/***.py"));
+        Ok(())
+    }
+
+    /// Test build environment settings
+    #[test]
+    fn test_env_backend() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        write(
+            temp_dir.path(),
+            "py2pyd.toml",
+            "env-backend = \"venv\"\ninstall-uv = false\n",
+        )?;
+
+        let project = ProjectConfig::load(&temp_dir.path().join("py2pyd.toml"))?.unwrap();
+        let mut config = CompileConfig::default();
+        project.apply_to(&mut config);
+        assert_eq!(config.env_backend, EnvBackend::Venv);
+        assert!(!config.install_uv);
+
+        write(temp_dir.path(), "py2pyd.toml", "env-backend = \"pip\"\n")?;
+        assert!(ProjectConfig::load(&temp_dir.path().join("py2pyd.toml")).is_err());
+        Ok(())
+    }
+
+    /// Test file settings, include/exclude selection and per-module overrides
+    #[test]
+    fn test_apply_to() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        write(root, "pyproject.toml", PYPROJECT)?;
+        write(root, "src/tool/core.py", "")?;
+        write(root, "src/tool/debug/trace.py", "")?;
+        write(root, "src/tool/tests/test_core.py", "")?;
+        write(root, "scripts/release.py", "")?;
+
+        let project = ProjectConfig::load(&root.join("pyproject.toml"))?.unwrap();
+        let mut config = CompileConfig::default();
+        project.apply_to(&mut config);
+
+        assert_eq!(config.optimize_level, 3);
+        assert_eq!(config.python_version.as_deref(), Some("3.10"));
+        assert_eq!(config.output_layout, OutputLayout::Flat);
+
+        assert!(config.is_selected(&root.join("src/tool/core.py")));
+        assert!(config.is_selected(&root.join("src/tool/debug/trace.py")));
+        assert!(!config.is_selected(&root.join("src/tool/tests/test_core.py")));
+        assert!(!config.is_selected(&root.join("scripts/release.py")));
+
+        let core = config.for_module(&root.join("src/tool/core.py"));
+        assert_eq!(core.optimize_level, 3);
+        assert_eq!(
+            core.cython_directives["boundscheck"],
+            Directive::Bool(false)
+        );
+
+        let trace = config.for_module(&root.join("src/tool/debug/trace.py"));
+        assert_eq!(trace.optimize_level, 0);
+        assert_eq!(
+            trace.cython_directives["boundscheck"],
+            Directive::Bool(true)
+        );
+        assert_eq!(trace.cython_directives["language_level"], Directive::Int(3));
+        Ok(())
+    }
+
+    /// Test command line directive parsing and rendering for setup.py
+    #[test]
+    fn test_directives() -> Result<()> {
+        assert_eq!(
+            parse_directive("boundscheck=False")?,
+            ("boundscheck".to_string(), Directive::Bool(false))
+        );
+        assert_eq!(
+            parse_directive("language_level = 3")?,
+            ("language_level".to_string(), Directive::Int(3))
+        );
+        assert_eq!(
+            parse_directive("binding=cpdef")?.1,
+            Directive::String("cpdef".to_string())
+        );
+        assert!(parse_directive("boundscheck").is_err());
+
+        assert_eq!(Directive::Bool(true).to_python(), "True");
+        assert_eq!(Directive::Int(3).to_python(), "3");
+        assert_eq!(Directive::String("cpdef".into()).to_python(), "\"cpdef\"");
+        Ok(())
+    }
+
+    /// Test that strings written into setup.py are valid Python literals
+    #[test]
+    fn test_python_string() {
+        assert_eq!(python_string("-O2"), "\"-O2\"");
+        assert_eq!(
+            python_string(r#"-DNAME="it's" C:\sdk"#),
+            r#""-DNAME=\"it's\" C:\\sdk""#
+        );
+        // Rust would write `\u{7f}` and `\u{1b}`, which Python does not read
+        assert_eq!(
+            Directive::String("a\u{7f}b\u{1b}[0m\n".into()).to_python(),
+            r#""a\x7fb\x1b[0m\n""#
+        );
+        assert_eq!(
+            python_string("caf\u{e9} \u{1f40d}"),
+            "\"caf\u{e9} \u{1f40d}\""
+        );
+    }
+
+    /// Test the built-in profiles without a configuration file
+    #[test]
+    fn test_builtin_profiles() -> Result<()> {
+        let mut release = CompileConfig::default();
+        find_profile(None, "release")?.apply_to(&mut release);
+        assert_eq!(release.optimize_level, 3);
+        assert!(release.lto && release.strip);
+        assert!(!release.debug_symbols && !release.line_tracing);
+
+        let mut debug = CompileConfig::default();
+        find_profile(None, "debug")?.apply_to(&mut debug);
+        assert_eq!(debug.optimize_level, 0);
+        assert!(debug.debug_symbols && debug.line_tracing && debug.keep_docstrings);
+        // Line tracing does not work with the limited API
+        assert!(release.abi3() && !debug.abi3());
+
+        let mut dev = CompileConfig::default();
+        find_profile(None, "dev")?.apply_to(&mut dev);
+        assert!(!dev.lto && dev.keep_docstrings);
+
+        let error = find_profile(None, "fast").unwrap_err();
+        assert!(error.to_string().contains("debug, dev, release"));
+        Ok(())
+    }
+
+    /// Test profiles defined in the configuration file
+    #[test]
+    fn test_configured_profiles() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let config = r#"
+[profiles.release]
+strip = false
+output-dir = "dist"
+directives = { boundscheck = false }
+
+[profiles.ci]
+optimize-level = 2
+line-tracing = true
+"#;
+        write(temp_dir.path(), "py2pyd.toml", config)?;
+        let project = ProjectConfig::load(&temp_dir.path().join("py2pyd.toml"))?.unwrap();
+
+        // Settings not listed come from the built-in profile
+        let release = find_profile(Some(&project), "release")?;
+        assert_eq!(release.lto, Some(true));
+        assert_eq!(release.strip, Some(false));
+        assert_eq!(release.output_dir, Some(temp_dir.path().join("dist")));
+
+        let ci = find_profile(Some(&project), "ci")?;
+        assert_eq!(
+            ci,
+            Profile {
+                optimize_level: Some(2),
+                line_tracing: Some(true),
+                ..Default::default()
+            }
+        );
+
+        let mut config = CompileConfig::default();
+        project.apply_to(&mut config);
+        release.apply_to(&mut config);
+        assert_eq!(config.output_dir, Some(temp_dir.path().join("dist")));
+        assert_eq!(
+            config.cython_directives["boundscheck"],
+            Directive::Bool(false)
+        );
+
+        let error = find_profile(Some(&project), "fast").unwrap_err();
+        assert!(error.to_string().contains("ci, debug, dev, release"));
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+//! Unit tests for cross-compilation
+//!
+//! These tests verify target parsing, ELF header checks and finding the
+//! target's Python headers and sysconfig data.
+
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+use py2pyd::cross::configure;
+use py2pyd::elf::{self, ElfHeader};
+use py2pyd::{CompileConfig, CrossTarget, TargetPython};
+
+/// Start of a little-endian ELF file with the given class and machine
+fn elf_bytes(is_64: bool, machine: u16) -> Vec<u8> {
+    let mut bytes = vec![0; 64];
+    bytes[..4].copy_from_slice(b"\x7fELF");
+    bytes[4] = if is_64 { 2 } else { 1 };
+    bytes[5] = 1;
+    bytes[18..20].copy_from_slice(&machine.to_le_bytes());
+    bytes
+}
+
+/// Lay out a target Python 3.11 under `dir`
+fn target_sysroot(dir: &Path, sysconfigdata: bool) -> Result<PathBuf> {
+    let include = dir.join("include").join("python3.11");
+    fs::create_dir_all(&include)?;
+    fs::write(include.join("Python.h"), "")?;
+    if sysconfigdata {
+        let lib = dir.join("lib").join("python3.11");
+        fs::create_dir_all(&lib)?;
+        fs::write(
+            lib.join("_sysconfigdata__linux_aarch64-linux-gnu.py"),
+            "build_time_vars = {}\n",
+        )?;
+    }
+    Ok(include)
+}
+
+#[cfg(test)]
+mod cross_tests {
+    use super::*;
+
+    /// Test that targets are parsed and architectures normalized
+    #[test]
+    fn test_parse_target() -> Result<()> {
+        let target: CrossTarget = "aarch64-linux-gnu".parse()?;
+        assert_eq!(target.arch, "aarch64");
+        assert_eq!(target.abi, "gnu");
+
+        let target: CrossTarget = "arm64-unknown-linux-musl".parse()?;
+        assert_eq!(target.to_string(), "aarch64-linux-musl");
+
+        let target: CrossTarget = "armv7-linux-gnueabihf".parse()?;
+        assert_eq!(target.machine(), Some((elf::EM_ARM, false)));
+        assert_eq!(target.zig_triple(), "arm-linux-gnueabihf");
+        assert_eq!(
+            target.gcc_names(),
+            vec![
+                "arm-linux-gnueabihf-gcc".to_string(),
+                "arm-unknown-linux-gnueabihf-gcc".to_string()
+            ]
+        );
+
+        assert!("x86_64-pc-windows-msvc".parse::<CrossTarget>().is_err());
+        assert!("sparc-linux-gnu".parse::<CrossTarget>().is_err());
+        Ok(())
+    }
+
+    /// Test that ELF headers are parsed and checked against the target
+    #[test]
+    fn test_check_output() -> Result<()> {
+        let header = ElfHeader::parse(&elf_bytes(true, elf::EM_AARCH64))?;
+        assert!(header.is_64);
+        assert!(header.little_endian);
+        assert_eq!(header.machine, elf::EM_AARCH64);
+        assert!(ElfHeader::parse(b"MZ\x90\x00").is_err());
+
+        let temp_dir = TempDir::new()?;
+        let output = temp_dir.path().join("module.so");
+        fs::write(&output, elf_bytes(true, elf::EM_AARCH64))?;
+
+        let aarch64: CrossTarget = "aarch64-linux-gnu".parse()?;
+        aarch64.check_output(&output)?;
+
+        let x86_64: CrossTarget = "x86_64-linux-gnu".parse()?;
+        let error = x86_64.check_output(&output).unwrap_err().to_string();
+        assert!(error.contains("aarch64"));
+        assert!(error.contains("not x86_64-linux-gnu"));
+        Ok(())
+    }
+
+    /// Test that the target's headers and sysconfig data are found
+    #[test]
+    fn test_target_python_from_dir() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let include = target_sysroot(temp_dir.path(), true)?;
+
+        let python = TargetPython::from_dir(temp_dir.path())?;
+        assert_eq!(python.version, (3, 11));
+        assert_eq!(python.include, include);
+        assert!(python
+            .sysconfigdata
+            .unwrap()
+            .ends_with("_sysconfigdata__linux_aarch64-linux-gnu.py"));
+
+        let empty = TempDir::new()?;
+        assert!(TargetPython::from_dir(empty.path()).is_err());
+
+        fs::create_dir_all(temp_dir.path().join("include").join("python3.12"))?;
+        fs::write(
+            temp_dir
+                .path()
+                .join("include")
+                .join("python3.12")
+                .join("Python.h"),
+            "",
+        )?;
+        assert!(TargetPython::from_dir(temp_dir.path()).is_err());
+        Ok(())
+    }
+
+    /// Test that the Python version defaults to the target's and must match it
+    #[test]
+    fn test_configure() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        target_sysroot(temp_dir.path(), false)?;
+
+        let mut config = CompileConfig {
+            target: Some("aarch64-linux-gnu".to_string()),
+            target_python: Some(temp_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        configure(&mut config)?;
+        assert_eq!(config.python_version.as_deref(), Some("3.11"));
+
+        config.python_version = Some("3.12".to_string());
+        assert!(configure(&mut config).is_err());
+
+        let mut config = CompileConfig {
+            target: Some("aarch64-linux-gnu".to_string()),
+            ..Default::default()
+        };
+        assert!(configure(&mut config).is_err());
+
+        let mut config = CompileConfig {
+            target_python: Some(temp_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert!(configure(&mut config).is_err());
+        Ok(())
+    }
+}
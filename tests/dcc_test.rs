@@ -0,0 +1,220 @@
+//! Unit tests for DCC target profiles
+//!
+//! These tests verify resolving built-in and user profiles, and applying a
+//! DCC release's Python version and settings to a compile configuration,
+//! including from library entry points.
+
+use anyhow::Result;
+use std::borrow::Cow;
+
+use py2pyd::dcc::{host_platform, DccRegistry};
+use py2pyd::CompileConfig;
+
+/// Create an executable file at `root/relative`
+#[cfg(target_os = "linux")]
+fn touch_executable(root: &std::path::Path, relative: &str) -> Result<()> {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = root.join(relative);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(&path, "#!/bin/sh\n")?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod dcc_tests {
+    use super::*;
+
+    /// Test that built-in profiles resolve to the embedded Python and ABI
+    #[test]
+    fn test_builtin_profiles() -> Result<()> {
+        let registry = DccRegistry::builtin();
+        for id in [
+            "maya", "houdini", "blender", "nuke", "3dsmax", "unreal", "katana",
+        ] {
+            assert!(registry.dccs.contains_key(id), "no {id} profile");
+        }
+
+        let maya = registry.resolve("maya-2024")?;
+        assert_eq!(maya.python, "3.10");
+        assert_eq!(maya.abi(), "cp310");
+        assert_eq!(maya.vfx_platform, Some(2023));
+        assert_eq!(maya.linux.manylinux.as_deref(), Some("manylinux_2_28"));
+        assert_eq!(maya.to_string(), "Autodesk Maya 2024 (Python 3.10, cp310)");
+
+        // Settings for every release apply to each of them
+        let unreal = registry.resolve("Unreal-5.4")?;
+        assert_eq!(unreal.python, "3.11");
+        assert_eq!(unreal.linux.manylinux.as_deref(), Some("manylinux2014"));
+
+        let error = registry.resolve("maya-1999").unwrap_err().to_string();
+        assert!(error.contains("2024"));
+        let error = registry.resolve("photoshop-2024").unwrap_err().to_string();
+        assert!(error.contains("Unknown DCC 'photoshop'"));
+        assert!(registry.resolve("maya").is_err());
+        Ok(())
+    }
+
+    /// Test that user profiles add releases and DCCs to the built-in ones
+    #[test]
+    fn test_user_profiles() -> Result<()> {
+        let mut registry = DccRegistry::builtin();
+        registry.extend(DccRegistry::from_toml(
+            r#"
+            [maya.versions]
+            "2024" = { python = "3.10", abi = "cp310d" }
+            "2027" = { python = "3.12" }
+
+            [studio-tool]
+            name = "Studio Tool"
+            linux = { compile-args = ["-DSTUDIO"] }
+            versions = { "1.0" = { python = "3.11", linux = { compile-args = ["-O1"] } } }
+            "#,
+        )?);
+
+        let maya = registry.resolve("maya-2024")?;
+        assert_eq!(maya.name, "Autodesk Maya");
+        assert_eq!(maya.abi(), "cp310d");
+        assert_eq!(registry.resolve("maya-2027")?.python, "3.12");
+        assert_eq!(registry.resolve("maya-2025")?.python, "3.11");
+
+        let tool = registry.resolve("studio-tool-1.0")?;
+        assert_eq!(tool.id, "studio-tool");
+        assert_eq!(tool.linux.compile_args, vec!["-DSTUDIO", "-O1"]);
+
+        assert!(DccRegistry::from_toml("[maya]\nunknown = 1\n").is_err());
+        Ok(())
+    }
+
+    /// Test that a release sets the Python version and rejects another one
+    #[test]
+    fn test_apply_to_config() -> Result<()> {
+        let registry = DccRegistry::from_toml(
+            r#"
+            [tool]
+            name = "Tool"
+            linux = { manylinux = "manylinux2014", link-args = ["-lfoo"] }
+            windows = { link-args = ["foo.lib"] }
+            macos = { link-args = ["-lfoo"] }
+            versions = { "1" = { python = "3.9" } }
+
+            [windows-only]
+            name = "Windows Only"
+            platforms = ["windows"]
+            versions = { "1" = { python = "3.9" } }
+            "#,
+        )?;
+        let tool = registry.resolve("tool-1")?;
+
+        let mut config = CompileConfig::default();
+        tool.apply_to(&mut config)?;
+        assert_eq!(config.python_version.as_deref(), Some("3.9"));
+        assert_eq!(config.link_args.len(), 1);
+        if host_platform() == "linux" {
+            assert_eq!(config.manylinux.as_deref(), Some("manylinux2014"));
+        }
+
+        let mut config = CompileConfig {
+            python_version: Some("3.9.13".to_string()),
+            ..Default::default()
+        };
+        tool.apply_to(&mut config)?;
+
+        let mut config = CompileConfig {
+            python_version: Some("3.11".to_string()),
+            ..Default::default()
+        };
+        let error = tool.apply_to(&mut config).unwrap_err().to_string();
+        assert!(error.contains("embeds Python 3.9"));
+
+        // Cross builds produce Linux modules
+        let mut config = CompileConfig {
+            target: Some("aarch64-linux-gnu".to_string()),
+            ..Default::default()
+        };
+        let windows_only = registry.resolve("windows-only-1")?;
+        assert!(windows_only.apply_to(&mut config).is_err());
+        Ok(())
+    }
+
+    /// Test that library callers get the release's profile applied, once
+    #[test]
+    fn test_with_dcc_profile() -> Result<()> {
+        let config = CompileConfig {
+            target_dcc: Some("maya-2024".to_string()),
+            // Skips looking for an installed mayapy
+            python_path: Some("/nonexistent/mayapy".into()),
+            ..Default::default()
+        };
+        let resolved = config.with_dcc_profile()?;
+        assert_eq!(resolved.python_version.as_deref(), Some("3.10"));
+        let target = resolved.dcc_target.as_ref().unwrap();
+        assert_eq!(
+            (target.id.as_str(), target.version.as_str()),
+            ("maya", "2024")
+        );
+
+        let again = resolved.with_dcc_profile()?;
+        assert!(matches!(again, Cow::Borrowed(_)));
+        assert_eq!(again.compile_args, resolved.compile_args);
+        assert_eq!(again.link_args, resolved.link_args);
+
+        let plain = CompileConfig::default();
+        assert!(matches!(plain.with_dcc_profile()?, Cow::Borrowed(_)));
+
+        let unknown = CompileConfig {
+            target_dcc: Some("photoshop-2024".to_string()),
+            ..Default::default()
+        };
+        assert!(unknown.with_dcc_profile().is_err());
+        Ok(())
+    }
+
+    /// Test that bundled interpreters are found under extra install roots
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_find_candidates() -> Result<()> {
+        use py2pyd::dcc::find_candidates;
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        touch_executable(root, "maya2024/bin/mayapy")?;
+        touch_executable(root, "hfs20.5.332/bin/hython")?;
+        touch_executable(root, "blender-4.1.0-linux-x64/4.1/python/bin/python3.11")?;
+        touch_executable(
+            root,
+            "blender-4.1.0-linux-x64/4.1/python/bin/python3.11-config",
+        )?;
+        std::os::unix::fs::symlink(
+            "python3.11",
+            root.join("blender-4.1.0-linux-x64/4.1/python/bin/python3"),
+        )?;
+
+        let registry = DccRegistry::builtin();
+        let roots = vec![root.to_path_buf()];
+        let mut found: Vec<(String, String)> = find_candidates(&registry, &roots, None)
+            .into_iter()
+            .filter(|(_, path)| path.starts_with(root))
+            .map(|(id, path)| (id, path.strip_prefix(root).unwrap().display().to_string()))
+            .collect();
+        found.sort();
+        // python3.11 is the same file as python3, and -config is a script
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "blender".to_string(),
+                    "blender-4.1.0-linux-x64/4.1/python/bin/python3".to_string()
+                ),
+                ("houdini".to_string(), "hfs20.5.332/bin/hython".to_string()),
+                ("maya".to_string(), "maya2024/bin/mayapy".to_string()),
+            ]
+        );
+
+        let only_maya = find_candidates(&registry, &roots, Some("maya"));
+        assert!(only_maya.iter().all(|(id, _)| id == "maya"));
+        Ok(())
+    }
+}
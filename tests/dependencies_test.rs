@@ -0,0 +1,165 @@
+//! Unit tests for the runtime dependency check
+//!
+//! These tests verify that imports are classified for the target Python
+//! version and that imports which do not resolve in the target interpreter
+//! are reported with the importing file and line.
+
+mod common;
+
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+use py2pyd::dependencies::{check_dependencies, classify_import};
+use py2pyd::parser::{is_stdlib_module_in, ImportKind, ModuleGraph};
+use py2pyd::{PythonVersion, Severity};
+
+use common::find_python;
+
+fn graph_for(dir: &Path, source: &str) -> Result<ModuleGraph> {
+    fs::write(dir.join("tool.py"), source)?;
+    fs::write(dir.join("helpers.py"), "")?;
+    ModuleGraph::from_directory(dir)
+}
+
+#[cfg(test)]
+mod dependencies_tests {
+    use super::*;
+
+    /// Test that stdlib membership follows additions and removals
+    #[test]
+    fn test_stdlib_for_version() {
+        let py38 = PythonVersion::new(3, 8);
+        let py311 = PythonVersion::new(3, 11);
+        let py312 = PythonVersion::new(3, 12);
+
+        assert!(is_stdlib_module_in("os.path", &py38));
+        assert!(!is_stdlib_module_in("tomllib", &py38));
+        assert!(is_stdlib_module_in("tomllib", &py311));
+        assert!(is_stdlib_module_in("distutils.util", &py311));
+        assert!(!is_stdlib_module_in("distutils.util", &py312));
+        assert!(!is_stdlib_module_in("numpy", &py311));
+    }
+
+    /// Test classification of internal, stdlib and third-party imports
+    #[test]
+    fn test_classify_imports() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let graph = graph_for(
+            temp_dir.path(),
+            "import helpers\nimport distutils.util\nimport yaml\n",
+        )?;
+
+        let kinds = |version: PythonVersion| -> Vec<ImportKind> {
+            graph.modules["tool"]
+                .imports
+                .iter()
+                .map(|edge| classify_import(edge, &version))
+                .collect()
+        };
+
+        assert_eq!(
+            kinds(PythonVersion::new(3, 11)),
+            vec![
+                ImportKind::Internal,
+                ImportKind::Stdlib,
+                ImportKind::ThirdParty
+            ]
+        );
+        assert_eq!(
+            kinds(PythonVersion::new(3, 12)),
+            vec![
+                ImportKind::Internal,
+                ImportKind::ThirdParty,
+                ImportKind::ThirdParty
+            ]
+        );
+        Ok(())
+    }
+
+    /// Test that guarded, deferred and type-checking imports are told apart
+    #[test]
+    fn test_import_context() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let source = r#"
+from typing import TYPE_CHECKING
+
+try:
+    import ujson as json
+except ImportError:
+    import json
+
+if TYPE_CHECKING:
+    import pandas
+
+def open_ui():
+    from PySide2 import QtWidgets
+"#;
+        let graph = graph_for(temp_dir.path(), source)?;
+
+        let imports: Vec<_> = graph.modules["tool"]
+            .imports
+            .iter()
+            .map(|edge| (edge.module.as_str(), edge.guarded, edge.deferred))
+            .collect();
+        assert_eq!(
+            imports,
+            vec![
+                ("typing", false, false),
+                ("ujson", true, false),
+                ("json", false, false),
+                ("PySide2", false, true),
+            ]
+        );
+        Ok(())
+    }
+
+    /// Test the missing-dependency report against a real interpreter
+    #[test]
+    fn test_check_dependencies() -> Result<()> {
+        let Some(python) = find_python() else {
+            return Ok(());
+        };
+
+        let temp_dir = TempDir::new()?;
+        let source = r#"import json
+import helpers
+import py2pyd_missing_package.sub
+
+try:
+    import py2pyd_optional_package
+except ImportError:
+    py2pyd_optional_package = None
+
+def later():
+    import py2pyd_missing_package
+"#;
+        let graph = graph_for(temp_dir.path(), source)?;
+
+        let report = check_dependencies(&graph, &python)?;
+
+        assert!(!report.is_ok());
+        assert_eq!(report.packages.get("py2pyd_missing_package"), Some(&false));
+        assert_eq!(report.packages.get("py2pyd_optional_package"), Some(&false));
+        assert!(!report.packages.contains_key("json"));
+
+        let missing: Vec<_> = report
+            .missing
+            .iter()
+            .map(|d| (d.line, d.severity, d.path.file_name().unwrap().to_owned()))
+            .collect();
+        assert_eq!(
+            missing,
+            vec![
+                (3, Severity::Error, "tool.py".into()),
+                (6, Severity::Warning, "tool.py".into()),
+                (11, Severity::Error, "tool.py".into()),
+            ]
+        );
+        assert!(report.missing[0]
+            .message
+            .contains("`py2pyd_missing_package.sub`"));
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+//! Unit tests for environment diagnostics
+//!
+//! These tests verify distribution detection and the install commands
+//! suggested for it, and the shape of the checks `doctor` reports.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::path::PathBuf;
+
+use py2pyd::{run_doctor, CheckStatus, CompileConfig, Distro, Message};
+
+#[cfg(test)]
+mod doctor_tests {
+    use super::*;
+
+    /// Test that distributions are recognised by ID and ID_LIKE
+    #[test]
+    fn test_distro_from_os_release() {
+        let ubuntu = "NAME=\"Ubuntu\"\nID=ubuntu\nID_LIKE=debian\nVERSION_ID=\"24.04\"\n";
+        assert_eq!(Distro::from_os_release(ubuntu), Distro::Debian);
+
+        let rocky = "ID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\n";
+        assert_eq!(Distro::from_os_release(rocky), Distro::Fedora);
+
+        let mint = "ID=linuxmint\nID_LIKE=\"ubuntu debian\"\n";
+        assert_eq!(Distro::from_os_release(mint), Distro::Debian);
+
+        assert_eq!(Distro::from_os_release("ID=alpine\n"), Distro::Alpine);
+        assert_eq!(
+            Distro::from_os_release("ID=\"opensuse-leap\"\n"),
+            Distro::Suse
+        );
+        assert_eq!(Distro::from_os_release("ID=nixos\n"), Distro::Unknown);
+    }
+
+    /// Test the packages and commands suggested for each distribution
+    #[test]
+    fn test_distro_packages() {
+        let debian = Distro::Debian;
+        assert_eq!(
+            debian.python_dev_package(Some((3, 11))).as_deref(),
+            Some("python3.11-dev")
+        );
+        assert_eq!(
+            debian
+                .install_command(debian.compiler_packages())
+                .as_deref(),
+            Some("sudo apt-get install -y build-essential")
+        );
+
+        assert_eq!(
+            Distro::Fedora.python_dev_package(None).as_deref(),
+            Some("python3-devel")
+        );
+        assert_eq!(
+            Distro::Alpine.install_command(&["python3-dev"]).as_deref(),
+            Some("apk add python3-dev")
+        );
+        assert_eq!(Distro::Unknown.python_dev_package(None), None);
+        assert_eq!(Distro::Unknown.install_command(&["gcc"]), None);
+    }
+
+    /// Test that a missing interpreter fails and the rest is still checked
+    #[test]
+    fn test_run_doctor() -> Result<()> {
+        let config = CompileConfig {
+            python_path: Some(PathBuf::from("/nonexistent/bin/python")),
+            ..Default::default()
+        };
+        let checks = run_doctor(&config, &[]);
+
+        let names: Vec<&str> = checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names[0], "uv");
+        assert!(names.contains(&"compiler"));
+        assert!(names.contains(&"disk temp"));
+        assert!(names.contains(&"write temp"));
+
+        let python = checks
+            .iter()
+            .find(|c| c.name == "python /nonexistent/bin/python")
+            .unwrap();
+        assert_eq!(python.status, CheckStatus::Fail);
+
+        let json: Value = serde_json::from_str(&Message::Doctor(python.clone()).to_json()?)?;
+        assert_eq!(json["reason"], "doctor");
+        assert_eq!(json["status"], "fail");
+        assert!(json.get("fix").is_none());
+        Ok(())
+    }
+}
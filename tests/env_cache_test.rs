@@ -0,0 +1,217 @@
+//! Unit tests for the build environment cache
+//!
+//! These tests verify environment keys, reusing and rebuilding cached
+//! environments, and listing, removing and pruning them. Environments are
+//! package directories for the `python3` on `PATH`, so no venv is created.
+
+mod common;
+
+use anyhow::{anyhow, Result};
+use std::cell::Cell;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tempfile::TempDir;
+
+use py2pyd::{EnvBackend, EnvCache, EnvKind, EnvSpec};
+
+/// A spec for a package directory of `interpreter`
+fn site_spec(interpreter: &str, packages: &[&str]) -> EnvSpec {
+    let packages: Vec<String> = packages.iter().map(|p| p.to_string()).collect();
+    EnvSpec::new(
+        EnvKind::Site,
+        interpreter,
+        None,
+        EnvBackend::Venv,
+        &packages,
+    )
+}
+
+/// The real Python 3 on PATH, if there is one
+fn python3() -> Option<String> {
+    let python = common::find_python()?;
+    Some(fs::canonicalize(python).ok()?.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod env_cache_tests {
+    use super::*;
+
+    /// Test that keys ignore package order but not the interpreter or version
+    #[test]
+    fn test_env_key() {
+        let spec = site_spec("/usr/bin/python3", &["cython>=3", "setuptools"]);
+        assert_eq!(
+            spec.key(),
+            site_spec(
+                "/usr/bin/python3",
+                &["setuptools", "cython>=3", "setuptools"]
+            )
+            .key()
+        );
+        assert_eq!(spec.key().len(), 16);
+        assert_ne!(
+            spec.key(),
+            site_spec("/usr/bin/python3.11", &["cython>=3", "setuptools"]).key()
+        );
+        assert_ne!(
+            spec.key(),
+            site_spec("/usr/bin/python3", &["cython>=3"]).key()
+        );
+
+        let versioned = EnvSpec {
+            python_version: Some("3.11".to_string()),
+            ..spec.clone()
+        };
+        assert_ne!(spec.key(), versioned.key());
+        let venv = EnvSpec {
+            kind: EnvKind::Venv,
+            ..spec.clone()
+        };
+        assert_ne!(spec.key(), venv.key());
+    }
+
+    /// Test that an environment is built once and then reused
+    #[test]
+    fn test_reuse() -> Result<()> {
+        let Some(python) = python3() else {
+            return Ok(());
+        };
+        let temp_dir = TempDir::new()?;
+        let cache = EnvCache::new(temp_dir.path());
+        let spec = site_spec(&python, &["cython"]);
+
+        let builds = Cell::new(0);
+        let build = |dir: &std::path::Path| {
+            builds.set(builds.get() + 1);
+            fs::create_dir_all(dir)?;
+            fs::write(dir.join("marker"), "built")?;
+            Ok(())
+        };
+        let first = cache.get_or_create(&spec, build)?;
+        assert_eq!(first.path, temp_dir.path().join(spec.key()));
+        assert_eq!(fs::read_to_string(first.env_dir().join("marker"))?, "built");
+        assert_eq!(first.python(), PathBuf::from(&python));
+
+        let second = cache.get_or_create(&spec, build)?;
+        assert_eq!(builds.get(), 1);
+        assert_eq!(second.path, first.path);
+        assert!(second.last_used >= first.last_used);
+        Ok(())
+    }
+
+    /// Test that a failed build leaves nothing behind
+    #[test]
+    fn test_failed_build() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let cache = EnvCache::new(temp_dir.path());
+        let spec = site_spec("/usr/bin/python3", &[]);
+
+        let result = cache.get_or_create(&spec, |dir| {
+            fs::create_dir_all(dir)?;
+            Err(anyhow!("no network"))
+        });
+        assert!(result.is_err());
+        assert!(cache.list()?.is_empty());
+        assert!(!fs::read_dir(temp_dir.path())?
+            .any(|entry| entry.map(|entry| entry.path().is_dir()).unwrap_or_default()));
+        Ok(())
+    }
+
+    /// Test that an environment whose interpreter is gone is not reused
+    #[test]
+    fn test_invalid_env_rebuilt() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let cache = EnvCache::new(temp_dir.path().join("envs"));
+        let python = temp_dir.path().join("python3");
+        let spec = site_spec(&python.to_string_lossy(), &[]);
+
+        let env = cache.get_or_create(&spec, |dir| Ok(fs::create_dir_all(dir)?))?;
+        assert!(env.path.is_dir());
+        assert!(env.validate().is_err());
+
+        let env = env.release();
+        assert_eq!(cache.lookup(&spec)?, None);
+        assert!(!env.path.exists());
+        Ok(())
+    }
+
+    /// Test listing environments and removing them by key prefix
+    #[test]
+    fn test_list_and_remove() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let cache = EnvCache::new(temp_dir.path());
+        assert!(cache.list()?.is_empty());
+
+        let first = cache.get_or_create(&site_spec("/a/python3", &[]), |dir| {
+            Ok(fs::create_dir_all(dir)?)
+        })?;
+        let second = cache.get_or_create(&site_spec("/b/python3", &[]), |dir| {
+            Ok(fs::create_dir_all(dir)?)
+        })?;
+        // Not removable while a build uses it
+        assert!(cache.remove(&first.key).is_err());
+        let (first, second) = (first.release(), second.release());
+        // Unfinished, e.g. still being built by another process
+        fs::create_dir(temp_dir.path().join(".tmp-building"))?;
+
+        let keys: Vec<String> = cache.list()?.into_iter().map(|env| env.key).collect();
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&first.key) && keys.contains(&second.key));
+
+        assert!(cache.remove("").is_err());
+        assert!(cache.remove("not-a-key").is_err());
+        assert_eq!(cache.remove(&first.key[..8])?.key, first.key);
+        assert!(!first.path.exists());
+        assert_eq!(cache.list()?.len(), 1);
+        Ok(())
+    }
+
+    /// Test pruning broken and unused environments, but not ones being built
+    #[test]
+    fn test_prune() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let cache = EnvCache::new(temp_dir.path());
+        let building = temp_dir.path().join(".tmp-building");
+        fs::create_dir(&building)?;
+
+        let broken = cache.get_or_create(&site_spec("/missing/python3", &[]), |dir| {
+            Ok(fs::create_dir_all(dir)?)
+        })?;
+        // Left alone while in use
+        assert!(cache.prune(None)?.is_empty());
+        let broken = broken.release();
+        assert_eq!(cache.prune(None)?, vec![broken.path.clone()]);
+        assert!(building.exists());
+
+        if let Some(python) = python3() {
+            let env = cache
+                .get_or_create(&site_spec(&python, &[]), |dir| Ok(fs::create_dir_all(dir)?))?;
+            let env = env.release();
+            assert!(cache.prune(Some(Duration::from_secs(86400)))?.is_empty());
+            assert_eq!(cache.prune(Some(Duration::ZERO))?, vec![env.path]);
+        }
+        Ok(())
+    }
+
+    /// Test that an environment is only removed after its last user is done
+    #[test]
+    fn test_remove_if_unused() -> Result<()> {
+        let Some(python) = python3() else {
+            return Ok(());
+        };
+        let temp_dir = TempDir::new()?;
+        let cache = EnvCache::new(temp_dir.path());
+        let spec = site_spec(&python, &[]);
+
+        let first = cache.get_or_create(&spec, |dir| Ok(fs::create_dir_all(dir)?))?;
+        let second = cache.get_or_create(&spec, |_| Err(anyhow!("built twice")))?;
+        let path = first.path.clone();
+
+        assert!(!cache.remove_if_unused(first)?);
+        assert!(path.exists());
+        assert!(cache.remove_if_unused(second)?);
+        assert!(!path.exists());
+        Ok(())
+    }
+}
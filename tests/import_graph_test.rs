@@ -0,0 +1,159 @@
+//! Unit tests for the import dependency graph
+//!
+//! These tests verify that imports are resolved across a project, including
+//! relative imports, and that build order and import cycles are derived
+//! from the resulting graph.
+
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+use py2pyd::parser::{ImportKind, ModuleGraph};
+
+/// Write `files` (relative path, contents) below `dir`
+fn write_project(dir: &Path, files: &[(&str, &str)]) -> Result<()> {
+    for (path, contents) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, contents)?;
+    }
+    Ok(())
+}
+
+fn imports(graph: &ModuleGraph, module: &str) -> Vec<(String, ImportKind)> {
+    graph.modules[module]
+        .imports
+        .iter()
+        .map(|edge| (edge.module.clone(), edge.kind))
+        .collect()
+}
+
+#[cfg(test)]
+mod import_graph_tests {
+    use super::*;
+
+    /// Test absolute and relative imports are resolved and classified
+    #[test]
+    fn test_resolve_imports() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        write_project(
+            temp_dir.path(),
+            &[
+                ("tool/__init__.py", "from .ui import window\n"),
+                ("tool/ui/__init__.py", "from . import window\n"),
+                (
+                    "tool/ui/window.py",
+                    "import os.path\nimport numpy as np\nfrom ..core import model\nfrom tool.core.model import Model\n",
+                ),
+                ("tool/core/__init__.py", ""),
+                ("tool/core/model.py", "import json\nfrom PySide2 import QtCore\n"),
+            ],
+        )?;
+
+        let graph = ModuleGraph::from_directory(&temp_dir.path().join("tool"))?;
+
+        assert_eq!(
+            graph.modules.keys().collect::<Vec<_>>(),
+            vec![
+                "tool",
+                "tool.core",
+                "tool.core.model",
+                "tool.ui",
+                "tool.ui.window"
+            ]
+        );
+        assert!(graph.modules["tool.ui"].is_package);
+        assert_eq!(
+            imports(&graph, "tool.ui.window"),
+            vec![
+                ("os.path".to_string(), ImportKind::Stdlib),
+                ("numpy".to_string(), ImportKind::ThirdParty),
+                ("tool.core.model".to_string(), ImportKind::Internal),
+            ]
+        );
+        assert_eq!(
+            imports(&graph, "tool.ui"),
+            vec![("tool.ui.window".to_string(), ImportKind::Internal)]
+        );
+        assert_eq!(graph.modules["tool.ui.window"].imports[2].line, 3);
+        Ok(())
+    }
+
+    /// Test that every module comes after the modules it imports
+    #[test]
+    fn test_build_order() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        write_project(
+            temp_dir.path(),
+            &[
+                ("app.py", "import services\n"),
+                ("services.py", "import models\nimport util\n"),
+                ("models.py", "import util\n"),
+                ("util.py", "import os\n"),
+            ],
+        )?;
+
+        let graph = ModuleGraph::from_directory(temp_dir.path())?;
+
+        assert_eq!(
+            graph.build_order(),
+            vec!["util", "models", "services", "app"]
+        );
+        assert!(graph.cycles().is_empty());
+        Ok(())
+    }
+
+    /// Test that circular imports are detected, ignoring function-level imports
+    #[test]
+    fn test_cycles() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        write_project(
+            temp_dir.path(),
+            &[
+                ("a.py", "import b\n"),
+                ("b.py", "from c import thing\n"),
+                ("c.py", "import a\n"),
+                ("d.py", "import a\n\ndef lazy():\n    import d2\n"),
+                ("d2.py", "import d\n"),
+            ],
+        )?;
+
+        let graph = ModuleGraph::from_directory(temp_dir.path())?;
+
+        assert_eq!(graph.cycles(), vec![vec!["a", "b", "c"]]);
+
+        let order = graph.build_order();
+        let position = |name: &str| order.iter().position(|m| *m == name).unwrap();
+        assert!(position("a") < position("d"));
+        assert!(position("d") < position("d2"));
+        Ok(())
+    }
+
+    /// Test JSON and DOT rendering
+    #[test]
+    fn test_output_formats() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        write_project(
+            temp_dir.path(),
+            &[
+                ("a.py", "import b\nimport requests\n"),
+                ("b.py", "import a\n"),
+            ],
+        )?;
+
+        let graph = ModuleGraph::from_directory(temp_dir.path())?;
+
+        let json: serde_json::Value = serde_json::from_str(&graph.to_json()?)?;
+        assert_eq!(json["modules"].as_array().unwrap().len(), 2);
+        assert_eq!(json["cycles"], serde_json::json!([["a", "b"]]));
+        assert_eq!(json["modules"][0]["imports"][1]["kind"], "third-party");
+
+        let dot = graph.to_dot(false);
+        assert!(dot.starts_with("digraph imports {"));
+        assert!(dot.contains("\"a\" -> \"b\" [color=red];"));
+        assert!(!dot.contains("requests"));
+        assert!(graph.to_dot(true).contains("\"a\" -> \"requests\";"));
+        Ok(())
+    }
+}
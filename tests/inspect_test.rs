@@ -0,0 +1,215 @@
+//! Unit tests for inspecting compiled extension modules
+//!
+//! These tests verify reading init symbols, ABI, architecture, needed
+//! libraries and build metadata from PE, Mach-O and ELF files.
+
+use anyhow::Result;
+use std::fs;
+use tempfile::TempDir;
+
+use py2pyd::{inspect, BinaryFormat, BuildInfo};
+
+/// A PE32+ DLL exporting `PyInit_fast` and importing `python3.dll`, with
+/// everything in one section at file offset 0x200, RVA 0x1000
+fn pe_module() -> Vec<u8> {
+    let rva = |offset: u32| 0x1000 + offset;
+    let mut bytes = vec![0u8; 0x200];
+    bytes[..2].copy_from_slice(b"MZ");
+    bytes[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+    bytes[0x40..0x44].copy_from_slice(b"PE\0\0");
+
+    let coff = 0x44;
+    bytes[coff..coff + 2].copy_from_slice(&0x8664u16.to_le_bytes());
+    bytes[coff + 2..coff + 4].copy_from_slice(&1u16.to_le_bytes());
+    bytes[coff + 16..coff + 18].copy_from_slice(&240u16.to_le_bytes());
+
+    let optional = coff + 20;
+    bytes[optional..optional + 2].copy_from_slice(&0x20bu16.to_le_bytes());
+    let directories = optional + 112;
+    // Export table, then import table
+    for (index, (address, size)) in [(rva(0), 40u32), (rva(128), 40)].iter().enumerate() {
+        let entry = directories + index * 8;
+        bytes[entry..entry + 4].copy_from_slice(&address.to_le_bytes());
+        bytes[entry + 4..entry + 8].copy_from_slice(&size.to_le_bytes());
+    }
+
+    let section = optional + 240;
+    bytes[section..section + 6].copy_from_slice(b".rdata");
+    bytes[section + 8..section + 12].copy_from_slice(&256u32.to_le_bytes());
+    bytes[section + 12..section + 16].copy_from_slice(&rva(0).to_le_bytes());
+    bytes[section + 16..section + 20].copy_from_slice(&256u32.to_le_bytes());
+    bytes[section + 20..section + 24].copy_from_slice(&0x200u32.to_le_bytes());
+
+    let mut data = vec![0u8; 256];
+    data[24..28].copy_from_slice(&1u32.to_le_bytes());
+    data[32..36].copy_from_slice(&rva(64).to_le_bytes());
+    data[64..68].copy_from_slice(&rva(80).to_le_bytes());
+    data[80..92].copy_from_slice(b"PyInit_fast\0");
+    data[140..144].copy_from_slice(&rva(192).to_le_bytes());
+    data[192..204].copy_from_slice(b"python3.dll\0");
+    bytes.extend_from_slice(&data);
+    bytes
+}
+
+/// A 64-bit x86-64 Mach-O bundle exporting `_PyInit_fast` and linking libSystem
+fn macho_module() -> Vec<u8> {
+    let mut bytes = vec![0u8; 142];
+    let mut put = |offset: usize, value: u32| {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+    put(0, 0xfeed_facf);
+    put(4, 0x0100_0007);
+    put(12, 8);
+    put(16, 2);
+    put(20, 80);
+
+    // LC_LOAD_DYLIB with the name at offset 24
+    put(32, 0xc);
+    put(36, 56);
+    put(40, 24);
+    // LC_SYMTAB: one symbol at 112, strings at 128
+    put(88, 0x2);
+    put(92, 24);
+    put(96, 112);
+    put(100, 1);
+    put(104, 128);
+    put(108, 14);
+    put(112, 1);
+
+    bytes[56..83].copy_from_slice(b"/usr/lib/libSystem.B.dylib\0");
+    // N_SECT | N_EXT, section 1
+    bytes[116] = 0x0f;
+    bytes[117] = 1;
+    bytes[128..142].copy_from_slice(b"\0_PyInit_fast\0");
+    bytes
+}
+
+/// A universal binary holding `slices`, each with its CPU type
+fn fat_binary(slices: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut bytes = vec![0xca, 0xfe, 0xba, 0xbe];
+    bytes.extend_from_slice(&(slices.len() as u32).to_be_bytes());
+    let mut offset = 8 + 20 * slices.len();
+    for (cpu, slice) in slices {
+        for value in [*cpu, 0, offset as u32, slice.len() as u32, 0] {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        offset += slice.len();
+    }
+    for (_, slice) in slices {
+        bytes.extend_from_slice(slice);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod inspect_tests {
+    use super::*;
+
+    /// Test that build metadata is embedded as C and found again
+    #[test]
+    fn test_build_info_round_trip() {
+        let info = BuildInfo {
+            py2pyd: "0.1.5".to_string(),
+            module: "fast".to_string(),
+            python_version: Some("3.11".to_string()),
+            abi3: true,
+            optimize_level: 3,
+            ..Default::default()
+        };
+        let source = info.to_c_source().unwrap();
+        assert!(source.contains("const char py2pyd_build_info[]"));
+        assert!(source.contains("\\\"module\\\":\\\"fast\\\""));
+
+        let json = serde_json::to_string(&info).unwrap();
+        let binary = [
+            b"\x7fELF junk ".as_slice(),
+            b"PY2PYD_BUILD_INFO:",
+            json.as_bytes(),
+            b"\0more junk",
+        ]
+        .concat();
+        assert_eq!(BuildInfo::find(&binary), Some(info));
+        assert_eq!(BuildInfo::find(b"no metadata here"), None);
+    }
+
+    /// Test inspecting a Windows module, and the warning for a renamed file
+    #[test]
+    fn test_inspect_pe() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("slow.pyd");
+        fs::write(&path, pe_module())?;
+
+        let info = inspect(&path)?;
+        assert_eq!(info.format, BinaryFormat::Pe);
+        assert_eq!(info.architectures, vec!["x86_64"]);
+        assert_eq!(info.init_symbols, vec!["fast"]);
+        assert_eq!(info.needed, vec!["python3.dll"]);
+        assert_eq!(info.python_library.as_deref(), Some("python3.dll"));
+        assert_eq!(info.abi3, Some(true));
+        assert!(info.stripped);
+        assert!(info.build_info.is_none());
+        assert_eq!(info.warnings.len(), 1);
+        assert!(info.warnings[0].contains("rename it to fast.pyd"));
+        Ok(())
+    }
+
+    /// Test inspecting a macOS module named after its init symbol
+    #[test]
+    fn test_inspect_macho() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("fast.cpython-311-darwin.so");
+        fs::write(&path, macho_module())?;
+
+        let info = inspect(&path)?;
+        assert_eq!(info.format, BinaryFormat::MachO);
+        assert_eq!(info.architectures, vec!["x86_64"]);
+        assert_eq!(info.init_symbols, vec!["fast"]);
+        assert_eq!(info.needed, vec!["/usr/lib/libSystem.B.dylib"]);
+        assert_eq!(info.python_library, None);
+        assert_eq!(info.abi3, Some(false));
+        assert!(info.stripped);
+        assert!(info.warnings.is_empty());
+        Ok(())
+    }
+
+    /// Test universal binaries, and that malformed slices are errors rather
+    /// than panics
+    #[test]
+    fn test_inspect_universal() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("fast.cpython-311-darwin.so");
+
+        fs::write(
+            &path,
+            fat_binary(&[(0x0100_0007, macho_module()), (0x0100_000c, macho_module())]),
+        )?;
+        let info = inspect(&path)?;
+        assert_eq!(info.architectures.len(), 2);
+        assert_eq!(info.architectures[0], "x86_64");
+        assert_eq!(info.init_symbols, vec!["fast"]);
+
+        for slice in [vec![], vec![0xfe, 0xed], fat_binary(&[(7, macho_module())])] {
+            fs::write(&path, fat_binary(&[(0x0100_0007, slice)]))?;
+            assert!(inspect(&path).is_err());
+        }
+        Ok(())
+    }
+
+    /// Test that an ELF file without init symbols is flagged, and that
+    /// other files are rejected
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_inspect_elf() -> Result<()> {
+        let info = inspect(std::path::Path::new("/proc/self/exe"))?;
+        assert_eq!(info.format, BinaryFormat::Elf);
+        assert!(info.init_symbols.is_empty());
+        assert!(info.manylinux.is_some());
+        assert!(info.warnings[0].contains("not a Python extension module"));
+
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("module.so");
+        fs::write(&path, "print('not a binary')")?;
+        assert!(inspect(&path).is_err());
+        Ok(())
+    }
+}
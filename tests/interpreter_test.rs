@@ -0,0 +1,221 @@
+//! Unit tests for interpreter discovery and introspection
+//!
+//! These tests verify parsing and matching interpreter requests, deriving ABI
+//! tags from sysconfig values, resolving conda environments and pyenv versions,
+//! and probing the interpreter on `PATH`.
+
+mod common;
+
+use anyhow::Result;
+use std::path::PathBuf;
+
+use py2pyd::interpreter::{self, find_candidates, pyenv_version};
+use py2pyd::{
+    find_conda_env, find_installation, CompileConfig, Implementation, InstallationSource,
+    PythonInstallation, PythonRequest,
+};
+
+use common::installation;
+
+#[cfg(test)]
+mod interpreter_tests {
+    use super::*;
+
+    /// Test parsing versions, implementations and free-threaded requests
+    #[test]
+    fn test_parse_request() -> Result<()> {
+        assert_eq!("any".parse::<PythonRequest>()?, PythonRequest::Any);
+        assert_eq!(
+            "3.11".parse::<PythonRequest>()?,
+            PythonRequest::Version {
+                major: 3,
+                minor: Some(11),
+                patch: None,
+                implementation: None,
+                free_threaded: false,
+            }
+        );
+        assert_eq!(
+            "3.13t".parse::<PythonRequest>()?,
+            PythonRequest::Version {
+                major: 3,
+                minor: Some(13),
+                patch: None,
+                implementation: None,
+                free_threaded: true,
+            }
+        );
+        assert_eq!(
+            "pypy3.10".parse::<PythonRequest>()?,
+            PythonRequest::Version {
+                major: 3,
+                minor: Some(10),
+                patch: None,
+                implementation: Some(Implementation::PyPy),
+                free_threaded: false,
+            }
+        );
+        assert_eq!(
+            "/opt/python/bin/python3".parse::<PythonRequest>()?,
+            PythonRequest::Path(PathBuf::from("/opt/python/bin/python3"))
+        );
+        assert!("3.x".parse::<PythonRequest>().is_err());
+        assert!("3.11.4.1".parse::<PythonRequest>().is_err());
+        Ok(())
+    }
+
+    /// Test that versions match exactly rather than as substrings
+    #[test]
+    fn test_request_matches_version() -> Result<()> {
+        let python_3_1 = installation((3, 1, 4));
+        let python_3_11 = installation((3, 11, 4));
+
+        let request: PythonRequest = "3.1".parse()?;
+        assert!(request.matches(&python_3_1));
+        assert!(!request.matches(&python_3_11));
+
+        assert!("3".parse::<PythonRequest>()?.matches(&python_3_11));
+        assert!("3.11.4".parse::<PythonRequest>()?.matches(&python_3_11));
+        assert!(!"3.11.5".parse::<PythonRequest>()?.matches(&python_3_11));
+        assert!(!"pypy3.11".parse::<PythonRequest>()?.matches(&python_3_11));
+        assert!(!"3.11t".parse::<PythonRequest>()?.matches(&python_3_11));
+        assert!(PythonRequest::Any.matches(&python_3_11));
+        Ok(())
+    }
+
+    /// Test that a path takes precedence over a version
+    #[test]
+    fn test_request_from_config() -> Result<()> {
+        let path = PathBuf::from("/usr/bin/python3");
+        assert_eq!(
+            PythonRequest::from_config(Some(&path), Some("3.11"))?,
+            PythonRequest::Path(path)
+        );
+        assert_eq!(PythonRequest::from_config(None, None)?, PythonRequest::Any);
+        assert!(matches!(
+            PythonRequest::from_config(None, Some("3.12"))?,
+            PythonRequest::Version {
+                minor: Some(12),
+                ..
+            }
+        ));
+        Ok(())
+    }
+
+    /// Test deriving ABI tags from SOABI, EXT_SUFFIX and the version
+    #[test]
+    fn test_abi_tag() {
+        let python = installation((3, 10, 12));
+        assert_eq!(python.abi_tag(), "cp310");
+
+        let debug = PythonInstallation {
+            soabi: Some("cpython-311d-darwin".to_string()),
+            ..installation((3, 11, 4))
+        };
+        assert_eq!(debug.abi_tag(), "cp311d");
+
+        let windows = PythonInstallation {
+            soabi: None,
+            ext_suffix: ".cp39-win_amd64.pyd".to_string(),
+            ..installation((3, 9, 13))
+        };
+        assert_eq!(windows.abi_tag(), "cp39");
+
+        let unknown = PythonInstallation {
+            soabi: None,
+            ext_suffix: ".so".to_string(),
+            ..installation((3, 7, 17))
+        };
+        assert_eq!(unknown.abi_tag(), "cp37");
+
+        let free_threaded = PythonInstallation {
+            soabi: None,
+            ext_suffix: ".so".to_string(),
+            free_threaded: true,
+            ..installation((3, 13, 1))
+        };
+        assert_eq!(free_threaded.abi_tag(), "cp313t");
+
+        let pypy = PythonInstallation {
+            implementation: Implementation::PyPy,
+            soabi: Some("pypy310-pp73-x86_64-linux-gnu".to_string()),
+            ..installation((3, 10, 14))
+        };
+        assert_eq!(pypy.abi_tag(), "pypy310_pp73");
+    }
+
+    /// Test that an explicit path is the only candidate for a path request
+    #[test]
+    fn test_find_candidates_for_path() {
+        let path = PathBuf::from("/nonexistent/python3");
+        assert_eq!(
+            find_candidates(&PythonRequest::Path(path.clone())),
+            vec![(path.clone(), InstallationSource::Explicit)]
+        );
+        assert!(find_installation(&PythonRequest::Path(path)).is_err());
+    }
+
+    /// Test resolving a conda environment given as a prefix
+    #[test]
+    fn test_find_conda_env_prefix() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let prefix = temp_dir.path().join("envs").join("tools");
+        std::fs::create_dir_all(&prefix)?;
+        assert!(find_conda_env(&prefix.to_string_lossy()).is_err());
+
+        std::fs::create_dir(prefix.join("conda-meta"))?;
+        assert_eq!(find_conda_env(&prefix.to_string_lossy())?, prefix);
+        assert!(find_conda_env("py2pyd-no-such-env").is_err());
+        Ok(())
+    }
+
+    /// Test reading the pyenv version from the nearest .python-version
+    #[test]
+    fn test_pyenv_version_file() -> Result<()> {
+        if std::env::var_os("PYENV_VERSION").is_some() {
+            return Ok(());
+        }
+        let temp_dir = tempfile::TempDir::new()?;
+        let nested = temp_dir.path().join("src").join("pkg");
+        std::fs::create_dir_all(&nested)?;
+        assert_eq!(pyenv_version(&nested), None);
+
+        std::fs::write(temp_dir.path().join(".python-version"), "3.10.13\n3.11\n")?;
+        assert_eq!(pyenv_version(&nested).as_deref(), Some("3.10.13"));
+
+        std::fs::write(temp_dir.path().join(".python-version"), "system\n")?;
+        assert_eq!(pyenv_version(&nested), None);
+        Ok(())
+    }
+
+    /// Test that --conda-env conflicts with --python-path
+    #[test]
+    fn test_configure_conda_env_with_python_path() {
+        let mut config = CompileConfig {
+            conda_env: Some("base".to_string()),
+            python_path: Some(PathBuf::from("/usr/bin/python3")),
+            ..CompileConfig::default()
+        };
+        assert!(interpreter::configure(&mut config).is_err());
+    }
+
+    /// Test probing the interpreter on PATH, if there is one
+    #[test]
+    fn test_probe_path_interpreter() -> Result<()> {
+        if let Some(python) = common::find_python() {
+            let installation = PythonInstallation::probe(&python)?;
+            assert_eq!(installation.version.major, 3);
+            assert!(!installation.ext_suffix.is_empty());
+            assert!(installation
+                .abi_tag()
+                .starts_with(match installation.implementation {
+                    Implementation::PyPy => "pypy",
+                    _ => "cp",
+                }));
+
+            let found = find_installation(&PythonRequest::Any)?;
+            assert_eq!(found.version.major, 3);
+        }
+        Ok(())
+    }
+}
@@ -29,7 +29,35 @@ fn test_compile_config_custom() {
         optimize_level: 3,
         keep_temp_files: true,
         target_dcc: Some("maya".to_string()),
+        dcc_target: None,
         packages: vec!["numpy".to_string(), "scipy".to_string()],
+        encrypt_strings: true,
+        keep_docstrings: true,
+        cython_directives: Default::default(),
+        include: vec![],
+        exclude: vec![],
+        output_dir: None,
+        output_layout: Default::default(),
+        overrides: vec![],
+        profile: None,
+        lto: false,
+        strip: false,
+        debug_symbols: false,
+        line_tracing: false,
+        cache: true,
+        target: None,
+        target_python: None,
+        manylinux: None,
+        repair: false,
+        no_venv: false,
+        conda_env: None,
+        conda_install: false,
+        env_backend: py2pyd::EnvBackend::Auto,
+        install_uv: false,
+        compile_args: vec![],
+        link_args: vec![],
+        project_root: None,
+        compiler: None,
     };
 
     assert_eq!(
@@ -41,6 +69,8 @@ fn test_compile_config_custom() {
     assert!(config.keep_temp_files);
     assert_eq!(config.target_dcc, Some("maya".to_string()));
     assert_eq!(config.packages.len(), 2);
+    assert!(config.encrypt_strings);
+    assert!(config.keep_docstrings);
 }
 
 /// Test that UvEnvConfig can be created with default values
@@ -62,6 +92,10 @@ fn test_uv_env_config_custom() {
         python_version: Some("3.11".to_string()),
         keep_venv: true,
         packages: vec!["requests".to_string()],
+        conda_env: None,
+        install_in_env: false,
+        backend: py2pyd::EnvBackend::Venv,
+        install_uv: false,
     };
 
     assert_eq!(
@@ -73,6 +107,23 @@ fn test_uv_env_config_custom() {
     assert_eq!(config.packages, vec!["requests".to_string()]);
 }
 
+/// Test parsing and resolving build environment backends
+#[test]
+fn test_env_backend() -> Result<()> {
+    use py2pyd::EnvBackend;
+
+    assert_eq!("venv".parse::<EnvBackend>()?, EnvBackend::Venv);
+    assert_eq!(EnvBackend::Uv.to_string(), "uv");
+    assert!("conda".parse::<EnvBackend>().is_err());
+
+    assert_eq!(EnvBackend::Venv.resolve(true), EnvBackend::Venv);
+    assert_eq!(EnvBackend::Uv.resolve(false), EnvBackend::Uv);
+    // Opting in to installing uv makes it the automatic choice
+    assert_eq!(EnvBackend::Auto.resolve(true), EnvBackend::Uv);
+    assert_ne!(EnvBackend::Auto.resolve(false), EnvBackend::Auto);
+    Ok(())
+}
+
 /// Test get_extension returns correct value for platform
 #[test]
 fn test_get_extension() {
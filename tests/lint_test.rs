@@ -0,0 +1,193 @@
+//! Unit tests for the Cython-compatibility linter
+//!
+//! These tests verify that constructs which behave differently under Cython
+//! are reported with the right rule and location, and can be suppressed.
+
+use anyhow::Result;
+use std::path::Path;
+
+fn codes(source: &str) -> Result<Vec<(usize, String)>> {
+    let diagnostics = py2pyd::lint_source(source, Path::new("module.py"))?;
+    Ok(diagnostics.into_iter().map(|d| (d.line, d.code)).collect())
+}
+
+#[cfg(test)]
+mod lint_tests {
+    use super::*;
+
+    /// Test that inspect source helpers are reported, including through aliases
+    #[test]
+    fn test_inspect_source() -> Result<()> {
+        let source = r#"
+import inspect
+import inspect as ins
+from inspect import getsourcelines as lines
+
+def show(obj):
+    print(inspect.getsource(obj))
+    print(ins.getsourcefile(obj))
+    print(lines(obj))
+    print(inspect.signature(obj))
+"#;
+
+        let found = codes(source)?;
+        assert_eq!(
+            found,
+            vec![
+                (7, "inspect-source".to_string()),
+                (8, "inspect-source".to_string()),
+                (9, "inspect-source".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    /// Test that `__file__` and frame introspection are reported
+    #[test]
+    fn test_file_and_frames() -> Result<()> {
+        let source = r#"
+import os
+import sys
+
+HERE = os.path.dirname(__file__)
+
+def caller():
+    return sys._getframe(1).f_code.co_name
+"#;
+
+        let found = codes(source)?;
+        assert_eq!(
+            found,
+            vec![
+                (5, "file-relative-path".to_string()),
+                (8, "frame-introspection".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    /// Test that writes through locals() are reported but reads are not
+    #[test]
+    fn test_locals_mutation() -> Result<()> {
+        let source = r#"
+def configure(**options):
+    locals()["debug"] = True
+    locals().update(options)
+    return locals()["options"]
+"#;
+
+        let found = codes(source)?;
+        assert_eq!(
+            found,
+            vec![
+                (3, "locals-mutation".to_string()),
+                (4, "locals-mutation".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    /// Test that custom metaclasses are reported but ABCMeta is not
+    #[test]
+    fn test_metaclass() -> Result<()> {
+        let source = r#"
+import abc
+
+class Registry(type):
+    @classmethod
+    def __prepare__(mcs, name, bases):
+        return {}
+
+class Plugin(metaclass=Registry):
+    pass
+
+class Base(metaclass=abc.ABCMeta):
+    pass
+"#;
+
+        let found = codes(source)?;
+        assert_eq!(
+            found,
+            vec![(6, "metaclass".to_string()), (9, "metaclass".to_string())]
+        );
+        Ok(())
+    }
+
+    /// Test that only `__class__` cell quirks are reported
+    #[test]
+    fn test_class_cell() -> Result<()> {
+        let source = r#"
+class Child(Base):
+    def method(self):
+        super().method()
+        return __class__
+
+    def nested(self):
+        def inner():
+            return super().method()
+        return inner
+
+    def with_lambda(self):
+        return lambda: __class__
+"#;
+
+        let found = codes(source)?;
+        assert_eq!(
+            found,
+            vec![
+                (9, "class-cell".to_string()),
+                (13, "class-cell".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    /// Test inline suppression comments
+    #[test]
+    fn test_suppression() -> Result<()> {
+        let source = r#"
+import sys
+
+A = __file__  # py2pyd: ignore
+B = __file__  # py2pyd: ignore[file-relative-path]
+C = __file__  # py2pyd: ignore[inspect-source]
+D = sys._getframe()  # py2pyd: ignore[inspect-source, frame-introspection]
+"#;
+
+        let found = codes(source)?;
+        assert_eq!(found, vec![(6, "file-relative-path".to_string())]);
+        Ok(())
+    }
+
+    /// Test that diagnostics carry the file, column and an explanation
+    #[test]
+    fn test_diagnostic_details() -> Result<()> {
+        let diagnostics = py2pyd::lint_source("x = 1\ny = __file__\n", Path::new("pkg/mod.py"))?;
+
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.path, Path::new("pkg/mod.py"));
+        assert_eq!((diagnostic.line, diagnostic.column), (2, 5));
+        assert_eq!(diagnostic.severity, py2pyd::Severity::Warning);
+        assert!(diagnostic.explanation.is_some());
+        assert!(diagnostic
+            .to_string()
+            .starts_with("pkg/mod.py:2:5: warning[file-relative-path]"));
+        Ok(())
+    }
+
+    /// Test that clean code produces no diagnostics
+    #[test]
+    fn test_clean_code() -> Result<()> {
+        let source = r#"
+import os
+
+def read(path):
+    with open(os.path.join("data", path)) as handle:
+        return handle.read()
+"#;
+
+        assert!(codes(source)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! Unit tests for cross-process file locks
+//!
+//! These tests verify shared and exclusive locking, recording the holder's
+//! PID, and timing out on a busy lock. Separate handles conflict like
+//! separate processes, so one test process is enough.
+
+use anyhow::Result;
+use std::time::Duration;
+use tempfile::TempDir;
+
+use py2pyd::lock::{lock_holder, lock_timeout, FileLock, LockMode, LOCK_TIMEOUT_ENV};
+
+#[cfg(test)]
+mod lock_tests {
+    use super::*;
+
+    /// Test that an exclusive lock keeps out every other holder
+    #[test]
+    fn test_exclusive() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("locks").join("env.lock");
+
+        let lock = FileLock::try_acquire(&path, LockMode::Exclusive)?;
+        assert!(lock.is_some());
+        assert!(FileLock::try_acquire(&path, LockMode::Exclusive)?.is_none());
+        assert!(FileLock::try_acquire(&path, LockMode::Shared)?.is_none());
+        assert_eq!(lock_holder(&path), Some(std::process::id()));
+
+        drop(lock);
+        assert_eq!(lock_holder(&path), None);
+        assert!(FileLock::try_acquire(&path, LockMode::Exclusive)?.is_some());
+        Ok(())
+    }
+
+    /// Test that shared locks only keep out exclusive ones
+    #[test]
+    fn test_shared() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("env.lock");
+
+        let first = FileLock::try_acquire(&path, LockMode::Shared)?;
+        let second = FileLock::try_acquire(&path, LockMode::Shared)?;
+        assert!(first.is_some() && second.is_some());
+        assert!(FileLock::try_acquire(&path, LockMode::Exclusive)?.is_none());
+
+        drop((first, second));
+        assert!(FileLock::try_acquire(&path, LockMode::Exclusive)?.is_some());
+        Ok(())
+    }
+
+    /// Test that waiting gives up after the timeout, naming the holder
+    #[test]
+    fn test_timeout() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("uv.lock");
+        let _lock = FileLock::acquire_with_timeout(
+            &path,
+            LockMode::Exclusive,
+            "the uv install",
+            Duration::ZERO,
+        )?;
+
+        let error = FileLock::acquire_with_timeout(
+            &path,
+            LockMode::Shared,
+            "the uv install",
+            Duration::from_millis(300),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("the uv install"));
+        assert!(error.contains(&format!("PID {}", std::process::id())));
+        assert!(error.contains(LOCK_TIMEOUT_ENV));
+        Ok(())
+    }
+
+    /// Test reading the timeout from PY2PYD_LOCK_TIMEOUT
+    #[test]
+    fn test_lock_timeout() {
+        std::env::set_var(LOCK_TIMEOUT_ENV, "5");
+        assert_eq!(lock_timeout(), Duration::from_secs(5));
+        std::env::set_var(LOCK_TIMEOUT_ENV, "soon");
+        assert_eq!(lock_timeout(), Duration::from_secs(600));
+        std::env::remove_var(LOCK_TIMEOUT_ENV);
+    }
+}
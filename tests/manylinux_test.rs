@@ -0,0 +1,246 @@
+//! Unit tests for manylinux compatibility checks
+//!
+//! These tests verify reading needed libraries and symbol versions from ELF
+//! files, mapping them to manylinux policies, and vendoring libraries.
+
+use anyhow::Result;
+use std::fs;
+use tempfile::TempDir;
+
+use py2pyd::elf::{self, ElfFile};
+use py2pyd::manylinux::{self, is_system_library};
+use py2pyd::ManylinuxPolicy;
+
+/// A little-endian 64-bit x86-64 shared object with only the sections the
+/// parser reads: `.dynstr`, `.dynamic` and `.gnu.version_r`
+fn shared_object(needed: &[&str], rpath: Option<&str>, versions: &[(&str, &[&str])]) -> Vec<u8> {
+    let mut strings = vec![0u8];
+    let mut add_string = |s: &str| {
+        let offset = strings.len() as u64;
+        strings.extend_from_slice(s.as_bytes());
+        strings.push(0);
+        offset
+    };
+
+    let mut dynamic = vec![];
+    for name in needed {
+        dynamic.push((1u64, add_string(name)));
+    }
+    if let Some(rpath) = rpath {
+        dynamic.push((15, add_string(rpath)));
+    }
+    dynamic.push((0, 0));
+
+    let mut verneed = vec![];
+    for (index, (file, names)) in versions.iter().enumerate() {
+        let next = if index + 1 == versions.len() {
+            0
+        } else {
+            16 + 16 * names.len() as u32
+        };
+        verneed.extend_from_slice(&1u16.to_le_bytes());
+        verneed.extend_from_slice(&(names.len() as u16).to_le_bytes());
+        verneed.extend_from_slice(&(add_string(file) as u32).to_le_bytes());
+        verneed.extend_from_slice(&16u32.to_le_bytes());
+        verneed.extend_from_slice(&next.to_le_bytes());
+        for (aux, name) in names.iter().enumerate() {
+            let next = if aux + 1 == names.len() { 0u32 } else { 16 };
+            verneed.extend_from_slice(&[0; 8]);
+            verneed.extend_from_slice(&(add_string(name) as u32).to_le_bytes());
+            verneed.extend_from_slice(&next.to_le_bytes());
+        }
+    }
+
+    let strings_offset = 64u64;
+    let dynamic_offset = (strings_offset + strings.len() as u64).next_multiple_of(8);
+    let verneed_offset = dynamic_offset + 16 * dynamic.len() as u64;
+    let sections_offset = (verneed_offset + verneed.len() as u64).next_multiple_of(8);
+
+    let mut bytes = vec![0u8; 64];
+    bytes[..6].copy_from_slice(b"\x7fELF\x02\x01");
+    bytes[18..20].copy_from_slice(&elf::EM_X86_64.to_le_bytes());
+    bytes[0x28..0x30].copy_from_slice(&sections_offset.to_le_bytes());
+    bytes[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+    bytes[0x3c..0x3e].copy_from_slice(&4u16.to_le_bytes());
+
+    bytes.extend_from_slice(&strings);
+    bytes.resize(dynamic_offset as usize, 0);
+    for (tag, value) in &dynamic {
+        bytes.extend_from_slice(&tag.to_le_bytes());
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&verneed);
+    bytes.resize(sections_offset as usize, 0);
+
+    // Null section, then (type, offset, size, link, info) of each section
+    let sections = [
+        (0u32, 0u64, 0u64, 0u32, 0u32),
+        (3, strings_offset, strings.len() as u64, 0, 0),
+        (6, dynamic_offset, 16 * dynamic.len() as u64, 1, 0),
+        (
+            0x6fff_fffe,
+            verneed_offset,
+            verneed.len() as u64,
+            1,
+            versions.len() as u32,
+        ),
+    ];
+    for (kind, offset, size, link, info) in sections {
+        let mut header = [0u8; 64];
+        header[4..8].copy_from_slice(&kind.to_le_bytes());
+        header[24..32].copy_from_slice(&offset.to_le_bytes());
+        header[32..40].copy_from_slice(&size.to_le_bytes());
+        header[40..44].copy_from_slice(&link.to_le_bytes());
+        header[44..48].copy_from_slice(&info.to_le_bytes());
+        bytes.extend_from_slice(&header);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod manylinux_tests {
+    use super::*;
+
+    /// Test that needed libraries, RPATH and symbol versions are read
+    #[test]
+    fn test_parse_dynamic() -> Result<()> {
+        let bytes = shared_object(
+            &["libfoo.so.1", "libc.so.6"],
+            Some("$ORIGIN:/opt/lib"),
+            &[
+                ("libc.so.6", &["GLIBC_2.2.5", "GLIBC_2.14"]),
+                ("libstdc++.so.6", &["GLIBCXX_3.4.19"]),
+            ],
+        );
+        let file = ElfFile::parse(&bytes)?;
+
+        assert_eq!(file.header.machine, elf::EM_X86_64);
+        assert_eq!(file.needed, vec!["libfoo.so.1", "libc.so.6"]);
+        assert_eq!(file.rpath, vec!["$ORIGIN", "/opt/lib"]);
+        assert!(file.runpath.is_empty());
+        assert_eq!(
+            file.versions["libc.so.6"],
+            vec!["GLIBC_2.2.5", "GLIBC_2.14"]
+        );
+        assert_eq!(file.versions["libstdc++.so.6"], vec!["GLIBCXX_3.4.19"]);
+
+        assert!(ElfFile::parse(&bytes[..100]).is_err());
+        Ok(())
+    }
+
+    /// Test that the test binary itself is read
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_host_binary() -> Result<()> {
+        let file = ElfFile::read(std::path::Path::new("/proc/self/exe"))?;
+        assert!(file.needed.iter().any(|name| name.starts_with("libc.so")));
+        Ok(())
+    }
+
+    /// Test that symbol versions map to the oldest policy allowing them
+    #[test]
+    fn test_policy() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let module = temp_dir.path().join("module.so");
+        fs::write(
+            &module,
+            shared_object(
+                &["libc.so.6", "libstdc++.so.6"],
+                None,
+                &[
+                    ("libc.so.6", &["GLIBC_2.2.5", "GLIBC_2.14", "GLIBC_PRIVATE"]),
+                    ("libstdc++.so.6", &["GLIBCXX_3.4.9", "CXXABI_1.3"]),
+                ],
+            ),
+        )?;
+        let compat = manylinux::check(&module, false)?;
+        assert_eq!(compat.glibc.as_deref(), Some("2.14"));
+        assert_eq!(compat.glibcxx.as_deref(), Some("3.4.9"));
+        assert_eq!(compat.policy.name, "manylinux_2_17");
+        assert_eq!(compat.tag, "manylinux_2_17_x86_64");
+        assert!(compat.external.is_empty());
+
+        compat.check_policy(&"manylinux2014".parse()?)?;
+        let error = compat
+            .check_policy(&"manylinux2010".parse()?)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("GLIBC_2.14"));
+
+        fs::write(
+            &module,
+            shared_object(&["libc.so.6"], None, &[("libc.so.6", &["GLIBC_2.34"])]),
+        )?;
+        let compat = manylinux::check(&module, false)?;
+        assert_eq!(compat.tag, "manylinux_2_34_x86_64");
+        Ok(())
+    }
+
+    /// Test parsing policy names and aliases
+    #[test]
+    fn test_parse_policy() -> Result<()> {
+        let policy: ManylinuxPolicy = "manylinux2014".parse()?;
+        assert_eq!(policy.name, "manylinux_2_17");
+        assert_eq!(policy.glibcxx, Some(19));
+
+        let policy: ManylinuxPolicy = "manylinux_2_28_x86_64".parse()?;
+        assert_eq!(policy.glibc, 28);
+
+        let policy: ManylinuxPolicy = "manylinux_2_41".parse()?;
+        assert_eq!(policy.glibc, 41);
+        assert_eq!(policy.glibcxx, None);
+
+        assert!("musllinux_1_2".parse::<ManylinuxPolicy>().is_err());
+        assert!(is_system_library("libm.so.6"));
+        assert!(is_system_library("ld-linux-aarch64.so.1"));
+        assert!(!is_system_library("libssl.so.3"));
+        Ok(())
+    }
+
+    /// Test that non-system libraries, and the ones they need, are vendored
+    #[test]
+    fn test_repair() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let libs = temp_dir.path().join("libs");
+        let out = temp_dir.path().join("out");
+        fs::create_dir_all(&libs)?;
+        fs::create_dir_all(&out)?;
+        fs::write(
+            libs.join("libfoo.so.1"),
+            shared_object(&["libbar.so.2", "libc.so.6"], None, &[]),
+        )?;
+        fs::write(
+            libs.join("libbar.so.2"),
+            shared_object(&["libc.so.6"], None, &[("libc.so.6", &["GLIBC_2.28"])]),
+        )?;
+
+        let module = out.join("module.so");
+        let rpath = format!("$ORIGIN:{}", libs.display());
+        fs::write(
+            &module,
+            shared_object(&["libfoo.so.1", "libc.so.6"], Some(&rpath), &[]),
+        )?;
+
+        let compat = manylinux::check(&module, false)?;
+        assert_eq!(compat.external, vec!["libfoo.so.1"]);
+        let error = compat
+            .check_policy(&"manylinux_2_28".parse()?)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("--repair"));
+
+        let compat = manylinux::check(&module, true)?;
+        assert!(out.join("libfoo.so.1").is_file());
+        assert!(out.join("libbar.so.2").is_file());
+        assert!(compat.external.is_empty());
+        assert_eq!(compat.vendored.len(), 2);
+        // A vendored library's glibc requirement counts
+        assert_eq!(compat.glibc.as_deref(), Some("2.28"));
+        compat.check_policy(&"manylinux_2_28".parse()?)?;
+
+        // Modules without an $ORIGIN RPATH cannot find vendored libraries
+        fs::write(&module, shared_object(&["libmissing.so.1"], None, &[]))?;
+        assert!(manylinux::repair(&module).is_err());
+        Ok(())
+    }
+}
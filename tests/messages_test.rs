@@ -0,0 +1,153 @@
+//! Unit tests for machine-readable messages
+//!
+//! These tests verify the JSON shape of each message, including the schema
+//! version, and that the reporter counts modules, failures and diagnostics
+//! into the final summary.
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use py2pyd::messages::MESSAGE_FORMAT_VERSION;
+use py2pyd::{Diagnostic, Message, MessageFormat, Reporter, Severity};
+
+fn lines(output: &[u8]) -> Result<Vec<Value>> {
+    std::str::from_utf8(output)?
+        .lines()
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+fn diagnostic() -> Diagnostic {
+    Diagnostic {
+        path: PathBuf::from("src/a.py"),
+        line: 2,
+        column: 6,
+        code: "assignment-expression".to_string(),
+        severity: Severity::Error,
+        message: "assignment expression".to_string(),
+        explanation: None,
+    }
+}
+
+#[cfg(test)]
+mod messages_tests {
+    use super::*;
+
+    /// Test that every message is tagged with its reason and the schema version
+    #[test]
+    fn test_message_json() -> Result<()> {
+        let started: Value = serde_json::from_str(
+            &Message::ModuleStarted {
+                source: PathBuf::from("src/a.py"),
+                output: PathBuf::from("build/a.so"),
+            }
+            .to_json()?,
+        )?;
+        assert_eq!(started["version"], MESSAGE_FORMAT_VERSION);
+        assert_eq!(started["reason"], "module-started");
+        assert_eq!(started["output"], "build/a.so");
+
+        let fresh: Value = serde_json::from_str(
+            &Message::ModuleFresh {
+                source: PathBuf::from("src/a.py"),
+                output: PathBuf::from("build/a.so"),
+            }
+            .to_json()?,
+        )?;
+        assert_eq!(fresh["reason"], "module-fresh");
+        assert_eq!(fresh["source"], "src/a.py");
+
+        let finished: Value = serde_json::from_str(
+            &Message::module_finished(
+                Path::new("src/a.py"),
+                Path::new("build/a.so"),
+                Duration::from_millis(1500),
+                Some(&anyhow!("Failed to build extension module")),
+            )
+            .to_json()?,
+        )?;
+        assert_eq!(finished["reason"], "module-finished");
+        assert_eq!(finished["success"], false);
+        assert_eq!(finished["duration_ms"], 1500);
+        assert_eq!(finished["error"], "Failed to build extension module");
+
+        let diagnostic: Value =
+            serde_json::from_str(&Message::Diagnostic(diagnostic()).to_json()?)?;
+        assert_eq!(diagnostic["reason"], "diagnostic");
+        assert_eq!(diagnostic["code"], "assignment-expression");
+        assert_eq!(diagnostic["severity"], "error");
+        assert_eq!(diagnostic["line"], 2);
+        Ok(())
+    }
+
+    /// Test that the summary counts what was reported and ends the stream
+    #[test]
+    fn test_reporter_summary() -> Result<()> {
+        let mut reporter = Reporter::with_writer(MessageFormat::Json, Vec::new());
+        for (name, error) in [("a", None), ("b", Some(anyhow!("boom")))] {
+            let source = PathBuf::from(format!("src/{name}.py"));
+            let output = PathBuf::from(format!("build/{name}.so"));
+            reporter.emit(Message::ModuleStarted {
+                source: source.clone(),
+                output: output.clone(),
+            });
+            reporter.emit(Message::module_finished(
+                &source,
+                &output,
+                Duration::ZERO,
+                error.as_ref(),
+            ));
+        }
+        reporter.emit(Message::Diagnostic(diagnostic()));
+
+        let output = reporter.finish("batch", &Ok(()));
+        let messages = lines(&output)?;
+        assert_eq!(messages.len(), 6);
+
+        let summary = messages.last().unwrap();
+        assert_eq!(summary["reason"], "summary");
+        assert_eq!(summary["command"], "batch");
+        assert_eq!(summary["success"], false);
+        assert_eq!(summary["modules"], 2);
+        assert_eq!(summary["failed"], 1);
+        assert_eq!(summary["diagnostics"], 1);
+        assert!(summary.get("error").is_none());
+
+        let reporter = Reporter::with_writer(MessageFormat::Json, Vec::new());
+        let output = reporter.finish("graph", &Err(anyhow!("No such directory")));
+        let summary = &lines(&output)?[0];
+        assert_eq!(summary["success"], false);
+        assert_eq!(summary["error"], "No such directory");
+        Ok(())
+    }
+
+    /// Test the human format and format names
+    #[test]
+    fn test_human_format() -> Result<()> {
+        assert_eq!("json".parse::<MessageFormat>()?, MessageFormat::Json);
+        assert_eq!("human".parse::<MessageFormat>()?, MessageFormat::Human);
+        assert!("xml".parse::<MessageFormat>().is_err());
+
+        let mut reporter = Reporter::with_writer(MessageFormat::Human, Vec::new());
+        reporter.emit(Message::ModuleStarted {
+            source: PathBuf::from("src/a.py"),
+            output: PathBuf::from("build/a.so"),
+        });
+        reporter.emit(Message::module_finished(
+            Path::new("src/a.py"),
+            Path::new("build/a.so"),
+            Duration::from_millis(2500),
+            None,
+        ));
+        reporter.emit(Message::Diagnostic(diagnostic()));
+
+        let output = String::from_utf8(reporter.finish("batch", &Ok(())))?;
+        assert_eq!(
+            output,
+            "ok     src/a.py (2.5s)\nsrc/a.py:2:6: error[assignment-expression]: assignment expression\n"
+        );
+        Ok(())
+    }
+}
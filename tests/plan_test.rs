@@ -0,0 +1,291 @@
+//! Unit tests for the build cache and dry-run plans
+//!
+//! These tests verify that module fingerprints follow the source, the
+//! settings it is compiled with and the toolchain, and that a plan lists
+//! module names, output paths, exclusions and cache hits without building
+//! anything.
+
+mod common;
+
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+use py2pyd::build_tools::{Compiler, CompilerKind};
+use py2pyd::cache::{fingerprint, fingerprint_file, BuildCache, Toolchain, CACHE_FILE_NAME};
+use py2pyd::config::Directive;
+use py2pyd::{plan_batch, plan_batch_with_toolchain, CompileConfig, OutputLayout, PythonVersion};
+
+fn toolchain(python_version: &str, cython: &str) -> Toolchain {
+    Toolchain {
+        python: Some(format!("/usr/bin/python{python_version}").into()),
+        python_version: python_version.parse().ok(),
+        cython: Some(cython.to_string()),
+        compiler: Some(CompilerKind::Gcc),
+        compiler_version: Some("13.2.0".to_string()),
+        target_python: None,
+    }
+}
+
+fn write(dir: &Path, name: &str, contents: &str) -> Result<()> {
+    let path = dir.join(name);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod plan_tests {
+    use super::*;
+
+    /// Test that fingerprints change with the source, build settings and
+    /// toolchain only
+    #[test]
+    fn test_fingerprint() -> Result<()> {
+        let config = CompileConfig::default();
+        let tools = toolchain("3.11.7", "3.0.11");
+        let base = fingerprint(b"x = 1\n", &config, &tools)?;
+        assert_eq!(base.len(), 64);
+        assert_eq!(base, fingerprint(b"x = 1\n", &config, &tools)?);
+        assert_ne!(base, fingerprint(b"x = 2\n", &config, &tools)?);
+
+        let mut optimized = config.clone();
+        optimized.optimize_level = 3;
+        assert_ne!(base, fingerprint(b"x = 1\n", &optimized, &tools)?);
+
+        let mut directives = config.clone();
+        directives
+            .cython_directives
+            .insert("boundscheck".to_string(), Directive::Bool(false));
+        assert_ne!(base, fingerprint(b"x = 1\n", &directives, &tools)?);
+
+        // A different interpreter, patch release, Cython, C compiler or target
+        // headers means a rebuild
+        for other in [
+            toolchain("3.12.1", "3.0.11"),
+            toolchain("3.11.8", "3.0.11"),
+            toolchain("3.11.7", "3.1.0"),
+            Toolchain {
+                python: Some("/opt/python3.11".into()),
+                ..tools.clone()
+            },
+            Toolchain {
+                compiler: Some(CompilerKind::Clang),
+                ..tools.clone()
+            },
+            Toolchain {
+                compiler_version: Some("14.1.0".to_string()),
+                ..tools.clone()
+            },
+            Toolchain {
+                target_python: Some("0".repeat(64)),
+                ..tools.clone()
+            },
+        ] {
+            assert_ne!(base, fingerprint(b"x = 1\n", &config, &other)?);
+        }
+
+        // Settings that do not change the module keep it fresh
+        let mut unrelated = config.clone();
+        unrelated.keep_temp_files = true;
+        unrelated.exclude = vec!["tests".to_string()];
+        assert_eq!(base, fingerprint(b"x = 1\n", &unrelated, &tools)?);
+        Ok(())
+    }
+
+    /// Test that the toolchain names the configured compiler and follows the
+    /// contents of the target's headers
+    #[test]
+    fn test_toolchain_resolve() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let include = temp_dir.path().join("include").join("python3.11");
+        write(&include, "Python.h", "#define PY_MINOR_VERSION 11\n")?;
+
+        let config = CompileConfig {
+            target: Some("aarch64-linux-gnu".to_string()),
+            target_python: Some(temp_dir.path().to_path_buf()),
+            python_version: Some("3.11".to_string()),
+            compiler: Some(Compiler {
+                kind: CompilerKind::Clang,
+                path: "/usr/bin/clang".into(),
+                args: vec![],
+                version: Some("17.0.6".to_string()),
+                target: None,
+                reason: "test".to_string(),
+                from_env: false,
+                cross: true,
+            }),
+            ..Default::default()
+        };
+        let before = Toolchain::resolve(&config, None);
+        assert_eq!(before.compiler, Some(CompilerKind::Clang));
+        assert_eq!(before.compiler_version.as_deref(), Some("17.0.6"));
+        assert!(before.target_python.is_some());
+        assert_eq!(before, Toolchain::resolve(&config, None));
+
+        write(&include, "pyconfig.h", "#define HAVE_FORK 1\n")?;
+        let after = Toolchain::resolve(&config, None);
+        assert_ne!(before.target_python, after.target_python);
+        Ok(())
+    }
+
+    /// Test recording, persisting and invalidating cache entries
+    #[test]
+    fn test_build_cache() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let output_dir = temp_dir.path();
+        let output = output_dir.join("pkg/core.so");
+
+        let mut cache = BuildCache::load(output_dir);
+        assert!(!cache.is_fresh(&output, "abc"));
+
+        cache.record(&output, "abc".to_string());
+        assert!(!cache.is_fresh(&output, "abc"), "output does not exist yet");
+        write(output_dir, "pkg/core.so", "")?;
+        assert!(cache.is_fresh(&output, "abc"));
+        assert!(!cache.is_fresh(&output, "def"));
+        cache.save()?;
+
+        let mut cache = BuildCache::load(output_dir);
+        assert!(cache.is_fresh(&output, "abc"));
+        cache.invalidate(&output);
+        assert!(!cache.is_fresh(&output, "abc"));
+
+        fs::write(output_dir.join(CACHE_FILE_NAME), "not json")?;
+        assert!(!BuildCache::load(output_dir).is_fresh(&output, "abc"));
+        Ok(())
+    }
+
+    /// Test the plan for a package tree with exclusions and a cached module
+    #[test]
+    fn test_plan_batch() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let src = temp_dir.path().join("src");
+        write(&src, "tool/__init__.py", "")?;
+        write(&src, "tool/core.py", "x = 1\n")?;
+        write(&src, "tool/ui/__init__.py", "")?;
+        write(&src, "tool/ui/window.py", "")?;
+        write(&src, "tool/tests/test_core.py", "")?;
+        let output_dir = temp_dir.path().join("build");
+
+        let config = CompileConfig {
+            python_version: Some("3.10".to_string()),
+            packages: vec!["numpy".to_string()],
+            exclude: vec!["**/tests".to_string()],
+            project_root: Some(temp_dir.path().to_path_buf()),
+            cache: true,
+            ..Default::default()
+        };
+        let tools = toolchain("3.10.13", "3.0.11");
+        let input = src.to_str().unwrap();
+
+        // Pretend core.py was built before
+        let core = src.join("tool/core.py");
+        let core_output = output_dir.join(if cfg!(windows) {
+            "tool/core.pyd"
+        } else {
+            "tool/core.so"
+        });
+        write(
+            &output_dir,
+            core_output.strip_prefix(&output_dir)?.to_str().unwrap(),
+            "",
+        )?;
+        let mut cache = BuildCache::load(&output_dir);
+        cache.record(&core_output, fingerprint_file(&core, &config, &tools)?);
+        cache.save()?;
+
+        let plan = plan_batch_with_toolchain(input, &output_dir, &config, true, &tools)?;
+
+        assert_eq!(plan.interpreter, tools.python);
+        assert_eq!(plan.python_version.as_deref(), Some("3.10.13"));
+        assert_eq!(plan.packages.last().map(String::as_str), Some("numpy"));
+        assert_eq!(plan.excluded, vec![src.join("tool/tests/test_core.py")]);
+
+        let mut modules: Vec<_> = plan
+            .modules
+            .iter()
+            .map(|m| (m.module.as_str(), m.cached))
+            .collect();
+        modules.sort_unstable();
+        assert_eq!(
+            modules,
+            vec![
+                ("tool", false),
+                ("tool.core", true),
+                ("tool.ui", false),
+                ("tool.ui.window", false)
+            ]
+        );
+        assert_eq!(plan.to_build(), 3);
+
+        let core_plan = plan.modules.iter().find(|m| m.source == core).unwrap();
+        assert_eq!(core_plan.output, core_output);
+        assert!(!output_dir.join("tool/ui").exists(), "plan must not build");
+
+        // Another Cython, or no known Cython, means a rebuild
+        let plan = plan_batch_with_toolchain(
+            input,
+            &output_dir,
+            &config,
+            true,
+            &toolchain("3.10.13", "3.1.0"),
+        )?;
+        assert_eq!(plan.to_build(), 4);
+        let unknown = Toolchain {
+            cython: None,
+            ..tools.clone()
+        };
+        let plan = plan_batch_with_toolchain(input, &output_dir, &config, true, &unknown)?;
+        assert_eq!(plan.to_build(), 4);
+
+        // Changing the source or disabling the cache means a rebuild
+        fs::write(&core, "x = 2\n")?;
+        let plan = plan_batch_with_toolchain(input, &output_dir, &config, true, &tools)?;
+        assert_eq!(plan.to_build(), 4);
+
+        let flat = CompileConfig {
+            output_layout: OutputLayout::Flat,
+            cache: false,
+            ..config
+        };
+        let plan = plan_batch_with_toolchain(input, &output_dir, &flat, true, &tools)?;
+        assert!(plan
+            .modules
+            .iter()
+            .all(|m| m.output.parent() == Some(output_dir.as_path())));
+        Ok(())
+    }
+
+    /// Test that the cache is off by default and a plan reports the installed
+    /// interpreter it resolves
+    #[test]
+    fn test_plan_interpreter() -> Result<()> {
+        assert!(!CompileConfig::default().cache);
+
+        let Some(python) = common::find_python() else {
+            return Ok(());
+        };
+        let temp_dir = TempDir::new()?;
+        write(temp_dir.path(), "src/core.py", "x = 1\n")?;
+        let config = CompileConfig {
+            python_path: Some(python.clone()),
+            ..Default::default()
+        };
+
+        let plan = plan_batch(
+            temp_dir.path().join("src").to_str().unwrap(),
+            &temp_dir.path().join("build"),
+            &config,
+            false,
+        )?;
+        assert!(plan.interpreter.is_some());
+        let version: PythonVersion = plan.python_version.as_deref().unwrap().parse()?;
+        assert_eq!(version.major, 3);
+        assert!(version.patch.is_some());
+        assert_eq!(plan.cython, None);
+        assert_eq!(plan.to_build(), 1);
+        Ok(())
+    }
+}
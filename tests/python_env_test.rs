@@ -0,0 +1,66 @@
+//! Unit tests for Python environment handles
+//!
+//! These tests verify that handles for installed interpreters can be created
+//! side by side and cleaned up independently. They use interpreters found on
+//! `PATH` or in pyenv and skip when none are installed; uv is never downloaded.
+
+mod common;
+
+use anyhow::Result;
+
+use py2pyd::PythonEnvironment;
+
+/// A handle for an installed Python `version`, if there is one
+fn installed(version: &str) -> Option<PythonEnvironment> {
+    let env = PythonEnvironment::new(None, Some(version), false).ok()?;
+    // Only an installed interpreter, not a venv created with uv
+    env.venv_path().is_none().then_some(env)
+}
+
+#[cfg(test)]
+mod python_env_tests {
+    use super::*;
+
+    /// Test that a handle for an explicit interpreter path uses that interpreter
+    #[test]
+    fn test_explicit_path() -> Result<()> {
+        let Some(python) = common::find_python() else {
+            return Ok(());
+        };
+        let env = PythonEnvironment::new(Some(&python), None, false)?;
+        assert!(env.python_path().exists());
+        assert_eq!(env.venv_path(), None);
+        env.check()?;
+        env.cleanup()?;
+        Ok(())
+    }
+
+    /// Test that handles for different interpreters coexist
+    #[test]
+    fn test_several_interpreters() -> Result<()> {
+        let (Some(first), Some(second)) = (installed("3.11"), installed("3.12")) else {
+            common::skip("needs Python 3.11 and 3.12 installed");
+            return Ok(());
+        };
+        assert_ne!(first.python_path(), second.python_path());
+        first.check()?;
+        second.check()?;
+
+        // Cleaning up one leaves the other usable
+        first.cleanup()?;
+        second.check()?;
+        second.cleanup()?;
+        Ok(())
+    }
+
+    /// Test that a missing interpreter is an error rather than a fallback
+    #[test]
+    fn test_missing_interpreter() {
+        let missing = std::path::Path::new("/nonexistent/bin/python3");
+        // Even with a version to fall back on, the explicit path is reported
+        let Err(e) = PythonEnvironment::new(Some(missing), Some("3.11"), false) else {
+            panic!("a missing interpreter was replaced");
+        };
+        assert!(format!("{e:#}").contains("/nonexistent/bin/python3"));
+    }
+}
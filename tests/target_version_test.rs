@@ -0,0 +1,224 @@
+//! Unit tests for the target-version syntax check
+//!
+//! These tests verify that syntax newer than the target interpreter is
+//! reported with its location and minimum version, and that older targets
+//! accept code that only uses their own grammar.
+
+use anyhow::Result;
+use std::path::Path;
+
+use py2pyd::PythonVersion;
+
+fn codes(source: &str, target: &str) -> Result<Vec<(usize, String)>> {
+    let target: PythonVersion = target.parse()?;
+    let diagnostics = py2pyd::check_target_version(source, Path::new("module.py"), &target)?;
+    Ok(diagnostics.into_iter().map(|d| (d.line, d.code)).collect())
+}
+
+#[cfg(test)]
+mod target_version_tests {
+    use super::*;
+
+    /// Test parsing and ordering of Python versions
+    #[test]
+    fn test_python_version_parsing() -> Result<()> {
+        let version: PythonVersion = "3.10".parse()?;
+        assert_eq!(version, PythonVersion::new(3, 10));
+        assert_eq!(version.to_string(), "3.10");
+
+        let version: PythonVersion = "Python 3.11.4".parse()?;
+        assert_eq!(
+            (version.major, version.minor, version.patch),
+            (3, 11, Some(4))
+        );
+        assert_eq!(version.to_string(), "3.11.4");
+
+        assert!("3.12.0rc1".parse::<PythonVersion>().is_ok());
+        assert!("latest".parse::<PythonVersion>().is_err());
+
+        // A bare major version is not read as 3.0
+        let error = "3".parse::<PythonVersion>().unwrap_err().to_string();
+        assert!(error.contains("needs a minor version"), "{error}");
+        assert!("Python 3".parse::<PythonVersion>().is_err());
+        assert!("3.".parse::<PythonVersion>().is_err());
+
+        assert!(PythonVersion::new(3, 9) < PythonVersion::new(3, 10));
+        assert!(PythonVersion::new(3, 10).at_least(3, 8));
+        assert!(!PythonVersion::new(3, 7).at_least(3, 8));
+        Ok(())
+    }
+
+    /// Test that equality is exact and `same_minor` ignores patch numbers
+    #[test]
+    fn test_python_version_same_minor() -> Result<()> {
+        let requested: PythonVersion = "3.11".parse()?;
+        let reported: PythonVersion = "3.11.4".parse()?;
+        assert_ne!(requested, reported);
+        assert_ne!(requested, "3.11.0".parse()?);
+        assert!(requested.same_minor(&reported));
+        assert!(reported.same_minor(&"3.11.0".parse()?));
+        assert!(!reported.same_minor(&PythonVersion::new(3, 12)));
+        assert_eq!(reported.major_minor(), requested);
+        Ok(())
+    }
+
+    /// Test the 3.8 features: walrus and positional-only parameters
+    #[test]
+    fn test_python_38_features() -> Result<()> {
+        let source = r#"
+def first(items, /, default=None):
+    if (n := len(items)) > 0:
+        return items[0]
+    return default
+"#;
+
+        assert_eq!(
+            codes(source, "3.7")?,
+            vec![
+                (2, "positional-only-parameters".to_string()),
+                (3, "assignment-expression".to_string()),
+            ]
+        );
+        assert!(codes(source, "3.8")?.is_empty());
+        Ok(())
+    }
+
+    /// Test `match` statements and parenthesized context managers
+    #[test]
+    fn test_python_310_statements() -> Result<()> {
+        let source = r#"
+def run(command, a, b):
+    with (open(a) as src, open(b, "w") as dst):
+        dst.write(src.read())
+    with (open(a)) as src, (open(b)) as dst:
+        pass
+    with (open(a), open(b)):
+        pass
+    match command:
+        case "quit":
+            return
+"#;
+
+        assert_eq!(
+            codes(source, "3.9")?,
+            vec![
+                (3, "parenthesized-context-managers".to_string()),
+                (7, "parenthesized-context-managers".to_string()),
+                (9, "match-statement".to_string()),
+            ]
+        );
+        assert!(codes(source, "3.10")?.is_empty());
+        Ok(())
+    }
+
+    /// Test that PEP 604 unions are only reported where they are evaluated
+    #[test]
+    fn test_runtime_unions() -> Result<()> {
+        let source = r#"
+def parse(value: int | None, flags):
+    checked: str | None = None
+    mask = flags | 0x4
+    return isinstance(value, int | str)
+"#;
+
+        assert_eq!(
+            codes(source, "3.9")?,
+            vec![
+                (2, "union-operator".to_string()),
+                (5, "union-operator".to_string()),
+            ]
+        );
+
+        let postponed = format!("from __future__ import annotations\n{source}");
+        assert_eq!(
+            codes(&postponed, "3.9")?,
+            vec![(6, "union-operator".to_string())]
+        );
+        Ok(())
+    }
+
+    /// Test the 3.9, 3.11 and 3.12 features
+    #[test]
+    fn test_other_versions() -> Result<()> {
+        let source = r#"
+Pairs = list[tuple[int, int]]
+
+@buttons[0].clicked.connect
+def on_click():
+    try:
+        pass
+    except* ValueError:
+        pass
+
+type Alias = int
+
+def identity[T](value: T) -> T:
+    return value
+"#;
+
+        assert_eq!(
+            codes(source, "3.8")?,
+            vec![
+                (2, "builtin-generics".to_string()),
+                (4, "relaxed-decorators".to_string()),
+                (8, "exception-groups".to_string()),
+                (11, "type-parameters".to_string()),
+                (13, "type-parameters".to_string()),
+            ]
+        );
+        assert_eq!(
+            codes(source, "3.11")?,
+            vec![
+                (11, "type-parameters".to_string()),
+                (13, "type-parameters".to_string()),
+            ]
+        );
+        assert!(codes(source, "3.12")?.is_empty());
+        Ok(())
+    }
+
+    /// Test that diagnostics are errors naming the minimum and target versions
+    #[test]
+    fn test_diagnostic_details() -> Result<()> {
+        let target = PythonVersion::new(3, 7);
+        let diagnostics = py2pyd::check_target_version(
+            "x = 1\nif (y := x):\n    pass\n",
+            Path::new("pkg/mod.py"),
+            &target,
+        )?;
+
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!((diagnostic.line, diagnostic.column), (2, 5));
+        assert_eq!(diagnostic.severity, py2pyd::Severity::Error);
+        assert!(diagnostic
+            .to_string()
+            .starts_with("pkg/mod.py:2:5: error[assignment-expression]"));
+        assert!(diagnostic.message.contains("requires Python 3.8"));
+        assert!(diagnostic.message.contains("target is Python 3.7"));
+        Ok(())
+    }
+
+    /// Test that compilation fails early on syntax the target cannot handle
+    #[test]
+    fn test_compile_preflight() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let input = temp_dir.path().join("modern.py");
+        std::fs::write(
+            &input,
+            "def f(x):\n    match x:\n        case _:\n            pass\n",
+        )?;
+
+        let config = py2pyd::CompileConfig {
+            python_version: Some("3.8".to_string()),
+            ..Default::default()
+        };
+        let error = py2pyd::compile_file(&input, &temp_dir.path().join("modern.so"), &config)
+            .expect_err("match statement should be rejected for Python 3.8");
+
+        let message = format!("{error:#}");
+        assert!(message.contains("not supported by Python 3.8"));
+        assert!(message.contains("match-statement"));
+        Ok(())
+    }
+}
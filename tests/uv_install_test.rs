@@ -0,0 +1,163 @@
+//! Unit tests for the managed uv installer
+//!
+//! These tests verify picking the release archive for a platform, reading
+//! published checksums, unpacking `.tar.gz` and `.zip` archives, and the
+//! `PY2PYD_UV` override. Nothing is downloaded.
+
+use anyhow::Result;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use tempfile::TempDir;
+
+use py2pyd::uv_env::locate_uv;
+use py2pyd::uv_install::{
+    archive_name, download_url, extract_executables, parse_checksum, sha256_file, uv_target,
+    UV_OVERRIDE_ENV,
+};
+
+/// Write a `.tar.gz` with `files` (path, contents) to `path`
+fn write_tar_gz(path: &Path, files: &[(&str, &[u8])]) -> Result<()> {
+    let encoder = flate2::write::GzEncoder::new(File::create(path)?, flate2::Compression::fast());
+    let mut builder = tar::Builder::new(encoder);
+    for (name, contents) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, name, *contents)?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod uv_install_tests {
+    use super::*;
+
+    /// Test mapping platforms to uv's release targets
+    #[test]
+    fn test_uv_target() -> Result<()> {
+        assert_eq!(
+            uv_target("linux", "x86_64", false)?,
+            "x86_64-unknown-linux-gnu"
+        );
+        assert_eq!(
+            uv_target("linux", "aarch64", true)?,
+            "aarch64-unknown-linux-musl"
+        );
+        assert_eq!(
+            uv_target("macos", "aarch64", false)?,
+            "aarch64-apple-darwin"
+        );
+        assert_eq!(
+            uv_target("windows", "x86_64", false)?,
+            "x86_64-pc-windows-msvc"
+        );
+        assert!(uv_target("freebsd", "x86_64", false).is_err());
+        Ok(())
+    }
+
+    /// Test that Windows gets a zip and everything else a tarball
+    #[test]
+    fn test_archive_name() {
+        assert_eq!(
+            archive_name("x86_64-pc-windows-msvc"),
+            "uv-x86_64-pc-windows-msvc.zip"
+        );
+        assert_eq!(
+            archive_name("aarch64-apple-darwin"),
+            "uv-aarch64-apple-darwin.tar.gz"
+        );
+        assert_eq!(
+            download_url("0.7.6", "uv-aarch64-apple-darwin.tar.gz"),
+            "https://github.com/astral-sh/uv/releases/download/0.7.6/uv-aarch64-apple-darwin.tar.gz"
+        );
+    }
+
+    /// Test reading and computing SHA-256 checksums
+    #[test]
+    fn test_checksums() -> Result<()> {
+        let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(
+            parse_checksum(&format!(
+                "{}  uv-x86_64-unknown-linux-gnu.tar.gz\n",
+                abc.to_uppercase()
+            ))?,
+            abc
+        );
+        assert!(parse_checksum("").is_err());
+        assert!(parse_checksum("not-a-hash  uv.tar.gz").is_err());
+
+        let temp_dir = TempDir::new()?;
+        let file = temp_dir.path().join("abc");
+        fs::write(&file, "abc")?;
+        assert_eq!(sha256_file(&file)?, abc);
+        Ok(())
+    }
+
+    /// Test unpacking uv and uvx from a tarball, without its top directory
+    #[test]
+    fn test_extract_tar_gz() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive = temp_dir.path().join("uv-x86_64-unknown-linux-gnu.tar.gz");
+        write_tar_gz(
+            &archive,
+            &[
+                ("uv-x86_64-unknown-linux-gnu/uv", b"uv"),
+                ("uv-x86_64-unknown-linux-gnu/uvx", b"uvx"),
+                ("uv-x86_64-unknown-linux-gnu/README.md", b"readme"),
+            ],
+        )?;
+
+        let dest = temp_dir.path().join("out");
+        extract_executables(&archive, &dest)?;
+        assert_eq!(fs::read_to_string(dest.join("uv"))?, "uv");
+        assert_eq!(fs::read_to_string(dest.join("uvx"))?, "uvx");
+        assert!(!dest.join("README.md").exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dest.join("uv"))?.permissions().mode();
+            assert_ne!(mode & 0o111, 0);
+        }
+        Ok(())
+    }
+
+    /// Test unpacking uv.exe from a Windows zip
+    #[test]
+    fn test_extract_zip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let archive = temp_dir.path().join("uv-x86_64-pc-windows-msvc.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive)?);
+        for name in ["uv.exe", "uvx.exe", "uvw.exe"] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+            zip.write_all(name.as_bytes())?;
+        }
+        zip.finish()?;
+
+        let dest = temp_dir.path().join("out");
+        extract_executables(&archive, &dest)?;
+        assert_eq!(fs::read_to_string(dest.join("uv.exe"))?, "uv.exe");
+        assert!(dest.join("uvx.exe").exists());
+        assert!(!dest.join("uvw.exe").exists());
+        Ok(())
+    }
+
+    /// Test that PY2PYD_UV takes precedence, and is not second-guessed when missing
+    #[test]
+    fn test_uv_override() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let uv = temp_dir.path().join("my-uv");
+        fs::write(&uv, "")?;
+
+        std::env::set_var(UV_OVERRIDE_ENV, &uv);
+        assert_eq!(locate_uv(), Some(uv));
+
+        std::env::set_var(UV_OVERRIDE_ENV, temp_dir.path().join("missing"));
+        assert_eq!(locate_uv(), None);
+        std::env::remove_var(UV_OVERRIDE_ENV);
+        Ok(())
+    }
+}
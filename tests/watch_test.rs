@@ -0,0 +1,135 @@
+//! Unit tests for watch mode
+//!
+//! These tests verify that changed modules are detected, that bursts of
+//! saves are collected into a single rebuild, and that only outputs older
+//! than their sources are rebuilt on start.
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+use py2pyd::uv_compiler::batch_output_path;
+use py2pyd::watch::{needs_rebuild, Watcher};
+use py2pyd::{OutputLayout, WatchOptions};
+
+/// Set the modification time of `path` relative to now
+fn touch(path: &Path, offset: Duration, later: bool) -> Result<()> {
+    let now = SystemTime::now();
+    let time = if later { now + offset } else { now - offset };
+    File::options().write(true).open(path)?.set_modified(time)?;
+    Ok(())
+}
+
+fn set(paths: &[&PathBuf]) -> BTreeSet<PathBuf> {
+    paths.iter().map(|p| (*p).clone()).collect()
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use super::*;
+
+    /// Test that created and modified Python files are reported once
+    #[test]
+    fn test_poll() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let core = temp_dir.path().join("core.py");
+        let nested = temp_dir.path().join("ui/window.py");
+        fs::create_dir_all(nested.parent().unwrap())?;
+        fs::write(&core, "x = 1\n")?;
+        fs::write(&nested, "y = 1\n")?;
+        fs::write(temp_dir.path().join("notes.txt"), "")?;
+
+        let mut watcher = Watcher::new(temp_dir.path())?;
+        assert_eq!(watcher.files().count(), 2);
+        assert!(watcher.poll()?.is_empty());
+
+        fs::write(&core, "x = 2\n")?;
+        touch(&core, Duration::from_secs(5), true)?;
+        let added = temp_dir.path().join("ui/dialog.py");
+        fs::write(&added, "")?;
+        fs::write(temp_dir.path().join("notes.txt"), "changed")?;
+
+        assert_eq!(watcher.poll()?, set(&[&core, &added]));
+        assert!(watcher.poll()?.is_empty());
+
+        fs::remove_file(&added)?;
+        assert!(watcher.poll()?.is_empty());
+        assert_eq!(watcher.files().count(), 2);
+        Ok(())
+    }
+
+    /// Test that a burst of saves ends up in a single set of changes
+    #[test]
+    fn test_debounce() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let a = temp_dir.path().join("a.py");
+        let b = temp_dir.path().join("b.py");
+        fs::write(&a, "")?;
+        fs::write(&b, "")?;
+
+        let mut watcher = Watcher::new(temp_dir.path())?;
+        let options = WatchOptions {
+            poll_interval: Duration::from_millis(20),
+            debounce: Duration::from_millis(200),
+        };
+
+        let writer = {
+            let (a, b) = (a.clone(), b.clone());
+            thread::spawn(move || -> Result<()> {
+                thread::sleep(Duration::from_millis(50));
+                fs::write(&a, "a = 1\n")?;
+                thread::sleep(Duration::from_millis(80));
+                fs::write(&b, "b = 1\n")?;
+                Ok(())
+            })
+        };
+
+        let changed = watcher.wait_for_changes(&options)?;
+        writer.join().unwrap()?;
+        assert_eq!(changed, set(&[&a, &b]));
+        Ok(())
+    }
+
+    /// Test which outputs are rebuilt on start and where they are written
+    #[test]
+    fn test_needs_rebuild() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let source = temp_dir.path().join("src/tool/core.py");
+        fs::create_dir_all(source.parent().unwrap())?;
+        fs::write(&source, "")?;
+
+        let build = temp_dir.path().join("build");
+        let output = batch_output_path(
+            &temp_dir.path().join("src"),
+            &source,
+            &build,
+            OutputLayout::Mirror,
+        );
+        let extension = if cfg!(windows) { "pyd" } else { "so" };
+        assert_eq!(output, build.join("tool").join(format!("core.{extension}")));
+        assert_eq!(
+            batch_output_path(
+                &temp_dir.path().join("src"),
+                &source,
+                &build,
+                OutputLayout::Flat
+            ),
+            build.join(format!("core.{extension}"))
+        );
+
+        assert!(needs_rebuild(&source, &output));
+
+        fs::create_dir_all(output.parent().unwrap())?;
+        fs::write(&output, "")?;
+        touch(&source, Duration::from_secs(60), false)?;
+        assert!(!needs_rebuild(&source, &output));
+
+        touch(&source, Duration::from_secs(60), true)?;
+        assert!(needs_rebuild(&source, &output));
+        Ok(())
+    }
+}